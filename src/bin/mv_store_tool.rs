@@ -0,0 +1,8 @@
+/// MVStoreTool的命令行入口,比如 mv_store_tool -info test.mv.db
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = h2_rust::mv_store_tool_main(&args, &mut std::io::stdout()) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
@@ -1 +1,2 @@
+pub mod set;
 pub mod set_types;
\ No newline at end of file
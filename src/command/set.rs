@@ -0,0 +1,38 @@
+use anyhow::Result;
+use crate::command::set_types;
+use crate::engine::database::Database;
+use crate::h2_rust_common::Integer;
+use crate::message::db_error::DbError;
+use crate::throw;
+
+/// This class represents the statement SET,
+/// 还没有SQL解析,目前只支持值为整数的数据库设置
+pub struct Set {
+    set_type: Integer,
+    int_value: Integer,
+}
+
+impl Set {
+    pub fn new(set_type: Integer, int_value: Integer) -> Set {
+        Set { set_type, int_value }
+    }
+
+    pub fn update(&self, database: &mut Database) -> Result<()> {
+        match self.set_type {
+            set_types::DB_CLOSE_DELAY => {
+                if self.int_value < -1 {
+                    throw!(DbError::get_invalid_value_exception("DB_CLOSE_DELAY", &self.int_value.to_string()));
+                }
+                database.set_close_delay(self.int_value);
+            }
+            set_types::RETENTION_TIME => {
+                database.set_retention_time(self.int_value);
+            }
+            _ => {
+                throw!(DbError::get_unsupported_exception(&format!("SET {}", set_types::get_type_name(self.set_type as usize))));
+            }
+        }
+
+        Ok(())
+    }
+}
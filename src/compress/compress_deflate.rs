@@ -0,0 +1,106 @@
+use anyhow::Result;
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+use crate::api::error_code;
+use crate::compress::compressor;
+use crate::compress::compressor::Compressor;
+use crate::h2_rust_common::Integer;
+use crate::message::db_error::DbError;
+use crate::throw;
+
+/// This is a wrapper class for the Deflater class.
+/// This algorithm supports the following options:
+/// - l or level: -1 (default), 0 (no compression), 1 (best speed), ..., 9 (best compression)
+///
+/// 和java版一样使用不带zlib header的raw deflate格式
+pub struct CompressDeflate {
+    level: Integer,
+}
+
+impl CompressDeflate {
+    pub fn new() -> CompressDeflate {
+        CompressDeflate {
+            level: -1
+        }
+    }
+
+    pub fn setLevel(&mut self, level: Integer) -> Result<()> {
+        if !(-1..=9).contains(&level) {
+            throw!(DbError::get(error_code::UNSUPPORTED_COMPRESSION_OPTIONS_1, vec![&format!("level {}", level)]));
+        }
+        self.level = level;
+        Ok(())
+    }
+
+    fn getCompression(&self) -> Compression {
+        if self.level == -1 {
+            Compression::default()
+        } else {
+            Compression::new(self.level as u32)
+        }
+    }
+}
+
+impl Compressor for CompressDeflate {
+    fn getAlgorithm(&self) -> Integer {
+        compressor::DEFLATE
+    }
+
+    fn compress(&mut self, input: &[u8], inPos: usize, inLen: usize, out: &mut [u8], outPos: usize) -> usize {
+        let mut deflater = Compress::new(self.getCompression(), false);
+        // 输出空间不够时 deflater只写满out,调用方会发现压缩后并不更小从而放弃压缩
+        let _ = deflater.compress(&input[inPos..inPos + inLen], &mut out[outPos..], FlushCompress::Finish);
+        outPos + deflater.total_out() as usize
+    }
+
+    fn expand(&self, input: &[u8], inPos: usize, inLen: usize, out: &mut [u8], outPos: usize, outLen: usize) -> Result<()> {
+        let mut inflater = Decompress::new(false);
+        let result = inflater.decompress(&input[inPos..inPos + inLen], &mut out[outPos..outPos + outLen], FlushDecompress::Finish);
+        if let Err(e) = result {
+            throw!(DbError::get(error_code::COMPRESSION_ERROR, vec![&e.to_string()]));
+        }
+
+        let len = inflater.total_out() as usize;
+        if len != outLen {
+            throw!(DbError::get(error_code::COMPRESSION_ERROR, vec![&format!("{} {}", len, outLen)]));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::api::error_code;
+    use crate::compress::compress_deflate::CompressDeflate;
+    use crate::compress::compressor::Compressor;
+    use crate::message::db_error::DbError;
+
+    #[test]
+    fn test_round_trip() {
+        let input: Vec<u8> = (0..4096).map(|i| (i % 17) as u8).collect();
+
+        for level in [-1, 0, 1, 9] {
+            let mut compressDeflate = CompressDeflate::new();
+            compressDeflate.setLevel(level).unwrap();
+
+            // 前面留出空间,和page写入时一样从outPos开始
+            let mut compressed = vec![0u8; input.len() * 2 + 16];
+            let len = compressDeflate.compress(&input, 0, input.len(), &mut compressed, 3) - 3;
+            if level != 0 {
+                assert!(len < input.len());
+            }
+
+            let mut expanded = vec![0u8; input.len() + 2];
+            compressDeflate.expand(&compressed, 3, len, &mut expanded, 2, input.len()).unwrap();
+            assert_eq!(&expanded[2..], &input[..]);
+
+            // 长度对不上
+            let mut expanded = vec![0u8; input.len() + 1];
+            let error = compressDeflate.expand(&compressed, 3, len, &mut expanded, 0, input.len() + 1).unwrap_err();
+            assert_eq!(error.downcast_ref::<DbError>().unwrap().error_code, error_code::COMPRESSION_ERROR);
+        }
+
+        let error = CompressDeflate::new().setLevel(10).unwrap_err();
+        assert_eq!(error.downcast_ref::<DbError>().unwrap().error_code, error_code::UNSUPPORTED_COMPRESSION_OPTIONS_1);
+    }
+}
@@ -0,0 +1,268 @@
+use anyhow::Result;
+use crate::api::error_code;
+use crate::compress::compressor;
+use crate::compress::compressor::Compressor;
+use crate::h2_rust_common::Integer;
+use crate::message::db_error::DbError;
+use crate::throw;
+
+/// The number of entries in the hash table. The size is a trade-off between
+/// hash collisions (reduced compression) and speed (amount that fits in CPU cache).
+const HASH_SIZE: usize = 1 << 14;
+
+/// The maximum number of literals in a chunk (32).
+const MAX_LITERAL: usize = 1 << 5;
+
+/// The maximum offset allowed for a back-reference (8192).
+const MAX_OFF: usize = 1 << 13;
+
+/// The maximum back-reference length (264).
+const MAX_REF: usize = (1 << 8) + (1 << 3);
+
+/// This class implements the LZF lossless data compression algorithm. LZF is a
+/// Lempel-Ziv variant with byte-aligned output, and optimized for speed.
+///
+/// 和java版的CompressLZF格式相同,可以读取h2写的压缩page
+///
+/// The first byte of the compressed stream is the control byte. For literal
+/// runs, the highest three bits of the control byte are not set, the lower
+/// bits are the literal run length, and the next bytes are data to copy to the
+/// uncompressed stream. For back-references, the highest three bits of the
+/// control byte are the back-reference length. If all three bits are set,
+/// then the back-reference length is stored in the next byte. The lower bits
+/// of the control byte combined with the next byte form the offset for the
+/// back-reference.
+pub struct CompressLZF {
+    /// Hash table for matching byte sequences (reused for performance).
+    cachedHashTable: Vec<usize>,
+}
+
+impl CompressLZF {
+    pub fn new() -> CompressLZF {
+        CompressLZF {
+            cachedHashTable: vec![0; HASH_SIZE],
+        }
+    }
+}
+
+/// Return the integer with the first two bytes 0, then the bytes at the
+/// index, then at index+1.
+fn first(input: &[u8], inPos: usize) -> Integer {
+    ((input[inPos] as Integer) << 8) | (input[inPos + 1] as Integer)
+}
+
+/// Shift the value 1 byte left, and add the byte at index inPos+2.
+fn next(v: Integer, input: &[u8], inPos: usize) -> Integer {
+    (v << 8) | (input[inPos + 2] as Integer)
+}
+
+/// Compute the address in the hash table.
+fn hash(h: Integer) -> usize {
+    ((h.wrapping_mul(2777) >> 9) as usize) & (HASH_SIZE - 1)
+}
+
+impl Compressor for CompressLZF {
+    fn getAlgorithm(&self) -> Integer {
+        compressor::LZF
+    }
+
+    fn compress(&mut self, input: &[u8], mut inPos: usize, inLen: usize, out: &mut [u8], mut outPos: usize) -> usize {
+        // 0表示没有,所以和java一样起始的位置不会被引用
+        let offset = inPos;
+        let inEnd = inPos + inLen;
+
+        let hashTab = &mut self.cachedHashTable;
+        hashTab.fill(0);
+
+        let mut literals = 0;
+        outPos += 1;
+
+        let mut future = if inLen >= 2 { first(input, inPos) } else { 0 };
+
+        while inPos + 4 < inEnd {
+            let p2 = input[inPos + 2];
+
+            // next
+            future = (future << 8) + p2 as Integer;
+            let h = hash(future);
+            let r#ref = hashTab[h];
+            hashTab[h] = inPos;
+
+            if r#ref < inPos
+                && r#ref > offset
+                && inPos - r#ref - 1 < MAX_OFF
+                && input[r#ref + 2] == p2
+                && input[r#ref + 1] == (future >> 8) as u8
+                && input[r#ref] == (future >> 16) as u8 {
+                let off = inPos - r#ref - 1;
+
+                // match
+                let maxLen = usize::min(inEnd - inPos - 2, MAX_REF);
+
+                if literals == 0 {
+                    // multiple back-references, so there is no literal run control byte
+                    outPos -= 1;
+                } else {
+                    // set the control byte at the start of the literal run to store the number of literals
+                    out[outPos - literals - 1] = (literals - 1) as u8;
+                    literals = 0;
+                }
+
+                let mut len = 3;
+                while len < maxLen && input[r#ref + len] == input[inPos + len] {
+                    len += 1;
+                }
+                len -= 2;
+
+                if len < 7 {
+                    out[outPos] = ((off >> 8) + (len << 5)) as u8;
+                    outPos += 1;
+                } else {
+                    out[outPos] = ((off >> 8) + (7 << 5)) as u8;
+                    out[outPos + 1] = (len - 7) as u8;
+                    outPos += 2;
+                }
+                out[outPos] = off as u8;
+                outPos += 1;
+
+                // move one byte forward to allow for a literal run control byte
+                outPos += 1;
+                inPos += len;
+
+                // rebuild the future, and store the last bytes to the hashtable.
+                // Storing hashes of the last bytes in back-reference improves
+                // the compression ratio and only reduces speed slightly.
+                future = first(input, inPos);
+                future = next(future, input, inPos);
+                hashTab[hash(future)] = inPos;
+                inPos += 1;
+                future = next(future, input, inPos);
+                hashTab[hash(future)] = inPos;
+                inPos += 1;
+            } else {
+                // copy one byte from input to output as part of literal
+                out[outPos] = input[inPos];
+                outPos += 1;
+                inPos += 1;
+                literals += 1;
+
+                // at the end of this literal chunk, write the length
+                // to the control byte and start a new chunk
+                if literals == MAX_LITERAL {
+                    out[outPos - literals - 1] = (literals - 1) as u8;
+                    literals = 0;
+                    // move ahead one byte to allow for the literal run control byte
+                    outPos += 1;
+                }
+            }
+        }
+
+        // write the remaining few bytes as literals
+        while inPos < inEnd {
+            out[outPos] = input[inPos];
+            outPos += 1;
+            inPos += 1;
+            literals += 1;
+
+            if literals == MAX_LITERAL {
+                out[outPos - literals - 1] = (literals - 1) as u8;
+                literals = 0;
+                outPos += 1;
+            }
+        }
+
+        // writes the final literal run length to the control byte
+        out[outPos - literals - 1] = (literals as u8).wrapping_sub(1);
+        if literals == 0 {
+            outPos -= 1;
+        }
+
+        outPos
+    }
+
+    fn expand(&self, input: &[u8], mut inPos: usize, inLen: usize, out: &mut [u8], mut outPos: usize, outLen: usize) -> Result<()> {
+        let inEnd = inPos + inLen;
+        let outEnd = outPos + outLen;
+
+        if inEnd > input.len() || outEnd > out.len() {
+            throw!(DbError::get(error_code::COMPRESSION_ERROR, vec![]));
+        }
+
+        while outPos < outEnd {
+            if inPos >= inEnd {
+                throw!(DbError::get(error_code::COMPRESSION_ERROR, vec![]));
+            }
+
+            let mut ctrl = input[inPos] as usize;
+            inPos += 1;
+
+            if ctrl < MAX_LITERAL {
+                // literal run of length = ctrl + 1,
+                ctrl += 1;
+
+                if inPos + ctrl > inEnd || outPos + ctrl > outEnd {
+                    throw!(DbError::get(error_code::COMPRESSION_ERROR, vec![]));
+                }
+
+                // copy to output and move forward this many bytes
+                out[outPos..outPos + ctrl].copy_from_slice(&input[inPos..inPos + ctrl]);
+                outPos += ctrl;
+                inPos += ctrl;
+            } else {
+                // back reference the highest 3 bits are the match length
+                let mut len = ctrl >> 5;
+
+                // if the length is maxed, add the next byte to the length
+                if len == 7 {
+                    len += input[inPos] as usize;
+                    inPos += 1;
+                }
+
+                // minimum back-reference is 3 bytes, so 2 was subtracted before storing size
+                len += 2;
+
+                // ctrl is now the offset for a back-reference...
+                // the logical AND operation removes the length bits
+                let off = ((ctrl & 0x1f) << 8) + input[inPos] as usize + 1;
+                inPos += 1;
+
+                if off > outPos || outPos + len > outEnd {
+                    throw!(DbError::get(error_code::COMPRESSION_ERROR, vec![]));
+                }
+
+                // copy the back-reference bytes from the given location in output to current position
+                // 可能和自身重叠,要逐个byte复制
+                let from = outPos - off;
+                for i in 0..len {
+                    out[outPos + i] = out[from + i];
+                }
+                outPos += len;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::compress::compress_lzf::CompressLZF;
+    use crate::compress::compressor::Compressor;
+
+    #[test]
+    fn test_compress_expand() {
+        let mut input = Vec::new();
+        for a in 0..1000 {
+            input.extend_from_slice(format!("key{} value{} ", a % 37, a).as_bytes());
+        }
+
+        let mut compressLZF = CompressLZF::new();
+        let mut comp = vec![0; input.len() * 2];
+        let compLen = compressLZF.compress(&input, 0, input.len(), &mut comp, 0);
+        assert!(compLen < input.len());
+
+        let mut expanded = vec![0; input.len()];
+        compressLZF.expand(&comp, 0, compLen, &mut expanded, 0, input.len()).unwrap();
+        assert_eq!(input, expanded);
+    }
+}
@@ -0,0 +1,27 @@
+use anyhow::Result;
+use crate::h2_rust_common::Integer;
+
+/// No compression is used.
+pub const NO: Integer = 0;
+
+/// The LZF compression algorithm is used
+pub const LZF: Integer = 1;
+
+/// The DEFLATE compression algorithm is used.
+pub const DEFLATE: Integer = 2;
+
+/// Each data compression algorithm must implement this interface.
+pub trait Compressor {
+    /// Get the compression algorithm type.
+    fn getAlgorithm(&self) -> Integer;
+
+    /// Compress a number of bytes.
+    ///
+    /// return the end position (outPos + compressed length)
+    fn compress(&mut self, input: &[u8], inPos: usize, inLen: usize, out: &mut [u8], outPos: usize) -> usize;
+
+    /// Expand a number of compressed bytes.
+    ///
+    /// outLen: the size of the uncompressed data
+    fn expand(&self, input: &[u8], inPos: usize, inLen: usize, out: &mut [u8], outPos: usize, outLen: usize) -> Result<()>;
+}
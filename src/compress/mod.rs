@@ -0,0 +1,3 @@
+pub mod compressor;
+pub mod compress_lzf;
+pub mod compress_deflate;
@@ -46,6 +46,10 @@ impl Store {
             mv_store_builder.fileName(mv_file_path);
             mv_store_builder.pageSplitSize(database.page_size);
 
+            if database.recover {
+                mv_store_builder.recoveryMode();
+            }
+
             if database.read_only {
                 mv_store_builder.readOnly();
             } else {
@@ -63,9 +67,9 @@ impl Store {
                 }
             }
 
-            if encryption_key.is_some() {
+            if let Some(encryption_key) = encryption_key.as_ref() {
                 encrypted = true;
-                // mvStoreBuilder.encryptionKey(decodePassword(encryptionKey));
+                mv_store_builder.encryptionKey(encryption_key);
             }
 
             if database.db_settings.compress_data {
@@ -83,6 +87,22 @@ impl Store {
 
         Ok(())
     }
+
+    pub fn close(&self) -> Result<()> {
+        get_ref_mut!(self.mv_store).close()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        get_ref!(self.mv_store).isClosed()
+    }
+
+    pub fn set_retention_time(&self, retention_time: Integer) {
+        get_ref_mut!(self.mv_store).setRetentionTime(retention_time);
+    }
+
+    pub fn get_retention_time(&self) -> Integer {
+        get_ref!(self.mv_store).getRetentionTime()
+    }
 }
 
 pub fn dataUtilsErrorCode2ErrorCode(data_utils_error_code: Integer) -> Integer {
@@ -92,6 +112,7 @@ pub fn dataUtilsErrorCode2ErrorCode(data_utils_error_code: Integer) -> Integer {
         data_utils::ERROR_FILE_CORRUPT => error_code::FILE_CORRUPTED_1,
         data_utils::ERROR_FILE_LOCKED => error_code::DATABASE_ALREADY_OPEN_1,
         data_utils::ERROR_READING_FAILED | data_utils::ERROR_WRITING_FAILED => error_code::IO_EXCEPTION_1,
+        data_utils::ERROR_UNKNOWN_DATA_TYPE => error_code::UNKNOWN_DATA_TYPE_1,
         _ => error_code::GENERAL_ERROR_1,
     }
 }
\ No newline at end of file
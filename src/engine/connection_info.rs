@@ -14,6 +14,7 @@ use crate::message::db_error::DbError;
 use crate::command::set_types;
 use crate::engine::db_settings::DbSettings;
 use crate::{h2_rust_common, throw};
+use crate::store::fs::encrypt::file_encrypt;
 use crate::store::fs::file_utils;
 use crate::util::{io_utils, string_utils, utils};
 
@@ -292,9 +293,23 @@ impl ConnectionInfo {
     }
 
     fn convert_passwords(&mut self) -> Result<()> {
-        let password = Self::remove_password(self);
+        let mut password = Self::remove_password(self);
         let password_hash = Self::remove_property_bool(self, "PASSWORD_HASH", false)?;
 
+        if Self::get_property(self, "CIPHER").is_some() {
+            // split password into (filePassword+' '+userPassword)
+            let space = match password.find(' ') {
+                Some(space) => space,
+                None => throw!(DbError::get(error_code::WRONG_PASSWORD_FORMAT, vec![]))
+            };
+
+            let file_password = password[..space].to_string();
+            password = password[space + 1..].to_string();
+
+            self.file_encryption_key = Some(Arc::new(file_encrypt::get_password_bytes(&file_password)));
+            self.file_password_hash = Some(Arc::new(Self::hash_password(password_hash, "file", &file_password)?));
+        }
+
         self.user_password_hash = Some(Arc::new(Self::hash_password(password_hash, &self.user, &password)?));
 
         Ok(())
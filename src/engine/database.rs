@@ -1,25 +1,28 @@
 use std::cell::RefCell;
 use std::fmt::format;
 use std::ops::{Add, DerefMut};
-use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
 use crate::engine::connection_info::ConnectionInfo;
 use crate::engine::db_settings::DbSettings;
 use anyhow::Result;
 use crate::api::error_code;
 use crate::db::store::{Store, StoreRef};
-use crate::engine::{constant, database};
+use crate::engine::{constant, database, engine};
 use crate::engine::mode::Mode;
 use crate::h2_rust_common::{Byte, h2_rust_constant, Integer, VecRef};
 use crate::h2_rust_common::h2_rust_cell::H2RustCell;
 use crate::message::db_error::DbError;
+use crate::mode::default_null_ordering;
 use crate::mode::default_null_ordering::DefaultNullOrdering;
 use crate::store::{file_lock, file_lock_method};
 use crate::store::file_lock_method::FileLockMethod;
 use crate::store::fs::encrypt::file_encrypt;
 use crate::store::fs::file_utils;
-use crate::{build_option_arc_h2RustCell, get_ref_mut, throw};
-use crate::util::string_utils;
+use crate::{build_option_arc_h2RustCell, get_ref, get_ref_mut, throw};
+use crate::util::{string_utils, utils};
 
 #[derive(Default)]
 pub struct Database {
@@ -34,6 +37,8 @@ pub struct Database {
     pub page_size: Integer,
     database_short_name: String,
     pub read_only: bool,
+    /// RECOVER,以恢复模式打开store
+    pub recover: bool,
     file_lock_method: FileLockMethod,
     database_url: String,
     mode: Option<&'static Mode>,
@@ -43,6 +48,16 @@ pub struct Database {
     lock_mode: Integer,
     starting: AtomicBool,
     store: StoreRef,
+    unnamed_in_memory: bool,
+
+    /// DB_CLOSE_DELAY,单位秒,0表示最后1个session关闭时立即关闭,-1表示一直保留到进程退出
+    close_delay: Integer,
+    session_count: AtomicI32,
+    closing: AtomicBool,
+    /// add_session/remove_session和延迟关闭的检查都在这个锁里进行
+    session_lock: Mutex<()>,
+    /// 每次add_session加1,延迟关闭时据此判断期间是否有session连接过
+    session_generation: AtomicU64,
 }
 
 pub type DatabaseRef = Option<Arc<H2RustCell<Database>>>;
@@ -62,6 +77,8 @@ impl Database {
 
         databaseMutRef.db_settings = connectionInfoMutRef.get_db_settings()?;
         databaseMutRef.persistent = connectionInfoMutRef.persistent;
+        databaseMutRef.unnamed_in_memory = connectionInfoMutRef.unnamed_in_memory;
+        databaseMutRef.close_delay = connectionInfoMutRef.get_property_int("DB_CLOSE_DELAY", 0)?;
 
         databaseMutRef.file_password_hash = connectionInfoMutRef.file_password_hash.clone();
         databaseMutRef.database_path = connectionInfoMutRef.get_database_path()?;
@@ -74,6 +91,11 @@ impl Database {
         // database.database_short_name = Self::parse_database_short_name(this.clone());
         databaseMutRef.database_short_name = databaseMutRef.parse_database_short_name();
 
+        // 只支持AES(XTS模式)
+        if !databaseMutRef.cipher.is_empty() && !"AES".eq(&string_utils::to_upper_english(&databaseMutRef.cipher)) {
+            throw!(DbError::get_unsupported_exception(&format!("CIPHER={}", databaseMutRef.cipher)));
+        }
+
         if !databaseMutRef.cipher.is_empty() && databaseMutRef.page_size % file_encrypt::BLOCK_SIZE != 0 {
             throw!( DbError::get_unsupported_exception(&format!("CIPHER && PAGE_SIZE={}",  databaseMutRef.page_size)));
         }
@@ -83,6 +105,8 @@ impl Database {
             databaseMutRef.read_only = true;
         }
 
+        databaseMutRef.recover = utils::parse_bool(&connectionInfoMutRef.get_property_string("RECOVER", h2_rust_constant::EMPTY_STR), false, false)?;
+
         let lock_method_name = connectionInfoMutRef.get_property_string("FILE_LOCK", h2_rust_constant::EMPTY_STR);
         databaseMutRef.file_lock_method =
             if !lock_method_name.is_empty() {
@@ -162,11 +186,94 @@ impl Database {
             }
 
             databaseMutRef.store = Store::new(database_ref.clone(), connectionInfoMutRef.file_encryption_key.clone())?;
+
+            if let Some(s) = connectionInfoMutRef.get_property("RETENTION_TIME") {
+                match Integer::from_str_radix(&s, 10) {
+                    Ok(retention_time) => databaseMutRef.set_retention_time(retention_time),
+                    Err(_) => throw!(DbError::get_invalid_value_exception("RETENTION_TIME", &s)),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 已经开始关闭的数据库不能再连接
+    pub fn add_session(&self) -> Result<()> {
+        let session_guard = self.session_lock.lock().unwrap();
+        if self.is_closing() {
+            throw!(DbError::get(error_code::DATABASE_IS_CLOSED, vec![]));
+        }
+
+        self.session_count.fetch_add(1, Ordering::AcqRel);
+        self.session_generation.fetch_add(1, Ordering::AcqRel);
+        Ok(())
+    }
+
+    /// 最后1个session关闭之后根据DB_CLOSE_DELAY关闭数据库,内存中的数据库关闭之后数据也就没有了
+    pub fn remove_session(database_ref: DatabaseRef) -> Result<()> {
+        let database = get_ref!(database_ref);
+        let session_guard = database.session_lock.lock().unwrap();
+        if database.session_count.fetch_sub(1, Ordering::AcqRel) > 1 {
+            return Ok(());
+        }
+
+        // 没有名字的内存数据库不会再被连接上
+        let close_delay = if database.unnamed_in_memory { 0 } else { database.close_delay };
+
+        match close_delay {
+            0 => get_ref_mut!(database_ref).close(),
+            close_delay if close_delay < 0 => Ok(()),
+            close_delay => {
+                let session_generation = database.session_generation.load(Ordering::Acquire);
+                let database_ref = database_ref.clone();
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_secs(close_delay as u64));
+
+                    // 期间有新的session连上来的话不关闭,即使它已经断开,那时会有另外的线程负责关闭
+                    let database = get_ref!(database_ref);
+                    let session_guard = database.session_lock.lock().unwrap();
+                    if database.session_count.load(Ordering::Acquire) <= 0 &&
+                        database.session_generation.load(Ordering::Acquire) == session_generation {
+                        let _ = get_ref_mut!(database_ref).close();
+                    }
+                });
+
+                Ok(())
+            }
+        }
+    }
+
+    pub fn close(&mut self) -> Result<()> {
+        if self.closing.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+
+        if !self.unnamed_in_memory {
+            engine::remove_database(&self.database_path);
+        }
+
+        if self.store.is_some() {
+            get_ref!(self.store).close()?;
         }
 
         Ok(())
     }
 
+    pub fn is_closing(&self) -> bool {
+        self.closing.load(Ordering::Acquire)
+    }
+
+    /// SET DB_CLOSE_DELAY, see command::set::Set
+    pub fn set_close_delay(&mut self, close_delay: Integer) {
+        self.close_delay = close_delay;
+    }
+
+    /// SET RETENTION_TIME, see command::set::Set,单位毫秒,负数表示chunk永远不会被覆盖
+    pub fn set_retention_time(&mut self, retention_time: Integer) {
+        get_ref!(self.store).set_retention_time(retention_time);
+    }
+
     fn parse_database_short_name(&self) -> String {
         //  let binding = (&*this).borrow_mut();
         //  let database = binding.unwrap();
@@ -229,4 +336,100 @@ impl Database {
             Ok(h2_rust_constant::EMPTY_STR.to_string())
         }
     }
-}
\ No newline at end of file
+
+    /// 没有通过DEFAULT_NULL_ORDERING指定时同java默认为LOW
+    pub fn get_default_null_ordering(&self) -> &'static DefaultNullOrdering {
+        self.default_null_ordering.unwrap_or(&default_null_ordering::LOW)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::thread;
+    use std::time::Duration;
+    use crate::api::error_code;
+    use crate::command::set::Set;
+    use crate::command::set_types;
+    use crate::engine::connection_info::ConnectionInfo;
+    use crate::engine::database::Database;
+    use crate::{get_ref, get_ref_mut};
+    use crate::message::db_error::DbError;
+    use crate::h2_rust_common::h2_rust_constant;
+
+    fn open(url: &str) -> crate::engine::database::DatabaseRef {
+        let mut connection_info = ConnectionInfo::new(url.to_string(), &HashMap::new(), "sa".to_string(), String::new()).unwrap();
+        Database::new(&mut connection_info, &h2_rust_constant::EMPTY_STR.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_close_delay() {
+        // 默认最后1个session关闭时关闭
+        let database = open("jdbc:h2:mem:test_close_delay_0");
+        get_ref!(database).add_session().unwrap();
+        get_ref!(database).add_session().unwrap();
+        Database::remove_session(database.clone()).unwrap();
+        assert!(!get_ref!(database).is_closing());
+        Database::remove_session(database.clone()).unwrap();
+        assert!(get_ref!(database).is_closing());
+        assert!(get_ref!(get_ref!(database).store).is_closed());
+
+        let database = open("jdbc:h2:mem:test_close_delay_1;DB_CLOSE_DELAY=-1");
+        get_ref!(database).add_session().unwrap();
+        Database::remove_session(database.clone()).unwrap();
+        assert!(!get_ref!(database).is_closing());
+        assert!(!get_ref!(get_ref!(database).store).is_closed());
+
+        // 没有名字的内存数据库总是立即关闭
+        let database = open("jdbc:h2:mem:;DB_CLOSE_DELAY=-1");
+        get_ref!(database).add_session().unwrap();
+        Database::remove_session(database.clone()).unwrap();
+        assert!(get_ref!(database).is_closing());
+        assert!(get_ref!(database).add_session().is_err());
+    }
+
+    #[test]
+    fn test_retention_time() {
+        let database = open("jdbc:h2:mem:test_retention_time;RETENTION_TIME=1000");
+        assert_eq!(get_ref!(get_ref!(database).store).get_retention_time(), 1000);
+
+        Set::new(set_types::RETENTION_TIME, 0).update(get_ref_mut!(database)).unwrap();
+        assert_eq!(get_ref!(get_ref!(database).store).get_retention_time(), 0);
+        Set::new(set_types::RETENTION_TIME, -1).update(get_ref_mut!(database)).unwrap();
+        assert_eq!(get_ref!(get_ref!(database).store).get_retention_time(), -1);
+        let error = Set::new(set_types::THROTTLE, 1).update(get_ref_mut!(database)).unwrap_err();
+        assert_eq!(error.downcast_ref::<DbError>().unwrap().error_code, error_code::FEATURE_NOT_SUPPORTED_1);
+        get_ref_mut!(database).close().unwrap();
+
+        let mut connection_info = ConnectionInfo::new("jdbc:h2:mem:test_retention_time_1;RETENTION_TIME=x".to_string(),
+                                                      &HashMap::new(), "sa".to_string(), String::new()).unwrap();
+        let error = Database::new(&mut connection_info, &h2_rust_constant::EMPTY_STR.to_string()).err().unwrap();
+        assert_eq!(error.downcast_ref::<DbError>().unwrap().error_code, error_code::INVALID_VALUE_2);
+    }
+
+    #[test]
+    fn test_close_delay_reconnect() {
+        let database = open("jdbc:h2:mem:test_close_delay_reconnect;DB_CLOSE_DELAY=1");
+        get_ref!(database).add_session().unwrap();
+        Database::remove_session(database.clone()).unwrap();
+
+        // 延迟期间重新连接又断开,第1个线程到时间后不关闭,由断开时的线程在1秒后关闭
+        thread::sleep(Duration::from_millis(200));
+        get_ref!(database).add_session().unwrap();
+        thread::sleep(Duration::from_millis(200));
+        Database::remove_session(database.clone()).unwrap();
+        thread::sleep(Duration::from_millis(800));
+        assert!(!get_ref!(database).is_closing());
+
+        // 重新连接之后一直保持连接
+        get_ref!(database).add_session().unwrap();
+        thread::sleep(Duration::from_millis(1200));
+        assert!(!get_ref!(database).is_closing());
+        assert!(!get_ref!(get_ref!(database).store).is_closed());
+
+        Database::remove_session(database.clone()).unwrap();
+        thread::sleep(Duration::from_millis(1500));
+        assert!(get_ref!(database).is_closing());
+        assert!(get_ref!(get_ref!(database).store).is_closed());
+    }
+}
@@ -117,6 +117,11 @@ fn open_session1(connection_info: &mut ConnectionInfo,
     todo!()
 }
 
+/// 数据库关闭之后从中移除,再次连接的时候会重新打开
+pub fn remove_database(database_path: &str) {
+    DATABASE_PATH_DATABASE_HOLDER.lock().unwrap().remove(database_path);
+}
+
 fn throw_not_found(if_exist: bool, forbid_creation: bool, name: &str) -> Result<()> {
     if if_exist {
         throw!(DbError::get(error_code::DATABASE_NOT_FOUND_WITH_IF_EXISTS_1, vec![name]));
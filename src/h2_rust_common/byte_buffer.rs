@@ -10,7 +10,7 @@ pub struct ByteBuffer {
 }
 
 pub fn allocate(size: usize) -> ByteBuffer {
-    let data = Vec::with_capacity(size);
+    let data = vec![0; size];
 
     wrapVec(data)
 }
@@ -22,11 +22,13 @@ pub fn wrapSlice(slice: &[u8]) -> ByteBuffer {
         byteBuffer.data[suffix_plus_plus!(byteBuffer.position)] = *v;
     }
 
+    byteBuffer.position = 0;
+
     byteBuffer
 }
 
 pub fn wrapVec(vec: Vec<u8>) -> ByteBuffer {
-    let capacity = vec.capacity();
+    let capacity = vec.len();
     let limit = capacity;
 
     ByteBuffer {
@@ -127,7 +129,7 @@ impl ByteBuffer {
         (self.data[oldPosition] as i32) << 24 |
             (self.data[oldPosition + 1] as i32) << 16 |
             (self.data[oldPosition + 2] as i32) << 8 |
-            (self.data[oldPosition + 3] as i32) << 24
+            (self.data[oldPosition + 3] as i32)
     }
 
     pub fn putI32(&mut self, a: i32) {
@@ -141,6 +143,36 @@ impl ByteBuffer {
         self.data[oldPosition + 3] = a as u8;
     }
 
+    pub fn putI32At(&mut self, index: usize, a: i32) {
+        if index + 4 > self.limit {
+            panic!("index + 4 > limit")
+        }
+
+        self.data[index] = (a >> 24) as u8;
+        self.data[index + 1] = (a >> 16) as u8;
+        self.data[index + 2] = (a >> 8) as u8;
+        self.data[index + 3] = a as u8;
+    }
+
+    pub fn putU16At(&mut self, index: usize, u: u16) {
+        if index + 2 > self.limit {
+            panic!("index + 2 > limit")
+        }
+
+        self.data[index] = (u >> 8) as u8;
+        self.data[index + 1] = u as u8;
+    }
+
+    pub fn putI64(&mut self, a: i64) {
+        let oldPosition = self.position;
+
+        self.setPosition(self.position + 8);
+
+        for a0 in 0..8 {
+            self.data[oldPosition + a0] = (a >> (56 - 8 * a0)) as u8;
+        }
+    }
+
     pub fn getI64(&mut self) -> i64 {
         let oldPosition = self.position;
 
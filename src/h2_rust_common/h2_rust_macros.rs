@@ -205,7 +205,7 @@ macro_rules! db_error_template {
 #[macro_export]
 macro_rules! load_atomic {
     ($atomic:expr) => {
-        $atomic.load(Ordering::Acquired)
+        $atomic.load(Ordering::Acquire)
     };
 }
 
@@ -1,11 +1,22 @@
+use std::any::Any;
 use std::sync::Arc;
 use crate::{enum_str, get_ref};
-use crate::h2_rust_common::{Integer, Void};
+use crate::h2_rust_common::{Double, Integer, Long, Void};
 use crate::h2_rust_common::h2_rust_cell::H2RustCell;
 
 pub enum H2RustType {
     String(Arc<H2RustCell<String>>),
     Integer(Integer),
+    Long(Long),
+    Double(Double),
+    Boolean(bool),
+    Bytes(Arc<H2RustCell<Vec<u8>>>),
+    /// most significant bits, least significant bits
+    Uuid(Long, Long),
+    /// 任意的rust类型,由SerdeDataType负责比较和序列化
+    Object(Arc<dyn Any + Send + Sync>),
+    /// 一行多列的值,由RowDataType负责比较和序列化
+    Array(Arc<H2RustCell<Vec<H2RustType>>>),
     Null,
 }
 
@@ -14,6 +25,13 @@ impl Clone for H2RustType {
         match self {
             H2RustType::String(a) => { H2RustType::String(a.clone()) }
             H2RustType::Integer(a) => { H2RustType::Integer(a.clone()) }
+            H2RustType::Long(a) => { H2RustType::Long(*a) }
+            H2RustType::Double(a) => { H2RustType::Double(*a) }
+            H2RustType::Boolean(a) => { H2RustType::Boolean(*a) }
+            H2RustType::Bytes(a) => { H2RustType::Bytes(a.clone()) }
+            H2RustType::Uuid(high, low) => { H2RustType::Uuid(*high, *low) }
+            H2RustType::Object(a) => { H2RustType::Object(a.clone()) }
+            H2RustType::Array(a) => { H2RustType::Array(a.clone()) }
             H2RustType::Null => { H2RustType::Null }
         }
     }
@@ -29,6 +47,55 @@ impl H2RustType {
         }
     }
 
+    pub fn castAsInteger(&self) -> Integer {
+        match self {
+            Self::Integer(i) => *i,
+            _ => panic!("need integer")
+        }
+    }
+
+    pub fn castAsLong(&self) -> Long {
+        match self {
+            Self::Long(l) => *l,
+            _ => panic!("need long")
+        }
+    }
+
+    pub fn castAsDouble(&self) -> Double {
+        match self {
+            Self::Double(d) => *d,
+            _ => panic!("need double")
+        }
+    }
+
+    pub fn castAsBoolean(&self) -> bool {
+        match self {
+            Self::Boolean(b) => *b,
+            _ => panic!("need boolean")
+        }
+    }
+
+    pub fn castAsBytesRef(&self) -> &Vec<u8> {
+        match self {
+            Self::Bytes(b) => { b.get_ref() }
+            _ => panic!("need bytes")
+        }
+    }
+
+    pub fn castAsObjectRef<T: 'static>(&self) -> &T {
+        match self {
+            Self::Object(o) => o.downcast_ref::<T>().expect("object of another type"),
+            _ => panic!("need object")
+        }
+    }
+
+    pub fn castAsArrayRef(&self) -> &Vec<H2RustType> {
+        match self {
+            Self::Array(a) => { a.get_ref() }
+            _ => panic!("need array")
+        }
+    }
+
     pub fn isNull(&self) -> bool {
         match self {
             H2RustType::Null => true,
@@ -44,4 +111,3 @@ impl H2RustType {
         }
     }
 }
-
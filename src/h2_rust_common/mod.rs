@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use std::error::Error;
 use std::sync::Weak;
 use std::sync::{Arc, Mutex, MutexGuard};
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::thread;
 use crate::h2_rust_common::h2_rust_constant::{NEGATIVE, POSITIVE};
 use anyhow::Result;
@@ -29,6 +29,7 @@ pub type Byte = i8;
 pub type UnsignedByte = u8;
 pub type Void = ();
 pub type Short = i16;
+pub type Double = f64;
 
 pub type VecRef<T> = Option<Arc<Vec<T>>>;
 
@@ -40,21 +41,35 @@ pub fn throw<T, E: Error + Send + Sync + 'static>(e: E) -> Result<T> {
 pub struct MyMutex<T> {
     mutex: ReentrantMutex<T>,
     ownerThreadId: AtomicU64,
+    /// 当前线程重入的次数,最外层的guard释放时才清掉ownerThreadId
+    holdCount: AtomicU32,
 }
 
 pub struct MyMutexGuard<'a, T: ?Sized + 'a> {
     mutexGuard: ReentrantMutexGuard<'a, T>,
     ownerThreadIdRef: &'a AtomicU64,
+    holdCountRef: &'a AtomicU32,
 }
 
 impl<T> MyMutex<T> {
     pub fn lock(&self) -> MyMutexGuard<T> {
         let reentrantMutexGuard = self.mutex.lock();
+        self.newGuard(reentrantMutexGuard)
+    }
+
+    /// 拿不到锁时返回None,不阻塞
+    pub fn tryLock(&self) -> Option<MyMutexGuard<'_, T>> {
+        let reentrantMutexGuard = self.mutex.try_lock()?;
+        Some(self.newGuard(reentrantMutexGuard))
+    }
 
+    fn newGuard<'a>(&'a self, reentrantMutexGuard: ReentrantMutexGuard<'a, T>) -> MyMutexGuard<'a, T> {
+        self.holdCount.fetch_add(1, Ordering::AcqRel);
         self.ownerThreadId.store(thread::current().id().as_u64().get(), Ordering::Release);
         MyMutexGuard {
             mutexGuard: reentrantMutexGuard,
             ownerThreadIdRef: &self.ownerThreadId,
+            holdCountRef: &self.holdCount,
         }
     }
 
@@ -66,7 +81,9 @@ impl<T> MyMutex<T> {
 
 impl<'a, T: ?Sized + 'a> Drop for MyMutexGuard<'a, T> {
     fn drop(&mut self) {
-        self.ownerThreadIdRef.store(0, Ordering::Release);
+        if self.holdCountRef.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.ownerThreadIdRef.store(0, Ordering::Release);
+        }
     }
 }
 
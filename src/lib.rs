@@ -0,0 +1,21 @@
+#![feature(thread_id_value)]
+#![feature(concat_idents)]
+#![allow(unused_assignments, unused_imports, dead_code, unused_variables, unused_must_use, non_snake_case, unused_macros)]
+
+mod jdbc;
+mod test;
+mod h2_rust_common;
+mod engine;
+mod message;
+mod api;
+mod command;
+mod util;
+mod store;
+mod mode;
+mod result;
+mod db;
+mod mvstore;
+mod compress;
+
+/// MVStoreTool的命令行入口,src/bin/mv_store_tool.rs只用到这1个
+pub use mvstore::mv_store_tool::main as mv_store_tool_main;
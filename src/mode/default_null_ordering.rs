@@ -50,5 +50,40 @@ impl DefaultNullOrdering {
             _ => None
         }
     }
+
+    pub fn name(&self) -> &'static str {
+        match (self.default_asc_nulls, self.default_desc_nulls) {
+            (sort_orders::NULLS_FIRST, sort_orders::NULLS_LAST) => "LOW",
+            (sort_orders::NULLS_LAST, sort_orders::NULLS_FIRST) => "HIGH",
+            (sort_orders::NULLS_LAST, sort_orders::NULLS_LAST) => "LAST",
+            _ => "FIRST"
+        }
+    }
+
+    /// Returns a sort type bit mask with sort_orders::NULLS_FIRST or sort_orders::NULLS_LAST explicitly set
+    pub fn add_explicit_null_ordering(&self, mut sort_type: Integer) -> Integer {
+        if sort_type & (sort_orders::NULLS_FIRST | sort_orders::NULLS_LAST) == 0 {
+            sort_type |= if sort_type & sort_orders::DESCENDING == 0 {
+                self.default_asc_nulls
+            } else {
+                self.default_desc_nulls
+            };
+        }
+        sort_type
+    }
+
+    /// Compare two values where exactly one of them is NULL.
+    ///
+    /// one_was_null表示前一个是NULL,返回值已经考虑了DESCENDING,不需要再取反
+    pub fn compare_null(&self, one_was_null: bool, sort_type: Integer) -> Integer {
+        if sort_type & sort_orders::NULLS_FIRST != 0 {
+            if one_was_null { -1 } else { 1 }
+        } else if sort_type & sort_orders::NULLS_LAST != 0 {
+            if one_was_null { 1 } else { -1 }
+        } else {
+            let comp = if sort_type & sort_orders::DESCENDING == 0 { self.null_asc } else { self.null_desc };
+            if one_was_null { comp } else { -comp }
+        }
+    }
 }
 
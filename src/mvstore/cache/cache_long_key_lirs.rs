@@ -1,24 +1,26 @@
 use std::cmp;
-use std::env::var;
-use std::ops::Deref;
-use std::sync::{Arc, Weak};
-use usync::lock_api::RawMutex;
-use usync::ReentrantMutex;
-use crate::{build_option_arc_h2RustCell, db_error_template, get_ref, get_ref_mut, h2_rust_cell_equals, suffix_minus_minus, suffix_plus_plus, throw, unsigned_right_shift};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use usync::RwLock;
+use crate::{build_option_arc_h2RustCell, get_ref, get_ref_mut, h2_rust_cell_equals, suffix_minus_minus, suffix_plus_plus, throw, unsigned_right_shift};
 use crate::api::error_code;
-use crate::h2_rust_common::{Downgrade, Integer, IntoOriginal, IntoWeak, Long, MyMutex, Optional, ULong, Upgrade};
+use crate::h2_rust_common::{Downgrade, Integer, IntoOriginal, IntoWeak, Long, Optional, ULong, Upgrade};
 use crate::h2_rust_common::h2_rust_cell::{H2RustCell, SharedPtr};
 use crate::mvstore::data_utils;
-use crate::mvstore::page::{get, Page};
-use crate::h2_rust_common::UInteger;
 use crate::message::db_error::DbError;
 use anyhow::Result;
 
+/// A scan resistant cache that uses keys of type long. It is meant to cache objects that are relatively costly to acquire,
+/// for example file content.
+///
+/// 每个Segment各自加锁,get/put/remove/clear只需要&self。
+/// 命中时只持有读锁,需要调整stack的时候用try_write,拿不到写锁就跳过调整,所以并发读之间不会互相阻塞
 #[derive(Default)]
 pub struct CacheLongKeyLIRS<V, R> {
     /// the maximum memory this cache should use.
     maxMemory: Long,
-    segmentArr: Option<Vec<SharedPtr<Segment<V, R>>>>,
+    /// 段的数量是固定的,resize时在段的写锁内替换段的内容
+    segmentArr: Vec<RwLock<Segment<V, R>>>,
     segmentCount: Integer,
     segmentShift: Integer,
     segmentMask: Integer,
@@ -28,108 +30,153 @@ pub struct CacheLongKeyLIRS<V, R> {
 }
 
 impl<V: Default + Clone + Optional + Downgrade<V, R>, R: Default + Clone + Optional + Upgrade<V>> CacheLongKeyLIRS<V, R> {
-    pub fn new(config: &CacheLongKeyLIRSConfig) -> CacheLongKeyLIRS<V, R> {
+    pub fn new(config: &CacheLongKeyLIRSConfig) -> Result<CacheLongKeyLIRS<V, R>> {
         let mut cache_long_key_lirs = CacheLongKeyLIRS::default();
-        cache_long_key_lirs.init(config);
-        cache_long_key_lirs
+        cache_long_key_lirs.init(config)?;
+        Ok(cache_long_key_lirs)
     }
 
-    pub fn init(&mut self, config: &CacheLongKeyLIRSConfig) {
-        data_utils::check_argument(Integer::count_ones(config.segment_count) == 1, "segment count must be a power of 2");
-        self.segmentCount = config.segment_count;
-
-        self.setMaxMemory(config.max_memory);
+    pub fn init(&mut self, config: &CacheLongKeyLIRSConfig) -> Result<()> {
+        self.setMaxMemory(config.max_memory)?;
         self.non_resident_queue_size = config.non_resident_queue_size;
         self.non_resident_queue_size_high = config.non_resident_queue_size_high;
 
+        data_utils::check_argument(config.segment_count > 0 && Integer::count_ones(config.segment_count) == 1,
+                                   &format!("The segment count must be a power of 2, is {}", config.segment_count))?;
+        self.segmentCount = config.segment_count;
         self.segmentMask = self.segmentCount - 1;
         self.stackMoveDistance = config.stack_move_distance;
-        self.segmentArr = Some(Vec::<SharedPtr<Segment<V, R>>>::with_capacity(self.segmentCount as usize));
-
-        self.clear();
+        self.segmentArr = (0..self.segmentCount).map(|_| RwLock::new(self.newSegment())).collect();
 
         // use the high bits for the segment
         self.segmentShift = (32 - Integer::count_ones(self.segmentMask)) as Integer;
+
+        Ok(())
     }
 
-    pub fn setMaxMemory(&mut self, max_memory: Long) {
-        data_utils::check_argument(max_memory > 0, "Max memory must be larger than 0");
+    pub fn setMaxMemory(&mut self, max_memory: Long) -> Result<()> {
+        data_utils::check_argument(max_memory > 0, "Max memory must be larger than 0")?;
         self.maxMemory = max_memory;
 
-        if self.segmentArr.is_some() {
-            let segment_arr = self.segmentArr.as_mut().unwrap();
-            let max = 1 + max_memory / segment_arr.len() as Long;
-            for segment_ref in segment_arr {
-                get_ref_mut!(segment_ref).maxMemory = max;
+        if !self.segmentArr.is_empty() {
+            let max = 1 + max_memory / self.segmentArr.len() as Long;
+            for segment in self.segmentArr.iter_mut() {
+                segment.get_mut().maxMemory = max;
             }
         }
+
+        Ok(())
     }
 
-    pub fn clear(&mut self) {
-        let max = self.get_max_item_size();
-        let segment_arr = self.segmentArr.as_mut().unwrap();
-        for _ in 0..self.segmentCount {
-            segment_arr.push(build_option_arc_h2RustCell!(Segment::<V,R>::new5(
-                max,
-                self.stackMoveDistance,
-                8,
-                self.non_resident_queue_size,
-                self.non_resident_queue_size_high)));
+    /// Remove all entries.
+    pub fn clear(&self) {
+        for segment in &self.segmentArr {
+            *segment.write() = self.newSegment();
         }
     }
 
+    fn newSegment(&self) -> Segment<V, R> {
+        Segment::<V, R>::new5(self.get_max_item_size(),
+                              self.stackMoveDistance,
+                              8,
+                              self.non_resident_queue_size,
+                              self.non_resident_queue_size_high)
+    }
+
     /// determines max size of the data item size to fit into cache
     pub fn get_max_item_size(&self) -> Long {
         cmp::max(1, self.maxMemory / self.segmentCount as Long)
     }
 
-    pub fn put(&mut self, key: Long, value: V, memory: Integer) -> Result<()> {
+    /// Get the number of segments, set by cacheConcurrency of the MVStore.
+    pub fn getSegmentCount(&self) -> Integer {
+        self.segmentCount
+    }
+
+    /// Add an entry to the cache. The entry may or may not exist in the cache yet.
+    /// This method will usually mark unknown entries as cold and known entries as hot.
+    pub fn put(&self, key: Long, value: V, memory: Integer) -> Result<()> {
         if value.isNone() {
             throw!(DbError::get(error_code::GENERAL_ERROR_1,vec!["The value may not be null"]));
         }
 
         let hash = getHash(key);
-        let segmentIndex = self.getSegmentIndex(hash);
-        let segmentRef = self.segmentArr.as_ref().unwrap().get(segmentIndex as usize).unwrap();
-
-        // check whether resize is required: synchronize on s, to avoid
-        // concurrent resizes (concurrent reads read from the old segment)
-        let segmentClone = segmentRef.clone();
-        let clone = get_ref!(segmentClone).reentrantMutexPtr.clone();
-        let mutexGuard = get_ref!(clone).lock();
-
-        let segmentRef = self.resizeIfNeeded(segmentRef.clone(), segmentIndex as usize);
-        get_ref_mut!(segmentRef).put(key, hash, value, memory);
+        let mut segment = self.getSegment(hash).write();
+        Self::resizeIfNeeded(&mut segment);
+        segment.put(key, hash, value, memory);
 
         Ok(())
     }
 
-    fn resizeIfNeeded(&mut self, mut segmentRef: SharedPtr<Segment<V, R>>, segmentIndex: usize) -> SharedPtr<Segment<V, R>> {
-        let newLen = get_ref!(segmentRef).getNewMapLen();
-        if newLen == 0 {
-            return segmentRef;
+    /// Remove an entry. Both resident and non-resident entries can be removed.
+    ///
+    /// return the old value, or None if there was no resident entry
+    pub fn remove(&self, key: Long) -> V {
+        let hash = getHash(key);
+        let mut segment = self.getSegment(hash).write();
+        Self::resizeIfNeeded(&mut segment);
+        segment.remove(key, hash)
+    }
+
+    /// 持有写锁,其它线程不会同时resize
+    fn resizeIfNeeded(segment: &mut Segment<V, R>) {
+        let newLen = segment.getNewMapLen();
+        if newLen != 0 {
+            *segment = Segment::<V, R>::new2(segment, newLen);
         }
+    }
 
-        // another thread might have resized (as we retrieved the segment before synchronizing on it)
-        let s2 = self.segmentArr.as_ref().unwrap().get(segmentIndex);
-        if segmentRef.as_ref().unwrap().equals(s2.unwrap().as_ref().unwrap()) {
-            // no other thread resized, so we do
-            segmentRef = Segment::<V, R>::new2(segmentRef.clone(), newLen);
-            self.segmentArr.as_mut().unwrap().insert(segmentIndex, segmentRef.clone());
+    /// Get the value for the given key if the entry is cached. This method adjusts the internal state of the cache sometimes,
+    /// to ensure commonly used entries stay in the cache.
+    pub fn get(&self, key: Long) -> V {
+        let hash = getHash(key);
+        let segment = self.getSegment(hash);
+
+        let (value, needAccess) = segment.read().get(key, hash);
+        if needAccess {
+            // 其它线程持有锁时放弃这次调整,不影响结果的正确性
+            if let Some(mut segment) = segment.try_write() {
+                let entry = segment.find(key, hash);
+                if entry.is_some() && get_ref!(entry).getValue().isSome() {
+                    segment.access(entry);
+                }
+            }
         }
 
-        return segmentRef;
+        value
     }
 
-    pub fn get(&mut self, key: Long) -> V {
-        let hash = getHash(key);
-        let segment_ref = self.get_segment(hash);
-        let entry_ref = get_ref!(segment_ref).find(key, hash);
-        get_ref_mut!(segment_ref).get(entry_ref) // 因为该函数内部需要V上有Optional相应函数使得CacheLongKeyLIRS的V也要实现Optional,部下污染了上头
+    /// Get the number of cache hits.
+    pub fn getHits(&self) -> Long {
+        self.sumSegments(|segment| segment.hits.load(Ordering::Relaxed))
+    }
+
+    /// Get the number of cache misses.
+    pub fn getMisses(&self) -> Long {
+        self.sumSegments(|segment| segment.misses.load(Ordering::Relaxed))
+    }
+
+    /// Get the number of resident entries that were evicted (became non-resident).
+    pub fn getEvictions(&self) -> Long {
+        self.sumSegments(|segment| segment.evictions)
+    }
+
+    /// Get the currently used memory.
+    pub fn getUsedMemory(&self) -> Long {
+        self.sumSegments(|segment| segment.usedMemory)
+    }
+
+    /// Get the maximum memory to use.
+    pub fn getMaxMemory(&self) -> Long {
+        self.maxMemory
+    }
+
+    fn sumSegments(&self, f: impl Fn(&Segment<V, R>) -> Long) -> Long {
+        self.segmentArr.iter().map(|segment| f(&segment.read())).sum()
     }
 
-    fn get_segment(&self, hash: Integer) -> &SharedPtr<Segment<V, R>> {
-        self.segmentArr.as_ref().unwrap().get(self.getSegmentIndex(hash) as usize).unwrap()
+    fn getSegment(&self, hash: Integer) -> &RwLock<Segment<V, R>> {
+        &self.segmentArr[self.getSegmentIndex(hash) as usize]
     }
 
     fn getSegmentIndex(&self, hash: Integer) -> Integer {
@@ -140,9 +187,8 @@ impl<V: Default + Clone + Optional + Downgrade<V, R>, R: Default + Clone + Optio
 fn getHash(key: Long) -> Integer {
     let mut hash = ((key as ULong >> 32) as Long ^ key) as Integer;
     // a supplemental secondary hash function to protect against hash codes that don't differ much
-    hash = unsigned_right_shift!(hash, 16, Integer);
-    hash = (unsigned_right_shift!(hash, 16, Integer) ^ hash) * 0x45d9f3b;
-    hash = (unsigned_right_shift!(hash, 16, Integer) ^ hash) * 0x45d9f3b;
+    hash = (unsigned_right_shift!(hash, 16, Integer) ^ hash).wrapping_mul(0x45d9f3b);
+    hash = (unsigned_right_shift!(hash, 16, Integer) ^ hash).wrapping_mul(0x45d9f3b);
     hash = unsigned_right_shift!(hash, 16, Integer) ^ hash;
     hash
 }
@@ -158,11 +204,14 @@ pub struct Segment<V, R> {
     /// The size of the LIRS queue for non-resident cold entries.
     queue2Size: Integer,
 
-    /// The number of cache hits.
-    hits: Long,
+    /// The number of cache hits, 只持有读锁时也会更新
+    hits: AtomicI64,
 
     /// The number of cache misses.
-    misses: Long,
+    misses: AtomicI64,
+
+    /// The number of resident entries that were evicted.
+    evictions: Long,
 
     /// The map array. The size is always a power of 2.
     entries: Vec<SharedPtr<Entry<V, R>>>,
@@ -210,25 +259,13 @@ pub struct Segment<V, R> {
 
     /// The number of times any item was moved to the top of the stack.
     stackMoveRoundCount: Integer,
-
-    // reentrantMutex: ReentrantMutex<()>,
-    reentrantMutexPtr: Option<Arc<H2RustCell<ReentrantMutex<()>>>>,
-
-    missCount: Integer,
-    hitCount: Integer,
 }
 
 impl<V: Default + Clone + Optional + Downgrade<V, R>, R: Default + Clone + Optional + Upgrade<V>> Segment<V, R> {
-    pub fn default1() -> Segment<V, R> {
-        let mut segment: Segment<V, R> = Default::default();
-        segment.reentrantMutexPtr = build_option_arc_h2RustCell!(ReentrantMutex::<()>::default());
-        segment
-    }
-
-    pub fn new2(old: SharedPtr<Segment<V, R>>, len: Integer) -> SharedPtr<Segment<V, R>> {
-        let old = get_ref!(old);
-
-        let mut segment = Segment::<V, R>::default1();
+    /// Create a new cache segment from an existing one. The caller must synchronize on the old segment,
+    /// to avoid concurrent modifications.
+    pub fn new2(old: &Segment<V, R>, len: Integer) -> Segment<V, R> {
+        let mut segment = Segment::<V, R>::default();
 
         Self::init5(&mut segment, old.maxMemory,
                     old.stackMoveDistance,
@@ -236,8 +273,9 @@ impl<V: Default + Clone + Optional + Downgrade<V, R>, R: Default + Clone + Optio
                     old.nonResidentQueueSize,
                     old.nonResidentQueueSizeHigh);
 
-        segment.hitCount = old.hitCount;
-        segment.missCount = old.missCount;
+        segment.hits = AtomicI64::new(old.hits.load(Ordering::Relaxed));
+        segment.misses = AtomicI64::new(old.misses.load(Ordering::Relaxed));
+        segment.evictions = old.evictions;
 
         let mut entrySharedPtr = get_ref!(old.stack).stackPrev.clone();
         while !h2_rust_cell_equals!(entrySharedPtr , old.stack) {
@@ -269,7 +307,7 @@ impl<V: Default + Clone + Optional + Downgrade<V, R>, R: Default + Clone + Optio
             entrySharedPtr = get_ref!(entrySharedPtr).queuePrev.clone();
         }
 
-        build_option_arc_h2RustCell!(segment)
+        segment
     }
 
     pub fn new5(max_memory: Long,
@@ -277,7 +315,7 @@ impl<V: Default + Clone + Optional + Downgrade<V, R>, R: Default + Clone + Optio
                 len: Integer,
                 nonResidentQueueSize: Integer,
                 nonResidentQueueSizeHigh: Integer) -> Segment<V, R> {
-        let mut segment = Segment::<V, R>::default1();
+        let mut segment = Segment::<V, R>::default();
         Self::init5(&mut segment,
                     max_memory,
                     stackMoveDistance,
@@ -317,8 +355,7 @@ impl<V: Default + Clone + Optional + Downgrade<V, R>, R: Default + Clone + Optio
         ref_mut.queuePrev = self.queue2.clone();
         ref_mut.queueNext = self.queue2.clone();
 
-        self.entries = Vec::<SharedPtr<Entry<V, R>>>::with_capacity(len as usize);
-        self.entries.fill(None);
+        self.entries = vec![None; len as usize];
     }
 
     pub fn find(&self, key: Long, hash: Integer) -> SharedPtr<Entry<V, R>> {
@@ -332,11 +369,11 @@ impl<V: Default + Clone + Optional + Downgrade<V, R>, R: Default + Clone + Optio
         entrySharedPtr.clone()
     }
 
-    pub fn get(&mut self, entry_ref: SharedPtr<Entry<V, R>>) -> V {
-        let clone = self.reentrantMutexPtr.clone();
-        let mutexGuard = get_ref!(clone).lock();
-
-        // let mutex = self.reentrant_mutex.lock();
+    /// Get the value, only needs the read lock.
+    ///
+    /// 返回的bool表示是否还需要在写锁下调用access()调整entry的位置
+    pub fn get(&self, key: Long, hash: Integer) -> (V, bool) {
+        let entry_ref = self.find(key, hash);
 
         let value = if entry_ref.is_none() {
             V::default() // 通过default()生成None
@@ -346,31 +383,41 @@ impl<V: Default + Clone + Optional + Downgrade<V, R>, R: Default + Clone + Optio
 
         // the entry was not found, or it was a non-resident entry
         if value.isNone() {
-            suffix_plus_plus!(self.missCount);
-        } else {
-            self.access(entry_ref);
-            suffix_plus_plus!(self.hitCount);
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return (value, false);
         }
 
-        return value;
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        (value, self.needAccess(&entry_ref))
+    }
+
+    /// access()对于已经在stack顶部附近的hot entry什么都不做
+    fn needAccess(&self, entry_ref: &SharedPtr<Entry<V, R>>) -> bool {
+        let entry = get_ref!(entry_ref);
+        if !entry.isHot() {
+            return true;
+        }
+
+        !h2_rust_cell_equals!(entry_ref, get_ref!(self.stack).stackNext)
+            && entry.stackNext.is_some()
+            && self.stackMoveRoundCount - entry.topMove > self.stackMoveDistance
     }
 
     fn access(&mut self, entry_ref: SharedPtr<Entry<V, R>>) {
         let entry = get_ref_mut!(entry_ref);
         if entry.isHot() { // stack体系动手
-            if h2_rust_cell_equals!(entry_ref, get_ref!(self.stack).stackNext) && entry.stackNext.is_some() {
-                if self.stackMoveRoundCount - entry.topMove > self.stackMoveDistance {
-                    // move a hot entry to the top of the stack unless it is already there
-                    let was_end = h2_rust_cell_equals!(entry_ref, get_ref!(self.stack).stackPrev);
-
-                    self.removeFromStack(entry_ref.clone());
+            if !h2_rust_cell_equals!(entry_ref, get_ref!(self.stack).stackNext) && entry.stackNext.is_some()
+                && self.stackMoveRoundCount - entry.topMove > self.stackMoveDistance {
+                // move a hot entry to the top of the stack unless it is already there
+                let was_end = h2_rust_cell_equals!(entry_ref, get_ref!(self.stack).stackPrev);
 
-                    if was_end {
-                        self.pruneStack();
-                    }
+                self.removeFromStack(entry_ref.clone());
 
-                    self.addToStack(entry_ref);
+                if was_end {
+                    self.pruneStack();
                 }
+
+                self.addToStack(entry_ref);
             }
         } else { // queue体系动手
             let value = entry.getValue();
@@ -398,23 +445,20 @@ impl<V: Default + Clone + Optional + Downgrade<V, R>, R: Default + Clone + Optio
             } else {
                 // cold entries that are not on the stack move to the front of the queue
                 self.addToQueue(self.queue.clone(), entry_ref.clone());
+            }
 
-                // in any case, the cold entry is moved to the top of the stack
-                self.addToStack(entry_ref.clone());
+            // in any case, the cold entry is moved to the top of the stack
+            self.addToStack(entry_ref.clone());
 
-                // but if newly promoted cold/non-resident is the only entry on a stack now
-                // that means last one is cold, need to prune
-                self.pruneStack();
-            }
+            // but if newly promoted cold/non-resident is the only entry on a stack now
+            // that means last one is cold, need to prune
+            self.pruneStack();
         }
     }
 
     fn put(&mut self, key: Long, hash: Integer, value: V, memory: Integer) -> V {
-        let clone = self.reentrantMutexPtr.clone();
-        let mutexGuard = get_ref!(clone).lock();
-
         let mut entry = self.find(key, hash);
-        let existed = entry.is_none();
+        let existed = entry.is_some();
 
         let mut old: V = Default::default();
         assert!(old.isNone());
@@ -448,13 +492,20 @@ impl<V: Default + Clone + Optional + Downgrade<V, R>, R: Default + Clone + Optio
             }
         }
 
+        suffix_plus_plus!(self.mapSize);
+
+        // added entries are always added to the stack
+        self.addToStack(entry.clone());
+
+        if existed {
+            // if it was there before (even non-resident), it becomes hot
+            self.access(entry);
+        }
+
         old
     }
 
     fn remove(&mut self, key: Long, hash: Integer) -> V {
-        let clone = self.reentrantMutexPtr.clone();
-        let mutexGuard = get_ref!(clone).lock();
-
         let index = (hash & self.mask) as usize;
 
         let mut entry = self.entries[index].clone();
@@ -515,7 +566,7 @@ impl<V: Default + Clone + Optional + Downgrade<V, R>, R: Default + Clone + Optio
         loop {
             self.evictBlock();
 
-            if self.usedMemory > self.maxMemory {
+            if self.usedMemory <= self.maxMemory {
                 break;
             }
         }
@@ -540,6 +591,7 @@ impl<V: Default + Clone + Optional + Downgrade<V, R>, R: Default + Clone + Optio
             get_ref_mut!(entrySharedPtr).value = V::default();
 
             self.addToQueue(self.queue2.clone(), entrySharedPtr);
+            self.evictions += 1;
 
             // the size of the non-resident-cold entries needs to be limited
             self.trimNonResidentQueue();
@@ -747,6 +799,7 @@ impl<V: Default + Clone + Optional + Downgrade<V, R>, R: Default + Clone + Optio
 
         entry.key = old.key;
         entry.value = old.value.clone();
+        entry.weakReference = old.weakReference.clone();
         entry.memory = old.memory;
 
         entry.topMove = old.topMove.clone();
@@ -763,8 +816,13 @@ impl<V: Default + Clone + Optional + Downgrade<V, R>, R: Default + Clone + Optio
         self.value.clone()
     }
 
+    /// non-resident的entry不占用内存
     pub fn getMemory(&self) -> Integer {
-        self.memory
+        if self.value.isNone() {
+            0
+        } else {
+            self.memory
+        }
     }
 }
 
@@ -803,4 +861,91 @@ impl Default for CacheLongKeyLIRSConfig {
             non_resident_queue_size_high: 12,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    use crate::build_option_arc_h2RustCell;
+    use crate::get_ref;
+    use crate::h2_rust_common::Long;
+    use crate::h2_rust_common::h2_rust_cell::{H2RustCell, SharedPtr, WeakPtr};
+    use crate::mvstore::cache::cache_long_key_lirs::{CacheLongKeyLIRS, CacheLongKeyLIRSConfig, getHash};
+
+    type Cache = CacheLongKeyLIRS<SharedPtr<Long>, WeakPtr<Long>>;
+
+    fn createCache(maxMemory: Long, segmentCount: i32) -> Cache {
+        let mut config = CacheLongKeyLIRSConfig::new();
+        config.max_memory = maxMemory;
+        config.segment_count = segmentCount;
+        CacheLongKeyLIRS::new(&config).unwrap()
+    }
+
+    #[test]
+    fn test_segment_count() {
+        assert!(CacheLongKeyLIRS::<SharedPtr<Long>, WeakPtr<Long>>::new(&CacheLongKeyLIRSConfig {
+            segment_count: 3,
+            ..CacheLongKeyLIRSConfig::new()
+        }).is_err());
+        assert_eq!(createCache(100, 4).getSegmentCount(), 4);
+    }
+
+    #[test]
+    fn test_concurrent() {
+        let cache = Arc::new(createCache(400, 4));
+        let threads: Vec<_> = (0..8).map(|t| {
+            let cache = cache.clone();
+            thread::spawn(move || {
+                for i in 0..20000 {
+                    let key = (i * 7 + t) % 1000;
+                    match i % 10 {
+                        0 => { cache.put(key, build_option_arc_h2RustCell!(key), 1).unwrap(); }
+                        1 => { cache.remove(key); }
+                        _ => {
+                            let value = cache.get(key);
+                            if value.is_some() {
+                                assert_eq!(*get_ref!(value), key);
+                            }
+                        }
+                    }
+                }
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // 8个线程各16000次get
+        assert_eq!(cache.getHits() + cache.getMisses(), 8 * 16000);
+        assert!(cache.getUsedMemory() <= cache.getMaxMemory() + 4);
+
+        cache.clear();
+        assert_eq!(cache.getUsedMemory(), 0);
+        assert!(cache.get(1).is_none());
+    }
+
+    #[test]
+    fn test_hit_does_not_block() {
+        let cache = Arc::new(createCache(1000, 4));
+        for key in 0..100 {
+            cache.put(key, build_option_arc_h2RustCell!(key), 1).unwrap();
+        }
+
+        // 另一个读者持有同一个段的读锁时,命中不需要等待
+        let key = 5;
+        let segment = cache.getSegment(getHash(key)).read();
+        let (sender, receiver) = mpsc::channel();
+        let cacheClone = cache.clone();
+        thread::spawn(move || {
+            for _ in 0..100 {
+                assert!(cacheClone.get(key).is_some());
+            }
+            sender.send(()).unwrap();
+        });
+        assert!(receiver.recv_timeout(Duration::from_secs(10)).is_ok());
+        drop(segment);
+    }
+}
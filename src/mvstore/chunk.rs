@@ -12,19 +12,20 @@ use crate::mvstore::{chunk, data_utils, mv_store};
 use crate::{build_option_arc_h2RustCell, get_ref, get_ref_mut, throw};
 use crate::h2_rust_common::byte_buffer::ByteBuffer;
 use crate::mvstore::file_store::{FileStore};
+use crate::mvstore::write_buffer::WriteBuffer;
 use crate::util::string_utils;
 
 /// The maximum chunk id.
 pub const MAX_ID: Integer = (1 << 26) - 1;
 
 /// The maximum length of a chunk header, in bytes.
-static MAX_HEADER_LENGTH: Integer = 1024;
+pub const MAX_HEADER_LENGTH: Integer = 1024;
 
 /// The length of the chunk footer. The longest footer is:
 ///
 /// chunk:ffffffff,block:ffffffffffffffff,
 /// version:ffffffffffffffff,fletcher:ffffffff
-static FOOTER_LENGTH: Integer = 128;
+pub const FOOTER_LENGTH: Integer = 128;
 
 const ATTR_CHUNK: &str = "chunk";
 const ATTR_BLOCK: &str = "block";
@@ -50,7 +51,6 @@ pub struct Chunk {
     pub id: Integer,
     pub version: Long,
     pub layoutRootPos: Long,
-    pub map_id: Integer,
     pub block: AtomicI64,
     /// 占有了多少个block
     pub blockCount: Integer,
@@ -69,11 +69,18 @@ pub struct Chunk {
 }
 
 impl Chunk {
+    pub fn new1(id: Integer) -> Chunk {
+        Chunk {
+            id,
+            ..Default::default()
+        }
+    }
+
     pub fn new(s: &String) -> Result<SharedPtr<Chunk>> {
         Self::new2(data_utils::parseMap(s)?, true)
     }
 
-    fn new2(map: HashMap<String, String>, full: bool) -> Result<SharedPtr<Chunk>> {
+    pub fn new2(map: HashMap<String, String>, full: bool) -> Result<SharedPtr<Chunk>> {
         let chunk_id: Integer = data_utils::readHexIntOrLong(&map, ATTR_CHUNK, 0)?;
         if 0 >= chunk_id {
             throw!(DbError::get(error_code::FILE_CORRUPTED_1,vec![&format!("invalid chunk id {}",chunk_id)]));
@@ -104,7 +111,7 @@ impl Chunk {
             let v = map.get(ATTR_OCCUPANCY);
             if v.is_some() {
                 let byteVec = string_utils::convertHexString2ByteArr(v.unwrap())?;
-                chunk.occupancy = BitSet::from_bytes(&byteVec);
+                chunk.occupancy = occupancyFromBytes(&byteVec);
                 let cardinality = chunk.occupancy.iter().count() as Integer;
                 if chunk.pageCount - chunk.pageCountLive != cardinality {
                     throw!( DbError::get(error_code::FILE_CORRUPTED_1,
@@ -118,6 +125,29 @@ impl Chunk {
 }
 
 impl Chunk {
+    /// Read the header from the byte buffer.
+    ///
+    /// start: the start of the chunk in the file
+    pub fn readChunkHeader(byteBuffer: &mut ByteBuffer, start: Long) -> Result<SharedPtr<Chunk>> {
+        let position = byteBuffer.getPosition();
+        let length = usize::min(byteBuffer.getRemaining(), MAX_HEADER_LENGTH as usize);
+        let data = byteBuffer.extractWithPosLen(position, length);
+
+        match data.iter().position(|b| *b == b'\n') {
+            Some(index) => {
+                let s: String = data[0..index].iter().map(|b| *b as char).collect();
+                // set the position to the start of the first page
+                byteBuffer.setPosition(position + index + 1);
+
+                match fromString(&s.trim().to_string()) {
+                    Ok(chunk) => Ok(chunk),
+                    Err(_) => throw!(DbError::get(error_code::FILE_CORRUPTED_1, vec![&format!("File corrupt reading chunk at position {}", start)]))
+                }
+            }
+            None => throw!(DbError::get(error_code::FILE_CORRUPTED_1, vec![&format!("File corrupt reading chunk at position {}", start)]))
+        }
+    }
+
     pub fn isSaved(&self) -> bool {
         self.block.load(Ordering::Acquire) != Long::MAX
     }
@@ -156,6 +186,187 @@ impl Chunk {
             }
         }
     }
+
+    /// Get the chunk data as a string.
+    pub fn asString(&self) -> String {
+        let mut buff = String::with_capacity(240);
+
+        data_utils::appendMap(&mut buff, ATTR_CHUNK, &self.id);
+        data_utils::appendMap(&mut buff, ATTR_BLOCK, &self.block.load(Ordering::Acquire));
+        data_utils::appendMap(&mut buff, ATTR_LEN, &self.blockCount);
+        if self.maxLen != self.maxLenLive {
+            data_utils::appendMap(&mut buff, ATTR_LIVE_MAX, &self.maxLenLive);
+        }
+        if self.pageCount != self.pageCountLive {
+            data_utils::appendMap(&mut buff, ATTR_LIVE_PAGES, &self.pageCountLive);
+        }
+        data_utils::appendMap(&mut buff, ATTR_MAP, &self.mapId);
+        data_utils::appendMap(&mut buff, ATTR_MAX, &self.maxLen);
+        if self.next != 0 {
+            data_utils::appendMap(&mut buff, ATTR_NEXT, &self.next);
+        }
+        data_utils::appendMap(&mut buff, ATTR_PAGES, &self.pageCount);
+        data_utils::appendMap(&mut buff, ATTR_ROOT, &self.layoutRootPos);
+        data_utils::appendMap(&mut buff, ATTR_TIME, &self.time);
+        if self.unused != 0 {
+            data_utils::appendMap(&mut buff, ATTR_UNUSED, &self.unused);
+        }
+        if self.unusedAtVersion != 0 {
+            data_utils::appendMap(&mut buff, ATTR_UNUSED_AT_VERSION, &self.unusedAtVersion);
+        }
+        data_utils::appendMap(&mut buff, ATTR_VERSION, &self.version);
+        if self.pinCount > 0 {
+            data_utils::appendMap(&mut buff, ATTR_PIN_COUNT, &self.pinCount);
+        }
+        if self.tocPos > 0 {
+            data_utils::appendMap(&mut buff, ATTR_TOC, &self.tocPos);
+        }
+        if !self.occupancy.is_empty() {
+            data_utils::appendMap(&mut buff, ATTR_OCCUPANCY, &string_utils::convertBytes2HexString(&occupancyToBytes(&self.occupancy)));
+        }
+
+        buff
+    }
+
+    /// Write the chunk header. <br>
+    /// minLength 的作用是为之后回头重写header预留空间
+    pub fn writeChunkHeader(&self, writeBuffer: &mut WriteBuffer, minLength: usize) -> Result<()> {
+        // 末尾的'\n'占据了1个byte
+        let delimiterPosition = writeBuffer.getPosition() + minLength;
+
+        writeBuffer.putSlice(self.asString().as_bytes());
+
+        while writeBuffer.getPosition() + 1 < delimiterPosition {
+            writeBuffer.putU8(b' ');
+        }
+
+        if minLength != 0 && writeBuffer.getPosition() + 1 > delimiterPosition {
+            throw!(DbError::get(error_code::GENERAL_ERROR_1, vec!["Chunk metadata too long"]));
+        }
+
+        writeBuffer.putU8(b'\n');
+
+        Ok(())
+    }
+
+    pub fn getFooterBytes(&self) -> Vec<u8> {
+        let mut buff = String::with_capacity(FOOTER_LENGTH as usize);
+
+        data_utils::appendMap(&mut buff, ATTR_CHUNK, &self.id);
+        data_utils::appendMap(&mut buff, ATTR_BLOCK, &self.block.load(Ordering::Acquire));
+        data_utils::appendMap(&mut buff, ATTR_VERSION, &self.version);
+
+        let checksum = data_utils::getFletcher32(buff.as_bytes(), 0, buff.len());
+        data_utils::appendMap(&mut buff, ATTR_FLETCHER, &checksum);
+
+        while buff.len() < FOOTER_LENGTH as usize - 1 {
+            buff.push(' ');
+        }
+        buff.push('\n');
+
+        buff.into_bytes()
+    }
+
+    /// 该chunk是否还有存活的page
+    pub fn isLive(&self) -> bool {
+        self.pageCountLive > 0
+    }
+
+    /// Calculate the fill rate in %. 0 means empty, 100 means full.
+    pub fn getFillRate(&self) -> Integer {
+        assert!(self.maxLenLive <= self.maxLen);
+        if self.maxLenLive <= 0 {
+            0
+        } else if self.maxLenLive == self.maxLen {
+            100
+        } else {
+            1 + (98 * self.maxLenLive / self.maxLen) as Integer
+        }
+    }
+
+    /// 有存活的page,也有已经不用的page,值得把存活的page重写到新的chunk
+    pub fn isRewritable(&self) -> bool {
+        self.isSaved() && self.isLive() && self.pageCountLive < self.pageCount && self.isEvacuatable()
+    }
+
+    /// pin住的chunk里的page不能移动
+    pub fn isEvacuatable(&self) -> bool {
+        self.pinCount == 0
+    }
+
+    /// Modifies internal state to reflect the fact that one more page is stored within this chunk.
+    ///
+    /// pageLengthOnDisk: size of the page
+    pub fn accountForWrittenPage(&mut self, pageLengthOnDisk: Integer) {
+        self.maxLen += pageLengthOnDisk as Long;
+        self.pageCount += 1;
+        self.maxLenLive += pageLengthOnDisk as Long;
+        self.pageCountLive += 1;
+    }
+
+    /// Modifies internal state to reflect the fact that one the pages within this chunk was removed from the map.
+    ///
+    /// pageNo: sequential page number within the chunk<br>
+    /// pageLength: on disk of the removed page<br>
+    /// now: is a moment in time (since creation of the store), when removal is recorded,
+    /// and retention period starts<br>
+    /// version: at which page was removed
+    ///
+    /// return true if all of the pages, this chunk contains, were already removed, and false otherwise
+    pub fn accountForRemovedPage(&mut self, pageNo: Integer, pageLength: Integer, now: Long, version: Long) -> bool {
+        assert!(self.isSaved());
+
+        // refreshChunkLiveness可能已经把这个page算作不存活了
+        if pageNo >= 0 && !self.occupancy.insert(pageNo as usize) {
+            return false;
+        }
+
+        self.maxLenLive -= pageLength as Long;
+        self.pageCountLive -= 1;
+
+        if self.unusedAtVersion < version {
+            self.unusedAtVersion = version;
+        }
+
+        assert!(self.pageCountLive >= 0);
+        assert!(self.maxLenLive >= 0);
+
+        if !self.isLive() {
+            self.unused = now;
+            return true;
+        }
+
+        false
+    }
+}
+
+/// java的BitSet.toByteArray()是低位在前的,而bit_set是高位在前,需要转换以保持文件格式的兼容
+fn occupancyToBytes(occupancy: &BitSet) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    for bit in occupancy.iter() {
+        let index = bit / 8;
+        if index >= bytes.len() {
+            bytes.resize(index + 1, 0);
+        }
+        bytes[index] |= 1 << (bit % 8);
+    }
+
+    bytes
+}
+
+fn occupancyFromBytes(bytes: &[u8]) -> BitSet {
+    let mut occupancy = BitSet::with_capacity(bytes.len() * 8);
+
+    for (index, byte) in bytes.iter().enumerate() {
+        for bit in 0..8 {
+            if byte & (1 << bit) != 0 {
+                occupancy.insert(index * 8 + bit);
+            }
+        }
+    }
+
+    occupancy
 }
 
 pub fn get_meta_key(chunk_id: Integer) -> String {
@@ -168,3 +379,74 @@ pub fn fromString(s: &String) -> Result<SharedPtr<Chunk>> {
     Chunk::new(s)
 }
 
+#[cfg(test)]
+mod test {
+    use crate::{get_ref, get_ref_mut};
+    use crate::mvstore::mv_map::MVMapBuilder;
+    use crate::h2_rust_common::Integer;
+    use crate::mvstore::mv_store::MVStore;
+    use crate::mvstore::test_utils::{string, TempStore};
+
+    fn hasChunk(mvStore: &mut MVStore, id: Integer) -> bool {
+        mvStore.getChunks().unwrap().iter().any(|chunk| get_ref!(chunk).id == id)
+    }
+
+    #[test]
+    fn test_drop_unused_chunks() {
+        let tempStore = TempStore::new("test_drop_unused_chunks");
+
+        let mvStore = tempStore.openWith(&[("autoCommitDelay", 0)]);
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+        mvStoreMutRef.setRetentionTime(0);
+        mvStoreMutRef.setVersionsToKeep(0);
+
+        // 第1个chunk含有meta的page,之后不会再被修改
+        let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+        mvStoreMutRef.commit().unwrap();
+        get_ref_mut!(data).put(string("1"), string("Hello")).unwrap();
+        mvStoreMutRef.commit().unwrap();
+        let firstChunk = mvStoreMutRef.getLastChunk();
+        assert!(get_ref!(firstChunk).isLive());
+
+        // 不用compact,page的删除在之后的chunk保存时计入
+        for round in 0..50 {
+            get_ref_mut!(data).put(string("1"), string(&format!("Hello {}", round))).unwrap();
+            mvStoreMutRef.commit().unwrap();
+        }
+        assert!(!get_ref!(firstChunk).isLive());
+        assert!(!hasChunk(mvStoreMutRef, get_ref!(firstChunk).id));
+        assert!(mvStoreMutRef.getChunks().unwrap().len() < 10);
+        let fileSize = get_ref!(mvStoreMutRef.getFileStore()).size();
+
+        // 打开的快照用到的chunk不会被删掉
+        let snapshot = get_ref!(data).openVersion(mvStoreMutRef.getCurrentVersion() - 1).unwrap();
+        let snapshotChunk = mvStoreMutRef.getLastChunk();
+        for round in 0..10 {
+            get_ref_mut!(data).put(string("1"), string(&format!("Hallo {}", round))).unwrap();
+            mvStoreMutRef.commit().unwrap();
+        }
+        assert!(!get_ref!(snapshotChunk).isLive());
+        assert!(hasChunk(mvStoreMutRef, get_ref!(snapshotChunk).id));
+        assert_eq!(get_ref!(snapshot).get(&string("1")).castAsStringRef(), "Hello 49");
+
+        drop(snapshot);
+        for round in 0..10 {
+            get_ref_mut!(data).put(string("1"), string(&format!("World {}", round))).unwrap();
+            mvStoreMutRef.commit().unwrap();
+        }
+        assert!(!hasChunk(mvStoreMutRef, get_ref!(snapshotChunk).id));
+        assert!(mvStoreMutRef.getChunks().unwrap().len() < 10);
+
+        // 负数的retentionTime表示永远不覆盖
+        mvStoreMutRef.setRetentionTime(-1);
+        let chunkCount = mvStoreMutRef.getChunks().unwrap().len();
+        for round in 0..10 {
+            get_ref_mut!(data).put(string("1"), string(&format!("Welt {}", round))).unwrap();
+            mvStoreMutRef.commit().unwrap();
+        }
+        assert_eq!(mvStoreMutRef.getChunks().unwrap().len(), chunkCount + 10);
+        assert!(get_ref!(mvStoreMutRef.getFileStore()).size() > fileSize);
+
+        mvStoreMutRef.close().unwrap();
+    }
+}
@@ -0,0 +1,285 @@
+use std::cmp::Ordering;
+use crate::{get_ref, get_ref_mut, suffix_minus_minus};
+use crate::h2_rust_common::h2_rust_cell::SharedPtr;
+use crate::h2_rust_common::h2_rust_type::H2RustType;
+use crate::h2_rust_common::h2_rust_type::H2RustType::Null;
+use crate::h2_rust_common::{Integer, Long};
+use crate::mvstore::cursor_pos;
+use crate::mvstore::cursor_pos::CursorPos;
+use crate::mvstore::page::PageTrait;
+use crate::mvstore::root_reference::RootReference;
+
+/// A cursor to iterate over elements in ascending or descending order.
+///
+/// 和java一样用H2RustType::Null表示没有上界(to)以及没有当前的key
+pub struct Cursor {
+    reverse: bool,
+    to: H2RustType,
+    cursorPos: SharedPtr<CursorPos>,
+    /// 已经遍历完的CursorPos,复用以减少分配
+    keeper: SharedPtr<CursorPos>,
+    current: H2RustType,
+    last: H2RustType,
+    lastValue: H2RustType,
+    lastPage: SharedPtr<dyn PageTrait>,
+}
+
+impl Cursor {
+    pub fn new(rootReference: &SharedPtr<RootReference>, from: &H2RustType, to: H2RustType, reverse: bool) -> Cursor {
+        let lastPage = get_ref!(rootReference).root.clone();
+        let cursorPos = traverseDown(lastPage.clone(), from, reverse);
+
+        Cursor {
+            reverse,
+            to,
+            cursorPos,
+            keeper: None,
+            current: Null,
+            last: Null,
+            lastValue: Null,
+            lastPage,
+        }
+    }
+
+    pub fn hasNext(&mut self) -> bool {
+        if self.cursorPos.is_some() {
+            let increment: Integer = if self.reverse { -1 } else { 1 };
+
+            while self.current.isNull() {
+                let mut page = get_ref!(self.cursorPos).page.clone();
+                let mut index = get_ref!(self.cursorPos).index;
+
+                if if self.reverse { index < 0 } else { index >= upperBound(&page) } {
+                    // traversal of this page is over, going up a level or stop if at the root already
+                    let tmp = self.cursorPos.clone();
+                    self.cursorPos = get_ref!(tmp).parent.clone();
+                    if self.cursorPos.is_none() {
+                        return false;
+                    }
+                    get_ref_mut!(tmp).parent = self.keeper.clone();
+                    self.keeper = tmp;
+                } else {
+                    // traverse down to the leaf taking the leftmost path
+                    while !get_ref!(page).isLeaf() {
+                        page = get_ref!(page).getChildPage(index);
+                        index = if self.reverse { upperBound(&page) - 1 } else { 0 };
+
+                        if self.keeper.is_none() {
+                            self.cursorPos = CursorPos::new(page.clone(), index, self.cursorPos.clone());
+                        } else {
+                            let tmp = self.keeper.clone();
+                            self.keeper = get_ref!(tmp).parent.clone();
+
+                            let tmpMutRef = get_ref_mut!(tmp);
+                            tmpMutRef.parent = self.cursorPos.clone();
+                            tmpMutRef.page = page.clone();
+                            tmpMutRef.index = index;
+
+                            self.cursorPos = tmp;
+                        }
+                    }
+
+                    let pageRef = get_ref!(page);
+                    if if self.reverse { index >= 0 } else { index < pageRef.getKeyCount() } {
+                        let key = pageRef.getKey(index);
+
+                        if !self.to.isNull() {
+                            let mvMap = pageRef.getMvMap();
+                            let ordering = get_ref!(mvMap).getKeyType().compare(&key, &self.to);
+                            let overflow = if self.reverse { Ordering::Less } else { Ordering::Greater };
+                            if ordering == overflow {
+                                return false;
+                            }
+                        }
+
+                        self.current = key.clone();
+                        self.last = key;
+                        self.lastValue = pageRef.getValue(index);
+                        self.lastPage = page.clone();
+                    }
+                }
+
+                get_ref_mut!(self.cursorPos).index += increment;
+            }
+        }
+
+        !self.current.isNull()
+    }
+
+    /// Skip over that many entries. This method is relatively fast (for this map implementation)
+    /// even if many entries need to be skipped.
+    ///
+    /// 和Iterator::skip重名,加上_区分
+    pub fn skip_(&mut self, mut n: Long) {
+        if n < 10 {
+            while suffix_minus_minus!(n) > 0 && self.hasNext() {
+                self.next();
+            }
+        } else if self.hasNext() {
+            assert!(self.cursorPos.is_some());
+
+            let mut cp = self.cursorPos.clone();
+            loop {
+                let parent = get_ref!(cp).parent.clone();
+                if parent.is_none() {
+                    break;
+                }
+                cp = parent;
+            }
+
+            let root = get_ref!(cp).page.clone();
+            let mvMap = get_ref!(root).getMvMap();
+            let mvMapRef = get_ref!(mvMap);
+
+            let key = self.next().unwrap();
+            let index = mvMapRef.getKeyIndex(&key);
+            self.last = mvMapRef.getKey(index + if self.reverse { -n } else { n });
+
+            // 跳过了末尾,java版这里会从头开始,直接结束遍历
+            self.cursorPos = if self.last.isNull() {
+                None
+            } else {
+                traverseDown(root, &self.last, self.reverse)
+            };
+        }
+    }
+
+    /// Get the last read key if there was one.
+    pub fn getKey(&self) -> H2RustType {
+        self.last.clone()
+    }
+
+    /// Get the last read value if there was one.
+    pub fn getValue(&self) -> H2RustType {
+        self.lastValue.clone()
+    }
+
+    /// Get the page where last retrieved key is located.
+    pub fn getPage(&self) -> SharedPtr<dyn PageTrait> {
+        self.lastPage.clone()
+    }
+}
+
+impl Iterator for Cursor {
+    type Item = H2RustType;
+
+    fn next(&mut self) -> Option<H2RustType> {
+        if !self.hasNext() {
+            return None;
+        }
+
+        self.current = Null;
+        Some(self.last.clone())
+    }
+}
+
+/// Fetch the next entry that is equal or larger than the given key, starting
+/// from the given page. This method returns the path.
+///
+/// from为Null时从头(reverse时从尾)开始
+pub fn traverseDown(page: SharedPtr<dyn PageTrait>, key: &H2RustType, reverse: bool) -> SharedPtr<CursorPos> {
+    let cursorPos = if !key.isNull() {
+        cursor_pos::traverseDown(page, key)
+    } else if reverse {
+        get_ref!(page).getAppendCursorPos(page.clone(), None)
+    } else {
+        get_ref!(page).getPrependCursorPos(page.clone(), None)
+    };
+
+    let cursorPosMutRef = get_ref_mut!(cursorPos);
+    let mut index = cursorPosMutRef.index;
+    if index < 0 {
+        index = !index;
+        if reverse {
+            index -= 1;
+        }
+        cursorPosMutRef.index = index;
+    }
+
+    cursorPos
+}
+
+fn upperBound(page: &SharedPtr<dyn PageTrait>) -> Integer {
+    let pageRef = get_ref!(page);
+    if pageRef.isLeaf() {
+        pageRef.getKeyCount()
+    } else {
+        pageRef.getKeyCount() + 1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::Any;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use crate::{build_arc_h2RustCell, get_ref, get_ref_mut};
+    use crate::h2_rust_common::h2_rust_cell::H2RustCell;
+    use crate::h2_rust_common::h2_rust_type::H2RustType;
+    use crate::mvstore::mv_map::MVMapBuilder;
+    use crate::mvstore::mv_store::MVStore;
+
+    fn key(a: usize) -> H2RustType {
+        H2RustType::String(build_arc_h2RustCell!(format!("k{:05}", a)))
+    }
+
+    #[test]
+    fn test_cursor() {
+        let mut config: HashMap<String, Box<dyn Any>> = HashMap::new();
+        let mvStore = MVStore::new(&mut config).unwrap();
+        let mvMap = get_ref_mut!(mvStore).openMap("data", &MVMapBuilder::new()).unwrap();
+        for a in (0..1000).step_by(2) {
+            get_ref_mut!(mvMap).put(key(a), key(a)).unwrap();
+        }
+
+        let mvMapRef = get_ref!(mvMap);
+        assert_eq!(mvMapRef.firstKey().castAsStringRef(), "k00000");
+        assert_eq!(mvMapRef.lastKey().castAsStringRef(), "k00998");
+        assert_eq!(mvMapRef.ceilingKey(&key(5)).castAsStringRef(), "k00006");
+        assert_eq!(mvMapRef.higherKey(&key(6)).castAsStringRef(), "k00008");
+        assert_eq!(mvMapRef.floorKey(&key(5)).castAsStringRef(), "k00004");
+        assert_eq!(mvMapRef.lowerKey(&key(4)).castAsStringRef(), "k00002");
+        assert!(mvMapRef.lowerKey(&key(0)).isNull());
+
+        assert_eq!(mvMapRef.cursor(&H2RustType::Null).count(), 500);
+
+        let keys: Vec<H2RustType> = mvMapRef.cursor3(&key(101), key(201), false).collect();
+        assert_eq!(keys.len(), 50);
+        assert_eq!(keys[0].castAsStringRef(), "k00102");
+        assert_eq!(keys[49].castAsStringRef(), "k00200");
+
+        let keys: Vec<H2RustType> = mvMapRef.cursor3(&key(201), key(101), true).collect();
+        assert_eq!(keys.len(), 50);
+        assert_eq!(keys[0].castAsStringRef(), "k00200");
+        assert_eq!(keys[49].castAsStringRef(), "k00102");
+    }
+
+    #[test]
+    fn test_skip() {
+        let mut config: HashMap<String, Box<dyn Any>> = HashMap::new();
+        let mvStore = MVStore::new(&mut config).unwrap();
+        let mvMap = get_ref_mut!(mvStore).openMap("data", &MVMapBuilder::new()).unwrap();
+        for a in (0..1000).step_by(2) {
+            get_ref_mut!(mvMap).put(key(a), key(a)).unwrap();
+        }
+
+        let mvMapRef = get_ref!(mvMap);
+        assert_eq!(mvMapRef.size_as_long(), 500);
+        assert_eq!(mvMapRef.getKey(123).castAsStringRef(), "k00246");
+        assert!(mvMapRef.getKey(500).isNull());
+        assert_eq!(mvMapRef.getKeyIndex(&key(246)), 123);
+        assert_eq!(mvMapRef.getKeyIndex(&key(247)), -125);
+
+        let mut cursor = mvMapRef.cursor(&H2RustType::Null);
+        cursor.skip_(3);
+        assert_eq!(cursor.next().unwrap().castAsStringRef(), "k00006");
+        cursor.skip_(100);
+        assert_eq!(cursor.next().unwrap().castAsStringRef(), "k00208");
+        cursor.skip_(1000);
+        assert!(cursor.next().is_none());
+
+        let mut cursor = mvMapRef.keyIteratorReverse(&H2RustType::Null);
+        cursor.skip_(100);
+        assert_eq!(cursor.next().unwrap().castAsStringRef(), "k00798");
+    }
+}
@@ -1,10 +1,69 @@
-use crate::h2_rust_common::h2_rust_cell::SharedPtr;
+use std::sync::Arc;
+use crate::{build_option_arc_h2RustCell, get_ref, get_ref_mut};
+use crate::h2_rust_common::h2_rust_cell::{H2RustCell, SharedPtr};
+use crate::h2_rust_common::h2_rust_type::H2RustType;
+use crate::h2_rust_common::{Integer, Long};
 use crate::mvstore::page::PageTrait;
 
+/// A position in a cursor.
+/// Instance represents a node in the linked list, which traces path
+/// from a specific (target) key within a leaf node all the way up to the root
+/// (bottom up path).
 pub struct CursorPos {
     /// the page at the current level.
-    pub page :SharedPtr<dyn PageTrait>,
+    pub page: SharedPtr<dyn PageTrait>,
 
+    /// Index of the key (within page above) used to go down to a lower level
+    /// in case of intermediate nodes, or index of the target key for leaf a node.
+    /// In a later case, it could be negative, if the key is not present.
+    pub index: Integer,
+
+    /// Next node in the linked list, representing the position within parent level,
+    /// or None, if we are at the root level already.
+    pub parent: SharedPtr<CursorPos>,
+}
+
+impl CursorPos {
+    pub fn new(page: SharedPtr<dyn PageTrait>, index: Integer, parent: SharedPtr<CursorPos>) -> SharedPtr<CursorPos> {
+        build_option_arc_h2RustCell!(CursorPos {
+            page,
+            index,
+            parent
+        })
+    }
 }
 
-// a
\ No newline at end of file
+/// Searches for a given key and creates a breadcrumb trail through a B-tree
+/// rooted at a given Page. Resulting path starts at "insertion point" for a
+/// given key and goes back to the root.
+pub fn traverseDown(mut page: SharedPtr<dyn PageTrait>, key: &H2RustType) -> SharedPtr<CursorPos> {
+    let mut cursorPos = None;
+
+    while !get_ref!(page).isLeaf() {
+        let mut index = get_ref_mut!(page).binarySearch(key) + 1;
+        if index < 0 {
+            index = -index;
+        }
+
+        let child = get_ref!(page).getChildPage(index);
+        cursorPos = CursorPos::new(page, index, cursorPos);
+        page = child;
+    }
+
+    let index = get_ref_mut!(page).binarySearch(key);
+    CursorPos::new(page, index, cursorPos)
+}
+
+/// Calculate the memory used by changes that are not yet stored.
+///
+/// 路径上的page都已经被copy替换掉了,逐个标记为removed
+pub fn processRemovalInfo(mut cursorPos: SharedPtr<CursorPos>, version: Long) -> Integer {
+    let mut unsavedMemory = 0;
+
+    while cursorPos.is_some() {
+        unsavedMemory += get_ref!(get_ref!(cursorPos).page).removePage(version);
+        cursorPos = get_ref!(cursorPos).parent.clone();
+    }
+
+    unsavedMemory
+}
@@ -2,14 +2,13 @@ use anyhow::Result;
 use std::any::Any;
 use std::collections::HashMap;
 use std::fmt::Display;
-use std::fs::File;
 use std::ops::Add;
-use std::os::unix::fs::FileExt;
 use crate::api::error_code;
-use crate::h2_rust_common::{h2_rust_constant, h2_rust_utils, Integer, Long, ULong};
+use crate::h2_rust_common::{h2_rust_constant, h2_rust_utils, Integer, Long, UInteger, ULong};
 use crate::message::db_error::DbError;
 use crate::{suffix_plus_plus, throw, unsigned_right_shift};
 use crate::h2_rust_common::byte_buffer::ByteBuffer;
+use crate::db::store;
 
 /// An error occurred while reading from the file.
 pub const ERROR_READING_FAILED: Integer = 1;
@@ -156,6 +155,84 @@ pub fn getPageChunkId(position: Long) -> Integer {
     unsigned_right_shift!(position, 38, Long) as Integer
 }
 
+/// Append a key-value pair to the string builder. Keys may not contain a colon. <br>
+/// Values that contain a comma or a double quote are enclosed in double quotes, with special characters escaped. <br>
+/// Integer and Long are written as hex, like the java version does
+pub fn appendMap(buff: &mut String, key: &str, value: &dyn Any) {
+    if !buff.is_empty() {
+        buff.push(h2_rust_constant::COMMA_CHAR);
+    }
+
+    buff.push_str(key);
+    buff.push(h2_rust_constant::COLON_CHAR);
+
+    if let Some(long) = value.downcast_ref::<Long>() {
+        buff.push_str(&format!("{:x}", long));
+        return;
+    }
+
+    if let Some(integer) = value.downcast_ref::<Integer>() {
+        buff.push_str(&format!("{:x}", integer));
+        return;
+    }
+
+    let v = if let Some(string) = value.downcast_ref::<String>() {
+        string.as_str()
+    } else if let Some(str) = value.downcast_ref::<&str>() {
+        *str
+    } else {
+        panic!("appendMap only supports Long, Integer and String")
+    };
+
+    if !v.contains(h2_rust_constant::COMMA_CHAR) && !v.contains('\"') {
+        buff.push_str(v);
+    } else {
+        buff.push('\"');
+        for c in v.chars() {
+            if c == '\"' || c == '\\' {
+                buff.push('\\');
+            }
+            buff.push(c);
+        }
+        buff.push('\"');
+    }
+}
+
+/// Calculate the Fletcher32 checksum.
+pub fn getFletcher32(bytes: &[u8], offset: usize, length: usize) -> Integer {
+    let mut s1: UInteger = 0xffff;
+    let mut s2: UInteger = 0xffff;
+
+    let mut a = offset;
+    let len = offset + (length & !1);
+
+    while a < len {
+        // reduce after 360 words (each word is two bytes)
+        let end = usize::min(a + 720, len);
+        while a < end {
+            let x = (bytes[a] as UInteger) << 8 | bytes[a + 1] as UInteger;
+            a += 2;
+            s1 = s1.wrapping_add(x);
+            s2 = s2.wrapping_add(s1);
+        }
+
+        s1 = (s1 & 0xffff) + (s1 >> 16);
+        s2 = (s2 & 0xffff) + (s2 >> 16);
+    }
+
+    // odd length: append 0
+    if (length & 1) != 0 {
+        let x = (bytes[a] as UInteger) << 8;
+        s1 = s1.wrapping_add(x);
+        s2 = s2.wrapping_add(s1);
+    }
+
+    s1 = (s1 & 0xffff) + (s1 >> 16);
+    s2 = (s2 & 0xffff) + (s2 >> 16);
+
+    (s2 << 16 | s1) as Integer
+}
+
 pub fn parseMap(s: &String) -> Result<HashMap<String, String>> {
     let mut map = HashMap::new();
 
@@ -169,7 +246,7 @@ pub fn parseMap(s: &String) -> Result<HashMap<String, String>> {
             None => {
                 throw!(DbError::get(error_code::FILE_CORRUPTED_1, vec![&format!("not a map: {}",s)]))
             }
-            Some(a) => { a }
+            Some(index) => { a + index }
         };
 
         let key = &s[startKey..suffix_plus_plus!(a)];
@@ -181,6 +258,61 @@ pub fn parseMap(s: &String) -> Result<HashMap<String, String>> {
     Ok(map)
 }
 
+/// Parse a key-value pair list and checks its checksum.
+///
+/// return the map without mapping for "fletcher", or None if checksum is wrong or parameter do not represent a properly formatted map serialization
+pub fn parseChecksummedMap(bytes: &[u8]) -> Result<Option<HashMap<String, String>>> {
+    let mut start = 0;
+    let mut end = bytes.len();
+    while start < end && bytes[start] <= b' ' {
+        start += 1;
+    }
+    while start < end && bytes[end - 1] <= b' ' {
+        end -= 1;
+    }
+
+    // 正常的map只含有ascii,其它的(比如加密文件使用了错误的密码)当作损坏,同时保证下边按照字节下标切分string是安全的
+    if !bytes[start..end].is_ascii() {
+        return Ok(None);
+    }
+
+    let s: String = bytes[start..end].iter().map(|b| *b as char).collect();
+    let size = s.len();
+
+    let mut map = HashMap::new();
+    let mut a = 0;
+    while a < size {
+        let startKey = a;
+        a = match s[a..].find(h2_rust_constant::COLON_CHAR) {
+            // corrupted map
+            None => return Ok(None),
+            Some(index) => a + index
+        };
+
+        if a - startKey == 8 && &s[startKey..a] == "fletcher" {
+            let check = match Long::from_str_radix(&s[a + 1..], 16) {
+                Ok(check) => check as Integer,
+                Err(_) => return Ok(None)
+            };
+            if startKey == 0 {
+                return Ok(None);
+            }
+            let checksum = getFletcher32(bytes, start, startKey - 1);
+            if check != checksum {
+                return Ok(None);
+            }
+            return Ok(Some(map));
+        }
+
+        let key = s[startKey..a].to_string();
+        let (value, nextIndex) = parseMapValue(&s, a + 1, size)?;
+        a = nextIndex;
+        map.insert(key, value);
+    }
+
+    Ok(None)
+}
+
 fn parseMapValue(s: &str, mut a: usize, size: usize) -> Result<(String, usize)> {
     let mut result = String::with_capacity(1024);
 
@@ -245,6 +377,22 @@ pub fn readHexIntOrLong<T: 'static + Display, TargetType: Copy + 'static>(map: &
     }
 }
 
+/// Parse an unsigned, hex long.
+pub fn parseHexLong(x: &str) -> Result<Long> {
+    match ULong::from_str_radix(x, 16) {
+        Ok(long) => Ok(long as Long),
+        Err(_) => throw!(DbError::get(error_code::FILE_CORRUPTED_1, vec![&format!("error parsing the value {}", x)]))
+    }
+}
+
+/// Parse an unsigned, hex int.
+pub fn parseHexInt(x: &str) -> Result<Integer> {
+    match UInteger::from_str_radix(x, 16) {
+        Ok(integer) => Ok(integer as Integer),
+        Err(_) => throw!(DbError::get(error_code::FILE_CORRUPTED_1, vec![&format!("error parsing the value {}", x)]))
+    }
+}
+
 pub trait Convertable<TargetType: Copy> {
     fn convert(&self) -> TargetType;
 }
@@ -274,51 +422,47 @@ pub fn getPageMaxLength(position: Long) -> Integer {
 }
 
 
-/// Get the maximum length for the given code,For the code 31, PAGE_LARGE is returned <br>
-/// return the maximum length
-pub fn decodePageLength(encodedPageLength: Integer) -> Integer {
-    if encodedPageLength == 31 {
-        PAGE_LARGE
-    } else {
-        (2 + (encodedPageLength & 1)) << ((encodedPageLength >> 1) + 4)
+/// Convert the length to a length code 0..31. 31 means more than 1 MB.
+pub fn encodeLength(len: Integer) -> Integer {
+    if len <= 32 {
+        return 0;
     }
-}
 
-pub fn readFully(file: &File, mut position: usize, byteBuffer: &mut ByteBuffer) -> Result<()> {
-    // java中fileChannel.read(byteBuffer)用的也是如下的套路
-    // 也是找了个中间的buffer 然后再让真正的dest去吸取
-    let mut a = Vec::<u8>::with_capacity(byteBuffer.getCapacity());
-    let aa: &mut [u8] = &mut a;
-
-    loop {
-        let len = file.read_at(aa, position as u64)?;
-        byteBuffer.putSlice_(aa, 0, len);
-
-        position += len;
-
-        if !byteBuffer.hasRemaining() {
-            break;
-        }
+    let mut code = len.leading_zeros() as Integer;
+    let remaining = len.wrapping_shl((code + 1) as u32);
+    code += code;
+    if (remaining & Integer::MIN) != 0 {
+        code -= 1;
+    }
+    if remaining.wrapping_shl(1) != 0 {
+        code -= 1;
     }
 
-    byteBuffer.rewind();
+    Integer::min(31, 52 - code)
+}
 
-    Ok(())
+/// Create table of content element. The layout is:
+/// - 26 bit map id
+/// - 32 bit offset
+/// - 5 bit length code
+/// - 1 bit type
+pub fn getTocElement(mapId: Integer, offset: Integer, length: Integer, pageType: Integer) -> Long {
+    ((mapId as Long) << 38) | ((offset as Long) << 6) | ((encodeLength(length) as Long) << 1) | pageType as Long
 }
 
-pub fn writeFully(file: &File, mut position: usize, src: &mut ByteBuffer) -> Result<()> {
-    loop {
-        let slice = src.extract();
-        let len = file.write_at(slice, position as u64)?;
-        position += len;
-        src.advance(len);
+/// Convert tocElement into pagePos by replacing mapId with chunkId.
+pub fn getPagePos(chunkId: Integer, tocElement: Long) -> Long {
+    (tocElement & 0x3FFFFFFFFF) | ((chunkId as Long) << 38)
+}
 
-        if !src.hasRemaining() {
-            break;
-        }
+/// Get the maximum length for the given code,For the code 31, PAGE_LARGE is returned <br>
+/// return the maximum length
+pub fn decodePageLength(encodedPageLength: Integer) -> Integer {
+    if encodedPageLength == 31 {
+        PAGE_LARGE
+    } else {
+        (2 + (encodedPageLength & 1)) << ((encodedPageLength >> 1) + 4)
     }
-
-    Ok(())
 }
 
 pub fn getPageType(position: Long) -> Integer {
@@ -332,6 +476,21 @@ pub fn getCheckValue(x: Integer) -> i16 {
     ((x >> 16) ^ x) as i16
 }
 
+/// Get the length of the variable size int.
+pub fn getVarIntLen(x: Integer) -> Integer {
+    if (x & (-1 << 7)) == 0 {
+        1
+    } else if (x & (-1 << 14)) == 0 {
+        2
+    } else if (x & (-1 << 21)) == 0 {
+        3
+    } else if (x & (-1 << 28)) == 0 {
+        4
+    } else {
+        5
+    }
+}
+
 pub fn readVarInt(byteBuffer: &mut ByteBuffer) -> i32 {
     let b = byteBuffer.getI8() as i32;
     if b >= 0 {
@@ -398,19 +557,23 @@ pub fn readString1(byteBuffer: &mut ByteBuffer) -> String {
     readString2(byteBuffer, len)
 }
 
+/// len是java char(utf16)的数量
 pub fn readString2(byteBuffer: &mut ByteBuffer, len: usize) -> String {
-    let mut chars: Vec<u8> = Vec::with_capacity(len);
-    for a in 0..len {
-        let x = byteBuffer.getI8() as i32 & 0xff;
+    let mut chars: Vec<u16> = Vec::with_capacity(len);
+    for _ in 0..len {
+        let x = byteBuffer.getU8() as i32;
         if x < 0x80 {
-            chars[a] = x as u8;
+            chars.push(x as u16);
         } else if x >= 0xe0 {
-            chars[a] = ((x & 0xf) << 12 + (byteBuffer.getI8() & 0x3f) << 6 + byteBuffer.getI8() & 0x3f) as u8;
+            let b1 = byteBuffer.getU8() as i32 & 0x3f;
+            let b2 = byteBuffer.getU8() as i32 & 0x3f;
+            chars.push(((x & 0xf) << 12 | b1 << 6 | b2) as u16);
         } else {
-            chars[a] = ((x & 0x1f) << 6 + byteBuffer.getI8() & 0x3f) as u8;
+            let b1 = byteBuffer.getU8() as i32 & 0x3f;
+            chars.push(((x & 0x1f) << 6 | b1) as u16);
         }
     }
-    String::from_utf8(chars).unwrap()
+    String::from_utf16_lossy(&chars)
 }
 
 pub fn getMapName(metadataString: &str) -> Result<Option<String>> {
@@ -425,7 +588,7 @@ pub fn getFromMap(metadataString: &str, key: &str) -> Result<Option<String>> {
     while a < metadataStringLength {
         let startKey: usize = a;
         if let Some(position) = (&metadataString[a..]).chars().position(|c| c == ':') {
-           a =  position
+            a += position
         } else {
             throw!(DbError::get(error_code::FILE_CORRUPTED_1, vec![&format!("not a map: {}", metadataString)]));
         };
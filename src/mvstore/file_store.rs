@@ -1,22 +1,13 @@
 use anyhow::Result;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::atomic::{AtomicI64, Ordering};
 use crate::h2_rust_common::{Byte, byte_buffer, Integer, Long};
-use std::{fs, i64};
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek};
-use std::io::SeekFrom::Start;
-use std::os::fd::AsRawFd;
-use std::os::unix::fs::FileExt;
-use std::path::Path;
-use crate::api::error_code;
 use crate::h2_rust_common::byte_buffer::ByteBuffer;
-use crate::h2_rust_common::file_lock::FileLock;
 use crate::h2_rust_common::h2_rust_cell::{H2RustCell, SharedPtr};
-use crate::message::db_error::DbError;
-use crate::mvstore::data_utils;
-use crate::store::fs::file_utils;
-use crate::throw;
+use crate::mvstore::free_space_bit_set::FreeSpaceBitSet;
+use crate::store::fs::encrypt::file_encrypt::FileEncrypt;
+use crate::store::fs::file_channel;
+use crate::store::fs::file_channel::FileChannel;
 
 #[derive(Default)]
 pub struct FileStore {
@@ -33,15 +24,18 @@ pub struct FileStore {
     file_name: String,
     read_only: bool,
     file_size: Long,
-    file: Option<File>,
 
-    file_lock: Option<FileLock>,
+    /// 根据文件名的前缀是磁盘文件,"nioMapped:"或"memFS:",传入了encryptionKey的话外边再包1层FileEncrypt
+    file: Option<Box<dyn FileChannel>>,
+
+    /// The free spaces between the chunks. The first block to use is block 2 (the first two blocks are the store header).
+    free_space: FreeSpaceBitSet,
 }
 
 impl FileStore {
     pub fn open(&mut self,
                 file_name: &str,
-                mut read_only: bool,
+                read_only: bool,
                 encryption_key: Option<Box<Vec<Byte>>>) -> Result<()> {
 
         if self.file.is_some() {
@@ -50,71 +44,30 @@ impl FileStore {
 
         self.file_name = file_name.to_string();
 
-        let file_path = Path::new(file_name);
-        let parent_dir_path = file_path.parent();
-        if parent_dir_path.is_some() && !parent_dir_path.unwrap().exists() {
-            throw!(DbError::get_internal_error("parent dir not exist"));
-        }
-
-        if file_path.exists() && !file_utils::can_write(file_path) {
-            read_only = true;
-        }
-
-        let mut open_options = OpenOptions::new();
-        open_options.read(true);
-
-        if !read_only {
-            open_options.create(true);
-            open_options.write(true);
-            open_options.append(true);
-        }
+        let mut file = file_channel::open(file_name, read_only)?;
+        self.read_only = file.is_read_only();
 
-        match open_options.open::<&Path>(file_name.as_ref()) {
-            Ok(file) => {
-                self.file = Some(file)
-            }
-            Err(e) => {
-                self.close();
-                throw!( DbError::get_internal_error("ERROR_READING_FAILED,Could not open file"));
-            }
-        }
+        // 只读的时候用共享锁
+        file.try_lock(self.read_only)?;
 
-        let file = self.file.as_ref().unwrap();
-        let fd = file.as_raw_fd();
-        let file_lock_result = if read_only {
-            FileLock::try_lock(fd, 0, i64::MAX, true)
-        } else {
-            FileLock::try_lock(fd, 0, i64::MAX, false)
-        };
-        match file_lock_result {
-            Ok(file_lock) => {
-                self.file_lock = Some(file_lock);
-            }
-            Err(e) => {
-                self.close();
-
-                let message = format!("try lock file failed,cause: {}", e);
-                throw!(DbError::get_internal_error(&message));
-            }
+        if let Some(encryption_key) = encryption_key {
+            let encryption_key: Vec<u8> = encryption_key.iter().map(|b| *b as u8).collect();
+            file = Box::new(FileEncrypt::new(file_name, &encryption_key, file)?);
         }
 
-        self.file_size = file_utils::get_size(&self.file_name)?;
+        self.file_size = file.size();
+        self.file = Some(file);
 
         Ok(())
     }
-    
+
     pub fn new() -> Result<SharedPtr<FileStore>> {
         Ok(Some(Arc::new(H2RustCell::new(FileStore::default()))))
     }
 
     pub fn close(&mut self) {
-        if self.file.is_some() {
-            if self.file_lock.is_some() {
-                self.file_lock.as_ref().unwrap().release();
-                self.file_lock = None;
-            }
-
-            self.file = None;
+        if let Some(mut file) = self.file.take() {
+            file.release_lock();
         }
     }
 
@@ -126,9 +79,25 @@ impl FileStore {
         self.file_size
     }
 
+    pub fn getFileName(&self) -> &str {
+        &self.file_name
+    }
+
+    pub fn isReadOnly(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn sync(&self) -> Result<()> {
+        if let Some(file) = self.file.as_ref() {
+            file.sync()?;
+        }
+
+        Ok(())
+    }
+
     pub fn readFully(&mut self, position: usize, len: usize) -> Result<ByteBuffer> {
         let mut byteBuffer = byte_buffer::allocate(len);
-        data_utils::readFully(self.file.as_ref().unwrap(), position, &mut byteBuffer);
+        self.file.as_ref().unwrap().read_fully(position as Long, byteBuffer.extractMut())?;
         self.read_count.fetch_add(1, Ordering::AcqRel);
         self.read_byte_count.fetch_add(len as Long, Ordering::AcqRel);
 
@@ -138,11 +107,100 @@ impl FileStore {
     pub fn writeFully(&mut self, position: usize, src: &mut ByteBuffer) -> Result<()> {
         let len = src.getRemaining();
         self.file_size = Long::max(self.file_size, (position + len) as Long);
-        data_utils::writeFully(self.file.as_ref().unwrap(), position, src);
+        self.file.as_mut().unwrap().write_fully(position as Long, src.extract())?;
+        src.advance(len);
         self.write_count.fetch_add(1, Ordering::AcqRel);
         self.write_byte_count.fetch_add(len as Long, Ordering::AcqRel);
 
         Ok(())
     }
+
+    /// Get the number of read operations since this store was opened.
+    pub fn getReadCount(&self) -> Long {
+        self.read_count.load(Ordering::Acquire)
+    }
+
+    /// Get the number of read bytes since this store was opened.
+    pub fn getReadBytes(&self) -> Long {
+        self.read_byte_count.load(Ordering::Acquire)
+    }
+
+    /// Get the number of write operations since this store was opened.
+    pub fn getWriteCount(&self) -> Long {
+        self.write_count.load(Ordering::Acquire)
+    }
+
+    /// Get the number of written bytes since this store was opened.
+    pub fn getWriteBytes(&self) -> Long {
+        self.write_byte_count.load(Ordering::Acquire)
+    }
+
+    /// Truncate the file.
+    pub fn truncate(&mut self, size: Long) -> Result<()> {
+        self.write_count.fetch_add(1, Ordering::AcqRel);
+        self.file.as_mut().unwrap().truncate(size)?;
+        self.file_size = Long::min(self.file_size, size);
+
+        Ok(())
+    }
+
+    /// Mark the space as in use.
+    pub fn markUsed(&mut self, pos: Long, length: Integer) -> Result<()> {
+        self.free_space.markUsed(pos, length)
+    }
+
+    /// Allocate a number of blocks and mark them as used.
+    ///
+    /// length: the number of bytes to allocate<br>
+    /// reservedLow: start block index of the reserved area (inclusive)<br>
+    /// reservedHigh: end block index of the reserved area (exclusive), special value -1 means beginning of the infinite free area
+    ///
+    /// return the start position in bytes
+    pub fn allocate(&mut self, length: Integer, reservedLow: Long, reservedHigh: Long) -> Long {
+        self.free_space.allocate(length, reservedLow, reservedHigh)
+    }
+
+    /// Calculate starting position of the prospective allocation.
+    ///
+    /// blocks: the number of blocks to allocate<br>
+    /// return the starting block index
+    pub fn predictAllocation(&self, blocks: Integer, reservedLow: Long, reservedHigh: Long) -> Long {
+        self.free_space.predictAllocation(blocks, reservedLow, reservedHigh)
+    }
+
+    /// Mark the space as free.
+    pub fn free(&mut self, pos: Long, length: Integer) {
+        self.free_space.free(pos, length);
+    }
+
+    /// Get the fill rate of the space in percent.
+    pub fn getFillRate(&self) -> Integer {
+        self.free_space.getFillRate()
+    }
+
+    /// Calculates a prospective fill rate, which store would have after rewrite of sparsely populated chunk(s)
+    /// and evacuation of still live data into a new chunk.
+    pub fn getProjectedFillRate(&self, vacatedBlocks: Integer) -> Integer {
+        self.free_space.getProjectedFillRate(vacatedBlocks)
+    }
+
+    /// Get the position of the first free space.
+    pub fn getFirstFree(&self) -> Long {
+        self.free_space.getFirstFree()
+    }
+
+    /// Get the length of the file that is used by chunks, 之后的部分都是空闲的,可以truncate掉
+    pub fn getFileLengthInUse(&self) -> Long {
+        self.free_space.getLastFree()
+    }
+
+    pub fn getMovePriority(&self, block: Integer) -> Integer {
+        self.free_space.getMovePriority(block)
+    }
+
+    /// Mark the file as empty.
+    pub fn clear(&mut self) {
+        self.free_space.clear();
+    }
 }
 
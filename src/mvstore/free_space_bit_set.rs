@@ -0,0 +1,355 @@
+use anyhow::Result;
+use std::fmt::{Display, Formatter};
+use bit_set::BitSet;
+use crate::h2_rust_common::{Integer, Long};
+use crate::message::db_error::DbError;
+use crate::mvstore::{data_utils, mv_store};
+use crate::db::store;
+use crate::throw;
+use crate::util::utils;
+
+/// A free space bit set.
+pub struct FreeSpaceBitSet {
+    /// The first usable block.
+    firstFreeBlock: Integer,
+
+    /// The block size in bytes.
+    blockSize: Integer,
+
+    /// The bit set.
+    set: BitSet,
+}
+
+impl Default for FreeSpaceBitSet {
+    /// 头两个block是store header
+    fn default() -> Self {
+        FreeSpaceBitSet::new(2, mv_store::BLOCK_SIZE)
+    }
+}
+
+impl FreeSpaceBitSet {
+    /// Create a new free space map.
+    ///
+    /// firstFreeBlock: the first free block<br>
+    /// blockSize: the block size
+    pub fn new(firstFreeBlock: Integer, blockSize: Integer) -> FreeSpaceBitSet {
+        let mut freeSpaceBitSet = FreeSpaceBitSet {
+            firstFreeBlock,
+            blockSize,
+            set: BitSet::new(),
+        };
+        freeSpaceBitSet.clear();
+        freeSpaceBitSet
+    }
+
+    /// Reset the list.
+    pub fn clear(&mut self) {
+        self.set.clear();
+        self.setRange(0, self.firstFreeBlock);
+    }
+
+    /// Check whether one of the blocks is in use.
+    pub fn isUsed(&self, pos: Long) -> bool {
+        self.set.contains(self.getBlock(pos) as usize)
+    }
+
+    /// Check whether one of the blocks is free.
+    pub fn isFree(&self, pos: Long, length: Integer) -> bool {
+        let start = self.getBlock(pos);
+        let blocks = self.getBlockCount(length);
+        (start..start + blocks).all(|block| !self.set.contains(block as usize))
+    }
+
+    /// Allocate a number of blocks and mark them as used.<br>
+    /// reservedLow和reservedHigh之间的区域不能使用,reservedHigh小于0时和保留区域重叠就分配在末尾
+    ///
+    /// return the start position in bytes
+    pub fn allocate(&mut self, length: Integer, reservedLow: Long, reservedHigh: Long) -> Long {
+        let blocks = self.getBlockCount(length);
+        let start = self.findFree(blocks, reservedLow as Integer, reservedHigh as Integer);
+        self.setRange(start, start + blocks);
+        self.getPos(start)
+    }
+
+    /// 不实际分配,只是预测allocate会分配到的block
+    pub fn predictAllocation(&self, blocks: Integer, reservedLow: Long, reservedHigh: Long) -> Long {
+        self.findFree(blocks, reservedLow as Integer, reservedHigh as Integer) as Long
+    }
+
+    fn findFree(&self, blocks: Integer, reservedLow: Integer, reservedHigh: Integer) -> Integer {
+        let mut i = 0;
+        loop {
+            let mut start = self.nextClearBit(i);
+            let end = self.nextSetBit(start + 1);
+            if end < 0 || end - start >= blocks {
+                if (reservedHigh < 0 || start < reservedHigh) && start + blocks > reservedLow {
+                    // overlap detected
+                    if reservedHigh < 0 {
+                        start = self.getAfterLastBlock();
+                    } else {
+                        i = reservedHigh;
+                        continue;
+                    }
+                }
+
+                assert!(self.nextSetBit(start) == -1 || self.nextSetBit(start) >= start + blocks, "Double alloc: {:x}/{:x} {}", start, blocks, self);
+                return start;
+            }
+            i = end;
+        }
+    }
+
+    /// Mark the space as in use.
+    pub fn markUsed(&mut self, pos: Long, length: Integer) -> Result<()> {
+        let start = self.getBlock(pos);
+        let blocks = self.getBlockCount(length);
+
+        // this is not an assert because we get called during file opening
+        let nextSetBit = self.nextSetBit(start);
+        if nextSetBit != -1 && nextSetBit < start + blocks {
+            let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_FILE_CORRUPT);
+            throw!(DbError::get(error_code, vec![&format!("Double mark: {:x}/{:x} {}", start, blocks, self)]));
+        }
+
+        self.setRange(start, start + blocks);
+
+        Ok(())
+    }
+
+    /// Mark the space as free.
+    pub fn free(&mut self, pos: Long, length: Integer) {
+        let start = self.getBlock(pos);
+        let blocks = self.getBlockCount(length);
+        assert!(self.nextClearBit(start) >= start + blocks, "Double free: {:x}/{:x} {}", start, blocks, self);
+
+        for block in start..start + blocks {
+            self.set.remove(block as usize);
+        }
+    }
+
+    /// Get the fill rate of the space in percent. The value 0 means the space is completely free,
+    /// and 100 means it is completely full.
+    pub fn getFillRate(&self) -> Integer {
+        self.getProjectedFillRate(0)
+    }
+
+    /// Calculates a prospective fill rate, which store would have after rewrite of sparsely populated chunk(s)
+    /// and evacuation of still live data into a new chunk.
+    ///
+    /// vacatedBlocks: number of blocks vacated
+    pub fn getProjectedFillRate(&self, vacatedBlocks: Integer) -> Integer {
+        // it's not bullet-proof against race condition but should be good enough
+        // to get approximation without holding a store lock
+        let mut usedBlocks = self.set.len() as Integer;
+        let mut totalBlocks = self.getAfterLastBlock();
+
+        // exclude header blocks
+        totalBlocks -= self.firstFreeBlock;
+        usedBlocks -= self.firstFreeBlock + vacatedBlocks;
+        if usedBlocks <= 0 || totalBlocks <= 0 {
+            return 0;
+        }
+
+        // use a ceiling function, so that 100 is only returned when the space is completely full
+        ((100 * usedBlocks as Long + totalBlocks as Long - 1) / totalBlocks as Long) as Integer
+    }
+
+    /// Get the position of the first free space.
+    pub fn getFirstFree(&self) -> Long {
+        self.getPos(self.nextClearBit(0))
+    }
+
+    /// Get the position of the last (infinite) free space.
+    pub fn getLastFree(&self) -> Long {
+        self.getPos(self.getAfterLastBlock())
+    }
+
+    /// Get the index of the first block after last occupied one. It marks the beginning of the last (infinite) free space.
+    pub fn getAfterLastBlock(&self) -> Integer {
+        self.previousSetBit(self.set.get_ref().len() as Integer - 1) + 1
+    }
+
+    /// Calculates relative "priority" for chunk to be moved.
+    ///
+    /// block: where chunk starts<br>
+    /// return priority, smaller number indicate that chunk need to be moved sooner
+    pub fn getMovePriority(&self, block: Integer) -> Integer {
+        // The most desirable chunks to move are the ones sitting within
+        // a relatively short span of occupied blocks which is surrounded
+        // from both sides by relatively long free spans
+        let mut prevEnd = self.previousClearBit(block);
+        let mut freeSize;
+        if prevEnd < 0 {
+            prevEnd = self.firstFreeBlock;
+            freeSize = 0;
+        } else {
+            freeSize = prevEnd - self.previousSetBit(prevEnd);
+        }
+
+        let nextStart = self.nextClearBit(block);
+        let nextEnd = self.nextSetBit(nextStart);
+        if nextEnd >= 0 {
+            freeSize += nextEnd - nextStart;
+        }
+
+        (nextStart - prevEnd - 1) * 1000 / (freeSize + 1)
+    }
+
+    fn getPos(&self, block: Integer) -> Long {
+        block as Long * self.blockSize as Long
+    }
+
+    fn getBlock(&self, pos: Long) -> Integer {
+        (pos / self.blockSize as Long) as Integer
+    }
+
+    fn getBlockCount(&self, length: Integer) -> Integer {
+        utils::roundUpInt(length, self.blockSize) / self.blockSize
+    }
+
+    fn setRange(&mut self, fromIndex: Integer, toIndex: Integer) {
+        for block in fromIndex..toIndex {
+            self.set.insert(block as usize);
+        }
+    }
+
+    /// 对应java BitSet.nextClearBit
+    fn nextClearBit(&self, fromIndex: Integer) -> Integer {
+        let mut index = fromIndex;
+        while self.set.contains(index as usize) {
+            index += 1;
+        }
+        index
+    }
+
+    /// 对应java BitSet.nextSetBit,没有时返回-1
+    fn nextSetBit(&self, fromIndex: Integer) -> Integer {
+        let capacity = self.set.get_ref().len() as Integer;
+        (fromIndex..capacity).find(|index| self.set.contains(*index as usize)).unwrap_or(-1)
+    }
+
+    /// 对应java BitSet.previousSetBit,没有时返回-1
+    fn previousSetBit(&self, fromIndex: Integer) -> Integer {
+        (0..=fromIndex).rev().find(|index| self.set.contains(*index as usize)).unwrap_or(-1)
+    }
+
+    /// 对应java BitSet.previousClearBit,没有时返回-1
+    fn previousClearBit(&self, fromIndex: Integer) -> Integer {
+        (0..=fromIndex).rev().find(|index| !self.set.contains(*index as usize)).unwrap_or(-1)
+    }
+}
+
+impl Display for FreeSpaceBitSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[")?;
+        let mut on = 0;
+        let mut off = 0;
+        for index in 0..self.getAfterLastBlock() {
+            if self.set.contains(index as usize) {
+                if off > 0 {
+                    write!(f, "-{}", off)?;
+                    off = 0;
+                }
+                on += 1;
+            } else {
+                if on > 0 {
+                    write!(f, "+{}", on)?;
+                    on = 0;
+                }
+                off += 1;
+            }
+        }
+        if on > 0 {
+            write!(f, "+{}", on)?;
+        }
+        write!(f, "]")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{get_ref, get_ref_mut};
+    use crate::mvstore::free_space_bit_set::FreeSpaceBitSet;
+    use crate::mvstore::mv_map::MVMapBuilder;
+    use crate::mvstore::test_utils::{string, TempStore};
+
+    #[test]
+    fn test_allocate_free() {
+        let mut freeSpaceBitSet = FreeSpaceBitSet::new(2, 4096);
+        assert_eq!(freeSpaceBitSet.getFirstFree(), 2 * 4096);
+        assert_eq!(freeSpaceBitSet.getFillRate(), 0);
+
+        assert_eq!(freeSpaceBitSet.allocate(4096, 0, 0), 2 * 4096);
+        assert_eq!(freeSpaceBitSet.allocate(3 * 4096, 0, 0), 3 * 4096);
+        assert_eq!(freeSpaceBitSet.allocate(10, 0, 0), 6 * 4096);
+        assert_eq!(freeSpaceBitSet.getFillRate(), 100);
+        assert_eq!(freeSpaceBitSet.getLastFree(), 7 * 4096);
+
+        freeSpaceBitSet.free(3 * 4096, 3 * 4096);
+        assert!(freeSpaceBitSet.isFree(3 * 4096, 3 * 4096));
+        assert_eq!(freeSpaceBitSet.getFillRate(), 40);
+        assert_eq!(freeSpaceBitSet.getProjectedFillRate(1), 20);
+
+        // 能放下就重用空出来的block
+        assert_eq!(freeSpaceBitSet.allocate(2 * 4096, 0, 0), 3 * 4096);
+        // 避开保留的区域
+        assert_eq!(freeSpaceBitSet.allocate(4096, 5, 7), 7 * 4096);
+        assert_eq!(freeSpaceBitSet.allocate(4096, 0, -1), 8 * 4096);
+
+        assert!(freeSpaceBitSet.markUsed(2 * 4096, 4096).is_err());
+        freeSpaceBitSet.clear();
+        assert_eq!(freeSpaceBitSet.getLastFree(), 2 * 4096);
+        assert!(freeSpaceBitSet.markUsed(2 * 4096, 4096).is_ok());
+    }
+
+    #[test]
+    fn test_compact_move_chunks() {
+        let tempStore = TempStore::new("test_compact_move_chunks");
+
+        {
+            let mvStore = tempStore.openWith(&[("autoCommitDelay", 0)]);
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+            mvStoreMutRef.setRetentionTime(0);
+            mvStoreMutRef.setVersionsToKeep(0);
+
+            // 登记版本使用,期间不再使用的chunk不会被删掉,空间也不会被复用
+            let txCounter = mvStoreMutRef.registerVersionUsage();
+
+            let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+            for round in 0..20 {
+                for a in 0..100 {
+                    get_ref_mut!(data).put(string(&format!("k{}", a)), string(&format!("v{}_{}", round, a))).unwrap();
+                }
+                mvStoreMutRef.commit().unwrap();
+            }
+
+            // 文件末尾的chunk一直存活,前边的chunk删掉后留下空洞
+            let tail = mvStoreMutRef.openMap("tail", &MVMapBuilder::new()).unwrap();
+            for a in 0..2000 {
+                get_ref_mut!(tail).put(string(&format!("t{}", a)), string(&format!("value of t{}", a))).unwrap();
+            }
+            mvStoreMutRef.commit().unwrap();
+            mvStoreMutRef.deregisterVersionUsage(&txCounter);
+
+            for _ in 0..5 {
+                mvStoreMutRef.compact(50, 16 * 1024 * 1024).unwrap();
+            }
+            assert!(mvStoreMutRef.getFillRate() < 100);
+
+            let sizeBefore = get_ref!(mvStoreMutRef.getFileStore()).size();
+            mvStoreMutRef.compactMoveChunks().unwrap();
+            assert!(get_ref!(mvStoreMutRef.getFileStore()).size() < sizeBefore);
+            assert_eq!(mvStoreMutRef.getFillRate(), 100);
+
+            mvStoreMutRef.close().unwrap();
+        }
+
+        let mvStore = tempStore.open();
+        let data = get_ref_mut!(mvStore).openMap("data", &MVMapBuilder::new()).unwrap();
+        assert_eq!(get_ref!(data).size_as_long(), 100);
+        assert_eq!(get_ref!(data).get(&string("k42")).castAsStringRef(), "v19_42");
+        let tail = get_ref_mut!(mvStore).openMap("tail", &MVMapBuilder::new()).unwrap();
+        assert_eq!(get_ref!(tail).get(&string("t1999")).castAsStringRef(), "value of t1999");
+        get_ref_mut!(mvStore).close().unwrap();
+    }
+}
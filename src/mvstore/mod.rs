@@ -3,13 +3,20 @@ pub mod mv_store_tool;
 
 pub mod data_utils;
 pub mod file_store;
+pub mod free_space_bit_set;
 pub mod cache;
 pub mod page;
 pub mod mv_map;
+pub mod typed_mv_map;
 pub mod r#type;
 pub mod write_buffer;
 pub mod root_reference;
 
 
 pub mod chunk;
-pub mod cursor_pos;
\ No newline at end of file
+pub mod cursor;
+pub mod cursor_pos;
+pub mod tx_counter;
+pub mod mv_store_stats;
+#[cfg(test)]
+pub mod test_utils;
@@ -1,20 +1,28 @@
+use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 use anyhow::Result;
 use std::sync::{Arc, Mutex, Weak};
 use std::sync::atomic::{AtomicBool, AtomicI64, AtomicPtr, Ordering};
+use std::thread;
+use std::time::Duration;
 use usync::RwLock;
 use crate::{build_option_arc_h2RustCell, get_ref, get_ref_mut, h2_rust_cell_equals, suffix_plus_plus, throw, weak_get_ref, weak_get_ref_mut};
 use crate::api::error_code;
+use crate::engine::constant;
 use crate::h2_rust_common::{h2_rust_utils, Integer, Long, Nullable};
 use crate::h2_rust_common::h2_rust_cell::{H2RustCell, SharedPtr, WeakPtr};
 use crate::h2_rust_common::h2_rust_type::H2RustType;
 use crate::message::db_error::DbError;
 use crate::mvstore::mv_store::{MVStore};
-use crate::mvstore::{data_utils, page};
-use crate::mvstore::page::{Page, PageTrait};
+use crate::mvstore::{cursor_pos, data_utils, page};
+use crate::mvstore::cursor::Cursor;
+use crate::mvstore::cursor_pos::CursorPos;
+use crate::mvstore::page::{Page, PageReference, PageTrait};
 use crate::mvstore::r#type::data_type::DataType;
+use crate::mvstore::r#type::{data_type_registry, string_data_type};
 use crate::mvstore::root_reference::{RootReference};
+use crate::mvstore::tx_counter::TxCounter;
 
 #[derive(Default)]
 pub struct MVMap {
@@ -38,6 +46,9 @@ pub struct MVMap {
     pub isVolatile: bool,
     readOnly: bool,
     pub closed: AtomicBool,
+
+    /// openVersion得到的只读快照登记的版本使用,快照drop时释放,期间这个版本用到的chunk不会被删掉
+    txCounter: Option<Arc<TxCounter>>,
 }
 
 impl MVMap where {
@@ -100,12 +111,42 @@ impl MVMap where {
         Ok(build_option_arc_h2RustCell!(mvMap))
     }
 
+    /// 对应java的MVMap(MVStore store, DataType keyType, DataType valueType, Map<String, Object> config)
+    /// config中的值是保存在meta map中的hex string
+    pub fn new2(mvStoreWeakPtr: WeakPtr<MVStore>,
+                keyType: Arc<dyn DataType>,
+                valueType: Arc<dyn DataType>,
+                config: &HashMap<String, String>) -> Result<SharedPtr<MVMap>> {
+        let id: Integer = data_utils::readHexIntOrLong(config, "id", 0)?;
+        let createVersion: Long = data_utils::readHexIntOrLong(config, "createVersion", 0)?;
+
+        let keysPerPage = weak_get_ref!(mvStoreWeakPtr).keysPerPage;
+        let currentVersion = weak_get_ref!(mvStoreWeakPtr).getCurrentVersion();
+
+        let mvMap = Self::new1(mvStoreWeakPtr,
+                               keyType,
+                               valueType,
+                               id,
+                               createVersion,
+                               None,
+                               keysPerPage,
+                               false)?;
+        let mvMapMutRef = get_ref_mut!(mvMap);
+        mvMapMutRef.setInitialRoot(mvMapMutRef.createEmptyLeaf(mvMap.clone()), currentVersion);
+
+        Ok(mvMap)
+    }
+
     fn createEmptyLeaf(&self, this: SharedPtr<MVMap>) -> SharedPtr<dyn PageTrait> {
         Page::createEmptyLeaf(this)
     }
 
     pub fn is_persistent(&self) -> bool {
-        return self.mvStore.as_ref().unwrap().upgrade().is_some() && !self.isVolatile;
+        // 内存中的store没有fileStore
+        match self.mvStore.as_ref().and_then(|mvStore| mvStore.upgrade()) {
+            Some(mvStore) => mvStore.get_ref().getFileStore().is_some() && !self.isVolatile,
+            None => false
+        }
     }
 
     fn setInitialRoot(&mut self, root_page: SharedPtr<dyn PageTrait>, version: Long) {
@@ -118,28 +159,31 @@ impl MVMap where {
     }
 
     /// set the position of the root page.
-    pub fn setRootPosition(&mut self, rootPosition: Long, version: Long, this: SharedPtr<MVMap>) {
-        let mut root: SharedPtr<dyn PageTrait> = self.readOrCreateRootPage(rootPosition, this.clone());
+    pub fn setRootPosition(&mut self, rootPosition: Long, version: Long, this: SharedPtr<MVMap>) -> Result<()> {
+        let mut root: SharedPtr<dyn PageTrait> = self.readOrCreateRootPage(rootPosition, this.clone())?;
 
         let mvMap = get_ref!(root).getMvMap();
-        if h2_rust_cell_equals!(mvMap,this) {
+        if !h2_rust_cell_equals!(mvMap,this) {
             // this can only happen on concurrent opening of existing map,
             // when second thread picks up some cached page already owned by the first map's instantiation (both maps share the same id)
             assert_eq!(self.id, get_ref!(mvMap).id);
 
+            // since it is unknown which one will win the race, let each map instance to have it's own copy
             root = get_ref!(root).copy(this, false, root.clone());
+        }
 
-            self.setInitialRoot(root, version);
+        self.setInitialRoot(root, version);
 
-            self.setWriteVersion(weak_get_ref!(self.mvStore).currentVersion.load(Ordering::Acquire));
-        }
+        self.setWriteVersion(weak_get_ref!(self.mvStore).currentVersion.load(Ordering::Acquire))?;
+
+        Ok(())
     }
 
-    fn readOrCreateRootPage(&self, rootPosition: Long, this: SharedPtr<MVMap>) -> SharedPtr<dyn PageTrait> {
+    fn readOrCreateRootPage(&self, rootPosition: Long, this: SharedPtr<MVMap>) -> Result<SharedPtr<dyn PageTrait>> {
         if rootPosition == 0 {
-            self.createEmptyLeaf(this)
+            Ok(self.createEmptyLeaf(this))
         } else {
-            self.readPage(this, rootPosition).unwrap()
+            self.readPage(this, rootPosition)
         }
     }
 
@@ -151,15 +195,252 @@ impl MVMap where {
         self.get2(self.getRootPage(), key)
     }
 
+    pub fn containsKey(&self, key: &H2RustType) -> bool {
+        !self.get(key).isNull()
+    }
+
     pub fn get2(&self, pageTraitSharedPtr: SharedPtr<dyn PageTrait>, key: &H2RustType) -> H2RustType {
         page::get(pageTraitSharedPtr, key)
     }
 
+    /// Get the first key, or Null if the map is empty.
+    pub fn firstKey(&self) -> H2RustType {
+        self.getFirstLast(true)
+    }
+
+    /// Get the last key, or Null if the map is empty.
+    pub fn lastKey(&self) -> H2RustType {
+        self.getFirstLast(false)
+    }
+
+    fn getFirstLast(&self, first: bool) -> H2RustType {
+        let mut page = self.getRootPage();
+        if get_ref!(page).getTotalCount() == 0 {
+            return H2RustType::Null;
+        }
+
+        loop {
+            let pageRef = get_ref!(page);
+            if pageRef.isLeaf() {
+                return pageRef.getKey(if first { 0 } else { pageRef.getKeyCount() - 1 });
+            }
+            let child = pageRef.getChildPage(if first { 0 } else { getChildPageCount(pageRef) - 1 });
+            page = child;
+        }
+    }
+
+    /// Get the smallest key that is larger than the given key, or Null if no such key exists.
+    pub fn higherKey(&self, key: &H2RustType) -> H2RustType {
+        self.getMinMax(self.getRootPage(), key, false, true)
+    }
+
+    /// Get the smallest key that is larger than or equal to this key, or Null if no such key exists.
+    pub fn ceilingKey(&self, key: &H2RustType) -> H2RustType {
+        self.getMinMax(self.getRootPage(), key, false, false)
+    }
+
+    /// Get the largest key that is smaller than or equal to this key, or Null if no such key exists.
+    pub fn floorKey(&self, key: &H2RustType) -> H2RustType {
+        self.getMinMax(self.getRootPage(), key, true, false)
+    }
+
+    /// Get the largest key that is smaller than the given key, or Null if no such key exists.
+    pub fn lowerKey(&self, key: &H2RustType) -> H2RustType {
+        self.getMinMax(self.getRootPage(), key, true, true)
+    }
+
+    fn getMinMax(&self, page: SharedPtr<dyn PageTrait>, key: &H2RustType, min: bool, excluding: bool) -> H2RustType {
+        let pageMutRef = get_ref_mut!(page);
+        let mut x = pageMutRef.binarySearch(key);
+
+        if pageMutRef.isLeaf() {
+            if x < 0 {
+                x = -x - if min { 2 } else { 1 };
+            } else if excluding {
+                x += if min { -1 } else { 1 };
+            }
+
+            if x < 0 || x >= pageMutRef.getKeyCount() {
+                return H2RustType::Null;
+            }
+
+            return pageMutRef.getKey(x);
+        }
+
+        if suffix_plus_plus!(x) < 0 {
+            x = -x;
+        }
+
+        loop {
+            if x < 0 || x >= getChildPageCount(pageMutRef) {
+                return H2RustType::Null;
+            }
+
+            let k = self.getMinMax(pageMutRef.getChildPage(x), key, min, excluding);
+            if !k.isNull() {
+                return k;
+            }
+
+            x += if min { -1 } else { 1 };
+        }
+    }
+
+    /// Get the key at the given index. This is a O(log(size)) operation.
+    ///
+    /// return the key, or Null if the index is out of range
+    pub fn getKey(&self, index: Long) -> H2RustType {
+        if index < 0 || index >= self.size_as_long() {
+            return H2RustType::Null;
+        }
+
+        let mut page = self.getRootPage();
+        let mut offset: Long = 0;
+        loop {
+            let pageRef = get_ref!(page);
+            if pageRef.isLeaf() {
+                if index >= offset + pageRef.getKeyCount() as Long {
+                    return H2RustType::Null;
+                }
+                return pageRef.getKey((index - offset) as Integer);
+            }
+
+            let size = getChildPageCount(pageRef);
+            let mut i = 0;
+            while i < size {
+                let c = pageRef.getCounts(i);
+                if index < c + offset {
+                    break;
+                }
+                offset += c;
+                i += 1;
+            }
+
+            if i == size {
+                return H2RustType::Null;
+            }
+
+            let child = pageRef.getChildPage(i);
+            page = child;
+        }
+    }
+
+    /// Get the index of the given key in the map. This is a O(log(size)) operation.
+    ///
+    /// If the key was found, the returned value is the index in the key array.
+    /// If not found, the returned value is negative, where -1 means the provided key is smaller than any keys.
+    pub fn getKeyIndex(&self, key: &H2RustType) -> Long {
+        let mut page = self.getRootPage();
+        if get_ref!(page).getTotalCount() == 0 {
+            return -1;
+        }
+
+        let mut offset: Long = 0;
+        loop {
+            let pageMutRef = get_ref_mut!(page);
+            let mut x = pageMutRef.binarySearch(key);
+
+            if pageMutRef.isLeaf() {
+                if x < 0 {
+                    offset = -offset;
+                }
+                return offset + x as Long;
+            }
+
+            if suffix_plus_plus!(x) < 0 {
+                x = -x;
+            }
+
+            for i in 0..x {
+                offset += pageMutRef.getCounts(i);
+            }
+
+            let child = pageMutRef.getChildPage(x);
+            page = child;
+        }
+    }
+
+    /// Get the number of entries, as a integer. Integer.MAX_VALUE is returned if there are more than this entries.
+    pub fn size(&self) -> Integer {
+        let size = self.size_as_long();
+        if size > Integer::MAX as Long { Integer::MAX } else { size as Integer }
+    }
+
+    /// Get the number of entries, as a long.
+    pub fn size_as_long(&self) -> Long {
+        get_ref!(self.getRootReference()).getTotalCount()
+    }
+
+    pub fn isEmpty(&self) -> bool {
+        self.size_as_long() == 0
+    }
+
+    /// Get a cursor to iterate over a number of keys and values in the latest version of this map.
+    pub fn cursor(&self, from: &H2RustType) -> Cursor {
+        self.cursor3(from, H2RustType::Null, false)
+    }
+
+    /// Get a cursor to iterate over a number of keys and values in the latest version of this map.
+    /// to为Null表示没有上界,reverse为true时从from往小的方向遍历
+    pub fn cursor3(&self, from: &H2RustType, to: H2RustType, reverse: bool) -> Cursor {
+        Cursor::new(&self.flushAndGetRootReference(), from, to, reverse)
+    }
+
+    /// Iterate over a number of keys.
+    pub fn keyIterator(&self, from: &H2RustType) -> Cursor {
+        self.cursor3(from, H2RustType::Null, false)
+    }
+
+    /// Iterate over a number of keys in reverse order.
+    pub fn keyIteratorReverse(&self, from: &H2RustType) -> Cursor {
+        self.cursor3(from, H2RustType::Null, true)
+    }
+
     pub fn getRootPage(&self) -> SharedPtr<dyn PageTrait> {
         let root_reference_ref = self.flushAndGetRootReference();
         get_ref!(root_reference_ref).root.clone()
     }
 
+    /// 恢复模式使用,遍历所有能读出来的entry,读取失败(比如check value不对)的子page连同下面的entry一起跳过,
+    /// 每跳过1个page调用1次onLost(page的position, 其中的entry数量, 错误)
+    pub fn salvage(&self,
+                   this: SharedPtr<MVMap>,
+                   onEntry: &mut dyn FnMut(H2RustType, H2RustType) -> Result<()>,
+                   onLost: &mut dyn FnMut(Long, Long, anyhow::Error)) -> Result<()> {
+        self.salvagePage(&this, self.getRootPage(), onEntry, onLost)
+    }
+
+    fn salvagePage(&self,
+                   this: &SharedPtr<MVMap>,
+                   page: SharedPtr<dyn PageTrait>,
+                   onEntry: &mut dyn FnMut(H2RustType, H2RustType) -> Result<()>,
+                   onLost: &mut dyn FnMut(Long, Long, anyhow::Error)) -> Result<()> {
+        let pageRef = get_ref!(page);
+        if pageRef.isLeaf() {
+            for index in 0..pageRef.getKeyCount() {
+                onEntry(pageRef.getKey(index), pageRef.getValue(index))?;
+            }
+            return Ok(());
+        }
+
+        // 不使用getChildPage,它读取失败时会panic
+        for index in 0..=pageRef.getKeyCount() {
+            let position = pageRef.getChildPagePos(index);
+            let child =
+                if data_utils::isPageSaved(position) {
+                    self.readPage(this.clone(), position)
+                } else {
+                    Ok(pageRef.getChildPage(index))
+                };
+
+            match child {
+                Ok(child) => self.salvagePage(this, child, onEntry, onLost)?,
+                Err(e) => onLost(position, pageRef.getCounts(index), e),
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn flushAndGetRootReference(&self) -> SharedPtr<RootReference> {
         let r = self.getRootReference();
         // todo 因为通常singleWriter是false 且 flushAppendBuffer()很难 暂时的略过
@@ -186,52 +467,693 @@ impl MVMap where {
         self.id
     }
 
-    pub fn setWriteVersion(&mut self, writeVersion: Long) -> SharedPtr<RootReference> {
+    pub fn getMvStore(&self) -> WeakPtr<MVStore> {
+        self.mvStore.clone()
+    }
+
+    pub fn getCreateVersion(&self) -> Long {
+        self.createVersion
+    }
+
+    pub fn isClosed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Close the map. Accessing the data is still possible (to allow concurrent reads), but it is marked as closed.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+    }
+
+    /// 模拟java的AtomicReference.compareAndSet
+    pub fn compareAndSetRoot(&mut self, expectedRootReference: &SharedPtr<RootReference>, updatedRootReference: SharedPtr<RootReference>) -> bool {
+        let write_guard = self.rootReferenceSetMutex.write();
+        if h2_rust_cell_equals!(self.rootReference, expectedRootReference) {
+            self.rootReference = updatedRootReference;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn setWriteVersion(&mut self, writeVersion: Long) -> Result<SharedPtr<RootReference>> {
         let mut attempt = 0;
         loop {
-            let mut rootReference = self.flushAndGetRootReference();
+            let rootReference = self.flushAndGetRootReference();
             if get_ref!(rootReference).version >= writeVersion {
-                return rootReference;
+                return Ok(rootReference);
             }
 
             if self.closed.load(Ordering::Acquire) {
                 // map was closed a while back and can not possibly be in use by now
                 // it's time to remove it completely from the store (it was anonymous already)
                 if get_ref!(rootReference).getVersion() + 1 < weak_get_ref!(self.mvStore).getOldestVersionToKeep() {
-                    //     mvStore.deregisterMapRoot(id);
-                    //     return Option::default();
+                    weak_get_ref_mut!(self.mvStore).deregisterMapRoot(self.id)?;
+                    return Ok(None);
                 }
             }
+
+            // 和operate一样,多次失败或root已被其它线程锁住时先拿到锁,lockRoot里有退避,不会空转
+            let mut rootReference = rootReference;
+            let mut locked = false;
+            attempt += 1;
+            if attempt > 3 || get_ref!(rootReference).isLocked() {
+                self.lockRoot(rootReference, attempt);
+                rootReference = self.flushAndGetRootReference();
+                locked = true;
+            }
+
+            let updatedRootReference = RootReference::tryUnlockAndUpdateVersion(&rootReference, writeVersion, attempt);
+            if updatedRootReference.is_some() {
+                self.removeUnusedOldVersions(&updatedRootReference);
+                return Ok(updatedRootReference);
+            }
+
+            // 对应java的finally
+            if locked {
+                self.unlockRoot(None);
+            }
         }
     }
 
-    pub fn remove(&mut self, key: H2RustType) {
-        self.operate(key, H2RustType::Null, DecisionMaker.REMOVE);
+    /// Roll the root back to the specified version.
+    ///
+    /// return true if a rollback was done
+    pub fn rollbackRoot(&mut self, version: Long) -> Result<bool> {
+        let mut rootReference = self.flushAndGetRootReference();
+        loop {
+            let previous = get_ref!(rootReference).getPrevious();
+            if get_ref!(rootReference).version < version || previous.is_none() {
+                break;
+            }
+
+            if self.compareAndSetRoot(&rootReference, previous.clone()) {
+                rootReference = previous;
+                self.closed.store(false, Ordering::Release);
+            }
+        }
+
+        self.setWriteVersion(version)?;
+
+        Ok(get_ref!(rootReference).version < version)
+    }
+
+    fn removeUnusedOldVersions(&self, rootReference: &SharedPtr<RootReference>) {
+        let oldestVersionToKeep = weak_get_ref!(self.mvStore).getOldestVersionToKeep();
+        get_ref_mut!(rootReference).removeUnusedOldVersions(oldestVersionToKeep);
     }
 
+    /// Add or replace a key-value pair.
+    ///
+    /// return the old value if the key existed, or Null otherwise
+    pub fn put(&mut self, key: H2RustType, value: H2RustType) -> Result<H2RustType> {
+        data_utils::check_argument(!value.isNull(), "The value may not be null")?;
+        self.operate(key, value, &mut PutDecisionMaker)
+    }
+
+    /// Add a key-value pair if it does not yet exist.
+    ///
+    /// return the old value if the key existed, or Null otherwise
+    pub fn putIfAbsent(&mut self, key: H2RustType, value: H2RustType) -> Result<H2RustType> {
+        self.operate(key, value, &mut IfAbsentDecisionMaker)
+    }
+
+    /// Replace a value for an existing key.
+    ///
+    /// return the old value, if the value was replaced, or Null
+    pub fn replace(&mut self, key: H2RustType, value: H2RustType) -> Result<H2RustType> {
+        self.operate(key, value, &mut IfPresentDecisionMaker)
+    }
+
+    /// Remove a key-value pair, if the key exists.
+    ///
+    /// return the old value if the key existed, or Null otherwise
+    pub fn remove(&mut self, key: H2RustType) -> Result<H2RustType> {
+        self.operate(key, H2RustType::Null, &mut RemoveDecisionMaker)
+    }
+
+    /// Add, replace or remove a key-value pair.
+    ///
+    /// decisionMaker: command object to make choices during transaction.
+    /// return previous value, if mapping for that key existed, or Null otherwise
     pub fn operate(&mut self,
                    key: H2RustType,
                    value: H2RustType,
-                   decisionMaker: impl DecisionMaker) {
-        let attempt = 0;
+                   decisionMaker: &mut dyn DecisionMaker) -> Result<H2RustType> {
+        // 写chunk时才发现不能序列化就晚了
+        self.getKeyType().validate(&key)?;
+        if !value.isNull() {
+            self.getValueType().validate(&value)?;
+        }
+
+        let mut attempt = 0;
 
         loop {
-            let rootReference = self.flushAndGetRootReference();
-            let locked = get_ref!(rootReference).isLockedByCurrentThread();
+            let mut rootReference = self.flushAndGetRootReference();
+            let mut locked = get_ref!(rootReference).isLockedByCurrentThread();
 
             if !locked {
                 if suffix_plus_plus!(attempt) == 0 {
-                    self.beforeWrite();
+                    self.beforeWrite()?;
+                }
+
+                if attempt > 3 || get_ref!(rootReference).isLocked() {
+                    rootReference = self.lockRoot(rootReference, attempt);
+                    locked = true;
                 }
             }
+
+            let mut rootPage = get_ref!(rootReference).root.clone();
+            let result = self.tryOperate(&rootReference, attempt, &key, value.clone(), decisionMaker, &mut rootPage);
+
+            // 对应java的finally
+            if locked {
+                self.unlockRoot(rootPage);
+            }
+
+            if let Some(result) = result {
+                return Ok(result);
+            }
         }
     }
 
-    pub fn beforeWrite(&self) -> Result<()> {
-        let mvStore = weak_get_ref_mut!(self.mvStore);
+    /// operate的1次尝试,返回None表示root已被其它线程改动或是decisionMaker要求重来
+    fn tryOperate(&mut self,
+                  rootReference: &SharedPtr<RootReference>,
+                  attempt: Integer,
+                  key: &H2RustType,
+                  mut value: H2RustType,
+                  decisionMaker: &mut dyn DecisionMaker,
+                  rootPage: &mut SharedPtr<dyn PageTrait>) -> Option<H2RustType> {
+        let locked = get_ref!(rootReference).isLockedByCurrentThread();
+        let mut unsavedMemory = 0;
+        // 合并时被替换掉的相邻page,不在tip的路径上
+        let mut removedPages = Vec::new();
+
+        let mut pos = cursor_pos::traverseDown(rootPage.clone(), key);
+        if !locked && self.isRootChanged(rootReference) {
+            return None;
+        }
+
+        let this = get_ref!(rootPage).getMvMap();
+
+        let tip = pos.clone();
+        let mut p = get_ref!(pos).page.clone();
+        let mut index = get_ref!(pos).index;
+        pos = get_ref!(pos).parent.clone();
+
+        let result = if index < 0 { H2RustType::Null } else { get_ref!(p).getValue(index) };
+
+        match decisionMaker.decide_3(&result, &value, &tip) {
+            Decision::REPEAT => {
+                decisionMaker.reset();
+                return None;
+            }
+            Decision::ABORT => {
+                if !locked && self.isRootChanged(rootReference) {
+                    decisionMaker.reset();
+                    return None;
+                }
+
+                return Some(result);
+            }
+            Decision::REMOVE => {
+                if index < 0 {
+                    if !locked && self.isRootChanged(rootReference) {
+                        decisionMaker.reset();
+                        return None;
+                    }
+
+                    return Some(H2RustType::Null);
+                }
+
+                // 整个page只剩这1个entry,删掉后上层的page也要变化
+                let mut collapsed = false;
+                if get_ref!(p).getTotalCount() == 1 && pos.is_some() {
+                    let mut keyCount;
+                    loop {
+                        p = get_ref!(pos).page.clone();
+                        index = get_ref!(pos).index;
+                        pos = get_ref!(pos).parent.clone();
+                        keyCount = get_ref!(p).getKeyCount();
+
+                        // condition below should always be false, but older versions (up to 1.4.197) may create
+                        // single-childed (with no keys) internal nodes, which we skip here
+                        if !(keyCount == 0 && pos.is_some()) {
+                            break;
+                        }
+                    }
+
+                    if keyCount <= 1 {
+                        if keyCount == 1 {
+                            assert!(index <= 1);
+                            p = get_ref!(p).getChildPage(1 - index);
+                        } else {
+                            // if root happens to be such single-childed (with no keys) internal node, then just replace it with empty leaf
+                            p = Page::createEmptyLeaf(this.clone());
+                        }
+                        collapsed = true;
+                    }
+                }
+
+                if !collapsed {
+                    p = get_ref!(p).copy0();
+                    get_ref_mut!(p).remove(index);
+
+                    if pos.is_some() && get_ref!(p).isLeaf() && self.isUnderfull(&p) {
+                        p = self.rebalanceLeaf(p, &mut pos, &mut removedPages, &mut unsavedMemory);
+                    }
+                }
+            }
+            Decision::PUT => {
+                value = decisionMaker.selectValue(&result, value);
+                p = get_ref!(p).copy0();
+
+                if index < 0 {
+                    get_ref_mut!(p).insertLeaf(-index - 1, key.clone(), value);
+
+                    let pageSplitSize = weak_get_ref!(self.mvStore).getPageSplitSize();
+                    loop {
+                        let keyCount = get_ref!(p).getKeyCount();
+                        let needSplit = keyCount > self.keysPerPage ||
+                            get_ref!(p).getMemory() > pageSplitSize && keyCount > if get_ref!(p).isLeaf() { 1 } else { 2 };
+                        if !needSplit {
+                            break;
+                        }
+
+                        let totalCount = get_ref!(p).getTotalCount();
+                        let at = keyCount >> 1;
+                        let k = get_ref!(p).getKey(at);
+                        let split = get_ref_mut!(p).split(at);
+                        unsavedMemory += get_ref!(p).getMemory() + get_ref!(split).getMemory();
+
+                        if pos.is_none() {
+                            let keys = vec![k];
+                            let children = vec![PageReference::new1(p), PageReference::new1(split)];
+                            p = Page::createNode(this.clone(), keys, children, totalCount, 0);
+                            break;
+                        }
+
+                        let c = p;
+                        p = get_ref!(pos).page.clone();
+                        index = get_ref!(pos).index;
+                        pos = get_ref!(pos).parent.clone();
+                        p = get_ref!(p).copy0();
+                        get_ref_mut!(p).setChild(index, split);
+                        get_ref_mut!(p).insertNode(index, k, c);
+                    }
+                } else {
+                    get_ref_mut!(p).setValue(index, value);
+                }
+            }
+        }
+
+        *rootPage = replacePage(pos, p, &mut unsavedMemory);
+
+        if !locked && !self.updateRoot(rootReference, rootPage.clone(), attempt) {
+            decisionMaker.reset();
+            return None;
+        }
+
+        if self.is_persistent() {
+            let version = get_ref!(rootReference).version;
+            unsavedMemory += cursor_pos::processRemovalInfo(tip, version);
+            for removedPage in removedPages {
+                unsavedMemory += get_ref!(removedPage).removePage(version);
+            }
+            weak_get_ref!(self.mvStore).registerUnsavedMemory(unsavedMemory);
+        }
+
+        Some(result)
+    }
+
+    /// 删除之后key的数量和占用的内存都不到上限的1/4
+    fn isUnderfull(&self, page: &SharedPtr<dyn PageTrait>) -> bool {
+        let pageRef = get_ref!(page);
+        if pageRef.getKeyCount() * 4 >= self.keysPerPage {
+            return false;
+        }
+
+        !self.is_persistent() || pageRef.getMemory() * 4 < weak_get_ref!(self.mvStore).getPageSplitSize()
+    }
+
+    /// Merge the underfull leaf p with its adjacent sibling. If the merged page would need a split,
+    /// the entries are evenly redistributed between the two pages instead.
+    ///
+    /// pos是p的父page,返回替换了父page的新page,pos移动到再上一层
+    fn rebalanceLeaf(&self,
+                     p: SharedPtr<dyn PageTrait>,
+                     pos: &mut SharedPtr<CursorPos>,
+                     removedPages: &mut Vec<SharedPtr<dyn PageTrait>>,
+                     unsavedMemory: &mut Integer) -> SharedPtr<dyn PageTrait> {
+        let parent = get_ref!(pos).page.clone();
+        let index = get_ref!(pos).index;
+
+        // older versions (up to 1.4.197) may create single-childed (with no keys) internal nodes
+        if get_ref!(parent).getKeyCount() == 0 {
+            return p;
+        }
+
+        // 删除时的collapse会让树的深度不一致,相邻的page可能不是leaf
+        let isLeafChild = |i: Integer| {
+            0 <= i && i <= get_ref!(parent).getKeyCount() && get_ref!(get_ref!(parent).getChildPage(i)).isLeaf()
+        };
+
+        // 总是把右边page的entry追加到左边page的副本
+        let (leftIndex, merged, right) = if isLeafChild(index - 1) {
+            let left = get_ref!(parent).getChildPage(index - 1);
+            removedPages.push(left.clone());
+            (index - 1, get_ref!(left).copy0(), p)
+        } else if isLeafChild(index + 1) {
+            let right = get_ref!(parent).getChildPage(index + 1);
+            removedPages.push(right.clone());
+            (index, p, right)
+        } else {
+            return p;
+        };
+
+        {
+            let rightRef = get_ref!(right);
+            let mergedMutRef = get_ref_mut!(merged);
+            for i in 0..rightRef.getKeyCount() {
+                let keyCount = mergedMutRef.getKeyCount();
+                mergedMutRef.insertLeaf(keyCount, rightRef.getKey(i), rightRef.getValue(i));
+            }
+        }
+
+        let newParent = get_ref!(parent).copy0();
+        let keyCount = get_ref!(merged).getKeyCount();
+        let pageSplitSize = weak_get_ref!(self.mvStore).getPageSplitSize();
+
+        // 删掉左边的child和它右侧的分隔key,原来右边child的位置放新的page
+        get_ref_mut!(newParent).remove(leftIndex);
+        if keyCount <= self.keysPerPage && get_ref!(merged).getMemory() <= pageSplitSize {
+            *unsavedMemory += get_ref!(merged).getMemory();
+            get_ref_mut!(newParent).setChild(leftIndex, merged.clone());
+        } else {
+            let at = keyCount >> 1;
+            let k = get_ref!(merged).getKey(at);
+            let split = get_ref_mut!(merged).split(at);
+            *unsavedMemory += get_ref!(merged).getMemory() + get_ref!(split).getMemory();
+            get_ref_mut!(newParent).insertNode(leftIndex, k, merged.clone());
+            get_ref_mut!(newParent).setChild(leftIndex + 1, split);
+        }
+
+        *pos = get_ref!(pos).parent.clone();
+
+        // 父page只剩合并后的page时由它代替,replacePage会再计入它的内存
+        if get_ref!(newParent).getKeyCount() == 0 {
+            *unsavedMemory -= get_ref!(merged).getMemory();
+            return merged;
+        }
+
+        newParent
+    }
+
+    /// Open an old version for the given map.
+    /// It will restore map at last known state of the version specified.
+    /// (at the point right before the commit() call, which advanced map to the next version)
+    /// Map is opened in read-only mode.
+    ///
+    /// 内存中保留的root不够旧时,从当时提交的chunk中保存的layout读取root的位置
+    pub fn openVersion(&self, version: Long) -> Result<SharedPtr<MVMap>> {
+        if self.readOnly {
+            throw!(DbError::get(error_code::GENERAL_ERROR_1, vec!["This map is read-only; need to call the method on the writable map"]));
+        }
+
+        data_utils::check_argument(version >= self.createVersion,
+                                   &format!("Unknown version {}; this map was created in version is {}", version, self.createVersion))?;
+
+        let mut rootReference = self.flushAndGetRootReference();
+        self.removeUnusedOldVersions(&rootReference);
+
+        let mut previous = get_ref!(rootReference).getPrevious();
+        while previous.is_some() && get_ref!(previous).version >= version {
+            rootReference = previous;
+            previous = get_ref!(rootReference).getPrevious();
+        }
+
+        let root = get_ref!(rootReference).root.clone();
+
+        if previous.is_none() {
+            if get_ref!(rootReference).version > version {
+                let this = get_ref!(root).getMvMap();
+                let rootPos = weak_get_ref_mut!(self.mvStore).getRootPos_(self.id, version)?;
+                let mvMap = self.openReadOnly_(rootPos, version, this)?;
+                self.registerVersionUsage(&mvMap);
+                return Ok(mvMap);
+            }
+
+            if version < weak_get_ref!(self.mvStore).getOldestVersionToKeep() {
+                throw!(DbError::get(error_code::GENERAL_ERROR_1, vec![&format!("Unknown version {}", version)]));
+            }
+        }
+
+        let mvMap = self.openReadOnly(root, version)?;
+        self.registerVersionUsage(&mvMap);
+        Ok(mvMap)
+    }
+
+    fn registerVersionUsage(&self, snapshot: &SharedPtr<MVMap>) {
+        get_ref_mut!(snapshot).txCounter = Some(weak_get_ref!(self.mvStore).registerVersionUsage());
+    }
+
+    /// Open a copy of the map in read-only mode.
+    pub fn openReadOnly_(&self, rootPos: Long, version: Long, this: SharedPtr<MVMap>) -> Result<SharedPtr<MVMap>> {
+        let root = self.readOrCreateRootPage(rootPos, this)?;
+        self.openReadOnly(root, version)
+    }
+
+    fn openReadOnly(&self, root: SharedPtr<dyn PageTrait>, version: Long) -> Result<SharedPtr<MVMap>> {
+        let mvMap = self.cloneIt()?;
+        let mvMapMutRef = get_ref_mut!(mvMap);
+        mvMapMutRef.readOnly = true;
+        mvMapMutRef.setInitialRoot(root, version);
+        Ok(mvMap)
+    }
 
+    /// Clone the current map.
+    fn cloneIt(&self) -> Result<SharedPtr<MVMap>> {
+        Self::new1(self.mvStore.clone(),
+                   self.getKeyType(),
+                   self.getValueType(),
+                   self.id,
+                   self.createVersion,
+                   self.getRootReference(),
+                   self.keysPerPage,
+                   self.single_writer)
+    }
+
+    pub fn isReadOnly(&self) -> bool {
+        self.readOnly
+    }
+
+    /// Remove all entries.
+    pub fn clear(&mut self, this: SharedPtr<MVMap>) -> Result<()> {
+        self.clearIt(this)?;
+        Ok(())
+    }
+
+    /// Remove all entries and return the root reference.
+    pub fn clearIt(&mut self, this: SharedPtr<MVMap>) -> Result<SharedPtr<RootReference>> {
+        let emptyRootPage = self.createEmptyLeaf(this);
+
+        let mut attempt = 0;
+
+        loop {
+            let mut rootReference = self.flushAndGetRootReference();
+            if get_ref!(rootReference).getTotalCount() == 0 {
+                return Ok(rootReference);
+            }
+
+            let mut locked = get_ref!(rootReference).isLockedByCurrentThread();
+            if !locked {
+                if suffix_plus_plus!(attempt) == 0 {
+                    self.beforeWrite()?;
+                } else if attempt > 3 || get_ref!(rootReference).isLocked() {
+                    rootReference = self.lockRoot(rootReference, attempt);
+                    locked = true;
+                }
+            }
+
+            let rootPage = get_ref!(rootReference).root.clone();
+            if !locked {
+                rootReference = RootReference::updateRootPage(&rootReference, emptyRootPage.clone(), attempt as Long);
+                if rootReference.is_none() {
+                    continue;
+                }
+            }
+
+            if self.is_persistent() {
+                let unsavedMemory = get_ref!(rootPage).removeAllRecursive(get_ref!(rootReference).version);
+                weak_get_ref!(self.mvStore).registerUnsavedMemory(unsavedMemory);
+            }
+
+            if locked {
+                self.unlockRoot(emptyRootPage);
+            }
+
+            return Ok(rootReference);
+        }
+    }
+
+    /// 生成保存在meta map中的map元信息
+    pub fn asString(&self, name: &str) -> String {
+        let mut buff = String::new();
+        data_utils::appendMap(&mut buff, "name", &name.to_string());
+        if self.createVersion != 0 {
+            data_utils::appendMap(&mut buff, "createVersion", &self.createVersion);
+        }
+        data_utils::appendMap(&mut buff, "key", &self.getKeyType().get_type_name());
+        data_utils::appendMap(&mut buff, "val", &self.getValueType().get_type_name());
+        buff
+    }
+
+    /// Re-write any pages that belong to one of the chunks in the given set.
+    ///
+    /// return the number of pages that were rewritten
+    pub fn rewrite(&mut self, set: &HashSet<Integer>) -> Result<Integer> {
+        let rootPage = self.getRootPage();
+        self.rewrite_(rootPage, set)
+    }
+
+    fn rewrite_(&mut self, page: SharedPtr<dyn PageTrait>, set: &HashSet<Integer>) -> Result<Integer> {
+        let pageRef = get_ref!(page);
+        if pageRef.isLeaf() {
+            let chunkId = data_utils::getPageChunkId(pageRef.getPosition());
+            if !set.contains(&chunkId) {
+                return Ok(0);
+            }
+
+            return Ok(if self.rewritePage(&page)? { 1 } else { 0 });
+        }
+
+        let mut writtenPageCount = 0;
+        for index in 0..getChildPageCount(pageRef) {
+            let childPos = pageRef.getChildPagePos(index);
+            if childPos != 0 && (data_utils::getPageType(childPos) & 1) == data_utils::PAGE_TYPE_LEAF {
+                // we would need to load the page, and it's a leaf:
+                // only do that if it's within the set of chunks we are interested in
+                if !set.contains(&data_utils::getPageChunkId(childPos)) {
+                    continue;
+                }
+            }
+
+            writtenPageCount += self.rewrite_(pageRef.getChildPage(index), set)?;
+        }
+
+        if writtenPageCount == 0 {
+            let chunkId = data_utils::getPageChunkId(pageRef.getPosition());
+            if set.contains(&chunkId) {
+                // an inner node page that is in one of the chunks, but only points to chunks that are not in the set:
+                // if no child was changed, we need to do that now
+                // (this is not needed if anyway one of the children was changed, as this would have updated this page as well)
+                let mut leaf = page.clone();
+                while !get_ref!(leaf).isLeaf() {
+                    leaf = get_ref!(leaf).getChildPage(0);
+                }
+
+                if self.rewritePage(&leaf)? {
+                    writtenPageCount = 1;
+                }
+            }
+        }
+
+        Ok(writtenPageCount)
+    }
+
+    /// 把page中第1个key的值原样再put一次,page及其上层page都会变成未保存的,下次提交时写到新的chunk
+    fn rewritePage(&mut self, page: &SharedPtr<dyn PageTrait>) -> Result<bool> {
+        let pageRef = get_ref!(page);
+        if pageRef.getKeyCount() == 0 || self.isClosed() {
+            return Ok(false);
+        }
+
+        let mut decisionMaker = RewriteDecisionMaker::new(pageRef.getPosition());
+        self.operate(pageRef.getKey(0), H2RustType::Null, &mut decisionMaker)?;
+
+        Ok(decisionMaker.decision != Some(Decision::ABORT))
+    }
+
+    fn isRootChanged(&self, rootReference: &SharedPtr<RootReference>) -> bool {
+        let current = self.getRootReference();
+        !h2_rust_cell_equals!(*rootReference, current)
+    }
+
+    /// Try to set the new root reference from now on.
+    fn updateRoot(&self, rootReference: &SharedPtr<RootReference>, newRootPage: SharedPtr<dyn PageTrait>, attemptUpdateCounter: Integer) -> bool {
+        RootReference::updateRootPage(rootReference, newRootPage, attemptUpdateCounter as Long).is_some()
+    }
+
+    fn lockRoot(&self, mut rootReference: SharedPtr<RootReference>, mut attempt: Integer) -> SharedPtr<RootReference> {
+        loop {
+            let lockedRootReference = self.tryLock(&rootReference, suffix_plus_plus!(attempt));
+            if lockedRootReference.is_some() {
+                return lockedRootReference;
+            }
+            rootReference = self.getRootReference();
+        }
+    }
+
+    /// Try to lock the root.
+    ///
+    /// return the new root reference, or None if not successful
+    fn tryLock(&self, rootReference: &SharedPtr<RootReference>, attempt: Integer) -> SharedPtr<RootReference> {
+        let lockedRootReference = RootReference::tryLock(rootReference, attempt);
+        if lockedRootReference.is_some() {
+            return lockedRootReference;
+        }
+
+        let rootReferenceRef = get_ref!(rootReference);
+        assert!(!rootReferenceRef.isLockedByCurrentThread());
+
+        let oldRootReference = rootReferenceRef.getPrevious();
+        let mut contention = 1;
+        if oldRootReference.is_some() {
+            let oldRootReferenceRef = get_ref!(oldRootReference);
+
+            let updateAttemptCounter = rootReferenceRef.getUpdateAttemptCounter() - oldRootReferenceRef.getUpdateAttemptCounter();
+            assert!(updateAttemptCounter >= 0);
+
+            let updateCounter = rootReferenceRef.getUpdateCounter() - oldRootReferenceRef.getUpdateCounter();
+            assert!(updateCounter >= 0);
+            assert!(updateAttemptCounter >= updateCounter);
+
+            contention += ((updateAttemptCounter + 1) / (updateCounter + 1)) as Integer;
+        }
+
+        if attempt > 4 {
+            if attempt <= 12 {
+                thread::yield_now();
+            } else if attempt <= 70 - 2 * contention {
+                thread::sleep(Duration::from_millis(contention as u64));
+            } else {
+                // java用的是lock.wait(5)等待unlock时的通知,这里简单的sleep
+                thread::sleep(Duration::from_millis(5));
+            }
+        }
+
+        None
+    }
+
+    fn unlockRoot(&self, newRootPage: SharedPtr<dyn PageTrait>) -> SharedPtr<RootReference> {
+        loop {
+            let rootReference = self.getRootReference();
+            let rootReferenceRef = get_ref!(rootReference);
+            assert!(rootReferenceRef.isLockedByCurrentThread());
+
+            let page = if newRootPage.is_none() { rootReferenceRef.root.clone() } else { newRootPage.clone() };
+            let updatedRootReference = RootReference::updatePageAndLockedStatus(&rootReference,
+                                                                                page,
+                                                                                rootReferenceRef.getHoldCount() > 1,
+                                                                                rootReferenceRef.getAppendCounter());
+            if updatedRootReference.is_some() {
+                return updatedRootReference;
+            }
+        }
+    }
+
+    pub fn beforeWrite(&self) -> Result<()> {
         if self.closed.load(Ordering::Acquire) {
-            let mapName = mvStore.getMapName(self.id);
             throw!(DbError::get(error_code::DATABASE_IS_CLOSED,vec![]));
         }
 
@@ -239,8 +1161,28 @@ impl MVMap where {
             throw!(DbError::get(error_code::GENERAL_ERROR_1,vec!["this map is read only"]));
         }
 
-        mvStore.beforeWrite(self);
-        todo!()
+        weak_get_ref_mut!(self.mvStore).beforeWrite(self)
+    }
+
+    pub fn isMemoryEstimationAllowed(&self) -> bool {
+        self.avgKeySize.is_some()
+    }
+
+    pub fn evaluateMemoryForKeys(&self, storage: &[H2RustType], count: Integer) -> Integer {
+        // todo rust略过MemoryEstimator,直接逐个计算
+        calculateMemory(self.getKeyType(), storage, count)
+    }
+
+    pub fn evaluateMemoryForValues(&self, storage: &[H2RustType], count: Integer) -> Integer {
+        calculateMemory(self.getValueType(), storage, count)
+    }
+
+    pub fn evaluateMemoryForKey(&self, key: &H2RustType) -> Integer {
+        self.getKeyType().get_memory(key)
+    }
+
+    pub fn evaluateMemoryForValue(&self, value: &H2RustType) -> Integer {
+        self.getValueType().get_memory(value)
     }
 
     pub fn hasChangesSince(&self, version: Long) -> bool {
@@ -248,6 +1190,103 @@ impl MVMap where {
     }
 }
 
+/// Get the child page count for this page. This is to allow another map
+/// implementation to override the default, in case the last child is not to be used.
+fn getChildPageCount(page: &dyn PageTrait) -> Integer {
+    page.getKeyCount() + 1
+}
+
+fn calculateMemory(dataType: Arc<dyn DataType>, storage: &[H2RustType], count: Integer) -> Integer {
+    let mut memory = count * constant::MEMORY_POINTER;
+    for obj in &storage[..count as usize] {
+        memory += dataType.get_memory(obj);
+    }
+    memory
+}
+
+impl Drop for MVMap {
+    fn drop(&mut self) {
+        if let Some(txCounter) = self.txCounter.take() {
+            if let Some(mvStore) = self.mvStore.as_ref().and_then(|mvStore| mvStore.upgrade()) {
+                mvStore.get_ref().deregisterVersionUsage(&txCounter);
+            }
+        }
+    }
+}
+
+/// 从下往上把路径上的page都copy,并把新的child设置进去
+fn replacePage(mut path: SharedPtr<CursorPos>, mut replacement: SharedPtr<dyn PageTrait>, unsavedMemory: &mut Integer) -> SharedPtr<dyn PageTrait> {
+    let mut memory = if get_ref!(replacement).isSaved() { 0 } else { get_ref!(replacement).getMemory() };
+
+    while path.is_some() {
+        let parent = get_ref!(path).page.clone();
+
+        // condition below should always be true, but older versions (up to 1.4.197)
+        // may create single-childed (with no keys) internal nodes, which we skip here
+        if get_ref!(parent).getKeyCount() > 0 {
+            let child = replacement;
+            replacement = get_ref!(parent).copy0();
+            get_ref_mut!(replacement).setChild(get_ref!(path).index, child);
+            memory += get_ref!(replacement).getMemory();
+        }
+
+        path = get_ref!(path).parent.clone();
+    }
+
+    *unsavedMemory += memory;
+    replacement
+}
+
+/// A builder for maps.
+///
+/// 没有指定的key/value类型时按meta中记录的类型名从data_type_registry获取,没有记录的默认使用StringDataType
+#[derive(Default)]
+pub struct MVMapBuilder {
+    keyType: Option<Arc<dyn DataType>>,
+    valueType: Option<Arc<dyn DataType>>,
+}
+
+impl MVMapBuilder {
+    pub fn new() -> Self {
+        MVMapBuilder::default()
+    }
+
+    pub fn keyType(&mut self, keyType: Arc<dyn DataType>) {
+        self.keyType = Some(keyType);
+    }
+
+    pub fn valueType(&mut self, valueType: Arc<dyn DataType>) {
+        self.valueType = Some(valueType);
+    }
+
+    pub fn getKeyType(&self) -> Option<Arc<dyn DataType>> {
+        self.keyType.clone()
+    }
+
+    pub fn getValueType(&self) -> Option<Arc<dyn DataType>> {
+        self.valueType.clone()
+    }
+
+    pub fn create(&self, mvStoreWeakPtr: WeakPtr<MVStore>, config: &HashMap<String, String>) -> Result<SharedPtr<MVMap>> {
+        let keyType = match &self.keyType {
+            Some(keyType) => keyType.clone(),
+            None => getDataType(config, "key")?,
+        };
+        let valueType = match &self.valueType {
+            Some(valueType) => valueType.clone(),
+            None => getDataType(config, "val")?,
+        };
+        MVMap::new2(mvStoreWeakPtr, keyType, valueType, config)
+    }
+}
+
+fn getDataType(config: &HashMap<String, String>, key: &str) -> Result<Arc<dyn DataType>> {
+    match config.get(key) {
+        Some(typeName) => data_type_registry::get(typeName),
+        None => Ok(string_data_type::INSTANCE.clone()),
+    }
+}
+
 pub fn getMapRootKey(mapId: Integer) -> String {
     format!("{}{}", data_utils::META_ROOT, format!("{:x}", mapId))
 }
@@ -256,6 +1295,7 @@ pub fn getMapKey(mapId: Integer) -> String {
     format!("{}{}", data_utils::META_MAP, h2_rust_utils::int2HexString(mapId))
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Decision {
     ABORT,
     REMOVE,
@@ -263,4 +1303,319 @@ pub enum Decision {
     REPEAT,
 }
 
-pub trait DecisionMaker {}
\ No newline at end of file
+/// Class DecisionMaker provides callback interface (and should become a such in Java 8)
+/// for MVMap.operate method.
+/// It provides control logic to make a decision about how to proceed with update
+/// at the point in execution when proper place and possible existing value
+/// for insert/update/delete key is found.
+/// Revised value for insert/update is also provided based on original input value
+/// and value currently existing in the map.
+pub trait DecisionMaker {
+    /// Makes a decision about how to proceed with the update.
+    ///
+    /// tip: pointing to the leaf page where the key is (or would be)
+    fn decide_3(&mut self, existingValue: &H2RustType, providedValue: &H2RustType, tip: &SharedPtr<CursorPos>) -> Decision {
+        self.decide_2(existingValue, providedValue)
+    }
+
+    /// Makes a decision about how to proceed with the update.
+    fn decide_2(&mut self, existingValue: &H2RustType, providedValue: &H2RustType) -> Decision;
+
+    /// Provides revised value for insert/update based on original input value
+    /// and value currently existing in the map.
+    /// This method is only invoked after call to decide(), if it returns Decision.PUT.
+    fn selectValue(&mut self, existingValue: &H2RustType, providedValue: H2RustType) -> H2RustType {
+        providedValue
+    }
+
+    /// Resets internal state (if any) of a this DecisionMaker to it's initial state.
+    /// This method is invoked whenever concurrent update failure is encountered,
+    /// so we can re-start update process.
+    fn reset(&mut self) {}
+}
+
+/// 对应java的DecisionMaker.PUT
+pub struct PutDecisionMaker;
+
+impl DecisionMaker for PutDecisionMaker {
+    fn decide_2(&mut self, existingValue: &H2RustType, providedValue: &H2RustType) -> Decision {
+        Decision::PUT
+    }
+}
+
+/// 对应java的DecisionMaker.REMOVE
+pub struct RemoveDecisionMaker;
+
+impl DecisionMaker for RemoveDecisionMaker {
+    fn decide_2(&mut self, existingValue: &H2RustType, providedValue: &H2RustType) -> Decision {
+        Decision::REMOVE
+    }
+}
+
+/// 对应java的DecisionMaker.IF_ABSENT
+pub struct IfAbsentDecisionMaker;
+
+impl DecisionMaker for IfAbsentDecisionMaker {
+    fn decide_2(&mut self, existingValue: &H2RustType, providedValue: &H2RustType) -> Decision {
+        if existingValue.isNull() {
+            Decision::PUT
+        } else {
+            Decision::ABORT
+        }
+    }
+}
+
+/// 对应java的DecisionMaker.IF_PRESENT
+pub struct IfPresentDecisionMaker;
+
+impl DecisionMaker for IfPresentDecisionMaker {
+    fn decide_2(&mut self, existingValue: &H2RustType, providedValue: &H2RustType) -> Decision {
+        if existingValue.isNull() {
+            Decision::ABORT
+        } else {
+            Decision::PUT
+        }
+    }
+}
+
+/// 只有key所在的leaf(或经过的non leaf)还是要重写的那个page时才put,page已经被其它线程改动过的话就不用再重写了
+struct RewriteDecisionMaker {
+    pagePos: Long,
+    /// None表示还没有decide
+    decision: Option<Decision>,
+}
+
+impl RewriteDecisionMaker {
+    fn new(pagePos: Long) -> Self {
+        RewriteDecisionMaker { pagePos, decision: None }
+    }
+}
+
+impl DecisionMaker for RewriteDecisionMaker {
+    fn decide_3(&mut self, existingValue: &H2RustType, providedValue: &H2RustType, tip: &SharedPtr<CursorPos>) -> Decision {
+        assert!(self.decision.is_none());
+
+        let mut decision = Decision::ABORT;
+        if (data_utils::getPageType(self.pagePos) & 1) != data_utils::PAGE_TYPE_LEAF {
+            let mut tip = get_ref!(tip).parent.clone();
+            while tip.is_some() {
+                if get_ref!(get_ref!(tip).page).getPosition() == self.pagePos {
+                    decision = self.decide_2(existingValue, providedValue);
+                    break;
+                }
+                tip = get_ref!(tip).parent.clone();
+            }
+        } else if get_ref!(get_ref!(tip).page).getPosition() == self.pagePos {
+            decision = self.decide_2(existingValue, providedValue);
+        }
+
+        self.decision = Some(decision);
+        decision
+    }
+
+    fn decide_2(&mut self, existingValue: &H2RustType, providedValue: &H2RustType) -> Decision {
+        if existingValue.isNull() {
+            Decision::ABORT
+        } else {
+            Decision::PUT
+        }
+    }
+
+    fn selectValue(&mut self, existingValue: &H2RustType, providedValue: H2RustType) -> H2RustType {
+        existingValue.clone()
+    }
+
+    fn reset(&mut self) {
+        self.decision = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{get_ref, get_ref_mut};
+    use crate::h2_rust_common::h2_rust_cell::SharedPtr;
+    use crate::h2_rust_common::h2_rust_type::H2RustType;
+    use crate::h2_rust_common::Long;
+    use crate::mvstore::mv_map::{Decision, DecisionMaker, IfAbsentDecisionMaker, IfPresentDecisionMaker, MVMapBuilder, PutDecisionMaker, RemoveDecisionMaker};
+    use crate::mvstore::mv_store::MVStoreBuilder;
+    use crate::mvstore::page::PageTrait;
+    use crate::mvstore::test_utils::{string, TempStore};
+
+    fn key(a: usize) -> H2RustType {
+        string(&format!("k{:05}", a))
+    }
+
+    /// 检查各层的totalCount,返回entry的数量
+    fn checkPages(page: &SharedPtr<dyn PageTrait>, isRoot: bool, keysPerPage: i32) -> Long {
+        let pageRef = get_ref!(page);
+        if pageRef.isLeaf() {
+            if !isRoot {
+                assert!(pageRef.getKeyCount() * 4 >= keysPerPage, "underfull leaf with {} keys", pageRef.getKeyCount());
+            }
+            return pageRef.getKeyCount() as Long;
+        }
+
+        let mut totalCount = 0;
+        for i in 0..=pageRef.getKeyCount() {
+            totalCount += checkPages(&pageRef.getChildPage(i), false, keysPerPage);
+        }
+        assert_eq!(pageRef.getTotalCount(), totalCount);
+        totalCount
+    }
+
+    #[test]
+    fn test_put_remove() {
+        let mvStore = MVStoreBuilder::new().open().unwrap();
+        let mvMap = get_ref_mut!(mvStore).openMap("data", &MVMapBuilder::new()).unwrap();
+        let mvMapMutRef = get_ref_mut!(mvMap);
+
+        assert!(mvMapMutRef.put(key(1), string("a")).unwrap().isNull());
+        assert_eq!(mvMapMutRef.put(key(1), string("b")).unwrap().castAsStringRef(), "a");
+        assert!(mvMapMutRef.put(key(2), H2RustType::Null).is_err());
+
+        assert_eq!(mvMapMutRef.putIfAbsent(key(1), string("c")).unwrap().castAsStringRef(), "b");
+        assert!(mvMapMutRef.putIfAbsent(key(2), string("c")).unwrap().isNull());
+        assert_eq!(mvMapMutRef.replace(key(2), string("d")).unwrap().castAsStringRef(), "c");
+        assert!(mvMapMutRef.replace(key(3), string("d")).unwrap().isNull());
+        assert!(!mvMapMutRef.containsKey(&key(3)));
+
+        assert_eq!(mvMapMutRef.remove(key(1)).unwrap().castAsStringRef(), "b");
+        assert!(mvMapMutRef.remove(key(1)).unwrap().isNull());
+        assert_eq!(mvMapMutRef.size_as_long(), 1);
+        assert_eq!(mvMapMutRef.get(&key(2)).castAsStringRef(), "d");
+
+        get_ref_mut!(mvStore).close().unwrap();
+    }
+
+    /// put时把新旧value拼接起来,每次decide都计数
+    #[derive(Default)]
+    struct AppendDecisionMaker {
+        decisions: usize,
+    }
+
+    impl DecisionMaker for AppendDecisionMaker {
+        fn decide_2(&mut self, existingValue: &H2RustType, providedValue: &H2RustType) -> Decision {
+            self.decisions += 1;
+            if providedValue.isNull() {
+                Decision::REMOVE
+            } else if providedValue.castAsStringRef().is_empty() {
+                Decision::ABORT
+            } else {
+                Decision::PUT
+            }
+        }
+
+        fn selectValue(&mut self, existingValue: &H2RustType, providedValue: H2RustType) -> H2RustType {
+            if existingValue.isNull() {
+                providedValue
+            } else {
+                string(&format!("{}{}", existingValue.castAsStringRef(), providedValue.castAsStringRef()))
+            }
+        }
+    }
+
+    #[test]
+    fn test_operate() {
+        let mvStore = MVStoreBuilder::new().open().unwrap();
+        let mvMap = get_ref_mut!(mvStore).openMap("data", &MVMapBuilder::new()).unwrap();
+        let mvMapMutRef = get_ref_mut!(mvMap);
+
+        let mut decisionMaker = AppendDecisionMaker::default();
+        assert!(mvMapMutRef.operate(key(1), string("a"), &mut decisionMaker).unwrap().isNull());
+        assert_eq!(mvMapMutRef.operate(key(1), string("b"), &mut decisionMaker).unwrap().castAsStringRef(), "a");
+        assert_eq!(mvMapMutRef.get(&key(1)).castAsStringRef(), "ab");
+
+        // ABORT返回现有的value,map不变
+        assert_eq!(mvMapMutRef.operate(key(1), string(""), &mut decisionMaker).unwrap().castAsStringRef(), "ab");
+        assert!(mvMapMutRef.operate(key(2), string(""), &mut decisionMaker).unwrap().isNull());
+        assert_eq!(mvMapMutRef.size_as_long(), 1);
+
+        assert_eq!(mvMapMutRef.operate(key(1), H2RustType::Null, &mut decisionMaker).unwrap().castAsStringRef(), "ab");
+        assert!(mvMapMutRef.isEmpty());
+        assert_eq!(decisionMaker.decisions, 5);
+
+        // 内置的DecisionMaker
+        let existing = string("v");
+        let provided = string("w");
+        assert_eq!(PutDecisionMaker.decide_2(&existing, &provided), Decision::PUT);
+        assert_eq!(PutDecisionMaker.decide_2(&H2RustType::Null, &provided), Decision::PUT);
+        assert_eq!(RemoveDecisionMaker.decide_2(&existing, &H2RustType::Null), Decision::REMOVE);
+        assert_eq!(IfAbsentDecisionMaker.decide_2(&existing, &provided), Decision::ABORT);
+        assert_eq!(IfAbsentDecisionMaker.decide_2(&H2RustType::Null, &provided), Decision::PUT);
+        assert_eq!(IfPresentDecisionMaker.decide_2(&existing, &provided), Decision::PUT);
+        assert_eq!(IfPresentDecisionMaker.decide_2(&H2RustType::Null, &provided), Decision::ABORT);
+        assert_eq!(PutDecisionMaker.selectValue(&existing, provided.clone()).castAsStringRef(), "w");
+
+        get_ref_mut!(mvStore).close().unwrap();
+    }
+
+    #[test]
+    fn test_remove_rebalances_leaves() {
+        let mvStore = MVStoreBuilder::new().open().unwrap();
+        let keysPerPage = get_ref!(mvStore).keysPerPage;
+        let mvMap = get_ref_mut!(mvStore).openMap("data", &MVMapBuilder::new()).unwrap();
+        let mvMapMutRef = get_ref_mut!(mvMap);
+        for a in 0..5000 {
+            mvMapMutRef.put(key(a), key(a)).unwrap();
+        }
+        assert_eq!(checkPages(&mvMapMutRef.getRootPage(), true, keysPerPage), 5000);
+
+        // 隔开删除,大部分leaf都会变得过小
+        for a in 0..5000 {
+            if a % 10 != 0 {
+                mvMapMutRef.remove(key(a)).unwrap();
+            }
+        }
+        assert_eq!(mvMapMutRef.size_as_long(), 500);
+        assert_eq!(checkPages(&mvMapMutRef.getRootPage(), true, keysPerPage), 500);
+        assert_eq!(mvMapMutRef.cursor(&H2RustType::Null).count(), 500);
+        for a in (0..5000).step_by(10) {
+            assert_eq!(mvMapMutRef.get(&key(a)).castAsStringRef(), key(a).castAsStringRef());
+        }
+
+        for a in (0..5000).step_by(10) {
+            mvMapMutRef.remove(key(a)).unwrap();
+        }
+        assert!(mvMapMutRef.isEmpty());
+        assert!(get_ref!(mvMapMutRef.getRootPage()).isLeaf());
+
+        get_ref_mut!(mvStore).close().unwrap();
+    }
+
+    #[test]
+    fn test_remove_all_file_store() {
+        let tempStore = TempStore::new("test_remove_all_file_store");
+
+        {
+            let mvStore = tempStore.openWith(&[("autoCommitDelay", 0)]);
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+            let keysPerPage = mvStoreMutRef.keysPerPage;
+            let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+            for a in 0..2000 {
+                get_ref_mut!(data).put(key(a), string(&format!("v{}", a))).unwrap();
+            }
+            mvStoreMutRef.commit().unwrap();
+
+            // 保存过的page从磁盘读取,删除时的内存估算不能变成负数
+            for a in (0..2000).rev().step_by(2) {
+                get_ref_mut!(data).remove(key(a)).unwrap();
+            }
+            mvStoreMutRef.commit().unwrap();
+            assert_eq!(checkPages(&get_ref!(data).getRootPage(), true, keysPerPage), 1000);
+
+            for a in 0..2000 {
+                get_ref_mut!(data).remove(key(a)).unwrap();
+            }
+            assert!(get_ref!(data).isEmpty());
+            mvStoreMutRef.commit().unwrap();
+            mvStoreMutRef.close().unwrap();
+        }
+
+        let mvStore = tempStore.open();
+        let data = get_ref_mut!(mvStore).openMap("data", &MVMapBuilder::new()).unwrap();
+        assert!(get_ref!(data).isEmpty());
+        get_ref_mut!(data).put(key(1), string("v1")).unwrap();
+        assert_eq!(get_ref!(data).get(&key(1)).castAsStringRef(), "v1");
+        get_ref_mut!(mvStore).close().unwrap();
+    }
+}
@@ -1,21 +1,26 @@
 use anyhow::Result;
 use std::any::Any;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::ops::{Deref, DerefMut};
-use std::sync::{Arc, Mutex, Weak};
+use std::sync::{Arc, Condvar, Mutex, Weak};
 use std::sync::atomic::{AtomicBool, AtomicI32, AtomicI64, AtomicPtr, Ordering};
 use std::thread;
-use std::time::{Instant, SystemTime};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
 use crossbeam::atomic::AtomicCell;
 use dashmap::DashMap;
-use crate::h2_rust_common::{Byte, h2_rust_constant, h2_rust_utils, Integer, Long, MyMutex, Optional};
+use bit_set::BitSet;
+use crate::h2_rust_common::{Byte, h2_rust_constant, h2_rust_utils, Integer, Long, MyMutex, Optional, ULong};
 use crate::mvstore::cache::cache_long_key_lirs::{CacheLongKeyLIRS, CacheLongKeyLIRSConfig};
 use crate::mvstore::{chunk, data_utils, mv_map, page};
 use crate::mvstore::file_store::{FileStore};
-use crate::mvstore::mv_map::{MVMap};
+use crate::mvstore::mv_map::{MVMap, MVMapBuilder};
+use crate::mvstore::typed_mv_map::TypedMVMap;
+use crate::mvstore::r#type::data_type::DataType;
+use crate::mvstore::r#type::serde_data_type::{SerdeDataType, SerdeType};
 use crate::mvstore::page::{Page, PageTrait};
-use crate::mvstore::r#type::string_data_type;
-use crate::{atomic_ref_cell, atomic_ref_cell_mut, get_ref_mut, build_option_arc_h2RustCell, get_ref, throw, build_arc_h2RustCell, load_atomic};
+use crate::mvstore::r#type::{data_type_registry, string_data_type};
+use crate::{atomic_ref_cell, atomic_ref_cell_mut, get_ref_mut, build_option_arc_h2RustCell, get_ref, throw, build_arc_h2RustCell, load_atomic, h2_rust_cell_equals};
 use crate::api::error_code;
 use crate::db::store;
 use crate::h2_rust_common::h2_rust_cell::{H2RustCell, SharedPtr, WeakPtr};
@@ -23,7 +28,14 @@ use crate::h2_rust_common::h2_rust_type::H2RustType;
 use crate::message::db_error;
 use crate::message::db_error::DbError;
 use crate::mvstore::chunk::{Chunk};
+use crate::mvstore::write_buffer::WriteBuffer;
+use crate::mvstore::tx_counter::TxCounter;
+use crate::mvstore::mv_store_stats::MVStoreStats;
+use crate::h2_rust_common::byte_buffer::ByteBuffer;
 use crate::util::utils;
+use crate::compress::compress_deflate::CompressDeflate;
+use crate::compress::compress_lzf::CompressLZF;
+use crate::compress::compressor::Compressor;
 
 /// The following are attribute names (keys) in store header map
 const HDR_H: &str = "H";
@@ -37,24 +49,44 @@ const HDR_VERSION: &str = "version";
 const HDR_CLEAN: &str = "clean";
 const HDR_FLETCHER: &str = "fletcher";
 
+/// layout map中保存meta map的id的key
+const META_ID_KEY: &str = "meta.id";
+
 pub const BLOCK_SIZE: Integer = 4 * 1024;
 const FORMAT_WRITE_MIN: Integer = 2;
 const FORMAT_WRITE_MAX: Integer = 2;
 const FORMAT_READ_MIN: Integer = 2;
 const FORMAT_READ_MAX: Integer = 2;
 
+/// 用来序列化chunk的WriteBuffer的初始大小
+const WRITE_BUFFER_INITIAL_SIZE: usize = 1024 * 1024;
+
 /// This designates the "last stored" version for a store which was just open for the first time.
 const INITIAL_VERSION: Long = -1;
 
+/// Store is open.
+const STATE_OPEN: Integer = 0;
+
 /// Store is about to close now, but is still operational.<br>
 /// Outstanding store operation by background writer or other thread may be in progress.<br>
 /// New updates must not be initiated, unless they are part of a closing procedure itself.
 const STATE_STOPPING: Integer = 1;
 
+/// Store is closing now, and any operation on it may fail.
+const STATE_CLOSING: Integer = 2;
+
+/// Store is closed.
+const STATE_CLOSED: Integer = 3;
+
+/// 后台线程写入失败时的回调,对应java的backgroundExceptionHandler
+pub type BackgroundExceptionHandler = Arc<dyn Fn(&anyhow::Error) + Send + Sync>;
+
 #[derive(Default)]
 pub struct MVStore {
     recoveryMode: bool,
     compressionLevel: Integer,
+    compressorFast: Option<CompressLZF>,
+    compressorHigh: Option<CompressDeflate>,
     fileStoreShallBeClosed: bool,
     fileStore: SharedPtr<FileStore>,
 
@@ -79,18 +111,48 @@ pub struct MVStore {
 
     retentionTime: Integer,
 
-    unsavedMemory: Integer,
+    /// 调用者的写入和后台线程的commit都会修改,用atomic
+    unsavedMemory: AtomicI32,
     autoCommitMemory: Integer,
     autoCompactFillRate: Integer,
+    /// 后台上次compact时的最后1个chunk的id,没有新chunk就不再compact
+    autoCompactLastChunkId: Integer,
+
+    /// 新chunk不能分配在[reservedLow, reservedHigh)的block中,移动chunk时使用
+    reservedLow: Long,
+    reservedHigh: Long,
+
+    /// 已经从layout中删掉的chunk,删除被保存到新的chunk之后才能释放它们占用的空间
+    deadChunks: Vec<SharedPtr<Chunk>>,
+
+    /// 没有存活page的chunk,等retentionTime过去并且不再被需要保留的版本用到后从layout中删掉
+    unusedChunks: Vec<SharedPtr<Chunk>>,
+
+    /// 被删除的已保存page,在保存下个chunk时计入所在chunk的存活信息
+    removedPages: Mutex<Vec<RemovedPageInfo>>,
+
+    /// 0表示不在后台自动提交
+    autoCommitDelay: Integer,
+    backgroundWriterThread: Mutex<Option<BackgroundWriterThread>>,
+    backgroundExceptionHandler: Option<BackgroundExceptionHandler>,
+    /// 没有handler时后台线程遇到的错误,下次commit()或close()时返回,对应java的panicException
+    backgroundException: Mutex<Option<anyhow::Error>>,
 
     /// Lock which governs access to major store operations: store(), close(), ...
     /// It serves as a replacement for synchronized(this), except it allows for non-blocking lock attempt
-    storeLock: MyMutex<()>,
-    serializationLock: MyMutex<()>,
-    saveChunkLock: MyMutex<()>,
+    /// 用Arc是为了持有guard的同时还能调用&mut self的函数
+    storeLock: Arc<MyMutex<()>>,
+    serializationLock: Arc<MyMutex<()>>,
+    saveChunkLock: Arc<MyMutex<()>>,
 
     creationTime: Long,
 
+    /// 上回commit的时间,是相对creationTime的
+    lastCommitTime: AtomicI64,
+
+    /// The version of the current store operation (if any). -1 表示当前没有在store
+    currentStoreVersion: AtomicI64,
+
     storeHeader: HashMap<String, Box<dyn Any + Send + Sync>>,
     lastChunk: AtomicCell<SharedPtr<Chunk>>,
     chunkId_chunk: DashMap<Integer, SharedPtr<Chunk>>,
@@ -99,6 +161,13 @@ pub struct MVStore {
 
     state: AtomicI32,
     oldestVersionToKeep: AtomicI64,
+
+    /// Ordered collection of all version usage counters for all versions starting
+    /// from oldestVersionToKeep and up to current.
+    versions: Mutex<VecDeque<Arc<TxCounter>>>,
+
+    /// Counter of open transactions for the latest (current) store version
+    currentTxCounter: Mutex<Arc<TxCounter>>,
     versionsToKeep: Integer,
     metaChanged: AtomicBool,
 
@@ -109,6 +178,7 @@ impl MVStore {
     pub fn new(config: &mut HashMap<String, Box<dyn Any>>) -> Result<SharedPtr<MVStore>> {
         let mut mvStore = MVStore::default();
         mvStore.versionsToKeep = 5;
+        mvStore.currentStoreVersion = AtomicI64::new(-1);
         let mv_store_ref = build_option_arc_h2RustCell!(mvStore);
         Self::init(mv_store_ref.clone(), config)?;
 
@@ -122,6 +192,9 @@ impl MVStore {
 
         mvStoreMutRef.recoveryMode = config.contains_key("recoveryMode");
         mvStoreMutRef.compressionLevel = data_utils::get_config_int_param(&config, "compress", 0);
+        // 即使不压缩也要能读取以前压缩过的page
+        mvStoreMutRef.compressorFast = Some(CompressLZF::new());
+        mvStoreMutRef.compressorHigh = Some(CompressDeflate::new());
         let file_name = h2_rust_utils::get_from_map::<String>(config, "fileName");
 
         let mut file_store_shall_be_open = false;
@@ -149,11 +222,11 @@ impl MVStore {
             chunkCacheConfig.as_mut().unwrap().max_memory = 1024 * 1024;
             pgSplitSize = 16 * 1024;
         }
-        if pageCacheConfig.is_some() {
-            mvStoreMutRef.pageCache = Some(CacheLongKeyLIRS::new(&pageCacheConfig.unwrap()));
+        if let Some(pageCacheConfig) = pageCacheConfig {
+            mvStoreMutRef.pageCache = Some(CacheLongKeyLIRS::new(&pageCacheConfig)?);
         }
-        if chunkCacheConfig.is_some() {
-            mvStoreMutRef.chunkCache = Some(CacheLongKeyLIRS::new(&chunkCacheConfig.unwrap()));
+        if let Some(chunkCacheConfig) = chunkCacheConfig {
+            mvStoreMutRef.chunkCache = Some(CacheLongKeyLIRS::new(&chunkCacheConfig)?);
         }
 
         pgSplitSize = data_utils::get_config_int_param(config, "pageSplitSize", pgSplitSize);
@@ -165,7 +238,7 @@ impl MVStore {
         }
         mvStoreMutRef.pageSplitSize = pgSplitSize;
         mvStoreMutRef.keysPerPage = data_utils::get_config_int_param(config, "keysPerPage", 48);
-        //backgroundExceptionHandler = (UncaughtExceptionHandler) config.get("backgroundExceptionHandler");
+        mvStoreMutRef.backgroundExceptionHandler = h2_rust_utils::get_from_map::<BackgroundExceptionHandler>(config, "backgroundExceptionHandler");
 
         mvStoreMutRef.layout = MVMap::new(Some(Arc::downgrade(mvStoreSharedPtr.as_ref().unwrap())),
                                           0,
@@ -206,11 +279,26 @@ impl MVStore {
                     mvStoreMutRef.storeHeader.insert(HDR_FORMAT.to_string(), Box::new(FORMAT_WRITE_MAX));
                     mvStoreMutRef.storeHeader.insert(HDR_CREATED.to_string(), Box::new(mvStoreMutRef.creationTime));
 
-                    mvStoreMutRef.setLastChunk(None);
+                    mvStoreMutRef.setLastChunk(None)?;
+                    mvStoreMutRef.writeStoreHeader()?;
+                } else {
+                    mvStoreMutRef.readStoreHeader()?;
                 }
             }
+
+            mvStoreMutRef.lastCommitTime.store(mvStoreMutRef.getTimeSinceCreation(), Ordering::Release);
+            mvStoreMutRef.meta = mvStoreMutRef.openMetaMap()?;
+
+            let delay = data_utils::get_config_int_param(config, "autoCommitDelay", 1000);
+            mvStoreMutRef.setAutoCommitDelay(delay);
+        } else {
+            mvStoreMutRef.autoCommitMemory = 0;
+            mvStoreMutRef.autoCompactFillRate = 0;
+            mvStoreMutRef.meta = mvStoreMutRef.openMetaMap()?;
         }
 
+        mvStoreMutRef.onVersionChange(mvStoreMutRef.getCurrentVersion());
+
         Ok(())
     }
 
@@ -218,7 +306,204 @@ impl MVStore {
         self.currentVersion.load(Ordering::Acquire)
     }
 
-    fn setLastChunk(&mut self, last_chunk: SharedPtr<Chunk>) {
+    /// Revert to the beginning of the given version. All later changes (stored or not) are forgotten.
+    /// All maps that were created later are closed.
+    /// A rollback to a version before the last stored version is immediately persisted.
+    /// Rollback to version 0 means all data is removed.
+    pub fn rollbackTo(&mut self, version: Long) -> Result<()> {
+        let storeLock = self.storeLock.clone();
+        let storeLockGuard = storeLock.lock();
+
+        self.checkOpen()?;
+
+        if version == 0 {
+            // special case: remove all data
+            return self.rollbackToInitial();
+        }
+
+        data_utils::check_argument(self.isKnownVersion(version)?, &format!("Unknown version {}", version))?;
+
+        let keep = self.getChunkForVersion(version)?;
+        if keep.is_some() {
+            let saveChunkLock = self.saveChunkLock.clone();
+            let saveChunkGuard = saveChunkLock.lock();
+
+            // 读取chunk header才有准确的layoutRootPos
+            let keep = self.readChunkHeader(get_ref!(keep).block.load(Ordering::Acquire))?;
+            self.setLastChunk(keep)?;
+            self.clearCaches();
+
+            // 被删掉的chunk可能又回到了layout中
+            self.deadChunks.clear();
+            self.removedPages.lock().unwrap().clear();
+            self.rebuildFreeSpace()?;
+
+            // 之后的chunk不在layout中了,文件末尾没有使用的部分截掉
+            let fileLengthInUse = get_ref!(self.fileStore).getFileLengthInUse();
+            if get_ref!(self.fileStore).size() > fileLengthInUse {
+                get_ref_mut!(self.fileStore).truncate(fileLengthInUse)?;
+                self.writeStoreHeader()?;
+                get_ref!(self.fileStore).sync()?;
+            }
+        } else {
+            get_ref_mut!(self.layout).rollbackRoot(version)?;
+
+            // 回滚掉的版本中删除的page又存活了
+            self.removedPages.lock().unwrap().retain(|removedPageInfo| removedPageInfo.version < version);
+        }
+
+        self.currentVersion.store(version, Ordering::Release);
+
+        let meta = self.meta.clone();
+        if !get_ref_mut!(meta).rollbackRoot(version)? {
+            let rootPos = self.getRootPos(get_ref!(meta).getId())?;
+            get_ref_mut!(meta).setRootPosition(rootPos, version - 1, meta.clone())?;
+        }
+        self.metaChanged.store(true, Ordering::Release);
+
+        let mvMaps: Vec<SharedPtr<MVMap>> = self.mvMapId_mvMap.iter().map(|entry| entry.value().clone()).collect();
+        for mvMap in mvMaps {
+            let mvMapMutRef = get_ref_mut!(mvMap);
+            let id = mvMapMutRef.getId();
+            if mvMapMutRef.getCreateVersion() >= version {
+                mvMapMutRef.close();
+                self.mvMapId_mvMap.remove(&id);
+            } else if !mvMapMutRef.rollbackRoot(version)? {
+                let rootPos = self.getRootPos(id)?;
+                mvMapMutRef.setRootPosition(rootPos, version - 1, mvMap.clone())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rollbackToInitial(&mut self) -> Result<()> {
+        self.currentVersion.store(0, Ordering::Release);
+
+        let layout = self.layout.clone();
+        get_ref_mut!(layout).setRootPosition(0, INITIAL_VERSION, layout.clone())?;
+        let meta = self.meta.clone();
+        get_ref_mut!(meta).setRootPosition(0, INITIAL_VERSION, meta.clone())?;
+        get_ref_mut!(layout).put(H2RustType::String(build_arc_h2RustCell!(META_ID_KEY.to_string())),
+                                 H2RustType::String(build_arc_h2RustCell!(h2_rust_utils::int2HexString(get_ref!(meta).getId()))))?;
+
+        self.chunkId_chunk.clear();
+        self.deadChunks.clear();
+        self.unusedChunks.clear();
+        self.removedPages.lock().unwrap().clear();
+        self.clearCaches();
+        self.lastChunk.store(None);
+        self.lastChunkId = 0;
+
+        if self.fileStore.is_some() {
+            let saveChunkLock = self.saveChunkLock.clone();
+            let saveChunkGuard = saveChunkLock.lock();
+
+            get_ref_mut!(self.fileStore).clear();
+            get_ref_mut!(self.fileStore).truncate(2 * BLOCK_SIZE as Long)?;
+            for key in [HDR_CHUNK, HDR_BLOCK, HDR_VERSION] {
+                self.storeHeader.remove(key);
+            }
+            self.writeStoreHeader()?;
+            get_ref!(self.fileStore).sync()?;
+        }
+
+        self.metaChanged.store(false, Ordering::Release);
+
+        for entry in self.mvMapId_mvMap.iter() {
+            get_ref!(entry.value()).close();
+        }
+        self.mvMapId_mvMap.clear();
+
+        Ok(())
+    }
+
+    fn isKnownVersion(&mut self, version: Long) -> Result<bool> {
+        if version > self.getCurrentVersion() || version < 0 {
+            return Ok(false);
+        }
+
+        if version == self.getCurrentVersion() || self.getLastChunk().is_none() {
+            // no stored data
+            return Ok(true);
+        }
+
+        // need to check if a chunk for this version exists
+        if self.getChunkForVersion(version)?.is_none() {
+            return Ok(false);
+        }
+
+        // also, all chunks referenced by this version need to be available in the file
+        match self.getLayoutMap_(version) {
+            Ok(oldLayout) => Ok(self.isKnownLayout(&oldLayout).unwrap_or(false)),
+            Err(_) => Ok(false)
+        }
+    }
+
+    fn clearCaches(&self) {
+        if let Some(pageCache) = self.pageCache.as_ref() {
+            pageCache.clear();
+        }
+        if let Some(chunkCache) = self.chunkCache.as_ref() {
+            chunkCache.clear();
+        }
+    }
+
+    /// Get the metadata map. This data is for informational purposes only.
+    /// The data is subject to change in future versions.
+    pub fn getMetaMap(&self) -> Result<SharedPtr<MVMap>> {
+        self.checkOpen()?;
+        Ok(self.meta.clone())
+    }
+
+    /// Get this store's layout map. This data is for informational purposes only.
+    /// The data is subject to change in future versions.
+    pub fn getLayoutMap(&self) -> SharedPtr<MVMap> {
+        self.layout.clone()
+    }
+
+    /// Get the file store. None for in-memory stores.
+    pub fn getFileStore(&self) -> SharedPtr<FileStore> {
+        self.fileStore.clone()
+    }
+
+    pub fn getPageSplitSize(&self) -> Integer {
+        self.pageSplitSize
+    }
+
+    /// Adjust amount of "unsaved memory" meaning amount of RAM occupied by pages not saved yet to the file.
+    /// This is the amount which triggers auto-commit.
+    pub fn registerUnsavedMemory(&self, memory: Integer) {
+        let newValue = self.unsavedMemory.fetch_add(memory, Ordering::AcqRel) + memory;
+        if newValue > self.autoCommitMemory && self.autoCommitMemory > 0 {
+            self.saveNeeded.store(true, Ordering::Release);
+        }
+    }
+
+    /// 0表示不压缩,1表示LZF,2表示deflate
+    pub fn getCompressionLevel(&self) -> Integer {
+        self.compressionLevel
+    }
+
+    /// 读取page时多个线程并发地expand,compressor在init里就创建好,这里只给出不可变引用
+    pub fn getCompressorFast(&self) -> &dyn Compressor {
+        self.compressorFast.as_ref().unwrap()
+    }
+
+    pub fn getCompressorHigh(&self) -> &dyn Compressor {
+        self.compressorHigh.as_ref().unwrap()
+    }
+
+    /// 压缩会改动compressor内部的hash表,只在拿着storeLock写chunk时调用
+    pub fn getCompressorFastForWrite(&mut self) -> &mut dyn Compressor {
+        self.compressorFast.as_mut().unwrap()
+    }
+
+    pub fn getCompressorHighForWrite(&mut self) -> &mut dyn Compressor {
+        self.compressorHigh.as_mut().unwrap()
+    }
+
+    fn setLastChunk(&mut self, last_chunk: SharedPtr<Chunk>) -> Result<()> {
         self.lastChunk.store(last_chunk.clone());
         self.chunkId_chunk.clear();
         self.lastChunkId = 0;
@@ -231,14 +516,355 @@ impl MVStore {
             self.lastChunkId = get_ref!(last_chunk).id;
             self.currentVersion.store(get_ref!(last_chunk).version, Ordering::Release);
             layout_root_pos = get_ref!(last_chunk).layoutRootPos;
-            map_id = get_ref!(last_chunk).map_id;
+            map_id = get_ref!(last_chunk).mapId;
             self.chunkId_chunk.insert(get_ref!(last_chunk).id, last_chunk);
         }
 
         self.lastMapId.store(map_id, Ordering::Release);
         get_ref_mut!(self.layout).setRootPosition(layout_root_pos,
                                                   self.currentVersion.load(Ordering::Acquire) - 1,
-                                                  self.layout.clone());
+                                                  self.layout.clone())
+    }
+
+    fn readStoreHeader(&mut self) -> Result<()> {
+        let mut newest: SharedPtr<Chunk> = None;
+        let mut assumeCleanShutdown = true;
+        let mut validStoreHeader = false;
+
+        // find out which chunk and version are the newest, read the first two blocks
+        let fileHeaderBlocks = get_ref_mut!(self.fileStore).readFully(0, 2 * BLOCK_SIZE as usize)?;
+        for a in 0..2 {
+            let buff = fileHeaderBlocks.extractWithPosLen(a * BLOCK_SIZE as usize, BLOCK_SIZE as usize);
+
+            // the following can fail for various reasons
+            let map = match data_utils::parseChecksummedMap(buff) {
+                Ok(Some(map)) => map,
+                _ => {
+                    assumeCleanShutdown = false;
+                    continue;
+                }
+            };
+
+            let version: Long = match data_utils::readHexIntOrLong(&map, HDR_VERSION, 0) {
+                Ok(version) => version,
+                Err(_) => {
+                    assumeCleanShutdown = false;
+                    continue;
+                }
+            };
+
+            // if both header blocks do agree on version
+            // we'll continue on happy path - assume that previous shutdown was clean
+            assumeCleanShutdown = assumeCleanShutdown && (newest.is_none() || version == get_ref!(newest).version);
+
+            if newest.is_none() || version > get_ref!(newest).version {
+                validStoreHeader = true;
+
+                self.creationTime = data_utils::readHexIntOrLong(&map, HDR_CREATED, 0).unwrap_or(0);
+                let chunkId: Integer = data_utils::readHexIntOrLong(&map, HDR_CHUNK, 0).unwrap_or(0);
+                let block: Long = data_utils::readHexIntOrLong(&map, HDR_BLOCK, 2).unwrap_or(2);
+
+                for (key, value) in map {
+                    self.storeHeader.insert(key, Box::new(value));
+                }
+
+                let test = self.readChunkHeaderAndFooter(block, chunkId);
+                if test.is_some() {
+                    newest = test;
+                }
+            }
+        }
+
+        if !validStoreHeader {
+            let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_FILE_CORRUPT);
+            throw!(DbError::get(error_code, vec![&format!("Store header is corrupt: {}", get_ref!(self.fileStore).getFileName())]));
+        }
+
+        let blockSize = self.readStoreHeaderHexLong(HDR_BLOCK_SIZE, BLOCK_SIZE as Long)?;
+        if blockSize != BLOCK_SIZE as Long {
+            let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_UNSUPPORTED_FORMAT);
+            throw!(DbError::get(error_code, vec![&format!("Block size {} is currently not supported", blockSize)]));
+        }
+
+        let mut format = self.readStoreHeaderHexLong(HDR_FORMAT, 1)?;
+        if !get_ref!(self.fileStore).isReadOnly() {
+            if format > FORMAT_WRITE_MAX as Long {
+                throw!(self.getUnsupportedWriteFormatError(format, FORMAT_WRITE_MAX, "The write format {} is larger than the supported format {}")?);
+            } else if format < FORMAT_WRITE_MIN as Long {
+                throw!(self.getUnsupportedWriteFormatError(format, FORMAT_WRITE_MIN, "The write format {} is smaller than the supported format {}")?);
+            }
+        }
+
+        format = self.readStoreHeaderHexLong(HDR_FORMAT_READ, format)?;
+        if format > FORMAT_READ_MAX as Long {
+            let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_UNSUPPORTED_FORMAT);
+            throw!(DbError::get(error_code, vec![&format!("The read format {} is larger than the supported format {}", format, FORMAT_READ_MAX)]));
+        } else if format < FORMAT_READ_MIN as Long {
+            let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_UNSUPPORTED_FORMAT);
+            throw!(DbError::get(error_code, vec![&format!("The read format {} is smaller than the supported format {}", format, FORMAT_READ_MIN)]));
+        }
+
+        assumeCleanShutdown = assumeCleanShutdown && newest.is_some() && !self.recoveryMode;
+        if assumeCleanShutdown {
+            assumeCleanShutdown = self.readStoreHeaderHexLong(HDR_CLEAN, 0)? != 0;
+        }
+
+        self.chunkId_chunk.clear();
+
+        let now = h2_rust_utils::getTimestamp();
+        // calculate the year (doesn't have to be exact; we assume 365.25 days per year, * 4 = 1461)
+        let year = 1970 + now / (1000 * 60 * 60 * 6 * 1461);
+        if year < 2014 {
+            // if the year is before 2014, we assume the system doesn't have a real-time clock,
+            // and we set the creationTime to the past, so that existing chunks are overwritten
+            self.creationTime = now - get_ref!(self.fileStore).get_default_retention_time() as Long;
+        } else if now < self.creationTime {
+            // the system time was set to the past: we change the creation time
+            self.creationTime = now;
+            self.storeHeader.insert(HDR_CREATED.to_string(), Box::new(self.creationTime));
+        }
+
+        let blocksInStore = get_ref!(self.fileStore).size() / BLOCK_SIZE as Long;
+
+        if self.recoveryMode {
+            newest = self.findNewestReadableChunk(blocksInStore)?;
+        } else if !assumeCleanShutdown {
+            let tailChunk = self.discoverChunk(blocksInStore);
+            if tailChunk.is_some() && (newest.is_none() || get_ref!(tailChunk).version > get_ref!(newest).version) {
+                newest = tailChunk;
+            }
+
+            if newest.is_some() {
+                // read the chunk header and footer, and follow the chain of next chunks
+                loop {
+                    let newestRef = get_ref!(newest);
+                    if newestRef.next == 0 || newestRef.next >= blocksInStore {
+                        // no (valid) next
+                        break;
+                    }
+
+                    let test = self.readChunkHeaderAndFooter(newestRef.next, newestRef.id + 1);
+                    if test.is_none() || get_ref!(test).version <= newestRef.version {
+                        break;
+                    }
+
+                    newest = test;
+                }
+            }
+        }
+
+        self.setLastChunk(newest)?;
+        self.rebuildFreeSpace()
+    }
+
+    /// 恢复模式使用,扫描整个文件中header和footer一致的chunk,从version最新的开始尝试,
+    /// 第1个layout中的page都能通过校验,并且layout用到的chunk都完好的chunk作为lastChunk
+    fn findNewestReadableChunk(&mut self, blocksInStore: Long) -> Result<SharedPtr<Chunk>> {
+        let mut candidates = Vec::new();
+        let mut block: Long = 2;
+        while block < blocksInStore {
+            let header = self.readChunkHeaderOptionally(block);
+            if header.is_some() {
+                let chunk = self.readChunkHeaderAndFooter(block, get_ref!(header).id);
+                if chunk.is_some() {
+                    block += Long::max(1, get_ref!(chunk).blockCount as Long);
+                    candidates.push(chunk);
+                    continue;
+                }
+            }
+            block += 1;
+        }
+        candidates.sort_by_key(|chunk| -get_ref!(chunk).version);
+
+        for candidate in &candidates {
+            if self.setLastChunk(candidate.clone()).is_err() {
+                continue;
+            }
+
+            // layout本身的page要通过chunk的元数据读取,先用文件中找到的chunk header代替,不去读可能损坏的layout
+            let version = get_ref!(candidate).version;
+            for chunk in &candidates {
+                let chunkRef = get_ref!(chunk);
+                if chunkRef.version <= version && !self.chunkId_chunk.contains_key(&chunkRef.id) {
+                    self.chunkId_chunk.insert(chunkRef.id, chunk.clone());
+                }
+            }
+
+            if self.isLayoutReadable(&candidates) {
+                return Ok(candidate.clone());
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// layout的每个page都能读出来,其中记录的chunk在文件中都有header和footer一致的副本
+    fn isLayoutReadable(&mut self, validChunks: &[SharedPtr<Chunk>]) -> bool {
+        let mut chunkValues = Vec::new();
+        let mut readable = true;
+
+        let layout = self.layout.clone();
+        let result = get_ref!(layout).salvage(layout.clone(),
+                                              &mut |key, value| {
+                                                  if key.castAsStringRef().starts_with(data_utils::META_CHUNK) {
+                                                      chunkValues.push(value);
+                                                  }
+                                                  Ok(())
+                                              },
+                                              &mut |_, _, _| readable = false);
+        if result.is_err() || !readable {
+            return false;
+        }
+
+        chunkValues.iter().all(|chunkValue| {
+            match chunk::fromString(chunkValue.castAsStringRef()) {
+                Ok(chunk) => {
+                    let chunkRef = get_ref!(chunk);
+                    validChunks.iter().any(|validChunk| {
+                        let validChunkRef = get_ref!(validChunk);
+                        validChunkRef.id == chunkRef.id &&
+                            validChunkRef.block.load(Ordering::Acquire) == chunkRef.block.load(Ordering::Acquire)
+                    })
+                }
+                Err(_) => false,
+            }
+        })
+    }
+
+    /// 根据layout中的chunk重新标记文件中已经使用的block,没有存活page的chunk等待删除
+    fn rebuildFreeSpace(&mut self) -> Result<()> {
+        get_ref_mut!(self.fileStore).clear();
+        self.unusedChunks.clear();
+
+        for chunk in self.getChunks()? {
+            let chunkRef = get_ref!(chunk);
+            let start = chunkRef.block.load(Ordering::Acquire) * BLOCK_SIZE as Long;
+            let length = chunkRef.blockCount * BLOCK_SIZE;
+            get_ref_mut!(self.fileStore).markUsed(start, length)?;
+
+            if !chunkRef.isLive() {
+                self.unusedChunks.push(chunk.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn getUnsupportedWriteFormatError(&self, format: Long, expectedFormat: Integer, message: &str) -> Result<DbError> {
+        let format = self.readStoreHeaderHexLong(HDR_FORMAT_READ, format)?;
+
+        let mut message = message.replacen("{}", &format.to_string(), 1).replacen("{}", &expectedFormat.to_string(), 1);
+        if format >= FORMAT_READ_MIN as Long && format <= FORMAT_READ_MAX as Long {
+            message.push_str(", and the file was not opened in read-only mode");
+        }
+
+        let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_UNSUPPORTED_FORMAT);
+        Ok(DbError::get(error_code, vec![&message]))
+    }
+
+    /// Discover a valid chunk, searching file backwards from the given block
+    ///
+    /// block: to start search from (found chunk footer should be no further than block-1)
+    fn discoverChunk(&mut self, mut block: Long) -> SharedPtr<Chunk> {
+        let mut candidateLocation = Long::MAX;
+        let mut candidate: SharedPtr<Chunk> = None;
+
+        loop {
+            if block == candidateLocation {
+                return candidate;
+            }
+
+            // number of blocks occupied by headers
+            if block == 2 {
+                return None;
+            }
+
+            let mut test = self.readChunkFooter(block);
+            if test.is_some() {
+                // if we encounter chunk footer (with or without corresponding header)
+                // in the middle of prospective chunk, stop considering it
+                candidateLocation = Long::MAX;
+                test = self.readChunkHeaderOptionally1(get_ref!(test).block.load(Ordering::Acquire), get_ref!(test).id);
+                if test.is_some() {
+                    // if that footer has a corresponding header, consider them as a new candidate for a valid chunk
+                    candidateLocation = get_ref!(test).block.load(Ordering::Acquire);
+                    candidate = test;
+                }
+            }
+
+            // if we encounter chunk header without corresponding footer
+            // (due to incomplete write?) in the middle of prospective chunk, stop considering it
+            block -= 1;
+            if block > candidateLocation && self.readChunkHeaderOptionally(block).is_some() {
+                candidateLocation = Long::MAX;
+            }
+        }
+    }
+
+    /// Read a chunk header and footer, and verify the stored data is consistent.
+    fn readChunkHeaderAndFooter(&mut self, block: Long, expectedId: Integer) -> SharedPtr<Chunk> {
+        let header = self.readChunkHeaderOptionally1(block, expectedId);
+        if header.is_some() {
+            let headerRef = get_ref!(header);
+            let footer = self.readChunkFooter(block + headerRef.blockCount as Long);
+            if footer.is_none() ||
+                get_ref!(footer).id != expectedId ||
+                get_ref!(footer).block.load(Ordering::Acquire) != headerRef.block.load(Ordering::Acquire) {
+                return None;
+            }
+        }
+
+        header
+    }
+
+    fn readChunkHeader(&mut self, block: Long) -> Result<SharedPtr<Chunk>> {
+        let position = block * BLOCK_SIZE as Long;
+        let mut byteBuffer = get_ref_mut!(self.fileStore).readFully(position as usize, chunk::MAX_HEADER_LENGTH as usize)?;
+        Chunk::readChunkHeader(&mut byteBuffer, position)
+    }
+
+    fn readChunkHeaderOptionally(&mut self, block: Long) -> SharedPtr<Chunk> {
+        match self.readChunkHeader(block) {
+            Ok(chunk) => {
+                if get_ref!(chunk).block.load(Ordering::Acquire) != block {
+                    None
+                } else {
+                    chunk
+                }
+            }
+            Err(_) => None
+        }
+    }
+
+    fn readChunkHeaderOptionally1(&mut self, block: Long, expectedId: Integer) -> SharedPtr<Chunk> {
+        let chunk = self.readChunkHeaderOptionally(block);
+        if chunk.is_none() || get_ref!(chunk).id != expectedId {
+            None
+        } else {
+            chunk
+        }
+    }
+
+    /// Try to read a chunk footer.
+    ///
+    /// block: the index of the next block after the chunk
+    fn readChunkFooter(&mut self, block: Long) -> SharedPtr<Chunk> {
+        // read the chunk footer of the last block of the file
+        let position = block * BLOCK_SIZE as Long - chunk::FOOTER_LENGTH as Long;
+        if position < 0 {
+            return None;
+        }
+
+        // the following can fail for various reasons
+        let lastBlock = match get_ref_mut!(self.fileStore).readFully(position as usize, chunk::FOOTER_LENGTH as usize) {
+            Ok(byteBuffer) => byteBuffer,
+            Err(_) => return None
+        };
+
+        match data_utils::parseChecksummedMap(lastBlock.extractWithPosLen(0, chunk::FOOTER_LENGTH as usize)) {
+            Ok(Some(map)) => Chunk::new2(map, false).unwrap_or(None),
+            _ => None
+        }
     }
 
     fn lastChunkVersion(&self) -> Long {
@@ -250,7 +876,11 @@ impl MVStore {
         }
     }
 
-    pub fn readPage(&mut self, mvMap: SharedPtr<MVMap>, position: Long) -> Result<SharedPtr<dyn PageTrait>> {
+    pub fn getLastChunk(&self) -> SharedPtr<Chunk> {
+        unsafe { &*self.lastChunk.as_ptr() }.clone()
+    }
+
+    pub fn readPage(&self, mvMap: SharedPtr<MVMap>, position: Long) -> Result<SharedPtr<dyn PageTrait>> {
         if !data_utils::isPageSaved(position) { // position不能是0
             throw!(DbError::get_internal_error("ERROR_FILE_CORRUPT,Position 0"))
         }
@@ -269,20 +899,20 @@ impl MVStore {
         Ok(pageTrait)
     }
 
-    fn readPageFromCache(&mut self, position: Long) -> SharedPtr<dyn PageTrait> {
-        if self.pageCache.is_none() {
-            None
-        } else {
-            self.pageCache.as_mut().unwrap().get(position)
+    fn readPageFromCache(&self, position: Long) -> SharedPtr<dyn PageTrait> {
+        match self.pageCache.as_ref() {
+            Some(pageCache) => pageCache.get(position),
+            None => None,
         }
     }
 
-    fn getChunk(&mut self, position: Long) -> Result<SharedPtr<Chunk>> {
+    fn getChunk(&self, position: Long) -> Result<SharedPtr<Chunk>> {
         let chunk_id = data_utils::getPageChunkId(position);
 
-        let pair = self.chunkId_chunk.get(&chunk_id);
+        // 不能持有DashMap的Ref再去insert,同1个shard会死锁
+        let chunk = self.chunkId_chunk.get(&chunk_id).and_then(|pair| pair.value().clone());
 
-        if pair.is_none() || pair.as_ref().unwrap().value().is_none() {
+        if chunk.is_none() {
             self.checkOpen()?;
 
             let s = get_ref!(self.layout).get(&H2RustType::String(build_arc_h2RustCell!(chunk::get_meta_key(chunk_id))));
@@ -299,7 +929,7 @@ impl MVStore {
             self.chunkId_chunk.insert(get_ref!(chunk).id, chunk.clone());
             Ok(chunk)
         } else {
-            Ok(pair.unwrap().value().clone())
+            Ok(chunk)
         }
     }
 
@@ -316,12 +946,12 @@ impl MVStore {
         self.state.load(Ordering::Acquire) <= STATE_STOPPING
     }
 
-    fn cachePage(&mut self, pageTrait: SharedPtr<dyn PageTrait>) -> Result<()> {
-        if self.pageCache.is_some() {
+    pub fn cachePage(&self, pageTrait: SharedPtr<dyn PageTrait>) -> Result<()> {
+        if let Some(pageCache) = self.pageCache.as_ref() {
             let position = get_ref!(pageTrait).getPosition();
             let memory = get_ref!(pageTrait).getMemory();
 
-            self.pageCache.as_mut().unwrap().put(position, pageTrait, memory)?;
+            pageCache.put(position, pageTrait, memory)?;
         }
 
         Ok(())
@@ -339,10 +969,78 @@ impl MVStore {
         v
     }
 
-    pub fn deregisterMapRoot(&mut self, mapId: Integer) {
-        if get_ref!(self.layout).remove(H2RustType::String(build_arc_h2RustCell!(mv_map::getMapRootKey(mapId)))) != null {
+    /// How many versions to retain for in-memory stores. If not set, 5 old versions are retained.
+    pub fn setVersionsToKeep(&mut self, count: Integer) {
+        self.versionsToKeep = count;
+    }
+
+    pub fn getVersionsToKeep(&self) -> Integer {
+        self.versionsToKeep
+    }
+
+    /// Register opened operation (transaction).
+    /// This would increment usage counter for the current version.
+    /// This version (and all after it) should not be dropped until all transactions involved are closed
+    /// and usage counter goes to zero.
+    ///
+    /// return TxCounter to be decremented when operation finishes (transaction closed).
+    pub fn registerVersionUsage(&self) -> Arc<TxCounter> {
+        let currentTxCounter = self.currentTxCounter.lock().unwrap();
+        currentTxCounter.incrementAndGet();
+        currentTxCounter.clone()
+    }
+
+    /// De-register (close) completed operation (transaction).
+    /// This will decrement usage counter for the corresponding version.
+    /// If counter reaches zero, that version (and all unused after it) can be dropped immediately.
+    pub fn deregisterVersionUsage(&self, txCounter: &Arc<TxCounter>) {
+        if txCounter.decrementAndGet() <= 0 {
+            self.dropUnusedVersions();
+        }
+    }
+
+    fn onVersionChange(&self, version: Long) {
+        {
+            let mut versions = self.versions.lock().unwrap();
+
+            let txCounter = std::mem::replace(&mut *self.currentTxCounter.lock().unwrap(), Arc::new(TxCounter::new(version)));
+            assert!(txCounter.get() >= 0);
+            txCounter.decrementAndGet();
+            versions.push_back(txCounter);
+        }
+
+        self.dropUnusedVersions();
+    }
+
+    /// 队列头部已经没人使用的版本丢掉,剩下的最老版本就是需要保留的最老版本
+    fn dropUnusedVersions(&self) {
+        let mut versions = self.versions.lock().unwrap();
+
+        while let Some(txCounter) = versions.front() {
+            if txCounter.get() >= 0 {
+                break;
+            }
+            versions.pop_front();
+        }
+
+        let version = match versions.front() {
+            Some(txCounter) => txCounter.version,
+            None => self.currentTxCounter.lock().unwrap().version
+        };
+        self.setOldestVersionToKeep(version);
+    }
+
+    fn setOldestVersionToKeep(&self, version: Long) {
+        // Oldest version may only advance, never goes back
+        self.oldestVersionToKeep.fetch_max(version, Ordering::AcqRel);
+    }
+
+    pub fn deregisterMapRoot(&mut self, mapId: Integer) -> Result<()> {
+        if !get_ref_mut!(self.layout).remove(H2RustType::String(build_arc_h2RustCell!(mv_map::getMapRootKey(mapId))))?.isNull() {
             self.markMetaChanged();
         }
+
+        Ok(())
     }
 
     /// changes in the metadata alone are usually not detected, as the meta map is changed after storing
@@ -360,68 +1058,1640 @@ impl MVStore {
         }
     }
 
-    fn requireStore(&self) -> bool {
-        return 3 * self.unsavedMemory > 4 * self.autoCommitMemory;
+    fn getMapId(&self, name: &str) -> Result<Integer> {
+        let m = get_ref!(self.meta).get(&H2RustType::String(build_arc_h2RustCell!(format!("{}{}", data_utils::META_NAME, name))));
+        if m.isNull() {
+            Ok(-1)
+        } else {
+            data_utils::parseHexInt(m.castAsStringRef())
+        }
     }
 
-    fn needStore(&self) -> bool {
-        return self.unsavedMemory > self.autoCommitMemory;
-    }
-
-    pub fn beforeWrite(&mut self, mvMap: &MVMap) {
-        if self.saveNeeded.load(Ordering::Acquire) {
-            if self.fileStore.is_some() {
-                if self.isOpenOrStopping() {
-                    // condition below is to prevent potential deadlock,
-                    // because we should never seek storeLock while holding map root lock
-                    if self.storeLock.isHeldByCurrentThread() || !get_ref!(mvMap.getRootReference()).isLockedByCurrentThread() {
-                        // to avoid infinite recursion via store() -> dropUnusedChunks() -> layout.remove()
-                        if mvMap as *const MVMap as usize != self.layout.as_ref().unwrap().get_addr() {
-                            self.saveNeeded.store(false, Ordering::Release);
-
-                            // check again, because it could have been written by now
-                            if self.autoCommitMemory > 0 && self.needStore() {
-                                if self.requireStore() && !mvMap.single_writer {
-                                    self.commit(MVStore::requireStore);
-                                } else {}
-                            }
-                        }
-                    }
-                }
-            }
+    fn getRootPos(&self, mapId: Integer) -> Result<Long> {
+        let root = get_ref!(self.layout).get(&H2RustType::String(build_arc_h2RustCell!(mv_map::getMapRootKey(mapId))));
+        if root.isNull() {
+            Ok(0)
+        } else {
+            data_utils::parseHexLong(root.castAsStringRef())
         }
     }
 
-    fn commit(&mut self, check: fn(&mvStore) -> bool) -> Long {
-        if !self.storeLock.isHeldByCurrentThread() || self.currentVersion.load(Ordering::Acquire) < 0 {
-            let mutexGuard = self.storeLock.lock();
+    /// layout持有的就是指向自己的weak,创建map时使用
+    fn getWeakThis(&self) -> WeakPtr<MVStore> {
+        get_ref!(self.layout).getMvStore()
+    }
 
-            if check(self) {
-                self.store(true);
-            }
-        }
+    fn openMetaMap(&mut self) -> Result<SharedPtr<MVMap>> {
+        let metaIdStr = get_ref!(self.layout).get(&H2RustType::String(build_arc_h2RustCell!(META_ID_KEY.to_string())));
 
-        self.currentVersion.load(Ordering::Acquire)
+        let metaId = if metaIdStr.isNull() {
+            let metaId = self.lastMapId.fetch_add(1, Ordering::AcqRel) + 1;
+            get_ref_mut!(self.layout).put(H2RustType::String(build_arc_h2RustCell!(META_ID_KEY.to_string())),
+                                          H2RustType::String(build_arc_h2RustCell!(h2_rust_utils::int2HexString(metaId))))?;
+            metaId
+        } else {
+            data_utils::parseHexInt(metaIdStr.castAsStringRef())?
+        };
+
+        let meta = MVMap::new(self.getWeakThis(),
+                              metaId,
+                              string_data_type::INSTANCE.clone(),
+                              string_data_type::INSTANCE.clone())?;
+        let rootPos = self.getRootPos(metaId)?;
+        get_ref_mut!(meta).setRootPosition(rootPos, self.getCurrentVersion() - 1, meta.clone())?;
+
+        Ok(meta)
     }
 
-    fn store(&mut self, syncWrite: bool) {
-        assert!(self.storeLock.isHeldByCurrentThread());
-        assert!(!self.saveChunkLock.isHeldByCurrentThread());
+    /// 在version提交时生成的chunk中保存的layout里读取map的root位置,提交version的chunk的版本是version + 1
+    pub fn getRootPos_(&mut self, mapId: Integer, version: Long) -> Result<Long> {
+        let oldLayout = self.getLayoutMap_(version + 1)?;
+        data_utils::check_argument(self.isKnownLayout(&oldLayout)?, &format!("Unknown version {}", version))?;
 
-        if !self.isOpenOrStopping() {
-            return;
+        let root = get_ref!(oldLayout).get(&H2RustType::String(build_arc_h2RustCell!(mv_map::getMapRootKey(mapId))));
+        if root.isNull() {
+            Ok(0)
+        } else {
+            data_utils::parseHexLong(root.castAsStringRef())
         }
+    }
 
-        if !self.hasUnsavedChanges() {
-            return;
-        }
+    /// 读取version对应的chunk中保存的layout
+    fn getLayoutMap_(&mut self, version: Long) -> Result<SharedPtr<MVMap>> {
+        let chunk = self.getChunkForVersion(version)?;
+        data_utils::check_argument(chunk.is_some(), &format!("Unknown version {}", version - 1))?;
 
-        ();
+        let block = get_ref!(chunk).block.load(Ordering::Acquire);
+        let chunk = self.readChunkHeader(block)?;
+
+        let layout = self.layout.clone();
+        get_ref!(layout).openReadOnly_(get_ref!(chunk).layoutRootPos, version, layout.clone())
     }
 
-    pub fn hasUnsavedChanges(&self) -> bool {
-        if self.metaChanged.load(Ordering::Acquire) {
-            return true;
+    /// version及之前最新的chunk
+    fn getChunkForVersion(&self, version: Long) -> Result<SharedPtr<Chunk>> {
+        let mut chunks: Vec<SharedPtr<Chunk>> = self.chunkId_chunk.iter().map(|entry| entry.value().clone()).collect();
+
+        // chunkId_chunk中只有用到过的chunk,其余的在layout中
+        let metaChunk = H2RustType::String(build_arc_h2RustCell!(data_utils::META_CHUNK.to_string()));
+        let mut cursor = get_ref!(self.layout).cursor(&metaChunk);
+        while let Some(key) = cursor.next() {
+            if !key.castAsStringRef().starts_with(data_utils::META_CHUNK) {
+                break;
+            }
+            chunks.push(chunk::fromString(cursor.getValue().castAsStringRef())?);
+        }
+
+        let mut newest: SharedPtr<Chunk> = None;
+        for c in chunks {
+            let cRef = get_ref!(c);
+            if cRef.isSaved() && cRef.version <= version && (newest.is_none() || cRef.id > get_ref!(newest).id) {
+                newest = c.clone();
+            }
+        }
+
+        Ok(newest)
+    }
+
+    /// 旧的layout引用的chunk都还在文件中,这个版本的数据才能读取
+    fn isKnownLayout(&mut self, oldLayout: &SharedPtr<MVMap>) -> Result<bool> {
+        let metaChunk = H2RustType::String(build_arc_h2RustCell!(data_utils::META_CHUNK.to_string()));
+        let mut cursor = get_ref!(oldLayout).cursor(&metaChunk);
+        while let Some(chunkKey) = cursor.next() {
+            if !chunkKey.castAsStringRef().starts_with(data_utils::META_CHUNK) {
+                break;
+            }
+
+            if !get_ref!(self.layout).containsKey(&chunkKey) {
+                let c = chunk::fromString(cursor.getValue().castAsStringRef())?;
+                let test = self.readChunkHeaderAndFooter(get_ref!(c).block.load(Ordering::Acquire), get_ref!(c).id);
+                if test.is_none() {
+                    return Ok(false);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Open a map with the given builder. The map is automatically create if it does not yet exist.
+    /// If a map with this name is already open, this map is returned.
+    pub fn openMap(&mut self, name: &str, mvMapBuilder: &MVMapBuilder) -> Result<SharedPtr<MVMap>> {
+        let id = self.getMapId(name)?;
+        if id >= 0 {
+            return self.openMap_(id, mvMapBuilder);
+        }
+
+        let id = self.lastMapId.fetch_add(1, Ordering::AcqRel) + 1;
+        assert!(self.getMap(id)?.is_none());
+
+        let curVersion = self.getCurrentVersion();
+
+        let mut config = HashMap::new();
+        config.insert("id".to_string(), h2_rust_utils::int2HexString(id));
+        config.insert("createVersion".to_string(), format!("{:x}", curVersion));
+        let mvMap = mvMapBuilder.create(self.getWeakThis(), &config)?;
+
+        let mapKey = H2RustType::String(build_arc_h2RustCell!(mv_map::getMapKey(id)));
+        get_ref_mut!(self.meta).put(mapKey.clone(), H2RustType::String(build_arc_h2RustCell!(get_ref!(mvMap).asString(name))))?;
+
+        let existing = get_ref_mut!(self.meta).putIfAbsent(H2RustType::String(build_arc_h2RustCell!(format!("{}{}", data_utils::META_NAME, name))),
+                                                            H2RustType::String(build_arc_h2RustCell!(h2_rust_utils::int2HexString(id))))?;
+        if !existing.isNull() {
+            // looks like map was created concurrently, cleanup and re-start
+            get_ref_mut!(self.meta).remove(mapKey)?;
+            return self.openMap(name, mvMapBuilder);
+        }
+
+        let lastStoredVersion = curVersion - 1;
+        get_ref_mut!(mvMap).setRootPosition(0, lastStoredVersion, mvMap.clone())?;
+        self.markMetaChanged();
+
+        // 同java的maps.putIfAbsent,并发创建时以先放入的为准
+        let mvMap = self.mvMapId_mvMap.entry(id).or_insert(mvMap).value().clone();
+        Ok(mvMap)
+    }
+
+    /// Open a map whose keys and values are arbitrary serde serializable rust types,
+    /// for example openTypedMap::<u64, MyRecord>("records", "u64", "myRecord").
+    /// The key and value type names are recorded in the map metadata and have to stay the same
+    /// when the map is opened again.
+    pub fn openTypedMap<K: SerdeType + Clone, V: SerdeType + Clone>(&mut self,
+                                                                  name: &str,
+                                                                  keyTypeName: &str,
+                                                                  valueTypeName: &str) -> Result<TypedMVMap<K, V>> {
+        data_type_registry::registerSerde::<K>(keyTypeName);
+        data_type_registry::registerSerde::<V>(valueTypeName);
+
+        let keyType = SerdeDataType::<K>::new(keyTypeName);
+        let valueType = SerdeDataType::<V>::new(valueTypeName);
+
+        // 已有的map以别的类型写入时,按这里的类型读取会得到错误的数据
+        let id = self.getMapId(name)?;
+        if id >= 0 {
+            let configAsString = get_ref!(self.meta).get(&H2RustType::String(build_arc_h2RustCell!(mv_map::getMapKey(id))));
+            let config = data_utils::parseMap(configAsString.castAsStringRef())?;
+            for (key, typeName) in [("key", keyType.get_type_name()), ("val", valueType.get_type_name())] {
+                let recorded = config.get(key).map_or(string_data_type::TYPE_NAME, |recorded| recorded.as_str());
+                if recorded != typeName {
+                    let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_UNKNOWN_DATA_TYPE);
+                    throw!(DbError::get(error_code, vec![&format!("Map {} was written with {} type {}, not {}", name, key, recorded, typeName)]));
+                }
+            }
+        }
+
+        let mut mvMapBuilder = MVMapBuilder::new();
+        mvMapBuilder.keyType(keyType);
+        mvMapBuilder.valueType(valueType);
+        Ok(TypedMVMap::new(self.openMap(name, &mvMapBuilder)?))
+    }
+
+    /// Open an existing map with the given id and builder.
+    pub fn openMap_(&mut self, id: Integer, mvMapBuilder: &MVMapBuilder) -> Result<SharedPtr<MVMap>> {
+        let mvMap = self.getMap(id)?;
+        if mvMap.is_some() {
+            return Ok(mvMap);
+        }
+
+        let configAsString = get_ref!(self.meta).get(&H2RustType::String(build_arc_h2RustCell!(mv_map::getMapKey(id))));
+        data_utils::check_argument(!configAsString.isNull(), &format!("Missing map with id {}", id))?;
+
+        let mut config = data_utils::parseMap(configAsString.castAsStringRef())?;
+        config.insert("id".to_string(), h2_rust_utils::int2HexString(id));
+        let mvMap = mvMapBuilder.create(self.getWeakThis(), &config)?;
+
+        let root = self.getRootPos(id)?;
+        let lastStoredVersion = self.getCurrentVersion() - 1;
+        get_ref_mut!(mvMap).setRootPosition(root, lastStoredVersion, mvMap.clone())?;
+
+        let mvMap = self.mvMapId_mvMap.entry(id).or_insert(mvMap).value().clone();
+        Ok(mvMap)
+    }
+
+    /// Get map by id, None if the map is not opened.
+    pub fn getMap(&self, id: Integer) -> Result<SharedPtr<MVMap>> {
+        self.checkOpen()?;
+        Ok(self.mvMapId_mvMap.get(&id).and_then(|pair| pair.value().clone()))
+    }
+
+    /// Get the set of all map names.
+    pub fn getMapNames(&self) -> Result<HashSet<String>> {
+        self.checkOpen()?;
+
+        let mut set = HashSet::new();
+        let metaName = H2RustType::String(build_arc_h2RustCell!(data_utils::META_NAME.to_string()));
+        for key in get_ref!(self.meta).keyIterator(&metaName) {
+            let x = key.castAsStringRef();
+            if !x.starts_with(data_utils::META_NAME) {
+                break;
+            }
+            set.insert(x[data_utils::META_NAME.len()..].to_string());
+        }
+
+        Ok(set)
+    }
+
+    /// Check whether a given map exists.
+    pub fn hasMap(&self, name: &str) -> bool {
+        get_ref!(self.meta).containsKey(&H2RustType::String(build_arc_h2RustCell!(format!("{}{}", data_utils::META_NAME, name))))
+    }
+
+    /// Check whether a given map exists and has data.
+    pub fn hasData(&self, name: &str) -> Result<bool> {
+        Ok(self.hasMap(name) && self.getRootPos(self.getMapId(name)?)? != 0)
+    }
+
+    /// Rename a map.
+    pub fn renameMap(&mut self, mvMap: &SharedPtr<MVMap>, newName: &str) -> Result<()> {
+        self.checkOpen()?;
+
+        let meta = self.meta.clone();
+        data_utils::check_argument(!h2_rust_cell_equals!(*mvMap, meta), "Renaming the meta map is not allowed")?;
+
+        let id = get_ref!(mvMap).getId();
+        let oldName = self.getMapName(id)?;
+        if let Some(oldName) = oldName.filter(|oldName| oldName != newName) {
+            let idHexStr = h2_rust_utils::int2HexString(id);
+
+            // at first create a new name as an "alias"
+            let existingIdHexStr = get_ref_mut!(meta).putIfAbsent(H2RustType::String(build_arc_h2RustCell!(format!("{}{}", data_utils::META_NAME, newName))),
+                                                                  H2RustType::String(build_arc_h2RustCell!(idHexStr.clone())))?;
+
+            // we need to cope with the case of previously unfinished rename
+            data_utils::check_argument(existingIdHexStr.isNull() || existingIdHexStr.castAsStringRef() == &idHexStr,
+                                       &format!("A map named {} already exists", newName))?;
+
+            // switch roles of a new and old names - old one is an alias now
+            get_ref_mut!(meta).put(H2RustType::String(build_arc_h2RustCell!(mv_map::getMapKey(id))),
+                                   H2RustType::String(build_arc_h2RustCell!(get_ref!(mvMap).asString(newName))))?;
+
+            // get rid of the old name completely
+            get_ref_mut!(meta).remove(H2RustType::String(build_arc_h2RustCell!(format!("{}{}", data_utils::META_NAME, oldName))))?;
+            self.markMetaChanged();
+        }
+
+        Ok(())
+    }
+
+    /// Remove a map from the current version of the store.
+    pub fn removeMap(&mut self, mvMap: &SharedPtr<MVMap>) -> Result<()> {
+        let storeLock = self.storeLock.clone();
+        let storeLockGuard = storeLock.lock();
+
+        self.checkOpen()?;
+
+        let layout = self.layout.clone();
+        let meta = self.meta.clone();
+        data_utils::check_argument(!h2_rust_cell_equals!(*mvMap, layout) && !h2_rust_cell_equals!(*mvMap, meta),
+                                   "Removing the meta map is not allowed")?;
+
+        get_ref_mut!(mvMap).clearIt(mvMap.clone())?;
+        get_ref!(mvMap).close();
+
+        let id = get_ref!(mvMap).getId();
+        let name = self.getMapName(id)?;
+        if !get_ref_mut!(meta).remove(H2RustType::String(build_arc_h2RustCell!(mv_map::getMapKey(id))))?.isNull() {
+            self.markMetaChanged();
+        }
+        if let Some(name) = name {
+            if !get_ref_mut!(meta).remove(H2RustType::String(build_arc_h2RustCell!(format!("{}{}", data_utils::META_NAME, name))))?.isNull() {
+                self.markMetaChanged();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove map by name.
+    pub fn removeMap_(&mut self, name: &str) -> Result<()> {
+        let id = self.getMapId(name)?;
+        if id > 0 {
+            let mut mvMap = self.getMap(id)?;
+            if mvMap.is_none() {
+                mvMap = self.openMap_(id, &MVMapBuilder::new())?;
+            }
+            self.removeMap(&mvMap)?;
+        }
+
+        Ok(())
+    }
+
+    fn requireStore(&self) -> bool {
+        3 * self.unsavedMemory.load(Ordering::Acquire) > 4 * self.autoCommitMemory
+    }
+
+    fn needStore(&self) -> bool {
+        self.unsavedMemory.load(Ordering::Acquire) > self.autoCommitMemory
+    }
+
+    pub fn beforeWrite(&mut self, mvMap: &MVMap) -> Result<()> {
+        if self.saveNeeded.load(Ordering::Acquire) && self.fileStore.is_some() && self.isOpenOrStopping()
+            // condition below is to prevent potential deadlock,
+            // because we should never seek storeLock while holding map root lock
+            && (self.storeLock.isHeldByCurrentThread() || !get_ref!(mvMap.getRootReference()).isLockedByCurrentThread())
+            // to avoid infinite recursion via store() -> dropUnusedChunks() -> layout.remove()
+            && mvMap as *const MVMap as usize != self.layout.as_ref().unwrap().get_addr() {
+            self.saveNeeded.store(false, Ordering::Release);
+
+            // check again, because it could have been written by now
+            if self.autoCommitMemory > 0 && self.needStore() {
+                // if unsaved memory creation rate is to high,
+                // some back pressure need to be applied to slow things down
+                if self.requireStore() && !mvMap.single_writer {
+                    self.commit1(MVStore::requireStore)?;
+                } else {
+                    self.tryCommit1(MVStore::needStore)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Commit the changes. <br>
+    /// This method does nothing if there are no unsaved changes, otherwise it increments the current version
+    /// and stores the data (for file based stores).
+    ///
+    /// return the new version (incremented if there were changes)
+    pub fn commit(&mut self) -> Result<Long> {
+        self.takeBackgroundException()?;
+        self.commit1(|_| true)
+    }
+
+    fn commit1(&mut self, check: fn(&MVStore) -> bool) -> Result<Long> {
+        // we need to prevent re-entrance, which may be possible,
+        // because meta map is modified within storeNow() and that
+        // causes beforeWrite() call with possibility of going back here
+        if !self.storeLock.isHeldByCurrentThread() || self.currentStoreVersion.load(Ordering::Acquire) < 0 {
+            let storeLock = self.storeLock.clone();
+            let mutexGuard = storeLock.lock();
+
+            if check(self) {
+                self.store(true)?;
+            }
+        }
+
+        Ok(self.currentVersion.load(Ordering::Acquire))
+    }
+
+    /// Unlike regular commit this method returns immediately if there is commit in progress on another thread,
+    /// otherwise it acts as regular commit.
+    ///
+    /// return the new version (incremented if there were changes)
+    pub fn tryCommit(&mut self) -> Result<Long> {
+        self.tryCommit1(|_| true)
+    }
+
+    fn tryCommit1(&mut self, check: fn(&MVStore) -> bool) -> Result<Long> {
+        // we need to prevent re-entrance, which may be possible,
+        // because meta map is modified within storeNow() and that
+        // causes beforeWrite() call with possibility of going back here
+        if !self.storeLock.isHeldByCurrentThread() || self.currentStoreVersion.load(Ordering::Acquire) < 0 {
+            let storeLock = self.storeLock.clone();
+            let mutexGuard = storeLock.tryLock();
+            if mutexGuard.is_some() && check(self) {
+                self.store(false)?;
+            }
+        }
+
+        Ok(self.currentVersion.load(Ordering::Acquire))
+    }
+
+    fn store(&mut self, syncWrite: bool) -> Result<()> {
+        assert!(self.storeLock.isHeldByCurrentThread());
+        assert!(!self.saveChunkLock.isHeldByCurrentThread());
+
+        if !self.isOpenOrStopping() {
+            return Ok(());
+        }
+
+        if !self.hasUnsavedChanges() {
+            return Ok(());
+        }
+
+        self.currentStoreVersion.store(self.currentVersion.load(Ordering::Acquire), Ordering::Release);
+
+        let result = if self.fileStore.is_none() {
+            // 内存中的store只递增版本
+            let version = self.currentVersion.fetch_add(1, Ordering::AcqRel) + 1;
+            self.setWriteVersion(version)
+        } else if get_ref!(self.fileStore).isReadOnly() {
+            let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_WRITING_FAILED);
+            Err(DbError::get(error_code, vec!["This store is read-only"]).into())
+        } else {
+            self.storeNow(syncWrite)
+        };
+
+        // in any case reset the current store version, to allow closing the store
+        self.currentStoreVersion.store(-1, Ordering::Release);
+
+        result
+    }
+
+    /// 当前没有单独的serialization和bufferSave线程,syncWrite与否都是在当前线程上完成的
+    fn storeNow(&mut self, syncWrite: bool) -> Result<()> {
+        // 从layout中删掉的chunk随这次的chunk一起保存,之后就能释放它们的空间
+        self.dropUnusedChunks()?;
+
+        let lastCommitTime = self.getTimeSinceCreation();
+        self.lastCommitTime.store(lastCommitTime, Ordering::Release);
+        let currentUnsavedMemory = self.unsavedMemory.load(Ordering::Acquire);
+
+        // it is ok, since that path suppose to be single-threaded under storeLock
+        let version = self.currentVersion.fetch_add(1, Ordering::AcqRel) + 1;
+        let changed = self.collectChangedMapRoots(version)?;
+        self.onVersionChange(version);
+
+        self.serializeAndStore(changed, lastCommitTime, version)?;
+
+        // some pages might have been changed in the meantime (in the newest version)
+        self.saveNeeded.store(false, Ordering::Release);
+        let _ = self.unsavedMemory.fetch_update(Ordering::AcqRel, Ordering::Acquire,
+                                                |unsavedMemory| Some(Integer::max(0, unsavedMemory - currentUnsavedMemory)));
+
+        Ok(())
+    }
+
+    /// 内存中的store在commit时调用,各个map的root开始新的版本,已关闭的map被移除
+    fn setWriteVersion(&mut self, version: Long) -> Result<()> {
+        let mvMaps: Vec<SharedPtr<MVMap>> = self.mvMapId_mvMap.iter().map(|entry| entry.value().clone()).collect();
+        for mvMap in mvMaps {
+            let mvMapMutRef = get_ref_mut!(mvMap);
+            if mvMapMutRef.setWriteVersion(version)?.is_none() {
+                self.mvMapId_mvMap.remove(&mvMapMutRef.getId());
+            }
+        }
+
+        get_ref_mut!(self.meta).setWriteVersion(version)?;
+        get_ref_mut!(self.layout).setWriteVersion(version)?;
+        self.onVersionChange(version);
+
+        self.metaChanged.store(false, Ordering::Release);
+        self.unsavedMemory.store(0, Ordering::Release);
+
+        Ok(())
+    }
+
+    /// 收集各个map的root page,只要root page还未保存或是leaf
+    fn collectChangedMapRoots(&mut self, version: Long) -> Result<Vec<SharedPtr<dyn PageTrait>>> {
+        let lastStoredVersion = version - 2;
+        let mut changed = Vec::new();
+
+        let mvMaps: Vec<SharedPtr<MVMap>> = self.mvMapId_mvMap.iter().map(|entry| entry.value().clone()).collect();
+        for mvMap in mvMaps {
+            let mvMapMutRef = get_ref_mut!(mvMap);
+            let rootReference = mvMapMutRef.setWriteVersion(version)?;
+
+            if rootReference.is_none() {
+                self.mvMapId_mvMap.remove(&mvMapMutRef.getId());
+            } else if mvMapMutRef.getCreateVersion() < version && // if map was created after storing started, skip it
+                !mvMapMutRef.isVolatile &&
+                mvMapMutRef.hasChangesSince(lastStoredVersion) {
+                assert!(get_ref!(rootReference).version <= version);
+
+                let rootPage = get_ref!(rootReference).root.clone();
+                // after deletion previously saved leaf may pop up as a root,
+                // but we still need to save new root pos in meta
+                if !get_ref!(rootPage).isSaved() || get_ref!(rootPage).isLeaf() {
+                    changed.push(rootPage);
+                }
+            }
+        }
+
+        if self.meta.is_some() {
+            let rootReference = get_ref_mut!(self.meta).setWriteVersion(version)?;
+            if get_ref!(self.meta).hasChangesSince(lastStoredVersion) || self.metaChanged.load(Ordering::Acquire) {
+                assert!(rootReference.is_some() && get_ref!(rootReference).version <= version);
+
+                let rootPage = get_ref!(rootReference).root.clone();
+                if !get_ref!(rootPage).isSaved() || get_ref!(rootPage).isLeaf() {
+                    changed.push(rootPage);
+                }
+            }
+        }
+
+        Ok(changed)
+    }
+
+    fn serializeAndStore(&mut self, mut changed: Vec<SharedPtr<dyn PageTrait>>, time: Long, version: Long) -> Result<()> {
+        let serializationLock = self.serializationLock.clone();
+        let serializationGuard = serializationLock.lock();
+
+        let chunk = self.createChunk(time, version)?;
+        self.chunkId_chunk.insert(get_ref!(chunk).id, chunk.clone());
+
+        let mut writeBuffer = WriteBuffer::new(WRITE_BUFFER_INITIAL_SIZE);
+        self.serializeToBuffer(&mut writeBuffer, &mut changed, chunk.clone())?;
+
+        self.storeBuffer(chunk, &mut writeBuffer, changed)
+    }
+
+    fn createChunk(&mut self, mut time: Long, version: Long) -> Result<SharedPtr<Chunk>> {
+        let mut chunkId = self.lastChunkId;
+        if chunkId != 0 {
+            chunkId &= chunk::MAX_ID;
+
+            let lastChunk = self.chunkId_chunk.get(&chunkId).and_then(|pair| pair.value().clone());
+            assert!(lastChunk.is_some());
+            assert!(get_ref!(lastChunk).isSaved());
+            assert_eq!(get_ref!(lastChunk).version + 1, version);
+
+            // the metadata of the last chunk was not stored so far, and needs to be
+            // set now (it's better not to update right after storing, because that
+            // would modify the meta map again)
+            get_ref_mut!(self.layout).put(H2RustType::String(build_arc_h2RustCell!(chunk::get_meta_key(chunkId))),
+                                          H2RustType::String(build_arc_h2RustCell!(get_ref!(lastChunk).asString())))?;
+
+            // never go backward in time
+            time = Long::max(get_ref!(lastChunk).time, time);
+        }
+
+        let mut newChunkId;
+        loop {
+            self.lastChunkId += 1;
+            newChunkId = self.lastChunkId & chunk::MAX_ID;
+
+            let old = self.chunkId_chunk.get(&newChunkId).and_then(|pair| pair.value().clone());
+            if old.is_none() {
+                break;
+            }
+
+            if !get_ref!(old).isSaved() {
+                throw!(DbError::get_internal_error(&format!("Last block {} not stored, possibly due to out-of-memory", get_ref!(old).asString())));
+            }
+        }
+
+        let mut chunk = Chunk::new1(newChunkId);
+        chunk.pageCount = 0;
+        chunk.pageCountLive = 0;
+        chunk.maxLen = 0;
+        chunk.maxLenLive = 0;
+        chunk.layoutRootPos = Long::MAX;
+        chunk.block = AtomicI64::new(Long::MAX);
+        chunk.blockCount = Integer::MAX;
+        chunk.time = time;
+        chunk.version = version;
+        chunk.next = Long::MAX;
+        chunk.occupancy = BitSet::new();
+
+        Ok(build_option_arc_h2RustCell!(chunk))
+    }
+
+    /// chunk的结构: header, 各个changed map的page, layout的page, toc, footer
+    fn serializeToBuffer(&mut self,
+                         writeBuffer: &mut WriteBuffer,
+                         changed: &mut Vec<SharedPtr<dyn PageTrait>>,
+                         chunkSharedPtr: SharedPtr<Chunk>) -> Result<()> {
+        let chunkMutRef = get_ref_mut!(chunkSharedPtr);
+
+        // need to patch the header later
+        chunkMutRef.writeChunkHeader(writeBuffer, 0)?;
+        let headerLength = writeBuffer.getPosition() + 44;
+        writeBuffer.setPosition(headerLength);
+
+        let version = chunkMutRef.version;
+        let mut toc: Vec<Long> = Vec::new();
+
+        for pageTrait in changed.iter() {
+            let pageTraitMutRef = get_ref_mut!(pageTrait);
+            let mapId = get_ref!(pageTraitMutRef.getMvMap()).getId();
+            let key = H2RustType::String(build_arc_h2RustCell!(mv_map::getMapRootKey(mapId)));
+
+            if pageTraitMutRef.getTotalCount() == 0 {
+                get_ref_mut!(self.layout).remove(key)?;
+            } else {
+                pageTraitMutRef.writeUnsavedRecursive(pageTrait.clone(), chunkMutRef, writeBuffer, &mut toc)?;
+                let rootPosition = pageTraitMutRef.getPosition();
+                get_ref_mut!(self.layout).put(key, H2RustType::String(build_arc_h2RustCell!(format!("{:x}", rootPosition))))?;
+            }
+        }
+
+        self.acceptChunkOccupancyChanges(chunkMutRef.time, version)?;
+
+        let layoutRootReference = get_ref_mut!(self.layout).setWriteVersion(version)?;
+        assert!(layoutRootReference.is_some());
+        assert_eq!(get_ref!(layoutRootReference).version, version);
+        self.metaChanged.store(false, Ordering::Release);
+
+        let layoutRoot = get_ref!(layoutRootReference).root.clone();
+        get_ref_mut!(layoutRoot).writeUnsavedRecursive(layoutRoot.clone(), chunkMutRef, writeBuffer, &mut toc)?;
+        chunkMutRef.layoutRootPos = get_ref!(layoutRoot).getPosition();
+        changed.push(layoutRoot);
+
+        // last allocated map id should be captured after the meta map was saved, because
+        // this will ensure that concurrently created map, which made it into meta before save,
+        // will have it's id reflected in mapid field of currently written chunk
+        chunkMutRef.mapId = self.lastMapId.load(Ordering::Acquire);
+
+        chunkMutRef.tocPos = writeBuffer.getPosition() as Integer;
+        for tocElement in &toc {
+            writeBuffer.putI64(*tocElement);
+        }
+        let chunkLength = writeBuffer.getPosition() as Integer;
+
+        // add the store header and round to the next block
+        let length = utils::roundUpInt(chunkLength + chunk::FOOTER_LENGTH, BLOCK_SIZE);
+        writeBuffer.setLimit(length as usize);
+
+        let saveChunkLock = self.saveChunkLock.clone();
+        let saveChunkGuard = saveChunkLock.lock();
+
+        let filePos = get_ref_mut!(self.fileStore).allocate(length, self.reservedLow, self.reservedHigh);
+        chunkMutRef.blockCount = length / BLOCK_SIZE;
+        chunkMutRef.block.store(filePos / BLOCK_SIZE as Long, Ordering::Release);
+
+        // calculate and set the likely next position, that is just after this chunk
+        chunkMutRef.next = chunkMutRef.block.load(Ordering::Acquire) + chunkMutRef.blockCount as Long;
+
+        assert_eq!(chunkMutRef.pageCountLive, chunkMutRef.pageCount);
+        assert!(chunkMutRef.occupancy.is_empty());
+
+        writeBuffer.setPosition(0);
+        chunkMutRef.writeChunkHeader(writeBuffer, headerLength)?;
+
+        writeBuffer.setPosition(writeBuffer.getLimit() - chunk::FOOTER_LENGTH as usize);
+        writeBuffer.putSlice(&chunkMutRef.getFooterBytes());
+
+        Ok(())
+    }
+
+    /// Put the page in the removed list, the chunk occupancy is updated when the next chunk is stored.
+    ///
+    /// pagePos: the position of the page<br>
+    /// version: at which page was removed<br>
+    /// pageNo: sequential page number within chunk, -1 if unknown
+    pub fn accountForRemovedPage(&self, pagePos: Long, version: Long, pageNo: Integer) {
+        assert!(data_utils::isPageSaved(pagePos));
+        self.removedPages.lock().unwrap().push(RemovedPageInfo { pagePos, version, pageNo });
+    }
+
+    /// Apply the freed space to the chunk metadata. The metadata is updated, but completely free chunks
+    /// are not removed from the set of chunks, and the disk space is not yet marked as free.
+    /// They are queued instead and wait until their usage is over.
+    ///
+    /// 只处理早于version的删除,它们不会再被version对应的chunk引用
+    fn acceptChunkOccupancyChanges(&mut self, time: Long, version: Long) -> Result<()> {
+        if self.getLastChunk().is_none() {
+            return Ok(());
+        }
+
+        // 更新layout中的chunk元信息又会删掉layout的page,反复处理直到没有新的删除
+        loop {
+            let accepted: Vec<RemovedPageInfo> = {
+                let mut removedPages = self.removedPages.lock().unwrap();
+                let (accepted, pending) = std::mem::take(&mut *removedPages).into_iter()
+                    .partition(|removedPageInfo| removedPageInfo.version < version);
+                *removedPages = pending;
+                accepted
+            };
+
+            if accepted.is_empty() {
+                return Ok(());
+            }
+
+            let mut modifiedChunks: HashMap<Integer, SharedPtr<Chunk>> = HashMap::new();
+            for removedPageInfo in accepted {
+                let chunkId = data_utils::getPageChunkId(removedPageInfo.pagePos);
+
+                // chunk可能已经被删掉了
+                let chunk = self.chunkId_chunk.get(&chunkId).and_then(|pair| pair.value().clone());
+                if chunk.is_none() {
+                    continue;
+                }
+
+                let pageNo = if removedPageInfo.pageNo >= 0 {
+                    removedPageInfo.pageNo
+                } else {
+                    self.calculatePageNo(&chunk, removedPageInfo.pagePos)?
+                };
+                let pageLength = data_utils::getPageMaxLength(removedPageInfo.pagePos);
+
+                if get_ref_mut!(chunk).accountForRemovedPage(pageNo, pageLength, time, removedPageInfo.version) {
+                    self.unusedChunks.push(chunk.clone());
+                }
+                modifiedChunks.insert(chunkId, chunk);
+            }
+
+            for (chunkId, chunk) in modifiedChunks {
+                get_ref_mut!(self.layout).put(H2RustType::String(build_arc_h2RustCell!(chunk::get_meta_key(chunkId))),
+                                              H2RustType::String(build_arc_h2RustCell!(get_ref!(chunk).asString())))?;
+            }
+        }
+    }
+
+    /// 根据chunk的toc找到page的pageNo,找不到返回-1
+    fn calculatePageNo(&mut self, chunk: &SharedPtr<Chunk>, pagePos: Long) -> Result<Integer> {
+        let chunkId = get_ref!(chunk).id;
+        let toc = self.getToC(chunk)?;
+
+        let pageNo = get_ref!(toc).iter().position(|tocElement| data_utils::getPagePos(chunkId, *tocElement) == pagePos);
+        Ok(pageNo.map(|pageNo| pageNo as Integer).unwrap_or(-1))
+    }
+
+    /// chunk占用的空间之后的第1个block,头两个block是store header
+    fn getAfterLastBlock(&self) -> Long {
+        get_ref!(self.fileStore).getFileLengthInUse() / BLOCK_SIZE as Long
+    }
+
+    fn storeBuffer(&mut self,
+                   chunkSharedPtr: SharedPtr<Chunk>,
+                   writeBuffer: &mut WriteBuffer,
+                   changed: Vec<SharedPtr<dyn PageTrait>>) -> Result<()> {
+        {
+            let saveChunkLock = self.saveChunkLock.clone();
+            let saveChunkGuard = saveChunkLock.lock();
+
+            writeBuffer.setPosition(0);
+            let filePos = get_ref!(chunkSharedPtr).block.load(Ordering::Acquire) * BLOCK_SIZE as Long;
+            self.write(filePos, writeBuffer.getBuffer())?;
+
+            // end of the used space is not necessarily the end of the file
+            let storeAtEndOfFile = filePos + writeBuffer.getLimit() as Long >= get_ref!(self.fileStore).size();
+            let writeStoreHeader = self.isWriteStoreHeader(&chunkSharedPtr, storeAtEndOfFile)?;
+
+            self.lastChunk.store(chunkSharedPtr);
+
+            if writeStoreHeader {
+                self.writeStoreHeader()?;
+            }
+
+            // 刚写的chunk中的layout已经不再包含这些chunk了
+            for deadChunk in std::mem::take(&mut self.deadChunks) {
+                let deadChunkRef = get_ref!(deadChunk);
+                let start = deadChunkRef.block.load(Ordering::Acquire) * BLOCK_SIZE as Long;
+                let length = deadChunkRef.blockCount * BLOCK_SIZE;
+                get_ref_mut!(self.fileStore).free(start, length);
+            }
+
+            self.shrinkFileIfPossible(1)?;
+        }
+
+        for pageTrait in changed {
+            get_ref_mut!(pageTrait).releaseSavedPages();
+        }
+
+        Ok(())
+    }
+
+    fn isWriteStoreHeader(&mut self, chunkSharedPtr: &SharedPtr<Chunk>, storeAtEndOfFile: bool) -> Result<bool> {
+        // whether we need to write the store header
+        let mut writeStoreHeader = false;
+
+        if !storeAtEndOfFile {
+            let lastChunk = self.getLastChunk();
+            if lastChunk.is_none() {
+                writeStoreHeader = true;
+            } else if get_ref!(lastChunk).next != get_ref!(chunkSharedPtr).block.load(Ordering::Acquire) {
+                // the last prediction did not matched
+                writeStoreHeader = true;
+            } else {
+                let headerVersion = self.readStoreHeaderHexLong(HDR_VERSION, 0)?;
+                if get_ref!(lastChunk).version - headerVersion > 20 {
+                    // we write after at least every 20 versions
+                    writeStoreHeader = true;
+                } else {
+                    let mut chunkId = self.readStoreHeaderHexLong(HDR_CHUNK, 0)? as Integer;
+                    while !writeStoreHeader && chunkId <= get_ref!(lastChunk).id {
+                        // one of the chunks in between was removed
+                        writeStoreHeader = !self.chunkId_chunk.contains_key(&chunkId);
+                        chunkId += 1;
+                    }
+                }
+            }
+        }
+
+        if self.storeHeader.remove(HDR_CLEAN).is_some() {
+            writeStoreHeader = true;
+        }
+
+        Ok(writeStoreHeader)
+    }
+
+    /// store header 在文件的头两个block各写一份
+    fn writeStoreHeader(&mut self) -> Result<()> {
+        let lastChunk = self.getLastChunk();
+        if lastChunk.is_some() {
+            let lastChunkRef = get_ref!(lastChunk);
+            self.storeHeader.insert(HDR_BLOCK.to_string(), Box::new(lastChunkRef.block.load(Ordering::Acquire)));
+            self.storeHeader.insert(HDR_CHUNK.to_string(), Box::new(lastChunkRef.id));
+            self.storeHeader.insert(HDR_VERSION.to_string(), Box::new(lastChunkRef.version));
+        }
+
+        let mut buff = String::with_capacity(112);
+        for (key, value) in &self.storeHeader {
+            data_utils::appendMap(&mut buff, key, &**value);
+        }
+
+        let checksum = data_utils::getFletcher32(buff.as_bytes(), 0, buff.len());
+        data_utils::appendMap(&mut buff, HDR_FLETCHER, &checksum);
+        buff.push('\n');
+
+        let bytes = buff.as_bytes();
+        let mut writeBuffer = WriteBuffer::new(2 * BLOCK_SIZE as usize);
+        writeBuffer.putSlice(bytes);
+        writeBuffer.setPosition(BLOCK_SIZE as usize);
+        writeBuffer.putSlice(bytes);
+        writeBuffer.setLimit(2 * BLOCK_SIZE as usize);
+        writeBuffer.setPosition(0);
+
+        self.write(0, writeBuffer.getBuffer())
+    }
+
+    /// storeHeader中的value可能是刚刚生成的Integer或Long,也可能是从文件读取的hex string
+    fn readStoreHeaderHexLong(&self, key: &str, defaultValue: Long) -> Result<Long> {
+        match self.storeHeader.get(key) {
+            None => Ok(defaultValue),
+            Some(value) => {
+                if let Some(long) = value.downcast_ref::<Long>() {
+                    return Ok(*long);
+                }
+
+                if let Some(integer) = value.downcast_ref::<Integer>() {
+                    return Ok(*integer as Long);
+                }
+
+                match value.downcast_ref::<String>() {
+                    Some(string) => {
+                        match ULong::from_str_radix(string, 16) {
+                            Ok(long) => Ok(long as Long),
+                            Err(_) => throw!(DbError::get(error_code::FILE_CORRUPTED_1, vec![&format!("error parsing the value {}", string)]))
+                        }
+                    }
+                    None => throw!(DbError::get(error_code::FILE_CORRUPTED_1, vec![&format!("error parsing the value of {}", key)]))
+                }
+            }
+        }
+    }
+
+    fn write(&mut self, filePos: Long, byteBuffer: &mut ByteBuffer) -> Result<()> {
+        get_ref_mut!(self.fileStore).writeFully(filePos as usize, byteBuffer)
+    }
+
+    fn getTimeSinceCreation(&self) -> Long {
+        Long::max(0, h2_rust_utils::getTimestamp() - self.creationTime)
+    }
+
+    pub fn isClosed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == STATE_CLOSED
+    }
+
+    /// Close the file and the store. Unsaved changes are written to disk first.
+    pub fn close(&mut self) -> Result<()> {
+        let result = self.closeStore(true);
+        self.takeBackgroundException()?;
+        result
+    }
+
+    fn closeStore(&mut self, normalShutdown: bool) -> Result<()> {
+        if self.isClosed() {
+            return Ok(());
+        }
+
+        self.stopBackgroundThread(normalShutdown);
+
+        let storeLock = self.storeLock.clone();
+        let storeLockGuard = storeLock.lock();
+
+        if self.state.load(Ordering::Acquire) != STATE_OPEN {
+            return Ok(());
+        }
+
+        self.state.store(STATE_STOPPING, Ordering::Release);
+
+        let result = self.closeStoreInternal(normalShutdown);
+
+        if self.fileStore.is_some() && self.fileStoreShallBeClosed {
+            get_ref_mut!(self.fileStore).close();
+        }
+
+        self.state.store(STATE_CLOSED, Ordering::Release);
+
+        result
+    }
+
+    fn closeStoreInternal(&mut self, normalShutdown: bool) -> Result<()> {
+        if normalShutdown && self.fileStore.is_some() && !get_ref!(self.fileStore).isReadOnly() {
+            let mvMaps: Vec<SharedPtr<MVMap>> = self.mvMapId_mvMap.iter().map(|entry| entry.value().clone()).collect();
+            for mvMap in mvMaps {
+                if get_ref!(mvMap).isClosed() {
+                    self.deregisterMapRoot(get_ref!(mvMap).getId())?;
+                }
+            }
+
+            self.commit()?;
+
+            let saveChunkLock = self.saveChunkLock.clone();
+            let saveChunkGuard = saveChunkLock.lock();
+            self.shrinkFileIfPossible(0)?;
+            self.storeHeader.insert(HDR_CLEAN.to_string(), Box::new(1 as Integer));
+            self.writeStoreHeader()?;
+            get_ref_mut!(self.fileStore).sync()?;
+        }
+
+        self.state.store(STATE_CLOSING, Ordering::Release);
+
+        // release memory early - this is important when called because of out of memory
+        for entry in self.mvMapId_mvMap.iter() {
+            get_ref!(entry.value()).close();
+        }
+        self.chunkId_chunk.clear();
+        self.mvMapId_mvMap.clear();
+
+        Ok(())
+    }
+
+    /// How long to retain old, persisted chunks, in milliseconds.<br>
+    /// Chunks that are older may be overwritten once they contain no live data.<br>
+    /// Set to 0 for immediate reuse, -1 to never overwrite.
+    pub fn setRetentionTime(&mut self, ms: Integer) {
+        self.retentionTime = ms;
+    }
+
+    pub fn getRetentionTime(&self) -> Integer {
+        self.retentionTime
+    }
+
+    /// Try to increase the fill rate by re-writing partially full chunks.
+    /// Chunks with a low number of live items are re-written.<br>
+    /// If the current fill rate is higher than the target fill rate, nothing is done.
+    ///
+    /// targetFillRate: the minimum percentage of live entries<br>
+    /// maxWriteBytes: the maximum number of bytes to write
+    ///
+    /// return if any chunk was re-written
+    pub fn compact(&mut self, targetFillRate: Integer, maxWriteBytes: Integer) -> Result<bool> {
+        if self.fileStore.is_none() || get_ref!(self.fileStore).isReadOnly() || self.getLastChunk().is_none() {
+            return Ok(false);
+        }
+
+        self.checkOpen()?;
+
+        let storeLock = self.storeLock.clone();
+        let storeGuard = storeLock.lock();
+
+        // 先把未保存的内容写到chunk,这样存活的page都有了position
+        self.commit()?;
+        self.refreshChunkLiveness()?;
+
+        let old = self.findOldChunks(maxWriteBytes, targetFillRate)?;
+        let rewritten = !old.is_empty();
+        if rewritten {
+            self.compactRewrite(&old)?;
+            self.refreshChunkLiveness()?;
+        }
+
+        self.dropUnusedChunks()?;
+
+        Ok(rewritten)
+    }
+
+    /// 后台线程在有新的chunk时compact
+    fn doMaintenance(&mut self) -> Result<()> {
+        let lastChunk = self.getLastChunk();
+        if lastChunk.is_none() || get_ref!(lastChunk).id == self.autoCompactLastChunkId {
+            return Ok(());
+        }
+
+        self.compact(self.autoCompactFillRate, self.autoCommitMemory)?;
+
+        let lastChunk = self.getLastChunk();
+        if lastChunk.is_some() {
+            self.autoCompactLastChunkId = get_ref!(lastChunk).id;
+        }
+
+        Ok(())
+    }
+
+    /// 把layout中全部chunk的元信息读到chunkId_chunk,返回已保存的chunk
+    pub fn getChunks(&mut self) -> Result<Vec<SharedPtr<Chunk>>> {
+        let metaChunk = H2RustType::String(build_arc_h2RustCell!(data_utils::META_CHUNK.to_string()));
+        let mut chunkValues = Vec::new();
+        let mut cursor = get_ref!(self.layout).cursor(&metaChunk);
+        while let Some(key) = cursor.next() {
+            let key = key.castAsStringRef();
+            if !key.starts_with(data_utils::META_CHUNK) {
+                break;
+            }
+            // 已经加载的chunk不用再解析layout中的value
+            let chunkId = data_utils::parseHexInt(&key[data_utils::META_CHUNK.len()..])?;
+            if !self.chunkId_chunk.contains_key(&chunkId) {
+                chunkValues.push(cursor.getValue());
+            }
+        }
+
+        for chunkValue in chunkValues {
+            let chunk = chunk::fromString(chunkValue.castAsStringRef())?;
+            self.chunkId_chunk.insert(get_ref!(chunk).id, chunk);
+        }
+
+        Ok(self.chunkId_chunk.iter()
+            .map(|entry| entry.value().clone())
+            .filter(|chunk| get_ref!(chunk).isSaved())
+            .collect())
+    }
+
+    /// 读取non leaf page的子page的position
+    fn readChildrenPositions(&mut self, position: Long) -> Result<Vec<Long>> {
+        let chunk = self.getChunk(position)?;
+        let pageOffset = data_utils::getPageOffset(position);
+        let mut byteBuffer = get_ref!(chunk).readBufferForPage(self.fileStore.clone(), pageOffset, position)?;
+        page::readChildrenPositions(&mut byteBuffer, position)
+    }
+
+    /// chunk的table of content,下标是pageNo
+    fn getToC(&mut self, chunk: &SharedPtr<Chunk>) -> Result<SharedPtr<Vec<Long>>> {
+        let chunkRef = get_ref!(chunk);
+
+        if let Some(chunkCache) = self.chunkCache.as_ref() {
+            let toc = chunkCache.get(chunkRef.id as Long);
+            if toc.is_some() {
+                return Ok(toc);
+            }
+        }
+
+        let tocPos = chunkRef.block.load(Ordering::Acquire) * BLOCK_SIZE as Long + chunkRef.tocPos as Long;
+        let mut byteBuffer = get_ref_mut!(self.fileStore).readFully(tocPos as usize, chunkRef.pageCount as usize * 8)?;
+        let mut toc = Vec::with_capacity(chunkRef.pageCount as usize);
+        for _ in 0..chunkRef.pageCount {
+            toc.push(byteBuffer.getI64());
+        }
+
+        let toc = build_option_arc_h2RustCell!(toc);
+        if let Some(chunkCache) = self.chunkCache.as_ref() {
+            chunkCache.put(chunkRef.id as Long, toc.clone(), chunkRef.pageCount * 8)?;
+        }
+
+        Ok(toc)
+    }
+
+    /// 从最后1个chunk的layout和各个map的root出发遍历全部存活的page,重新计算各个chunk的pageCountLive,maxLenLive和occupancy.
+    /// 没有存活page的chunk记下变成unused的时间和版本
+    fn refreshChunkLiveness(&mut self) -> Result<()> {
+        let lastChunk = self.getLastChunk();
+        if lastChunk.is_none() {
+            return Ok(());
+        }
+
+        let mut rootPositions = vec![get_ref!(lastChunk).layoutRootPos];
+        let metaRoot = H2RustType::String(build_arc_h2RustCell!(data_utils::META_ROOT.to_string()));
+        let mut cursor = get_ref!(self.layout).cursor(&metaRoot);
+        while let Some(key) = cursor.next() {
+            if !key.castAsStringRef().starts_with(data_utils::META_ROOT) {
+                break;
+            }
+            rootPositions.push(data_utils::parseHexLong(cursor.getValue().castAsStringRef())?);
+        }
+
+        // leaf只需要position,只有non leaf需要读出来
+        let mut livePositions: HashSet<Long> = HashSet::new();
+        while let Some(position) = rootPositions.pop() {
+            if !data_utils::isPageSaved(position) || !livePositions.insert(position) {
+                continue;
+            }
+            if data_utils::getPageType(position) == data_utils::PAGE_TYPE_NODE {
+                rootPositions.extend(self.readChildrenPositions(position)?);
+            }
+        }
+
+        let time = self.getTimeSinceCreation();
+        let version = self.getCurrentVersion();
+        let lastChunkId = get_ref!(lastChunk).id;
+
+        for chunk in self.getChunks()? {
+            let toc = self.getToC(&chunk)?;
+            let chunkMutRef = get_ref_mut!(chunk);
+
+            let mut pageCountLive = 0;
+            let mut maxLenLive = 0;
+            let mut occupancy = BitSet::new();
+            for (pageNo, tocElement) in get_ref!(toc).iter().enumerate() {
+                let position = data_utils::getPagePos(chunkMutRef.id, *tocElement);
+                if livePositions.contains(&position) {
+                    pageCountLive += 1;
+                    maxLenLive += data_utils::getPageMaxLength(position) as Long;
+                } else {
+                    occupancy.insert(pageNo);
+                }
+            }
+
+            let mut changed = chunkMutRef.pageCountLive != pageCountLive || chunkMutRef.maxLenLive != maxLenLive;
+            chunkMutRef.pageCountLive = pageCountLive;
+            chunkMutRef.maxLenLive = maxLenLive;
+            chunkMutRef.occupancy = occupancy;
+
+            if pageCountLive == 0 && chunkMutRef.unused == 0 {
+                chunkMutRef.unused = time;
+                chunkMutRef.unusedAtVersion = version;
+                changed = true;
+                self.unusedChunks.push(chunk.clone());
+            }
+
+            // 最后1个chunk的元信息在下个chunk创建时才写到layout
+            if changed && chunkMutRef.id != lastChunkId {
+                get_ref_mut!(self.layout).put(H2RustType::String(build_arc_h2RustCell!(chunk::get_meta_key(chunkMutRef.id))),
+                                              H2RustType::String(build_arc_h2RustCell!(chunkMutRef.asString())))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 挑出存活比例不高于targetFillRate,并且已经过了retentionTime的chunk,越旧越空的越优先,合计不超过maxWriteBytes
+    fn findOldChunks(&mut self, maxWriteBytes: Integer, targetFillRate: Integer) -> Result<HashSet<Integer>> {
+        let time = self.getTimeSinceCreation();
+        let latestVersion = self.lastChunkVersion() + 1;
+
+        let mut candidates: Vec<(Long, Long, Integer)> = Vec::new();
+        for chunk in self.getChunks()? {
+            let chunkRef = get_ref!(chunk);
+            // only look at chunk older than the retention time (it's possible to compact chunks earlier, but right
+            // now we don't do that)
+            if chunkRef.isRewritable() && self.isSeasonedChunk(chunkRef, time) {
+                let fillRate = chunkRef.getFillRate();
+                if fillRate <= targetFillRate {
+                    let age = Long::max(1, latestVersion - chunkRef.version);
+                    let priority = fillRate as Long * 1000 / age;
+                    candidates.push((priority, chunkRef.maxLenLive, chunkRef.id));
+                }
+            }
+        }
+
+        candidates.sort();
+
+        let mut old = HashSet::new();
+        let mut totalSize: Long = 0;
+        for (_, maxLenLive, chunkId) in candidates {
+            totalSize += maxLenLive;
+            if !old.is_empty() && totalSize > maxWriteBytes as Long {
+                break;
+            }
+            old.insert(chunkId);
+        }
+
+        Ok(old)
+    }
+
+    /// 把old中的chunk里存活的page重写到新的chunk
+    fn compactRewrite(&mut self, old: &HashSet<Integer>) -> Result<()> {
+        let mvMaps: Vec<SharedPtr<MVMap>> = self.mvMapId_mvMap.iter().map(|entry| entry.value().clone()).collect();
+        for mvMap in mvMaps {
+            let mvMapMutRef = get_ref_mut!(mvMap);
+            if !mvMapMutRef.isClosed() && !mvMapMutRef.single_writer {
+                mvMapMutRef.rewrite(old)?;
+            }
+        }
+
+        if get_ref_mut!(self.meta).rewrite(old)? > 0 {
+            self.markMetaChanged();
+        }
+
+        get_ref_mut!(self.layout).rewrite(old)?;
+
+        self.commit()?;
+
+        Ok(())
+    }
+
+    /// 没有存活page,并且过了retentionTime,也不会再被需要保留的旧版本用到的chunk从layout中删掉,
+    /// 它们占用的空间在下个chunk保存之后释放
+    fn dropUnusedChunks(&mut self) -> Result<()> {
+        if self.unusedChunks.is_empty() {
+            return Ok(());
+        }
+
+        let lastChunk = self.getLastChunk();
+        let lastChunkId = if lastChunk.is_none() { 0 } else { get_ref!(lastChunk).id };
+        let time = self.getTimeSinceCreation();
+        let oldestVersionToKeep = self.getOldestVersionToKeep();
+
+        let mut notYet = Vec::new();
+        for chunk in std::mem::take(&mut self.unusedChunks) {
+            let chunkRef = get_ref!(chunk);
+
+            // 已经删掉了
+            if !self.chunkId_chunk.contains_key(&chunkRef.id) {
+                continue;
+            }
+
+            // 最后1个chunk被store header和下个chunk的创建用到
+            if chunkRef.id == lastChunkId ||
+                !self.isSeasonedChunk(chunkRef, time) ||
+                chunkRef.unused + self.retentionTime as Long > time ||
+                !self.canOverwriteChunk(chunkRef, oldestVersionToKeep) {
+                notYet.push(chunk.clone());
+                continue;
+            }
+
+            self.chunkId_chunk.remove(&chunkRef.id);
+            get_ref_mut!(self.layout).remove(H2RustType::String(build_arc_h2RustCell!(chunk::get_meta_key(chunkRef.id))))?;
+            self.markMetaChanged();
+            self.deadChunks.push(chunk.clone());
+        }
+        self.unusedChunks = notYet;
+
+        Ok(())
+    }
+
+    /// 创建已经超过retentionTime的chunk,retentionTime为负数时永远不会被覆盖
+    fn isSeasonedChunk(&self, chunk: &Chunk, time: Long) -> bool {
+        self.retentionTime >= 0 && chunk.time + self.retentionTime as Long <= time
+    }
+
+    /// 没有存活page,并且不会再被需要保留的旧版本用到
+    fn canOverwriteChunk(&self, chunk: &Chunk, oldestVersionToKeep: Long) -> bool {
+        !chunk.isLive() && chunk.unusedAtVersion < oldestVersionToKeep
+    }
+
+    /// Get the current fill rate (percentage of used space in the file). Unlike the chunk fill rate,
+    /// this includes only chunks, so the store header and free space at the end of the file are not considered.
+    pub fn getFillRate(&self) -> Integer {
+        get_ref!(self.fileStore).getFillRate()
+    }
+
+    /// Get the current fill rate of the chunks (percentage of live data in all chunks).
+    pub fn getChunksFillRate(&self) -> Integer {
+        let mut maxLengthSum: Long = 1;
+        let mut maxLengthLiveSum: Long = 1;
+        for entry in self.chunkId_chunk.iter() {
+            let chunk = get_ref!(entry.value());
+            maxLengthSum += chunk.maxLen;
+            maxLengthLiveSum += chunk.maxLenLive;
+        }
+
+        (100 * maxLengthLiveSum / maxLengthSum) as Integer
+    }
+
+    /// Get a snapshot of the store statistics: the file io counters, chunk count and fill rates,
+    /// the page cache counters, unsaved memory and the current version.
+    pub fn stats(&self) -> MVStoreStats {
+        let mut stats = MVStoreStats {
+            chunkCount: self.chunkId_chunk.len() as Integer,
+            chunksFillRate: self.getChunksFillRate(),
+            unsavedMemory: self.unsavedMemory.load(Ordering::Acquire),
+            currentVersion: self.getCurrentVersion(),
+            ..Default::default()
+        };
+
+        if self.fileStore.is_some() {
+            let fileStore = get_ref!(self.fileStore);
+            stats.readCount = fileStore.getReadCount();
+            stats.readBytes = fileStore.getReadBytes();
+            stats.writeCount = fileStore.getWriteCount();
+            stats.writeBytes = fileStore.getWriteBytes();
+            stats.fillRate = fileStore.getFillRate();
+        }
+
+        if let Some(pageCache) = self.pageCache.as_ref() {
+            stats.cacheHits = pageCache.getHits();
+            stats.cacheMisses = pageCache.getMisses();
+            stats.cacheEvictions = pageCache.getEvictions();
+            stats.cacheUsedMemory = pageCache.getUsedMemory();
+            stats.cacheMaxMemory = pageCache.getMaxMemory();
+        }
+
+        stats
+    }
+
+    /// Compact store file, that is, compact blocks that have a low fill rate,
+    /// and move chunks next to each other. This will typically shrink the file.
+    /// Changes are flushed to the file, and old chunks are overwritten.
+    ///
+    /// return if anything was moved
+    pub fn compactMoveChunks(&mut self) -> Result<bool> {
+        self.compactMoveChunks2(100, Long::MAX)
+    }
+
+    /// Compact the store by moving all chunks next to each other, if there is free space between chunks.
+    /// This might temporarily increase the file size. Chunks are overwritten irrespective of the current retention time.
+    /// Before overwriting chunks and before resizing the file, syncFile() is called.
+    ///
+    /// targetFillRate: do nothing if the file store fill rate is higher than this<br>
+    /// moveSize: the number of bytes to move
+    ///
+    /// return if anything was moved
+    pub fn compactMoveChunks2(&mut self, targetFillRate: Integer, moveSize: Long) -> Result<bool> {
+        if self.fileStore.is_none() || get_ref!(self.fileStore).isReadOnly() {
+            return Ok(false);
+        }
+
+        let storeLock = self.storeLock.clone();
+        let storeGuard = storeLock.lock();
+
+        self.checkOpen()?;
+
+        // 先把未保存的内容写出去,删掉的chunk也在保存之后才释放
+        self.dropUnusedChunks()?;
+        self.commit()?;
+
+        if self.getLastChunk().is_none() || self.getFillRate() > targetFillRate {
+            return Ok(false);
+        }
+
+        let start = get_ref!(self.fileStore).getFirstFree() / BLOCK_SIZE as Long;
+        let chunksToMove = self.findChunksToMove(start, moveSize)?;
+        if chunksToMove.is_empty() {
+            self.shrinkFileIfPossible(0)?;
+            return Ok(false);
+        }
+
+        self.compactMoveChunks1(chunksToMove)?;
+
+        Ok(true)
+    }
+
+    /// 位于start之后的chunk,被大片空闲空间包围的小chunk优先,合计不超过moveSize,按照在文件中的位置排序
+    fn findChunksToMove(&mut self, startBlock: Long, moveSize: Long) -> Result<Vec<SharedPtr<Chunk>>> {
+        let maxBlocksToMove = moveSize / BLOCK_SIZE as Long;
+        if maxBlocksToMove <= 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut candidates: Vec<(Integer, Long, SharedPtr<Chunk>)> = Vec::new();
+        for chunk in self.getChunks()? {
+            let block = get_ref!(chunk).block.load(Ordering::Acquire);
+            if block > startBlock {
+                // instead of selection just closest to beginning of the file,
+                // pick smaller chunk(s) which sit in between bigger holes
+                let priority = get_ref!(self.fileStore).getMovePriority(block as Integer);
+                candidates.push((priority, block, chunk));
+            }
+        }
+        candidates.sort_by_key(|(priority, block, _)| (*priority, *block));
+
+        let mut chunksToMove = Vec::new();
+        let mut size: Long = 0;
+        for (_, _, chunk) in candidates {
+            size += get_ref!(chunk).blockCount as Long;
+            if size > maxBlocksToMove {
+                break;
+            }
+            chunksToMove.push(chunk);
+        }
+        chunksToMove.sort_by_key(|chunk| get_ref!(chunk).block.load(Ordering::Acquire));
+
+        Ok(chunksToMove)
+    }
+
+    fn compactMoveChunks1(&mut self, chunksToMove: Vec<SharedPtr<Chunk>>) -> Result<()> {
+        // this will ensure better recognition of the last chunk in case of power failure,
+        // since we are going to move older chunks to the end of the file
+        self.writeStoreHeader()?;
+        self.sync()?;
+
+        let leftmostBlock = get_ref!(chunksToMove[0]).block.load(Ordering::Acquire);
+        let originalBlockCount = self.getAfterLastBlock();
+        // we need to ensure that chunks moved within the following loop
+        // do not overlap with space just released by chunks moved before them,
+        // hence the need to reserve this area [leftmostBlock, originalBlockCount)
+        for chunk in &chunksToMove {
+            self.moveChunk(chunk, leftmostBlock, originalBlockCount)?;
+        }
+        // update the metadata (hopefully within the file)
+        self.store2(leftmostBlock, originalBlockCount)?;
+        self.sync()?;
+
+        let chunkToMove = self.getLastChunk();
+        let postEvacuationBlockCount = self.getAfterLastBlock();
+
+        let mut chunkToMoveIsAlreadyInside = get_ref!(chunkToMove).block.load(Ordering::Acquire) < leftmostBlock;
+        let mut movedToEOF = !chunkToMoveIsAlreadyInside;
+        // move all chunks, which previously did not fit before reserved area
+        // now we can re-use previously reserved area [leftmostBlock, originalBlockCount),
+        // but need to reserve [originalBlockCount, postEvacuationBlockCount)
+        for chunk in &chunksToMove {
+            if get_ref!(chunk).block.load(Ordering::Acquire) >= originalBlockCount &&
+                self.moveChunk(chunk, originalBlockCount, postEvacuationBlockCount)? {
+                assert!(get_ref!(chunk).block.load(Ordering::Acquire) < originalBlockCount);
+                movedToEOF = true;
+                chunkToMoveIsAlreadyInside = get_ref!(chunkToMove).block.load(Ordering::Acquire) < originalBlockCount;
+            }
+        }
+
+        if movedToEOF {
+            let mut moved = self.moveChunkInside(&chunkToMove, originalBlockCount)?;
+
+            // store a new chunk with updated metadata (hopefully within a file)
+            self.store2(originalBlockCount, -1)?;
+            self.sync()?;
+
+            // if chunkToMove did not fit within originalBlockCount (move is false),
+            // and since now previously reserved area [originalBlockCount, postEvacuationBlockCount) also can be used,
+            // lets try to move that chunk into this area, closer to the beginning of the file
+            let lastBoundary = if moved || chunkToMoveIsAlreadyInside {
+                postEvacuationBlockCount
+            } else {
+                get_ref!(chunkToMove).block.load(Ordering::Acquire)
+            };
+            moved = !moved && self.moveChunkInside(&chunkToMove, lastBoundary)?;
+            let lastChunk = self.getLastChunk();
+            if self.moveChunkInside(&lastChunk, lastBoundary)? || moved {
+                self.store2(lastBoundary, -1)?;
+            }
+        }
+
+        self.shrinkFileIfPossible(0)?;
+        self.sync()
+    }
+
+    fn moveChunkInside(&mut self, chunkToMove: &SharedPtr<Chunk>, boundary: Long) -> Result<bool> {
+        let chunkRef = get_ref!(chunkToMove);
+        let moved = chunkRef.block.load(Ordering::Acquire) >= boundary &&
+            get_ref!(self.fileStore).predictAllocation(chunkRef.blockCount, boundary, -1) < boundary &&
+            self.moveChunk(chunkToMove, boundary, -1)?;
+        assert!(!moved || chunkRef.block.load(Ordering::Acquire) + chunkRef.blockCount as Long <= boundary);
+        Ok(moved)
+    }
+
+    /// Move specified chunk into free area of the file. "Reserved" area specifies file interval to be avoided,
+    /// when un-allocated space will be chosen for a new chunk's location.
+    ///
+    /// return true if block was moved, false otherwise
+    fn moveChunk(&mut self, chunk: &SharedPtr<Chunk>, reservedAreaLow: Long, reservedAreaHigh: Long) -> Result<bool> {
+        let chunkMutRef = get_ref_mut!(chunk);
+
+        // ignore if already removed during the previous store operations
+        if !self.chunkId_chunk.contains_key(&chunkMutRef.id) {
+            return Ok(false);
+        }
+
+        let saveChunkLock = self.saveChunkLock.clone();
+        let saveChunkGuard = saveChunkLock.lock();
+
+        let start = chunkMutRef.block.load(Ordering::Acquire) * BLOCK_SIZE as Long;
+        let length = chunkMutRef.blockCount * BLOCK_SIZE;
+
+        let mut readBuff = get_ref_mut!(self.fileStore).readFully(start as usize, length as usize)?;
+        let chunkFromFile = Chunk::readChunkHeader(&mut readBuff, start)?;
+        let chunkHeaderLen = readBuff.getPosition();
+
+        let pos = get_ref_mut!(self.fileStore).allocate(length, reservedAreaLow, reservedAreaHigh);
+        let block = pos / BLOCK_SIZE as Long;
+        // in the absence of a reserved area, block should always move closer to the beginning of the file
+        assert!(reservedAreaHigh > 0 || block <= chunkMutRef.block.load(Ordering::Acquire));
+
+        let mut writeBuffer = WriteBuffer::new(length as usize);
+        writeBuffer.putSlice(readBuff.extractWithPosLen(0, length as usize));
+        writeBuffer.setLimit(length as usize);
+
+        // can not set chunk's new block/len until it's fully written at new location,
+        // because concurrent reader can pick it up prematurely
+        get_ref!(chunkFromFile).block.store(block, Ordering::Release);
+        get_ref_mut!(chunkFromFile).next = 0;
+        writeBuffer.setPosition(0);
+        get_ref!(chunkFromFile).writeChunkHeader(&mut writeBuffer, chunkHeaderLen)?;
+        writeBuffer.setPosition(length as usize - chunk::FOOTER_LENGTH as usize);
+        writeBuffer.putSlice(&get_ref!(chunkFromFile).getFooterBytes());
+        writeBuffer.setPosition(0);
+        self.write(pos, writeBuffer.getBuffer())?;
+
+        get_ref_mut!(self.fileStore).free(start, length);
+        chunkMutRef.block.store(block, Ordering::Release);
+        chunkMutRef.next = 0;
+
+        get_ref_mut!(self.layout).put(H2RustType::String(build_arc_h2RustCell!(chunk::get_meta_key(chunkMutRef.id))),
+                                      H2RustType::String(build_arc_h2RustCell!(chunkMutRef.asString())))?;
+        self.markMetaChanged();
+
+        Ok(true)
+    }
+
+    /// 保存时新的chunk不使用[reservedLow, reservedHigh)的空间
+    fn store2(&mut self, reservedLow: Long, reservedHigh: Long) -> Result<()> {
+        let storeLock = self.storeLock.clone();
+        let storeGuard = storeLock.lock();
+
+        self.reservedLow = reservedLow;
+        self.reservedHigh = reservedHigh;
+
+        let result = self.store(true);
+
+        self.reservedLow = 0;
+        self.reservedHigh = 0;
+
+        result
+    }
+
+    /// 文件末尾没有被chunk使用的部分至少占minPercent时截掉
+    fn shrinkFileIfPossible(&mut self, minPercent: Integer) -> Result<()> {
+        if get_ref!(self.fileStore).isReadOnly() {
+            return Ok(());
+        }
+
+        let end = get_ref!(self.fileStore).getFileLengthInUse();
+        let fileSize = get_ref!(self.fileStore).size();
+        if end >= fileSize {
+            return Ok(());
+        }
+
+        if minPercent > 0 && fileSize - end < BLOCK_SIZE as Long {
+            return Ok(());
+        }
+
+        let savedPercent = (100 - (end * 100 / fileSize)) as Integer;
+        if savedPercent < minPercent {
+            return Ok(());
+        }
+
+        if self.isOpenOrStopping() {
+            self.sync()?;
+        }
+
+        get_ref_mut!(self.fileStore).truncate(end)
+    }
+
+    /// Force all stored changes to be written to the storage.
+    fn sync(&self) -> Result<()> {
+        get_ref!(self.fileStore).sync()
+    }
+
+    /// Set the maximum delay in milliseconds to auto-commit changes.<br>
+    /// To disable auto-commit, set the value to 0. In this case, changes are only committed when explicitly calling commit.<br>
+    /// The default is 1000, meaning all changes are committed after at most one second.
+    pub fn setAutoCommitDelay(&mut self, millis: Integer) {
+        if self.autoCommitDelay == millis {
+            return;
+        }
+
+        self.autoCommitDelay = millis;
+
+        if self.fileStore.is_none() || get_ref!(self.fileStore).isReadOnly() {
+            return;
+        }
+
+        self.stopBackgroundThread(true);
+
+        // start the background thread if needed
+        if millis > 0 && self.state.load(Ordering::Acquire) == STATE_OPEN {
+            let sleep = Integer::max(1, millis / 10);
+            let backgroundWriterThread = BackgroundWriterThread::start(self.getWeakThis(), sleep, get_ref!(self.fileStore).getFileName());
+            *self.backgroundWriterThread.lock().unwrap() = Some(backgroundWriterThread);
+        }
+    }
+
+    pub fn getAutoCommitDelay(&self) -> Integer {
+        self.autoCommitDelay
+    }
+
+    /// Commit and save all changes, if there are any.
+    ///
+    /// 只在拿到storeLock之后才可变地访问store,和调用者的commit/close互斥;正在提交时跳过这一次
+    fn writeInBackground(mvStore: &SharedPtr<MVStore>) -> Result<()> {
+        let mvStoreRef = get_ref!(mvStore);
+        if !mvStoreRef.isOpenOrStopping() || get_ref!(mvStoreRef.fileStore).isReadOnly() {
+            return Ok(());
+        }
+
+        let storeLock = mvStoreRef.storeLock.clone();
+        let storeLockGuard = storeLock.tryLock();
+        if storeLockGuard.is_none() {
+            return Ok(());
+        }
+
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+        let time = mvStoreMutRef.getTimeSinceCreation();
+        if time > mvStoreMutRef.lastCommitTime.load(Ordering::Acquire) + mvStoreMutRef.autoCommitDelay as Long ||
+            (mvStoreMutRef.autoCommitMemory > 0 && mvStoreMutRef.needStore()) {
+            mvStoreMutRef.store(false)?;
+        }
+
+        if mvStoreMutRef.autoCompactFillRate > 0 {
+            mvStoreMutRef.doMaintenance()?;
+        }
+
+        Ok(())
+    }
+
+    /// 有handler时交给handler,线程继续运行,否则记下错误并结束后台线程,下次commit()或close()时返回
+    fn handleBackgroundException(&self, e: anyhow::Error) -> bool {
+        match self.backgroundExceptionHandler.as_ref() {
+            Some(backgroundExceptionHandler) => {
+                backgroundExceptionHandler(&e);
+                true
+            }
+            None => {
+                *self.backgroundException.lock().unwrap() = Some(e);
+                false
+            }
+        }
+    }
+
+    fn takeBackgroundException(&self) -> Result<()> {
+        match self.backgroundException.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn stopBackgroundThread(&self, waitForIt: bool) {
+        let backgroundWriterThread = self.backgroundWriterThread.lock().unwrap().take();
+        if let Some(backgroundWriterThread) = backgroundWriterThread {
+            backgroundWriterThread.stop(waitForIt);
+        }
+    }
+
+    pub fn hasUnsavedChanges(&self) -> bool {
+        if self.metaChanged.load(Ordering::Acquire) {
+            return true;
         }
 
         let lastStoredVersion = load_atomic!(self.currentVersion) - 1;
@@ -439,6 +2709,70 @@ impl MVStore {
     }
 }
 
+/// 被删除的已保存page,对应java的MVStore.RemovedPageInfo
+struct RemovedPageInfo {
+    pagePos: Long,
+    version: Long,
+
+    /// -1表示不知道,需要从chunk的toc中找
+    pageNo: Integer,
+}
+
+/// 定时唤醒,调用MVStore::writeInBackground
+struct BackgroundWriterThread {
+    /// true表示已经要求停止,java版的sync.wait/notifyAll
+    sync: Arc<(Mutex<bool>, Condvar)>,
+    joinHandle: JoinHandle<()>,
+}
+
+impl BackgroundWriterThread {
+    fn start(mvStoreWeakPtr: WeakPtr<MVStore>, sleep: Integer, fileName: &str) -> BackgroundWriterThread {
+        let sync = Arc::new((Mutex::new(false), Condvar::new()));
+        let syncClone = sync.clone();
+
+        let joinHandle = thread::Builder::new()
+            .name(format!("MVStore background writer {}", fileName))
+            .spawn(move || {
+                let (stopped, condvar) = &*syncClone;
+                loop {
+                    {
+                        let stoppedGuard = stopped.lock().unwrap();
+                        let (stoppedGuard, _) =
+                            condvar.wait_timeout_while(stoppedGuard, Duration::from_millis(sleep as u64), |stopped| !*stopped).unwrap();
+                        if *stoppedGuard {
+                            break;
+                        }
+                    }
+
+                    // 持有的是weak,store已经不在了也就没必要继续
+                    let mvStore = match mvStoreWeakPtr.as_ref().unwrap().upgrade() {
+                        Some(mvStore) => Some(mvStore),
+                        None => break,
+                    };
+
+                    if let Err(e) = MVStore::writeInBackground(&mvStore) {
+                        if !get_ref!(mvStore).handleBackgroundException(e) {
+                            break;
+                        }
+                    }
+                }
+            }).unwrap();
+
+        BackgroundWriterThread { sync, joinHandle }
+    }
+
+    fn stop(self, waitForIt: bool) {
+        let (stopped, condvar) = &*self.sync;
+        *stopped.lock().unwrap() = true;
+        condvar.notify_all();
+
+        // if called from within the thread itself - can not join
+        if waitForIt && self.joinHandle.thread().id() != thread::current().id() {
+            let _ = self.joinHandle.join();
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct MVStoreBuilder {
     pub config: HashMap<String, Box<dyn Any>>,
@@ -461,6 +2795,18 @@ impl MVStoreBuilder {
         self.config.insert("readOnly".to_string(), Box::new(1));
     }
 
+    /// Open the file in recovery mode, where some errors may be ignored.
+    ///
+    /// 不信任store header,扫描整个文件,使用最新的能完整读出layout的chunk
+    pub fn recoveryMode(&mut self) {
+        self.config.insert("recoveryMode".to_string(), Box::new(1));
+    }
+
+    /// Set the listener to be used for exceptions that occur when writing in the background thread.
+    pub fn backgroundExceptionHandler(&mut self, backgroundExceptionHandler: BackgroundExceptionHandler) {
+        self.config.insert("backgroundExceptionHandler".to_string(), Box::new(backgroundExceptionHandler));
+    }
+
     pub fn autoCommitDisabled(&mut self) {
         self.config.insert("autoCommitDelay".to_string(), Box::new(0));
     }
@@ -473,7 +2819,504 @@ impl MVStoreBuilder {
         self.config.insert("compress".to_string(), Box::new(1));
     }
 
+    /// 使用deflate压缩,比compress()省空间但更慢
+    pub fn compressHigh(&mut self) {
+        self.config.insert("compress".to_string(), Box::new(2));
+    }
+
+    /// Set the read cache size in MB. The default is 16 MB.
+    pub fn cacheSize(&mut self, mb: Integer) {
+        self.config.insert("cacheSize".to_string(), Box::new(mb));
+    }
+
+    /// Set the read cache concurrency, the number of independently locked segments of the page cache.
+    /// It must be a power of 2, the default is 16.
+    pub fn cacheConcurrency(&mut self, concurrency: Integer) {
+        self.config.insert("cacheConcurrency".to_string(), Box::new(concurrency));
+    }
+
+    /// Encrypt / decrypt the file using the given password.
+    /// This method has no effect for in-memory stores.
+    ///
+    /// password是file_encrypt::get_password_bytes()得到的UTF-16字节
+    pub fn encryptionKey(&mut self, password: &[u8]) {
+        let encryptionKey: Vec<Byte> = password.iter().map(|b| *b as Byte).collect();
+        self.config.insert("encryptionKey".to_string(), Box::new(encryptionKey));
+    }
+
     pub fn open(&mut self) -> Result<SharedPtr<MVStore>> {
         MVStore::new(&mut self.config)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{get_ref, get_ref_mut};
+    use crate::h2_rust_common::h2_rust_type::H2RustType;
+    use crate::mvstore::mv_map::MVMapBuilder;
+    use crate::mvstore::mv_store::MVStoreBuilder;
+    use crate::mvstore::test_utils::{string, TempStore};
+
+    #[test]
+    fn test_store_commit_read_back() {
+        let tempStore = TempStore::new("test_store_commit_read_back");
+
+        let version;
+        {
+            let mvStore = tempStore.openWith(&[("autoCommitDelay", 0)]);
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+            let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+            for a in 0..5000 {
+                get_ref_mut!(data).put(string(&format!("k{:05}", a)), string(&format!("v{}", a))).unwrap();
+            }
+            assert!(mvStoreMutRef.hasUnsavedChanges());
+            let firstVersion = mvStoreMutRef.commit().unwrap();
+            assert!(!mvStoreMutRef.hasUnsavedChanges());
+            assert_eq!(mvStoreMutRef.getChunks().unwrap().len(), 1);
+
+            // 保存之后的page从文件中读回
+            for a in (0..5000).step_by(7) {
+                assert_eq!(get_ref!(data).get(&string(&format!("k{:05}", a))).castAsStringRef(), &format!("v{}", a));
+            }
+
+            for a in (0..5000).step_by(2) {
+                get_ref_mut!(data).put(string(&format!("k{:05}", a)), string(&format!("w{}", a))).unwrap();
+            }
+            version = mvStoreMutRef.commit().unwrap();
+            assert_eq!(version, firstVersion + 1);
+            assert_eq!(get_ref!(data).get(&string("k00002")).castAsStringRef(), "w2");
+            assert_eq!(get_ref!(data).get(&string("k00003")).castAsStringRef(), "v3");
+            mvStoreMutRef.close().unwrap();
+        }
+
+        let mvStore = tempStore.open();
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+        assert_eq!(mvStoreMutRef.getCurrentVersion(), version);
+        let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+        assert_eq!(get_ref!(data).size_as_long(), 5000);
+        for a in 0..5000 {
+            let expected = if a % 2 == 0 { format!("w{}", a) } else { format!("v{}", a) };
+            assert_eq!(get_ref!(data).get(&string(&format!("k{:05}", a))).castAsStringRef(), &expected);
+        }
+        assert_eq!(get_ref!(data).cursor(&H2RustType::Null).count(), 5000);
+        mvStoreMutRef.close().unwrap();
+    }
+
+    #[test]
+    fn test_open_map() {
+        let tempStore = TempStore::new("test_open_map");
+
+        {
+            let mvStore = tempStore.open();
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+
+            let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+            for a in 0..1000 {
+                get_ref_mut!(data).put(string(&format!("k{}", a)), string(&format!("v{}", a))).unwrap();
+            }
+            let other = mvStoreMutRef.openMap("other", &MVMapBuilder::new()).unwrap();
+            get_ref_mut!(other).put(string("a"), string("b")).unwrap();
+            let removed = mvStoreMutRef.openMap("removed", &MVMapBuilder::new()).unwrap();
+            get_ref_mut!(removed).put(string("a"), string("b")).unwrap();
+            mvStoreMutRef.commit().unwrap();
+
+            mvStoreMutRef.renameMap(&other, "renamed").unwrap();
+            mvStoreMutRef.removeMap(&removed).unwrap();
+            assert!(!mvStoreMutRef.hasMap("removed"));
+            mvStoreMutRef.close().unwrap();
+        }
+
+        let mvStore = tempStore.open();
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+
+        let mut mapNames: Vec<String> = mvStoreMutRef.getMapNames().unwrap().into_iter().collect();
+        mapNames.sort();
+        assert_eq!(mapNames, vec!["data", "renamed"]);
+
+        let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+        assert_eq!(get_ref!(data).size_as_long(), 1000);
+        assert_eq!(get_ref!(data).get(&string("k123")).castAsStringRef(), "v123");
+
+        let renamed = mvStoreMutRef.openMap("renamed", &MVMapBuilder::new()).unwrap();
+        assert_eq!(get_ref!(renamed).get(&string("a")).castAsStringRef(), "b");
+
+        mvStoreMutRef.close().unwrap();
+    }
+
+    #[test]
+    fn test_open_version() {
+        let tempStore = TempStore::new("test_open_version");
+
+        let first;
+        let old;
+        {
+            let mvStore = tempStore.open();
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+
+            let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+            get_ref_mut!(data).put(string("0"), string("test")).unwrap();
+            first = mvStoreMutRef.getCurrentVersion();
+            mvStoreMutRef.commit().unwrap();
+
+            get_ref_mut!(data).put(string("1"), string("Hello")).unwrap();
+            old = mvStoreMutRef.getCurrentVersion();
+            mvStoreMutRef.commit().unwrap();
+
+            get_ref_mut!(data).put(string("1"), string("Hallo")).unwrap();
+
+            // 内存中保留的版本
+            let mFirst = get_ref!(data).openVersion(first).unwrap();
+            assert_eq!(get_ref!(mFirst).size_as_long(), 1);
+            assert!(get_ref!(mFirst).isReadOnly());
+            assert!(get_ref!(mFirst).openVersion(first).is_err());
+
+            let mOld = get_ref!(data).openVersion(old).unwrap();
+            assert_eq!(get_ref!(mOld).get(&string("1")).castAsStringRef(), "Hello");
+            assert_eq!(get_ref!(data).get(&string("1")).castAsStringRef(), "Hallo");
+
+            assert!(get_ref!(data).openVersion(-3).is_err());
+
+            mvStoreMutRef.close().unwrap();
+        }
+
+        // 重新打开后旧版本只能从chunk中读取
+        let mvStore = tempStore.open();
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+        let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+
+        let mFirst = get_ref!(data).openVersion(first).unwrap();
+        assert_eq!(get_ref!(mFirst).size_as_long(), 1);
+        assert!(get_ref!(mFirst).get(&string("1")).isNull());
+
+        let mOld = get_ref!(data).openVersion(old).unwrap();
+        assert_eq!(get_ref!(mOld).get(&string("1")).castAsStringRef(), "Hello");
+        assert_eq!(get_ref!(data).get(&string("1")).castAsStringRef(), "Hallo");
+
+        mvStoreMutRef.close().unwrap();
+    }
+
+    #[test]
+    fn test_rollback_to() {
+        let tempStore = TempStore::new("test_rollback_to");
+
+        {
+            let mvStore = tempStore.open();
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+
+            let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+            get_ref_mut!(data).put(string("1"), string("Hello")).unwrap();
+            let version = mvStoreMutRef.commit().unwrap();
+            let fileSize = tempStore.getFileSize();
+
+            // 失败的批量导入,中间已经提交过
+            for a in 0..100 {
+                get_ref_mut!(data).put(string(&format!("k{}", a)), string("v")).unwrap();
+            }
+            let imported = mvStoreMutRef.openMap("imported", &MVMapBuilder::new()).unwrap();
+            get_ref_mut!(imported).put(string("a"), string("b")).unwrap();
+            mvStoreMutRef.commit().unwrap();
+            get_ref_mut!(data).put(string("1"), string("Hallo")).unwrap();
+            assert!(tempStore.getFileSize() > fileSize);
+
+            mvStoreMutRef.rollbackTo(version).unwrap();
+            assert_eq!(mvStoreMutRef.getCurrentVersion(), version);
+            assert_eq!(tempStore.getFileSize(), fileSize);
+            assert_eq!(get_ref!(data).size_as_long(), 1);
+            assert_eq!(get_ref!(data).get(&string("1")).castAsStringRef(), "Hello");
+            assert!(get_ref!(imported).isClosed());
+            assert!(!mvStoreMutRef.hasMap("imported"));
+
+            assert!(mvStoreMutRef.rollbackTo(version + 1).is_err());
+
+            get_ref_mut!(data).put(string("2"), string("World")).unwrap();
+            mvStoreMutRef.close().unwrap();
+        }
+
+        let mvStore = tempStore.open();
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+        let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+        assert_eq!(get_ref!(data).size_as_long(), 2);
+        assert_eq!(get_ref!(data).get(&string("2")).castAsStringRef(), "World");
+        assert!(!mvStoreMutRef.hasMap("imported"));
+
+        mvStoreMutRef.rollbackTo(0).unwrap();
+        assert!(mvStoreMutRef.getMapNames().unwrap().is_empty());
+        mvStoreMutRef.close().unwrap();
+
+        let mvStore = tempStore.open();
+        assert!(get_ref!(mvStore).getMapNames().unwrap().is_empty());
+        get_ref_mut!(mvStore).close().unwrap();
+    }
+
+    #[test]
+    fn test_background_writer() {
+        let tempStore = TempStore::new("test_background_writer");
+
+        {
+            let mvStore = tempStore.openWith(&[("autoCommitDelay", 10)]);
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+            assert_eq!(mvStoreMutRef.getAutoCommitDelay(), 10);
+
+            let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+            get_ref_mut!(data).put(string("1"), string("Hello")).unwrap();
+            let version = mvStoreMutRef.getCurrentVersion();
+
+            // 不调用commit,等后台线程提交
+            for _ in 0..200 {
+                if mvStoreMutRef.getCurrentVersion() > version {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            assert!(mvStoreMutRef.getCurrentVersion() > version);
+            assert!(!mvStoreMutRef.hasUnsavedChanges());
+
+            mvStoreMutRef.close().unwrap();
+            assert!(mvStoreMutRef.backgroundWriterThread.lock().unwrap().is_none());
+        }
+
+        let mvStore = tempStore.open();
+        let data = get_ref_mut!(mvStore).openMap("data", &MVMapBuilder::new()).unwrap();
+        assert_eq!(get_ref!(data).get(&string("1")).castAsStringRef(), "Hello");
+        get_ref_mut!(mvStore).close().unwrap();
+    }
+
+    #[test]
+    fn test_put_while_auto_committing() {
+        let tempStore = TempStore::new("test_put_while_auto_committing");
+
+        {
+            let mvStore = tempStore.openWith(&[("autoCommitDelay", 1)]);
+            let data = get_ref_mut!(mvStore).openMap("data", &MVMapBuilder::new()).unwrap();
+
+            // 调用者在另外的线程写入,同时后台线程不断地提交
+            let writer = {
+                let data = data.clone();
+                std::thread::spawn(move || {
+                    for a in 0..5000 {
+                        get_ref_mut!(data).put(string(&format!("k{}", a)), string(&format!("v{}", a))).unwrap();
+                        // 让后台线程在写入过程中提交
+                        if a % 500 == 0 {
+                            std::thread::sleep(std::time::Duration::from_millis(2));
+                        }
+                    }
+                })
+            };
+
+            let version = get_ref!(mvStore).getCurrentVersion();
+            for _ in 0..200 {
+                if get_ref!(mvStore).getCurrentVersion() > version + 1 {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+            writer.join().unwrap();
+            assert!(get_ref!(mvStore).getCurrentVersion() > version + 1);
+            get_ref_mut!(mvStore).close().unwrap();
+        }
+
+        let mvStore = tempStore.open();
+        let data = get_ref_mut!(mvStore).openMap("data", &MVMapBuilder::new()).unwrap();
+        assert_eq!(get_ref!(data).size(), 5000);
+        for a in (0..5000).step_by(97) {
+            assert_eq!(get_ref!(data).get(&string(&format!("k{}", a))).castAsStringRef(), &format!("v{}", a));
+        }
+        get_ref_mut!(mvStore).close().unwrap();
+    }
+
+    #[test]
+    fn test_store_lock_reentrant() {
+        let tempStore = TempStore::new("test_store_lock_reentrant");
+        let mvStore = tempStore.open();
+        let storeLock = get_ref!(mvStore).storeLock.clone();
+
+        {
+            let outer = storeLock.lock();
+            {
+                let inner = storeLock.lock();
+                assert!(storeLock.isHeldByCurrentThread());
+            }
+            // 内层的guard释放后外层仍然持有
+            assert!(storeLock.isHeldByCurrentThread());
+
+            // 持有storeLock时store()里对layout的写入不会让外层失去所有权
+            let data = get_ref_mut!(mvStore).openMap("data", &MVMapBuilder::new()).unwrap();
+            get_ref_mut!(data).put(string("k"), string("v")).unwrap();
+            get_ref_mut!(mvStore).commit().unwrap();
+            assert!(storeLock.isHeldByCurrentThread());
+        }
+        assert!(!storeLock.isHeldByCurrentThread());
+
+        get_ref_mut!(mvStore).close().unwrap();
+    }
+
+    #[test]
+    fn test_background_exception() {
+        let tempStore = TempStore::new("test_background_exception");
+        let mvStore = tempStore.openWith(&[("autoCommitDelay", 0)]);
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+
+        // 没有handler,后台线程的错误留到下次commit()时返回,只返回1次
+        assert!(!mvStoreMutRef.handleBackgroundException(anyhow::anyhow!("background failure")));
+        let e = mvStoreMutRef.commit().unwrap_err();
+        assert_eq!(e.to_string(), "background failure");
+        mvStoreMutRef.commit().unwrap();
+
+        assert!(!mvStoreMutRef.handleBackgroundException(anyhow::anyhow!("background failure")));
+        assert!(mvStoreMutRef.close().is_err());
+        assert!(mvStoreMutRef.isClosed());
+    }
+
+    #[test]
+    fn test_compact() {
+        let tempStore = TempStore::new("test_compact");
+
+        {
+            let mvStore = tempStore.openWith(&[("autoCommitDelay", 0)]);
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+            mvStoreMutRef.setRetentionTime(0);
+            mvStoreMutRef.setVersionsToKeep(0);
+
+            let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+            for round in 0..5 {
+                for a in 0..200 {
+                    get_ref_mut!(data).put(string(&format!("k{}_{}", round, a)), string(&format!("v{}", a))).unwrap();
+                }
+                mvStoreMutRef.commit().unwrap();
+            }
+
+            // 大部分数据改写,旧chunk只剩少量存活的page
+            for round in 0..5 {
+                for a in 0..190 {
+                    get_ref_mut!(data).put(string(&format!("k{}_{}", round, a)), string(&format!("w{}", a))).unwrap();
+                }
+            }
+            mvStoreMutRef.commit().unwrap();
+
+            let chunkCount = mvStoreMutRef.getChunks().unwrap().len();
+
+            assert!(mvStoreMutRef.compact(80, 16 * 1024 * 1024).unwrap());
+            for _ in 0..5 {
+                mvStoreMutRef.compact(80, 16 * 1024 * 1024).unwrap();
+            }
+
+            assert!(mvStoreMutRef.getChunks().unwrap().len() < chunkCount);
+            assert_eq!(get_ref!(data).size_as_long(), 1000);
+            mvStoreMutRef.close().unwrap();
+        }
+
+        let mvStore = tempStore.open();
+        let data = get_ref_mut!(mvStore).openMap("data", &MVMapBuilder::new()).unwrap();
+        assert_eq!(get_ref!(data).size_as_long(), 1000);
+        assert_eq!(get_ref!(data).get(&string("k3_195")).castAsStringRef(), "v195");
+        assert_eq!(get_ref!(data).get(&string("k3_5")).castAsStringRef(), "w5");
+        get_ref_mut!(mvStore).close().unwrap();
+    }
+
+    #[test]
+    fn test_compress() {
+        let mut fileSizes = Vec::new();
+
+        // 0不压缩,1是LZF,2是deflate
+        for compressionLevel in 0..3 {
+            let tempStore = TempStore::new(&format!("test_compress_{}", compressionLevel));
+            let value = "Hello World ".repeat(20);
+
+            {
+                let mut mvStoreBuilder = tempStore.builder();
+                match compressionLevel {
+                    1 => mvStoreBuilder.compress(),
+                    2 => mvStoreBuilder.compressHigh(),
+                    _ => {}
+                }
+                let mvStore = mvStoreBuilder.open().unwrap();
+                let mvStoreMutRef = get_ref_mut!(mvStore);
+                assert_eq!(mvStoreMutRef.getCompressionLevel(), compressionLevel);
+
+                let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+                for a in 0..500 {
+                    get_ref_mut!(data).put(string(&format!("k{}", a)), string(&format!("{}{}", value, a))).unwrap();
+                }
+                mvStoreMutRef.commit().unwrap();
+                mvStoreMutRef.close().unwrap();
+            }
+            fileSizes.push(tempStore.getFileSize());
+
+            // 重新打开时不需要指定压缩,按page上的标记解压
+            let mvStore = tempStore.open();
+            let data = get_ref_mut!(mvStore).openMap("data", &MVMapBuilder::new()).unwrap();
+            assert_eq!(get_ref!(data).size(), 500);
+            for a in 0..500 {
+                assert_eq!(get_ref!(data).get(&string(&format!("k{}", a))).castAsStringRef(), &format!("{}{}", value, a));
+            }
+            get_ref_mut!(mvStore).close().unwrap();
+        }
+
+        assert!(fileSizes[1] < fileSizes[0]);
+        assert!(fileSizes[2] < fileSizes[0]);
+    }
+
+    #[test]
+    fn test_in_memory() {
+        let mvStore = MVStoreBuilder::new().open().unwrap();
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+        assert!(mvStoreMutRef.getFileStore().is_none());
+
+        let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+        for a in 0..1000 {
+            get_ref_mut!(data).put(string(&format!("k{}", a)), string(&format!("v{}", a))).unwrap();
+        }
+
+        // commit只是递增版本
+        let version = mvStoreMutRef.getCurrentVersion();
+        assert_eq!(mvStoreMutRef.commit().unwrap(), version + 1);
+        assert!(!mvStoreMutRef.hasUnsavedChanges());
+        assert_eq!(mvStoreMutRef.commit().unwrap(), version + 1);
+
+        for a in 0..500 {
+            get_ref_mut!(data).remove(string(&format!("k{}", a))).unwrap();
+        }
+        get_ref_mut!(data).put(string("k999"), string("changed")).unwrap();
+        assert_eq!(get_ref!(data).size_as_long(), 500);
+
+        let old = get_ref!(data).openVersion(version).unwrap();
+        assert_eq!(get_ref!(old).size_as_long(), 1000);
+        assert_eq!(get_ref!(old).get(&string("k999")).castAsStringRef(), "v999");
+        drop(old);
+
+        mvStoreMutRef.commit().unwrap();
+        mvStoreMutRef.rollbackTo(version + 1).unwrap();
+        assert_eq!(get_ref!(data).size_as_long(), 1000);
+        assert_eq!(get_ref!(data).get(&string("k999")).castAsStringRef(), "v999");
+
+        let other = mvStoreMutRef.openMap("other", &MVMapBuilder::new()).unwrap();
+        get_ref_mut!(other).put(string("a"), string("b")).unwrap();
+        mvStoreMutRef.renameMap(&other, "renamed").unwrap();
+        assert!(mvStoreMutRef.hasMap("renamed"));
+        mvStoreMutRef.removeMap(&other).unwrap();
+        assert!(!mvStoreMutRef.hasMap("renamed"));
+        mvStoreMutRef.commit().unwrap();
+
+        mvStoreMutRef.rollbackTo(0).unwrap();
+        let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+        assert_eq!(get_ref!(data).size_as_long(), 0);
+
+        mvStoreMutRef.close().unwrap();
+    }
+
+    #[test]
+    fn test_cache_concurrency() {
+        let tempStore = TempStore::new("test_cache_concurrency");
+
+        let mut mvStoreBuilder = tempStore.builder();
+        mvStoreBuilder.cacheConcurrency(4);
+        let mvStore = mvStoreBuilder.open().unwrap();
+        assert_eq!(get_ref!(mvStore).pageCache.as_ref().unwrap().getSegmentCount(), 4);
+        get_ref_mut!(mvStore).close().unwrap();
+
+        // 段的数量必须是2的幂
+        let mut mvStoreBuilder = tempStore.builder();
+        mvStoreBuilder.cacheConcurrency(3);
+        assert!(mvStoreBuilder.open().is_err());
+    }
+}
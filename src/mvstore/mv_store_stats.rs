@@ -0,0 +1,119 @@
+use crate::h2_rust_common::{Integer, Long};
+
+/// A point-in-time snapshot of the MVStore statistics, see MVStore::stats().
+///
+/// 内存模式下没有fileStore,io计数和fillRate都是0
+#[derive(Default, Clone, Debug)]
+pub struct MVStoreStats {
+    /// The number of read operations on the file.
+    pub readCount: Long,
+
+    /// The number of bytes read from the file.
+    pub readBytes: Long,
+
+    /// The number of write operations on the file.
+    pub writeCount: Long,
+
+    /// The number of bytes written to the file.
+    pub writeBytes: Long,
+
+    /// The number of chunks currently known to the store.
+    pub chunkCount: Integer,
+
+    /// The fill rate of the file in percent (used blocks / all blocks).
+    pub fillRate: Integer,
+
+    /// The fill rate of the chunks in percent (live data / all data in chunks).
+    pub chunksFillRate: Integer,
+
+    /// The number of page cache hits.
+    pub cacheHits: Long,
+
+    /// The number of page cache misses.
+    pub cacheMisses: Long,
+
+    /// The number of pages evicted from the page cache.
+    pub cacheEvictions: Long,
+
+    /// The memory used by the page cache.
+    pub cacheUsedMemory: Long,
+
+    /// The maximum memory of the page cache.
+    pub cacheMaxMemory: Long,
+
+    /// The estimated memory used by the changes that are not yet stored.
+    pub unsavedMemory: Integer,
+
+    /// The current version of the store.
+    pub currentVersion: Long,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{get_ref, get_ref_mut};
+    use crate::mvstore::mv_map::MVMapBuilder;
+    use crate::mvstore::mv_store::MVStoreBuilder;
+    use crate::mvstore::test_utils::{string, TempStore};
+
+    #[test]
+    fn test_stats() {
+        let tempStore = TempStore::new("test_stats");
+
+        let value = "v".repeat(200);
+        {
+            let mvStore = tempStore.open();
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+            let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+            for a in 0..20000 {
+                get_ref_mut!(data).put(string(&format!("k{:05}", a)), string(&value)).unwrap();
+            }
+
+            let unsaved = mvStoreMutRef.stats();
+            assert!(unsaved.unsavedMemory > 0);
+            assert_eq!(unsaved.currentVersion, mvStoreMutRef.getCurrentVersion());
+
+            let version = mvStoreMutRef.commit().unwrap();
+            let stats = mvStoreMutRef.stats();
+            assert!(stats.unsavedMemory < unsaved.unsavedMemory);
+            assert_eq!(stats.currentVersion, version);
+            assert!(stats.writeCount > 0);
+            assert!(stats.writeBytes > 20000 * 200);
+            assert!(stats.chunkCount > 0);
+            assert!(stats.fillRate > 0 && stats.fillRate <= 100);
+            assert!(stats.chunksFillRate > 0 && stats.chunksFillRate <= 100);
+            mvStoreMutRef.close().unwrap();
+        }
+
+        // 最小的1MB缓存放不下全部page,读一遍会有缓存淘汰
+        let mvStore = tempStore.openWith(&[("cacheSize", 1)]);
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+        let before = mvStoreMutRef.stats();
+
+        let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+        for a in 0..20000 {
+            assert_eq!(get_ref!(data).get(&string(&format!("k{:05}", a))).castAsStringRef(), &value);
+        }
+        let stats = mvStoreMutRef.stats();
+        assert!(stats.readCount > before.readCount);
+        assert!(stats.readBytes > before.readBytes + 20000 * 200);
+        assert!(stats.cacheMisses > before.cacheMisses);
+        assert!(stats.cacheHits > before.cacheHits);
+        assert!(stats.cacheEvictions > 0);
+        assert!(stats.cacheUsedMemory <= stats.cacheMaxMemory);
+        assert_eq!(stats.cacheMaxMemory, 1024 * 1024);
+        assert_eq!(stats.writeCount, before.writeCount);
+        mvStoreMutRef.close().unwrap();
+
+        // 内存模式没有io计数
+        let mvStore = MVStoreBuilder::new().open().unwrap();
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+        let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+        get_ref_mut!(data).put(string("a"), string("b")).unwrap();
+        let version = mvStoreMutRef.commit().unwrap();
+        let stats = mvStoreMutRef.stats();
+        assert_eq!(stats.currentVersion, version);
+        assert_eq!(stats.readCount + stats.writeCount + stats.cacheHits + stats.cacheMisses, 0);
+        assert_eq!(stats.chunkCount, 0);
+        mvStoreMutRef.close().unwrap();
+    }
+}
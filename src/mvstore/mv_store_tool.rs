@@ -1,8 +1,411 @@
 use anyhow::Result;
+use std::collections::BTreeMap;
+use std::io::Write;
 use std::ops::Add;
+use std::sync::atomic::Ordering;
 use crate::engine::constant;
+use crate::h2_rust_common::{Integer, Long};
+use crate::h2_rust_common::h2_rust_type::H2RustType;
+use crate::h2_rust_common::h2_rust_cell::SharedPtr;
+use crate::mvstore::{chunk, data_utils, mv_store};
+use crate::mvstore::chunk::Chunk;
+use crate::mvstore::file_store::FileStore;
+use crate::mvstore::mv_map::MVMapBuilder;
+use crate::mvstore::mv_store::{MVStore, MVStoreBuilder};
 use crate::store::fs::file_utils;
+use crate::{get_ref, get_ref_mut};
 
+/// -recover时目标文件的后缀
+pub const SUFFIX_RECOVERED_FILE: &str = ".recovered";
+
+/// Runs this tool. Options are case sensitive. Supported options are:
+///
+/// `-dump <fileName> [-details]`: Dump the contents of the file<br>
+/// `-info <fileName>`: Get summary information about a file<br>
+/// `-compact <fileName>`: Compact a store<br>
+/// `-compress <fileName>`: Compact a store with compression enabled<br>
+/// `-recover <fileName>`: Salvage the readable data of a corrupted store into `<fileName>.recovered`
+pub fn main(args: &[String], writer: &mut dyn Write) -> Result<()> {
+    let mut a = 0;
+    while a + 1 < args.len() {
+        let file_name = &args[a + 1];
+        match args[a].as_str() {
+            "-dump" => {
+                let details = args.get(a + 2).is_some_and(|arg| arg == "-details");
+                dump(file_name, details, writer)?;
+                if details {
+                    a += 1;
+                }
+            }
+            "-info" => info(file_name, writer)?,
+            "-compact" => compact(file_name, false)?,
+            "-compress" => compact(file_name, true)?,
+            "-recover" => recover(file_name, &format!("{}{}", file_name, SUFFIX_RECOVERED_FILE), writer)?,
+            option => {
+                writeln!(writer, "Unsupported option: {}", option)?;
+                writeln!(writer, "Usage: -dump <fileName> [-details] | -info <fileName> | -compact <fileName> | -compress <fileName> | -recover <fileName>")?;
+                return Ok(());
+            }
+        }
+        a += 2;
+    }
+
+    Ok(())
+}
+
+/// Read the contents of the file and write them to system out.
+///
+/// 逐个block扫描文件,打印store header,每个chunk的header,page(map id,key数量,类型,长度),footer以及校验的结果,
+/// details为false时不打印单个page
+pub fn dump(file_name: &str, details: bool, writer: &mut dyn Write) -> Result<()> {
+    let mut file_store = FileStore::default();
+    file_store.open(file_name, true, None)?;
+    let result = dump_file_store(&mut file_store, details, writer);
+    file_store.close();
+    result
+}
+
+fn dump_file_store(file_store: &mut FileStore, details: bool, writer: &mut dyn Write) -> Result<()> {
+    let block_size = mv_store::BLOCK_SIZE as Long;
+    let file_size = file_store.size();
+    let len = format!("{:x}", file_size).len();
+
+    writeln!(writer, "File {}, {} bytes, {} MB", file_store.getFileName(), file_size, file_size / 1024 / 1024)?;
+
+    let mut map_sizes_total: BTreeMap<Integer, Long> = BTreeMap::new();
+    let mut page_size_total: Long = 0;
+    let mut page_count: Long = 0;
+
+    let mut pos: Long = 0;
+    while pos + block_size <= file_size {
+        let mut block = file_store.readFully(pos as usize, block_size as usize)?;
+        let header_type = block.getU8();
+
+        // 前2个block是store header,其中的key是无序的,不一定以"H:"开头
+        if pos < 2 * block_size {
+            block.setPosition(0);
+            let bytes = block.extract();
+            let end = bytes.iter().position(|b| *b == b'\n').unwrap_or(bytes.len());
+            let header: String = bytes[0..end].iter().map(|b| *b as char).collect();
+            writeln!(writer, "{:0len$x} fileHeader {} ({})", pos, header.trim(), checksum_state(&bytes[0..end]), len = len)?;
+            pos += block_size;
+            continue;
+        }
+
+        if header_type != b'c' {
+            pos += block_size;
+            continue;
+        }
+
+        block.setPosition(0);
+        let chunk = match Chunk::readChunkHeader(&mut block, pos) {
+            Ok(chunk) => chunk,
+            Err(_) => {
+                // 恰好以'c'开头的page数据
+                pos += block_size;
+                continue;
+            }
+        };
+        let chunk = get_ref!(chunk);
+        if chunk.blockCount <= 0 {
+            pos += block_size;
+            continue;
+        }
+
+        writeln!(writer)?;
+        writeln!(writer, "{:0len$x} chunkHeader {}", pos, chunk.asString(), len = len)?;
+
+        let length = Long::min(chunk.blockCount as Long * block_size, file_size - pos) as usize;
+        let mut chunk_buffer = file_store.readFully(pos as usize, length)?;
+        let footer_pos = length - chunk::FOOTER_LENGTH as usize;
+        let pages_end = if chunk.tocPos > 0 { chunk.tocPos as usize } else { footer_pos };
+
+        let mut p = block.getPosition();
+        pos += length as Long;
+        page_count += chunk.pageCount as Long;
+
+        let mut map_sizes: BTreeMap<Integer, Long> = BTreeMap::new();
+        let mut page_size_sum: Long = 0;
+        let mut remaining = chunk.pageCount;
+        while remaining > 0 {
+            if p + 4 > pages_end {
+                writeln!(writer, "ERROR illegal position {}", p)?;
+                break;
+            }
+
+            chunk_buffer.setPosition(p);
+            let page_size = chunk_buffer.getI32();
+            if page_size < 4 || p + page_size as usize > pages_end {
+                writeln!(writer, "ERROR illegal page length {} at {}", page_size, p)?;
+                break;
+            }
+
+            let check = chunk_buffer.getI16();
+            let page_no = data_utils::readVarInt(&mut chunk_buffer);
+            let map_id = data_utils::readVarInt(&mut chunk_buffer);
+            let entries = data_utils::readVarInt(&mut chunk_buffer);
+            let page_type = chunk_buffer.getI8() as Integer;
+
+            let check_test = data_utils::getCheckValue(chunk.id)
+                ^ data_utils::getCheckValue(p as Integer)
+                ^ data_utils::getCheckValue(page_size);
+
+            let node = (page_type & data_utils::PAGE_TYPE_NODE) != 0;
+            let compressed = (page_type & data_utils::PAGE_COMPRESSED) != 0;
+            if details {
+                writeln!(writer, "+{:0len$x} {}{}, page {}, map {:x}, {} entries, {} bytes{}",
+                         p,
+                         if node { "node" } else { "leaf" },
+                         if compressed { " compressed" } else { "" },
+                         page_no,
+                         map_id,
+                         if node { entries + 1 } else { entries },
+                         page_size,
+                         if check == check_test { "" } else { ", ERROR check value mismatch" },
+                         len = len)?;
+            } else if check != check_test {
+                writeln!(writer, "+{:0len$x} ERROR check value mismatch, expected {}, got {}", p, check_test, check, len = len)?;
+            }
+
+            p += page_size as usize;
+            *map_sizes.entry(map_id).or_insert(0) += page_size as Long;
+            *map_sizes_total.entry(map_id).or_insert(0) += page_size as Long;
+            page_size_sum += page_size as Long;
+            page_size_total += page_size as Long;
+            remaining -= 1;
+        }
+
+        let page_size_sum = Long::max(1, page_size_sum);
+        for (map_id, size) in &map_sizes {
+            writeln!(writer, "map {:x}: {} bytes, {}%", map_id, size, 100 * size / page_size_sum)?;
+        }
+
+        let footer = chunk_buffer.extractWithPosLen(footer_pos, chunk::FOOTER_LENGTH as usize);
+        let footer_string: String = footer.iter().map(|b| *b as char).collect();
+        writeln!(writer, "+{:0len$x} chunkFooter {} ({})", footer_pos, footer_string.trim(), checksum_state(footer), len = len)?;
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "{:0len$x} eof", file_size, len = len)?;
+    writeln!(writer)?;
+
+    let page_count = Long::max(1, page_count);
+    writeln!(writer, "page size total: {} bytes, page count: {}, average page size: {} bytes", page_size_total, page_count, page_size_total / page_count)?;
+    let page_size_total = Long::max(1, page_size_total);
+    for (map_id, size) in &map_sizes_total {
+        writeln!(writer, "map {:x}: {} bytes, {}%", map_id, size, 100 * size / page_size_total)?;
+    }
+
+    Ok(())
+}
+
+fn checksum_state(bytes: &[u8]) -> &'static str {
+    match data_utils::parseChecksummedMap(bytes) {
+        Ok(Some(_)) => "checksum ok",
+        _ => "ERROR checksum mismatch"
+    }
+}
+
+/// Read the summary information of the file and write them to system out.
+///
+/// 打印每个chunk的fill rate,每个map的entry数量,以及chunk中存活和已经废弃的空间
+pub fn info(file_name: &str, writer: &mut dyn Write) -> Result<()> {
+    let mut builder = MVStoreBuilder::new();
+    builder.fileName(file_name);
+    builder.readOnly();
+    let mv_store = builder.open()?;
+
+    let result = write_info(&mv_store, writer);
+    get_ref_mut!(mv_store).close()?;
+    result
+}
+
+fn write_info(mv_store: &SharedPtr<MVStore>, writer: &mut dyn Write) -> Result<()> {
+    let file_size = get_ref!(get_ref!(mv_store).getFileStore()).size();
+
+    let mut chunks = get_ref_mut!(mv_store).getChunks()?;
+    chunks.sort_by_key(|chunk| get_ref!(chunk).id);
+
+    let mut chunk_length: Long = 0;
+    let mut max_length: Long = 0;
+    let mut max_length_live: Long = 0;
+    let mut max_length_not_empty: Long = 0;
+    for chunk in &chunks {
+        let chunk = get_ref!(chunk);
+        chunk_length += chunk.blockCount as Long * mv_store::BLOCK_SIZE as Long;
+        max_length += chunk.maxLen;
+        max_length_live += chunk.maxLenLive;
+        if chunk.maxLenLive > 0 {
+            max_length_not_empty += chunk.maxLen;
+        }
+    }
+
+    writeln!(writer, "File length: {}", file_size)?;
+    writeln!(writer, "Chunk length: {}", chunk_length)?;
+    writeln!(writer, "Chunk count: {}", chunks.len())?;
+    writeln!(writer, "Used space: {}%", get_percent(chunk_length, file_size))?;
+    writeln!(writer, "Chunk fill rate: {}%", if max_length == 0 { 100 } else { get_percent(max_length_live, max_length) })?;
+    writeln!(writer, "Chunk fill rate excluding empty chunks: {}%",
+             if max_length_not_empty == 0 { 100 } else { get_percent(max_length_live, max_length_not_empty) })?;
+    writeln!(writer, "Live space: {} bytes, dead space: {} bytes", max_length_live, max_length - max_length_live)?;
+
+    for chunk in &chunks {
+        let chunk = get_ref!(chunk);
+        writeln!(writer, "  Chunk {} at {}, {} blocks, version {}, {}/{} pages live, fill rate {}%",
+                 chunk.id,
+                 chunk.block.load(Ordering::Acquire),
+                 chunk.blockCount,
+                 chunk.version,
+                 chunk.pageCountLive,
+                 chunk.pageCount,
+                 get_percent(chunk.maxLenLive, chunk.maxLen))?;
+    }
+
+    let mut map_names: Vec<String> = get_ref!(mv_store).getMapNames()?.into_iter().collect();
+    map_names.sort();
+    for map_name in map_names {
+        let map = get_ref_mut!(mv_store).openMap(&map_name, &MVMapBuilder::new())?;
+        writeln!(writer, "Map {} (id {:x}): {} entries", map_name, get_ref!(map).getId(), get_ref!(map).size_as_long())?;
+    }
+
+    Ok(())
+}
+
+fn get_percent(value: Long, max: Long) -> Integer {
+    if value == 0 {
+        0
+    } else if value >= max {
+        100
+    } else {
+        (1 + 98 * value / max) as Integer
+    }
+}
+
+/// Salvage the readable data of a corrupted store into a new store and write a report of what was lost.
+///
+/// 源文件以恢复模式打开,使用最新的能完整读出layout的chunk,比它新的chunk整个丢弃。
+/// 每个map中校验失败的page连同下面的entry一起跳过,能读出来的entry都复制到target_file_name
+pub fn recover(file_name: &str, target_file_name: &str, writer: &mut dyn Write) -> Result<()> {
+    let mut source_builder = MVStoreBuilder::new();
+    source_builder.fileName(file_name);
+    source_builder.readOnly();
+    source_builder.recoveryMode();
+    let source = source_builder.open()?;
+
+    if file_utils::exist(target_file_name) {
+        file_utils::delete(target_file_name)?;
+    }
+
+    let mut target_builder = MVStoreBuilder::new();
+    target_builder.fileName(target_file_name);
+    target_builder.autoCommitDisabled();
+    let target = target_builder.open()?;
+
+    let result = recover_stores(&source, &target, writer);
+
+    get_ref_mut!(target).close()?;
+    get_ref_mut!(source).close()?;
+
+    result
+}
+
+fn recover_stores(source: &SharedPtr<MVStore>, target: &SharedPtr<MVStore>, writer: &mut dyn Write) -> Result<()> {
+    let last_chunk = get_ref!(source).getLastChunk();
+    if last_chunk.is_none() {
+        writeln!(writer, "No readable chunk found, nothing recovered")?;
+        return Ok(());
+    }
+    let last_version = get_ref!(last_chunk).version;
+    writeln!(writer, "Recovering from chunk {}, version {}", get_ref!(last_chunk).id, last_version)?;
+
+    // 比使用的chunk新的chunk中的修改全部丢失
+    let file_store = get_ref!(source).getFileStore();
+    let blocks_in_store = get_ref!(file_store).size() / mv_store::BLOCK_SIZE as Long;
+    for block in 2..blocks_in_store {
+        let position = block * mv_store::BLOCK_SIZE as Long;
+        let mut byte_buffer = get_ref_mut!(file_store).readFully(position as usize, chunk::MAX_HEADER_LENGTH as usize)?;
+        if byte_buffer.getU8() != b'c' {
+            continue;
+        }
+        byte_buffer.setPosition(0);
+        if let Ok(chunk) = Chunk::readChunkHeader(&mut byte_buffer, position) {
+            let chunk = get_ref!(chunk);
+            if chunk.version > last_version && chunk.block.load(Ordering::Acquire) == block {
+                writeln!(writer, "Chunk {} at {}, version {} skipped, it is newer than the last readable chunk",
+                         chunk.id, block, chunk.version)?;
+            }
+        }
+    }
+
+    // 不使用getMapNames,meta中可能有读不出来的page
+    let mut map_names = Vec::new();
+    let source_meta = get_ref!(source).getMetaMap()?;
+    let target_meta = get_ref!(target).getMetaMap()?;
+    let mut meta_entries = Vec::new();
+    get_ref!(source_meta).salvage(source_meta.clone(),
+                                  &mut |key, value| {
+                                      let key_str = key.castAsStringRef();
+                                      if let Some(map_name) = key_str.strip_prefix(data_utils::META_NAME) {
+                                          map_names.push(map_name.to_string());
+                                      } else if !key_str.starts_with(data_utils::META_MAP) {
+                                          meta_entries.push((key.clone(), value));
+                                      }
+                                      Ok(())
+                                  },
+                                  &mut |position, count, e| {
+                                      let _ = writeln!(writer, "Meta: page {:x} in chunk {} with {} entries lost, {}",
+                                                       position, data_utils::getPageChunkId(position), count, e);
+                                  })?;
+    for (key, value) in meta_entries {
+        get_ref_mut!(target_meta).put(key, value)?;
+    }
+
+    let mut entries_recovered: Long = 0;
+    let mut entries_lost: Long = 0;
+    let mut maps_lost: Integer = 0;
+
+    map_names.sort();
+    for map_name in map_names {
+        let source_map = match get_ref_mut!(source).openMap(&map_name, &MVMapBuilder::new()) {
+            Ok(source_map) => source_map,
+            Err(e) => {
+                writeln!(writer, "Map {}: lost, {}", map_name, e)?;
+                maps_lost += 1;
+                continue;
+            }
+        };
+
+        let mut target_builder = MVMapBuilder::new();
+        target_builder.keyType(get_ref!(source_map).getKeyType());
+        target_builder.valueType(get_ref!(source_map).getValueType());
+        let target_map = get_ref_mut!(target).openMap(&map_name, &target_builder)?;
+
+        let mut recovered: Long = 0;
+        let mut lost: Long = 0;
+        get_ref!(source_map).salvage(source_map.clone(),
+                                     &mut |key, value| {
+                                         get_ref_mut!(target_map).put(key, value)?;
+                                         recovered += 1;
+                                         Ok(())
+                                     },
+                                     &mut |position, count, e| {
+                                         let _ = writeln!(writer, "Map {}: page {:x} in chunk {} with {} entries lost, {}",
+                                                          map_name, position, data_utils::getPageChunkId(position), count, e);
+                                         lost += count;
+                                     })?;
+        writeln!(writer, "Map {}: {} entries recovered, {} entries lost", map_name, recovered, lost)?;
+
+        entries_recovered += recovered;
+        entries_lost += lost;
+        get_ref_mut!(target).commit()?;
+    }
+
+    writeln!(writer, "Recovered {} entries, lost {} entries and {} maps", entries_recovered, entries_lost, maps_lost)?;
+
+    Ok(())
+}
+
+/// compact时中途崩溃留下的临时文件,打开之前收尾
 pub fn compact_clean_up(file_path: &str) -> Result<()> {
     let temp_path = file_path.to_string().add(constant::SUFFIX_MV_STORE_TEMP_FILE);
     if file_utils::exist(&temp_path) {
@@ -19,4 +422,286 @@ pub fn compact_clean_up(file_path: &str) -> Result<()> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Compress the store by creating a new file and copying the live pages there.
+/// Temporarily, a file with the suffix ".tempFile" is created. This file is then renamed, replacing the original file,
+/// if possible. If not, the new file is renamed to ".newFile", then the old file is removed,
+/// and the new file is renamed. This process is then crash safe, compact_clean_up finishes it on the next open.
+pub fn compact(file_path: &str, compress: bool) -> Result<()> {
+    let temp_path = file_path.to_string().add(constant::SUFFIX_MV_STORE_TEMP_FILE);
+    if file_utils::exist(&temp_path) {
+        file_utils::delete(&temp_path)?;
+    }
+
+    compact_to(file_path, &temp_path, compress)?;
+
+    if file_utils::move1(&temp_path, file_path).is_err() {
+        let new_path = file_path.to_string().add(constant::SUFFIX_MV_STORE_NEW_FILE);
+        if file_utils::exist(&new_path) {
+            file_utils::delete(&new_path)?;
+        }
+        file_utils::move1(&temp_path, &new_path)?;
+        file_utils::delete(file_path)?;
+        file_utils::move1(&new_path, file_path)?;
+    }
+
+    Ok(())
+}
+
+/// Copy all live pages from the source store to the target store.
+pub fn compact_to(source_file_path: &str, target_file_path: &str, compress: bool) -> Result<()> {
+    let mut source_builder = MVStoreBuilder::new();
+    source_builder.fileName(source_file_path);
+    source_builder.readOnly();
+    let source = source_builder.open()?;
+
+    if file_utils::exist(target_file_path) {
+        file_utils::delete(target_file_path)?;
+    }
+
+    let mut target_builder = MVStoreBuilder::new();
+    target_builder.fileName(target_file_path);
+    target_builder.autoCommitDisabled();
+    if compress {
+        target_builder.compress();
+    }
+    let target = target_builder.open()?;
+
+    let result = compact_stores(&source, &target);
+
+    get_ref_mut!(target).close()?;
+    get_ref_mut!(source).close()?;
+
+    result
+}
+
+/// Copy all maps and the user metadata from the source store to the target store.
+pub fn compact_stores(source: &SharedPtr<MVStore>, target: &SharedPtr<MVStore>) -> Result<()> {
+    let source_meta = get_ref!(source).getMetaMap()?;
+    let target_meta = get_ref!(target).getMetaMap()?;
+
+    // map.<id>和name.<name>在目标store中打开map时重新生成
+    let mut cursor = get_ref!(source_meta).cursor(&H2RustType::Null);
+    while let Some(key) = cursor.next() {
+        let key_str = key.castAsStringRef();
+        if !key_str.starts_with(data_utils::META_MAP) && !key_str.starts_with(data_utils::META_NAME) {
+            get_ref_mut!(target_meta).put(key.clone(), cursor.getValue())?;
+        }
+    }
+
+    let mut map_names: Vec<String> = get_ref!(source).getMapNames()?.into_iter().collect();
+    map_names.sort();
+    for map_name in map_names {
+        let source_map = get_ref_mut!(source).openMap(&map_name, &MVMapBuilder::new())?;
+
+        // 目标map沿用源map的key/value类型
+        let mut target_builder = MVMapBuilder::new();
+        target_builder.keyType(get_ref!(source_map).getKeyType());
+        target_builder.valueType(get_ref!(source_map).getValueType());
+        let target_map = get_ref_mut!(target).openMap(&map_name, &target_builder)?;
+
+        let mut cursor = get_ref!(source_map).cursor(&H2RustType::Null);
+        while let Some(key) = cursor.next() {
+            get_ref_mut!(target_map).put(key, cursor.getValue())?;
+        }
+
+        get_ref_mut!(target).commit()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::constant;
+    use crate::mvstore::mv_map::MVMapBuilder;
+    use crate::mvstore::mv_store_tool;
+    use crate::mvstore::test_utils::{string, TempStore};
+    use crate::store::fs::file_utils;
+    use crate::{get_ref, get_ref_mut};
+
+    #[test]
+    fn test_compact() {
+        let tempStore = TempStore::new("test_tool_compact");
+        let fileName = tempStore.getFileName();
+
+        {
+            let mut mvStoreBuilder = tempStore.builder();
+            mvStoreBuilder.autoCommitDisabled();
+            let mvStore = mvStoreBuilder.open().unwrap();
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+
+            let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+            for round in 0..10 {
+                for a in 0..100 {
+                    get_ref_mut!(data).put(string(&format!("k{}", a)), string(&format!("v{}_{}", round, a))).unwrap();
+                }
+                mvStoreMutRef.commit().unwrap();
+            }
+            let other = mvStoreMutRef.openMap("other", &MVMapBuilder::new()).unwrap();
+            get_ref_mut!(other).put(string("a"), string("b")).unwrap();
+            get_ref_mut!(mvStoreMutRef.getMetaMap().unwrap()).put(string("setting.x"), string("1")).unwrap();
+            mvStoreMutRef.close().unwrap();
+        }
+
+        let sizeBefore = tempStore.getFileSize();
+        mv_store_tool::compact(fileName, false).unwrap();
+        assert!(tempStore.getFileSize() < sizeBefore);
+        assert!(!file_utils::exist(format!("{}{}", fileName, constant::SUFFIX_MV_STORE_TEMP_FILE)));
+
+        let mvStore = tempStore.open();
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+
+        let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+        assert_eq!(get_ref!(data).size_as_long(), 100);
+        assert_eq!(get_ref!(data).get(&string("k42")).castAsStringRef(), "v9_42");
+        let other = mvStoreMutRef.openMap("other", &MVMapBuilder::new()).unwrap();
+        assert_eq!(get_ref!(other).get(&string("a")).castAsStringRef(), "b");
+        assert_eq!(get_ref!(mvStoreMutRef.getMetaMap().unwrap()).get(&string("setting.x")).castAsStringRef(), "1");
+
+        mvStoreMutRef.close().unwrap();
+    }
+
+    #[test]
+    fn test_dump_and_info() {
+        let tempStore = TempStore::new("test_tool_dump");
+        let fileName = tempStore.getFileName();
+
+        {
+            let mut mvStoreBuilder = tempStore.builder();
+            mvStoreBuilder.autoCommitDisabled();
+            let mvStore = mvStoreBuilder.open().unwrap();
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+
+            let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+            for round in 0..3 {
+                for a in 0..100 {
+                    get_ref_mut!(data).put(string(&format!("k{}", a)), string(&format!("v{}_{}", round, a))).unwrap();
+                }
+                mvStoreMutRef.commit().unwrap();
+            }
+            mvStoreMutRef.close().unwrap();
+        }
+
+        let mut out = Vec::new();
+        mv_store_tool::dump(fileName, false, &mut out).unwrap();
+        let dump = String::from_utf8(out).unwrap();
+        assert!(dump.contains("fileHeader"));
+        assert!(dump.contains("chunkHeader chunk:1,"));
+        assert!(dump.contains("chunkFooter chunk:1,"));
+        assert!(dump.contains("eof"));
+        assert!(!dump.contains("ERROR"));
+        assert!(!dump.contains(" leaf"));
+
+        let mut out = Vec::new();
+        mv_store_tool::dump(fileName, true, &mut out).unwrap();
+        let details = String::from_utf8(out).unwrap();
+        assert!(details.contains(" node, page 0, map"));
+        assert!(details.contains(" leaf, page 1, map"));
+
+        let mut out = Vec::new();
+        mv_store_tool::info(fileName, &mut out).unwrap();
+        let info = String::from_utf8(out).unwrap();
+        assert!(info.contains("Chunk fill rate:"));
+        assert!(info.contains("dead space:"));
+        assert!(info.contains("  Chunk 1 at 2,"));
+        assert!(info.contains("  Chunk 3 at 4,"));
+        assert!(info.contains("Map data (id 2): 100 entries"));
+
+        // 破坏第一个chunk的footer
+        let mut bytes = std::fs::read(fileName).unwrap();
+        let footerPos = dump.lines()
+            .find(|line| line.contains("chunkFooter chunk:1,")).unwrap()
+            .trim_start_matches('+').split(' ').next().unwrap();
+        let pos = 2 * 4096 + usize::from_str_radix(footerPos, 16).unwrap();
+        bytes[pos] = b'x';
+        std::fs::write(fileName, bytes).unwrap();
+
+        let mut out = Vec::new();
+        mv_store_tool::main(&["-dump".to_string(), fileName.to_string()], &mut out).unwrap();
+        let dump = String::from_utf8(out).unwrap();
+        assert!(dump.contains("ERROR checksum mismatch"));
+    }
+
+    #[test]
+    fn test_recover() {
+        let tempStore = TempStore::new("test_tool_recover");
+        let target = TempStore::new("test_tool_recover_target");
+
+        {
+            let mut mvStoreBuilder = tempStore.builder();
+            mvStoreBuilder.autoCommitDisabled();
+            let mvStore = mvStoreBuilder.open().unwrap();
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+
+            // chunk 1
+            let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+            for a in 0..1000 {
+                get_ref_mut!(data).put(string(&format!("k{}", a)), string(&format!("v0_{}", a))).unwrap();
+            }
+            let other = mvStoreMutRef.openMap("other", &MVMapBuilder::new()).unwrap();
+            get_ref_mut!(other).put(string("a"), string("b")).unwrap();
+            mvStoreMutRef.commit().unwrap();
+
+            // chunk 2只改写第1个leaf
+            for a in 0..5 {
+                get_ref_mut!(data).put(string(&format!("k{}", a)), string(&format!("v1_{}", a))).unwrap();
+            }
+            mvStoreMutRef.commit().unwrap();
+
+            // chunk 3
+            get_ref_mut!(data).put(string("late"), string("x")).unwrap();
+            mvStoreMutRef.commit().unwrap();
+            mvStoreMutRef.close().unwrap();
+        }
+
+        let mut out = Vec::new();
+        mv_store_tool::dump(tempStore.getFileName(), true, &mut out).unwrap();
+        let dump = String::from_utf8(out).unwrap();
+
+        // chunk 1中map data的最后1个leaf,chunk 2没有改写它
+        let mut chunkPos = 0;
+        let mut leafPos = None;
+        let mut footerPos = None;
+        for line in dump.lines() {
+            let pos = line.trim_start_matches('+').split(' ').next().unwrap();
+            if line.contains("chunkHeader chunk:") {
+                chunkPos = usize::from_str_radix(pos, 16).unwrap();
+            } else if line.contains(" leaf, page") && line.contains(", map 2,") && chunkPos == 2 * 4096 {
+                leafPos = Some(chunkPos + usize::from_str_radix(pos, 16).unwrap());
+            } else if line.contains("chunkFooter chunk:3,") {
+                footerPos = Some(chunkPos + usize::from_str_radix(pos, 16).unwrap());
+            }
+        }
+
+        // 破坏page的check value以及chunk 3的footer
+        let mut bytes = std::fs::read(tempStore.getFileName()).unwrap();
+        bytes[leafPos.unwrap() + 4] ^= 0xff;
+        bytes[footerPos.unwrap()] = b'x';
+        std::fs::write(tempStore.getFileName(), bytes).unwrap();
+
+        let mut out = Vec::new();
+        mv_store_tool::recover(tempStore.getFileName(), target.getFileName(), &mut out).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.contains("Recovering from chunk 2, version 2"));
+        assert!(report.contains("Chunk 3 at "));
+        assert!(report.contains("skipped, it is newer than the last readable chunk"));
+        assert!(report.lines().any(|line| line.starts_with("Map data: page ") && line.contains(" in chunk 1 with ")));
+        assert!(report.contains("Map other: 1 entries recovered, 0 entries lost"));
+        assert!(!report.contains("Map data: 1000 entries recovered"));
+
+        let mvStore = target.open();
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+
+        let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+        let size = get_ref!(data).size_as_long();
+        assert!(size > 0 && size < 1000);
+        assert!(report.contains(&format!("Map data: {} entries recovered, {} entries lost", size, 1000 - size)));
+        assert_eq!(get_ref!(data).get(&string("k0")).castAsStringRef(), "v1_0");
+        assert!(get_ref!(data).get(&string("late")).isNull());
+        let other = mvStoreMutRef.openMap("other", &MVMapBuilder::new()).unwrap();
+        assert_eq!(get_ref!(other).get(&string("a")).castAsStringRef(), "b");
+        mvStoreMutRef.close().unwrap();
+    }
+}
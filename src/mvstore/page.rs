@@ -4,18 +4,24 @@ use anyhow::Result;
 use std::ops::Deref;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicI64, Ordering};
+use usync::RwLock;
 use lazy_static::lazy_static;
 use crate::engine::constant;
-use crate::{get_ref, get_ref_mut, suffix_plus_plus, build_option_arc_h2RustCell, throw, db_error_template, load_atomic};
+use crate::{get_ref, get_ref_mut, suffix_plus_plus, build_option_arc_h2RustCell, throw, db_error_template, load_atomic, weak_get_ref, weak_get_ref_mut, h2_rust_cell_equals};
 use crate::api::error_code;
 use crate::h2_rust_common::h2_rust_cell::{H2RustCell, SharedPtr};
 use crate::h2_rust_common::h2_rust_type::H2RustType;
 use crate::h2_rust_common::h2_rust_type::H2RustType::Null;
 use crate::h2_rust_common::{Integer, Long, Short};
+use crate::h2_rust_common::byte_buffer;
 use crate::h2_rust_common::byte_buffer::ByteBuffer;
+use crate::db::store;
 use crate::message::db_error::DbError;
+use crate::mvstore::chunk::Chunk;
 use crate::mvstore::data_utils;
+use crate::mvstore::write_buffer::WriteBuffer;
 use crate::mvstore::mv_map::{MVMap};
+use crate::mvstore::cursor_pos::CursorPos;
 
 /// The estimated number of bytes used per child entry.
 const PAGE_MEMORY_CHILD: Integer = constant::MEMORY_POINTER + 16; //  16 = two longs
@@ -66,6 +72,12 @@ pub trait PageTrait {
     /// Get the total number of key-value pairs, including child pages.
     fn getTotalCount(&self) -> Long;
 
+    /// abstract Get the descendant counts for the given child.
+    fn getCounts(&self, index: Integer) -> Long;
+
+    /// abstract Get the position of the child.
+    fn getChildPagePos(&self, index: Integer) -> Long;
+
     /// 父类实现
     fn getPosition(&self) -> Long;
 
@@ -82,7 +94,7 @@ pub trait PageTrait {
     fn createValueStorage(&self, size: Integer) -> Vec<H2RustType>;
 
     /// abstract
-    fn readPayLoad(&mut self, byteBuffer: &mut ByteBuffer);
+    fn readPayLoad(&mut self, byteBuffer: &mut ByteBuffer) -> Result<()>;
 
     /// 父类实现
     fn is_persistent(&self) -> bool;
@@ -106,6 +118,83 @@ pub trait PageTrait {
 
     /// 父类实现
     fn isSaved(&self) -> bool;
+
+    /// 父类实现 Store the page and update the position.
+    ///
+    /// return the position of the buffer, where the serialized child page references (if any) begin
+    fn write(&mut self,
+             actual: SharedPtr<dyn PageTrait>,
+             chunk: &mut Chunk,
+             writeBuffer: &mut WriteBuffer,
+             toc: &mut Vec<Long>) -> Result<usize>;
+
+    /// abstract 写入子page的position以及(可选的)count
+    fn writeChildren(&self, writeBuffer: &mut WriteBuffer, withCounts: bool);
+
+    /// abstract 写入value
+    fn writeValues(&self, writeBuffer: &mut WriteBuffer) -> Result<()>;
+
+    /// abstract Store this page and all children that are changed, in reverse order, and update the position and the children.
+    fn writeUnsavedRecursive(&mut self,
+                             actual: SharedPtr<dyn PageTrait>,
+                             chunk: &mut Chunk,
+                             writeBuffer: &mut WriteBuffer,
+                             toc: &mut Vec<Long>) -> Result<()>;
+
+    /// abstract Unlink the children recursively after all data is written.
+    fn releaseSavedPages(&mut self);
+
+    /// 父类实现
+    fn getKey(&self, index: Integer) -> H2RustType;
+
+    /// abstract Create a copy of this page, 用于copy on write,新的page是未保存的
+    fn copy0(&self) -> SharedPtr<dyn PageTrait>;
+
+    /// abstract Split the page. This modifies the current page.
+    ///
+    /// at: the split index
+    /// return the page with the entries after the split index
+    fn split(&mut self, at: Integer) -> SharedPtr<dyn PageTrait>;
+
+    /// abstract Insert a key-value pair into this leaf.
+    fn insertLeaf(&mut self, index: Integer, key: H2RustType, value: H2RustType);
+
+    /// abstract Insert a child page into this node.
+    fn insertNode(&mut self, index: Integer, key: H2RustType, childPage: SharedPtr<dyn PageTrait>);
+
+    /// abstract Replace the value at an index in this page.
+    ///
+    /// return the old value
+    fn setValue(&mut self, index: Integer, value: H2RustType) -> H2RustType;
+
+    /// abstract Replace the child page.
+    fn setChild(&mut self, index: Integer, childPage: SharedPtr<dyn PageTrait>);
+
+    /// 父类实现 子类覆盖 Remove the key and value (or child) at the given index.
+    fn remove(&mut self, index: Integer);
+
+    /// 父类实现 子类覆盖
+    fn calculateMemory(&self) -> Integer;
+
+    /// 父类实现 子类覆盖
+    fn recalculateMemory(&mut self);
+
+    /// abstract Create CursorPos chain for the first key of this page subtree.
+    fn getPrependCursorPos(&self, actual: SharedPtr<dyn PageTrait>, cursorPos: SharedPtr<CursorPos>) -> SharedPtr<CursorPos>;
+
+    /// abstract Create CursorPos chain past the last key of this page subtree.
+    fn getAppendCursorPos(&self, actual: SharedPtr<dyn PageTrait>, cursorPos: SharedPtr<CursorPos>) -> SharedPtr<CursorPos>;
+
+    /// 父类实现 Make accounting changes (chunk occupancy) related to removal of this page.
+    ///
+    /// return amount (negative), by which unsaved memory should be adjusted,
+    /// if page was not saved yet, 0 otherwise
+    fn removePage(&self, version: Long) -> Integer;
+
+    /// abstract Remove this page and all child pages.
+    ///
+    /// return amount (negative), by which unsaved memory should be adjusted
+    fn removeAllRecursive(&self, version: Long) -> Integer;
 }
 
 pub type PageSharedPtr = Option<Arc<H2RustCell<Page>>>;
@@ -165,14 +254,52 @@ impl Page {
     }
 
 
-    fn recalculateMemory(&mut self) {
-        assert!(self.is_persistent());
-        self.memory = self.calculateMemory();
+    /// Create a new non-leaf page. The arrays are not cloned.
+    pub fn createNode(mvMap: SharedPtr<MVMap>,
+                      keys: Vec<H2RustType>,
+                      children: Vec<PageReferenceSharedPtr>,
+                      totalCount: Long,
+                      memory: Integer) -> SharedPtr<dyn PageTrait> {
+        let mut nonLeaf = NonLeaf::new4(mvMap, keys, children, totalCount);
+        nonLeaf.initMemoryCount(memory);
+        let page_trait_ref = Arc::new(H2RustCell::new(nonLeaf)) as Arc<H2RustCell<dyn PageTrait>>;
+        Some(page_trait_ref)
     }
 
-    fn calculateMemory(&self) -> Integer {
-        // todo mvMap.evaluateMemoryForKeys(keys, getKeyCount());
-        1
+    /// 复制page的共有部分,新的page是未保存的
+    fn copyPage(&self) -> SharedPtr<Page> {
+        let page = Page {
+            mvMap: self.mvMap.clone(),
+            memory: self.memory,
+            keys: self.keys.clone(),
+            pageNo: -1,
+            ..Default::default()
+        };
+
+        build_option_arc_h2RustCell!(page)
+    }
+
+    /// Split the current keys array into two arrays.
+    ///
+    /// return the second array, the current page keeps the first array
+    fn splitKeys(&mut self, aCount: Integer, bCount: Integer) -> Vec<H2RustType> {
+        let keyCount = self.getKeyCount();
+        assert!(aCount + bCount <= keyCount);
+
+        let bKeys = self.keys[(keyCount - bCount) as usize..].to_vec();
+        self.keys.truncate(aCount as usize);
+        bKeys
+    }
+
+    /// Insert a key into the key array
+    fn insertKey(&mut self, index: Integer, key: H2RustType) {
+        assert!(index <= self.getKeyCount());
+
+        if self.is_persistent() {
+            self.add_memory(constant::MEMORY_POINTER + get_ref!(self.mvMap).evaluateMemoryForKey(&key));
+        }
+
+        self.keys.insert(index as usize, key);
     }
 
     fn add_memory(&mut self, mem: Integer) {
@@ -255,6 +382,14 @@ impl PageTrait for Page {
         unimplemented!("abstract 需要由子类实现")
     }
 
+    fn getCounts(&self, index: Integer) -> Long {
+        unimplemented!("abstract 需要由子类实现")
+    }
+
+    fn getChildPagePos(&self, index: Integer) -> Long {
+        unimplemented!("abstract 需要由子类实现")
+    }
+
     fn getPosition(&self) -> Long {
         self.position.load(Ordering::Acquire)
     }
@@ -299,30 +434,50 @@ impl PageTrait for Page {
         let keyCount = data_utils::readVarInt(byteBuffer);
         self.keys = self.createKeyStorage(keyCount);
         let type7 = byteBuffer.getI8() as Integer;
-        if self.isLeaf() != ((type7 & 1) == data_utils::PAGE_TYPE_LEAF) {
-            throw!(db_error_template!(error_code::FILE_CORRUPTED_1, "file corrupted in chunk {}, expected node type {}, got {}", chunkId, if self.isLeaf() {"0"} else {"1"}, type7 ));
+        let isLeaf = get_ref!(actual).isLeaf();
+        if isLeaf != ((type7 & 1) == data_utils::PAGE_TYPE_LEAF) {
+            throw!(db_error_template!(error_code::FILE_CORRUPTED_1, "file corrupted in chunk {}, expected node type {}, got {}", chunkId, if isLeaf {"0"} else {"1"}, type7 ));
         }
 
         byteBuffer.setLimit(start + pageLength as usize);
 
 
-        if !self.isLeaf() {
+        if !isLeaf {
             // 需要由下边的实现子类来具体实现 抽象level里又涉及到子类的具体
             // 虚实的结和
-            get_ref_mut!(actual).readPayLoad(byteBuffer);
+            get_ref_mut!(actual).readPayLoad(byteBuffer)?;
         }
 
-        // todo rust略过压缩
+        let mut expanded;
+        let byteBuffer = if (type7 & data_utils::PAGE_COMPRESSED) != 0 {
+            let lenAdd = data_utils::readVarInt(byteBuffer) as usize;
+            let compLen = byteBuffer.getRemaining();
+            let l = compLen + lenAdd;
+            expanded = byte_buffer::allocate(l);
+
+            let mvStoreWeakPtr = get_ref!(self.mvMap).getMvStore();
+            if (type7 & data_utils::PAGE_COMPRESSED_HIGH) == data_utils::PAGE_COMPRESSED_HIGH {
+                weak_get_ref!(mvStoreWeakPtr).getCompressorHigh()
+                    .expand(byteBuffer.getInternalSlice(), byteBuffer.getPosition(), compLen, expanded.extractMut(), 0, l)?;
+            } else {
+                weak_get_ref!(mvStoreWeakPtr).getCompressorFast()
+                    .expand(byteBuffer.getInternalSlice(), byteBuffer.getPosition(), compLen, expanded.extractMut(), 0, l)?;
+            }
 
-        get_ref!(self.mvMap).getKeyType().read_3(byteBuffer, &mut self.keys, keyCount);
+            &mut expanded
+        } else {
+            byteBuffer
+        };
+
+        get_ref!(self.mvMap).getKeyType().read_3(byteBuffer, &mut self.keys, keyCount)?;
 
-        if self.isLeaf() {
-            get_ref_mut!(actual).readPayLoad(byteBuffer);
+        if isLeaf {
+            get_ref_mut!(actual).readPayLoad(byteBuffer)?;
         }
 
         self.diskSpaceUsed = pageLength;
 
-        self.recalculateMemory();
+        get_ref_mut!(actual).recalculateMemory();
 
         Ok(())
     }
@@ -337,7 +492,7 @@ impl PageTrait for Page {
         valueType.create_storage(size)
     }
 
-    fn readPayLoad(&mut self, byteBuffer: &mut ByteBuffer) {
+    fn readPayLoad(&mut self, byteBuffer: &mut ByteBuffer) -> Result<()> {
         unimplemented!("abstract 需要由子类实现")
     }
 
@@ -364,6 +519,191 @@ impl PageTrait for Page {
     fn isSaved(&self) -> bool {
         data_utils::isPageSaved(load_atomic!(self.position))
     }
+
+    fn write(&mut self,
+             actual: SharedPtr<dyn PageTrait>,
+             chunk: &mut Chunk,
+             writeBuffer: &mut WriteBuffer,
+             toc: &mut Vec<Long>) -> Result<usize> {
+        self.pageNo = toc.len() as Integer;
+        let keyCount = self.getKeyCount();
+        let mvMap = get_ref!(self.mvMap);
+
+        let start = writeBuffer.getPosition();
+        writeBuffer.putI32(0) // placeholder for pageLength
+            .putI16(0) // placeholder for check
+            .putVarInt(self.pageNo)
+            .putVarInt(mvMap.getId())
+            .putVarInt(keyCount);
+
+        let typePos = writeBuffer.getPosition();
+        let pageType = if get_ref!(actual).isLeaf() { data_utils::PAGE_TYPE_LEAF } else { data_utils::PAGE_TYPE_NODE };
+        writeBuffer.putU8(pageType as u8);
+
+        let childrenPos = writeBuffer.getPosition();
+        get_ref!(actual).writeChildren(writeBuffer, true);
+
+        let compressStart = writeBuffer.getPosition();
+        mvMap.getKeyType().write_3(writeBuffer, &self.keys, keyCount)?;
+        get_ref!(actual).writeValues(writeBuffer)?;
+
+        let mvStoreWeakPtr = mvMap.getMvStore();
+
+        let expLen = writeBuffer.getPosition() - compressStart;
+        if expLen > 16 {
+            let compressionLevel = weak_get_ref!(mvStoreWeakPtr).getCompressionLevel();
+            if compressionLevel > 0 {
+                let mut comp = vec![0; expLen * 2];
+                let exp = writeBuffer.getBuffer().getInternalSlice();
+
+                let (compLen, compressType) = if compressionLevel == 1 {
+                    (weak_get_ref_mut!(mvStoreWeakPtr).getCompressorFastForWrite().compress(exp, compressStart, expLen, &mut comp, 0),
+                     data_utils::PAGE_COMPRESSED)
+                } else {
+                    (weak_get_ref_mut!(mvStoreWeakPtr).getCompressorHighForWrite().compress(exp, compressStart, expLen, &mut comp, 0),
+                     data_utils::PAGE_COMPRESSED_HIGH)
+                };
+
+                // 压缩后更小才使用
+                if compLen < expLen && compLen + (data_utils::getVarIntLen((expLen - compLen) as Integer) as usize) < expLen {
+                    writeBuffer.setPosition(typePos).putU8((pageType | compressType) as u8);
+                    writeBuffer.setPosition(compressStart)
+                        .putVarInt((expLen - compLen) as Integer)
+                        .putSlice_(&comp, 0, compLen);
+                }
+            }
+        }
+
+        let pageLength = (writeBuffer.getPosition() - start) as Integer;
+        let tocElement = data_utils::getTocElement(mvMap.getId(), start as Integer, pageLength, pageType);
+        toc.push(tocElement);
+
+        let chunkId = chunk.id;
+        let check = data_utils::getCheckValue(chunkId) as Integer
+            ^ data_utils::getCheckValue(start as Integer) as Integer
+            ^ data_utils::getCheckValue(pageLength) as Integer;
+        writeBuffer.putI32At(start, pageLength).putI16At(start + 4, check as Short);
+
+        if self.isSaved() {
+            throw!(DbError::get(store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_INTERNAL), vec!["Page already stored"]));
+        }
+
+        let pagePos = data_utils::getPagePos(chunkId, tocElement);
+        self.setPosition(pagePos);
+
+        weak_get_ref_mut!(mvStoreWeakPtr).cachePage(actual.clone())?;
+        if pageType == data_utils::PAGE_TYPE_NODE {
+            // cache again - this will make sure nodes stays in the cache for a longer time
+            weak_get_ref_mut!(mvStoreWeakPtr).cachePage(actual.clone())?;
+        }
+
+        let pageLengthEncoded = data_utils::getPageMaxLength(pagePos);
+        chunk.accountForWrittenPage(pageLengthEncoded);
+
+        self.diskSpaceUsed = if pageLengthEncoded != data_utils::PAGE_LARGE { pageLengthEncoded } else { pageLength };
+
+        Ok(childrenPos)
+    }
+
+    fn writeChildren(&self, writeBuffer: &mut WriteBuffer, withCounts: bool) {
+        unimplemented!("abstract 需要由子类实现")
+    }
+
+    fn writeValues(&self, writeBuffer: &mut WriteBuffer) -> Result<()> {
+        unimplemented!("abstract 需要由子类实现")
+    }
+
+    fn writeUnsavedRecursive(&mut self,
+                             actual: SharedPtr<dyn PageTrait>,
+                             chunk: &mut Chunk,
+                             writeBuffer: &mut WriteBuffer,
+                             toc: &mut Vec<Long>) -> Result<()> {
+        unimplemented!("abstract 需要由子类实现")
+    }
+
+    fn releaseSavedPages(&mut self) {
+        unimplemented!("abstract 需要由子类实现")
+    }
+
+    fn getKey(&self, index: Integer) -> H2RustType {
+        self.keys[index as usize].clone()
+    }
+
+    fn copy0(&self) -> SharedPtr<dyn PageTrait> {
+        unimplemented!("abstract 需要由子类实现")
+    }
+
+    fn split(&mut self, at: Integer) -> SharedPtr<dyn PageTrait> {
+        unimplemented!("abstract 需要由子类实现")
+    }
+
+    fn insertLeaf(&mut self, index: Integer, key: H2RustType, value: H2RustType) {
+        unimplemented!("abstract 需要由子类实现")
+    }
+
+    fn insertNode(&mut self, index: Integer, key: H2RustType, childPage: SharedPtr<dyn PageTrait>) {
+        unimplemented!("abstract 需要由子类实现")
+    }
+
+    fn setValue(&mut self, index: Integer, value: H2RustType) -> H2RustType {
+        unimplemented!("abstract 需要由子类实现")
+    }
+
+    fn setChild(&mut self, index: Integer, childPage: SharedPtr<dyn PageTrait>) {
+        unimplemented!("abstract 需要由子类实现")
+    }
+
+    fn remove(&mut self, mut index: Integer) {
+        let keyCount = self.getKeyCount();
+        if index == keyCount {
+            index -= 1;
+        }
+
+        let old = self.keys.remove(index as usize);
+        if self.is_persistent() {
+            // 允许估算时由Leaf和NonLeaf按比例扣减
+            if !get_ref!(self.mvMap).isMemoryEstimationAllowed() {
+                self.add_memory(-constant::MEMORY_POINTER - get_ref!(self.mvMap).evaluateMemoryForKey(&old));
+            }
+        }
+    }
+
+    fn calculateMemory(&self) -> Integer {
+        get_ref!(self.mvMap).evaluateMemoryForKeys(&self.keys, self.getKeyCount())
+    }
+
+    fn recalculateMemory(&mut self) {
+        assert!(self.is_persistent());
+        self.memory = self.calculateMemory();
+    }
+
+    fn getPrependCursorPos(&self, actual: SharedPtr<dyn PageTrait>, cursorPos: SharedPtr<CursorPos>) -> SharedPtr<CursorPos> {
+        unimplemented!("abstract 需要由子类实现")
+    }
+
+    fn getAppendCursorPos(&self, actual: SharedPtr<dyn PageTrait>, cursorPos: SharedPtr<CursorPos>) -> SharedPtr<CursorPos> {
+        unimplemented!("abstract 需要由子类实现")
+    }
+
+    fn removePage(&self, version: Long) -> Integer {
+        if !self.is_persistent() {
+            return 0;
+        }
+
+        // 未保存的page不占用chunk的空间,只需要把登记的unsaved memory减掉
+        if !self.isSaved() {
+            return -self.memory;
+        }
+
+        let mvMap = get_ref!(self.mvMap);
+        weak_get_ref_mut!(mvMap.getMvStore()).accountForRemovedPage(self.getPosition(), version, self.pageNo);
+
+        0
+    }
+
+    fn removeAllRecursive(&self, version: Long) -> Integer {
+        unimplemented!("abstract 需要由子类实现")
+    }
 }
 
 #[derive(Default)]
@@ -389,7 +729,17 @@ impl Leaf {
 
     pub fn new2(mvMapSharedPtr: SharedPtr<MVMap>, source: SharedPtr<dyn PageTrait>) -> Leaf {
         let mut leaf = Self::new1(mvMapSharedPtr);
-        leaf.values = get_ref!(source).getValues();
+
+        let sourceRef = get_ref!(source);
+        {
+            let pageMutRef = get_ref_mut!(leaf.page);
+            pageMutRef.memory = sourceRef.getMemory();
+            pageMutRef.keys = (0..sourceRef.getKeyCount()).map(|a| sourceRef.getKey(a)).collect();
+        }
+
+        // 不和source共用,各自修改互不影响
+        let values = sourceRef.getValues();
+        leaf.values = build_option_arc_h2RustCell!(get_ref!(values).clone());
         leaf
     }
 
@@ -413,7 +763,12 @@ impl Leaf {
 
 impl PageTrait for Leaf {
     fn initMemoryCount(&mut self, memory_count: Integer) {
-        get_ref_mut!(self.page).initMemoryCount(memory_count);
+        let mvMap = self.getMvMap();
+        if memory_count == 0 && get_ref!(mvMap).is_persistent() {
+            self.recalculateMemory();
+        } else {
+            get_ref_mut!(self.page).initMemoryCount(memory_count);
+        }
     }
 
     fn binarySearch(&mut self, key: &H2RustType) -> Integer {
@@ -443,6 +798,14 @@ impl PageTrait for Leaf {
         self.getKeyCount() as Long
     }
 
+    fn getCounts(&self, index: Integer) -> Long {
+        unimplemented!("leaf not support")
+    }
+
+    fn getChildPagePos(&self, index: Integer) -> Long {
+        unimplemented!("leaf not support")
+    }
+
     fn getPosition(&self) -> Long {
         get_ref!(self.page).getPosition()
     }
@@ -463,13 +826,13 @@ impl PageTrait for Leaf {
         get_ref!(self.page).createValueStorage(size)
     }
 
-    fn readPayLoad(&mut self, byteBuffer: &mut ByteBuffer) {
+    fn readPayLoad(&mut self, byteBuffer: &mut ByteBuffer) -> Result<()> {
         let keyCount = self.getKeyCount();
         self.values = build_option_arc_h2RustCell!(self.createValueStorage(keyCount));
 
         let page = get_ref!(self.page);
 
-        get_ref!(page.mvMap).getValueType().read_3(byteBuffer, get_ref_mut!(self.values), keyCount);
+        get_ref!(page.mvMap).getValueType().read_3(byteBuffer, get_ref_mut!(self.values), keyCount)
     }
 
     fn is_persistent(&self) -> bool {
@@ -495,6 +858,147 @@ impl PageTrait for Leaf {
     fn isSaved(&self) -> bool {
         get_ref!(self.page).isSaved()
     }
+
+    fn write(&mut self,
+             actual: SharedPtr<dyn PageTrait>,
+             chunk: &mut Chunk,
+             writeBuffer: &mut WriteBuffer,
+             toc: &mut Vec<Long>) -> Result<usize> {
+        get_ref_mut!(self.page).write(actual, chunk, writeBuffer, toc)
+    }
+
+    fn writeChildren(&self, writeBuffer: &mut WriteBuffer, withCounts: bool) {
+        // leaf没有子page
+    }
+
+    fn writeValues(&self, writeBuffer: &mut WriteBuffer) -> Result<()> {
+        let page = get_ref!(self.page);
+        get_ref!(page.mvMap).getValueType().write_3(writeBuffer, get_ref!(self.values), self.getKeyCount())
+    }
+
+    fn writeUnsavedRecursive(&mut self,
+                             actual: SharedPtr<dyn PageTrait>,
+                             chunk: &mut Chunk,
+                             writeBuffer: &mut WriteBuffer,
+                             toc: &mut Vec<Long>) -> Result<()> {
+        if !self.isSaved() {
+            self.write(actual, chunk, writeBuffer, toc)?;
+        }
+
+        Ok(())
+    }
+
+    fn releaseSavedPages(&mut self) {}
+
+    fn getKey(&self, index: Integer) -> H2RustType {
+        get_ref!(self.page).getKey(index)
+    }
+
+    fn copy0(&self) -> SharedPtr<dyn PageTrait> {
+        let leaf = Leaf {
+            page: get_ref!(self.page).copyPage(),
+            values: build_option_arc_h2RustCell!(get_ref!(self.values).clone()),
+        };
+
+        build_option_arc_h2RustCell!(leaf)
+    }
+
+    fn split(&mut self, at: Integer) -> SharedPtr<dyn PageTrait> {
+        assert!(!self.isSaved());
+
+        let b = self.getKeyCount() - at;
+        let bKeys = get_ref_mut!(self.page).splitKeys(at, b);
+        let bValues = get_ref_mut!(self.values).split_off(at as usize);
+
+        let newPage = Page::createLeaf(self.getMvMap(), bKeys, build_option_arc_h2RustCell!(bValues), 0);
+        if self.is_persistent() {
+            self.recalculateMemory();
+        }
+
+        newPage
+    }
+
+    fn insertLeaf(&mut self, index: Integer, key: H2RustType, value: H2RustType) {
+        get_ref_mut!(self.page).insertKey(index, key);
+
+        if self.is_persistent() {
+            let mvMap = self.getMvMap();
+            get_ref_mut!(self.page).add_memory(constant::MEMORY_POINTER + get_ref!(mvMap).evaluateMemoryForValue(&value));
+        }
+
+        get_ref_mut!(self.values).insert(index as usize, value);
+    }
+
+    fn insertNode(&mut self, index: Integer, key: H2RustType, childPage: SharedPtr<dyn PageTrait>) {
+        unimplemented!("leaf not support")
+    }
+
+    fn setValue(&mut self, index: Integer, value: H2RustType) -> H2RustType {
+        let values = get_ref_mut!(self.values);
+        let old = std::mem::replace(&mut values[index as usize], value);
+
+        if self.is_persistent() {
+            let mvMap = self.getMvMap();
+            let mvMapRef = get_ref!(mvMap);
+            get_ref_mut!(self.page).add_memory(mvMapRef.evaluateMemoryForValue(&values[index as usize]) - mvMapRef.evaluateMemoryForValue(&old));
+        }
+
+        old
+    }
+
+    fn setChild(&mut self, index: Integer, childPage: SharedPtr<dyn PageTrait>) {
+        unimplemented!("leaf not support")
+    }
+
+    fn remove(&mut self, index: Integer) {
+        let keyCount = self.getKeyCount();
+        get_ref_mut!(self.page).remove(index);
+
+        if self.values.is_some() {
+            let old = get_ref_mut!(self.values).remove(index as usize);
+
+            if self.is_persistent() {
+                let mvMap = self.getMvMap();
+                let memory = if get_ref!(mvMap).isMemoryEstimationAllowed() {
+                    -self.getMemory() / keyCount
+                } else {
+                    -constant::MEMORY_POINTER - get_ref!(mvMap).evaluateMemoryForValue(&old)
+                };
+                get_ref_mut!(self.page).add_memory(memory);
+            }
+        }
+    }
+
+    fn calculateMemory(&self) -> Integer {
+        let keyCount = self.getKeyCount();
+        let mvMap = self.getMvMap();
+
+        get_ref!(self.page).calculateMemory() + PAGE_LEAF_MEMORY +
+            if self.values.is_none() { 0 } else { get_ref!(mvMap).evaluateMemoryForValues(get_ref!(self.values), keyCount) }
+    }
+
+    fn recalculateMemory(&mut self) {
+        assert!(self.is_persistent());
+        let memory = self.calculateMemory();
+        get_ref_mut!(self.page).memory = memory;
+    }
+
+    fn getPrependCursorPos(&self, actual: SharedPtr<dyn PageTrait>, cursorPos: SharedPtr<CursorPos>) -> SharedPtr<CursorPos> {
+        CursorPos::new(actual, -1, cursorPos)
+    }
+
+    fn getAppendCursorPos(&self, actual: SharedPtr<dyn PageTrait>, cursorPos: SharedPtr<CursorPos>) -> SharedPtr<CursorPos> {
+        let keyCount = self.getKeyCount();
+        CursorPos::new(actual, !keyCount, cursorPos)
+    }
+
+    fn removePage(&self, version: Long) -> Integer {
+        get_ref!(self.page).removePage(version)
+    }
+
+    fn removeAllRecursive(&self, version: Long) -> Integer {
+        self.removePage(version)
+    }
 }
 
 #[derive(Default)]
@@ -523,6 +1027,18 @@ impl NonLeaf {
         nonLeaf
     }
 
+    pub fn new4(mvMapSharedPtr: SharedPtr<MVMap>,
+                keys: Vec<H2RustType>,
+                children: Vec<PageReferenceSharedPtr>,
+                totalCount: Long) -> NonLeaf {
+        let mut nonLeaf = Self::new1(mvMapSharedPtr);
+        get_ref_mut!(nonLeaf.page).keys = keys;
+        nonLeaf.children = children;
+        nonLeaf.totalCount = totalCount;
+
+        nonLeaf
+    }
+
     fn calculateTotalCount(&self) -> Long {
         let mut totalCount = 0;
         let keyCount = self.getKeyCount();
@@ -531,11 +1047,28 @@ impl NonLeaf {
         }
         totalCount
     }
+
+    fn writeChildrenRecursive(&self, chunk: &mut Chunk, writeBuffer: &mut WriteBuffer, toc: &mut Vec<Long>) -> Result<()> {
+        for pageReference in &self.children {
+            let pageTrait = get_ref!(pageReference).getPage();
+            if pageTrait.is_some() {
+                get_ref_mut!(pageTrait).writeUnsavedRecursive(pageTrait.clone(), chunk, writeBuffer, toc)?;
+                get_ref!(pageReference).resetPos();
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl PageTrait for NonLeaf {
     fn initMemoryCount(&mut self, memory_count: Integer) {
-        get_ref_mut!(self.page).initMemoryCount(memory_count);
+        let mvMap = self.getMvMap();
+        if memory_count == 0 && get_ref!(mvMap).is_persistent() {
+            self.recalculateMemory();
+        } else {
+            get_ref_mut!(self.page).initMemoryCount(memory_count);
+        }
     }
 
     fn binarySearch(&mut self, key: &H2RustType) -> Integer {
@@ -556,14 +1089,14 @@ impl PageTrait for NonLeaf {
 
     fn getChildPage(&self, index: Integer) -> SharedPtr<dyn PageTrait> {
         let pageReferenceRef = get_ref!(self.children.get(index as usize).unwrap());
-        let mut pageTraitSharedPtr = pageReferenceRef.page.clone();
+        let mut pageTraitSharedPtr = pageReferenceRef.getPage();
 
         if pageTraitSharedPtr.is_none() {
             let pageRef = get_ref!(self.page);
             let mvMapRef = get_ref!(pageRef.mvMap);
 
-            pageTraitSharedPtr = mvMapRef.readPage(pageRef.mvMap.clone(), pageReferenceRef.position).unwrap();
-            assert_eq!(pageReferenceRef.position, get_ref!(pageTraitSharedPtr).getPosition());
+            pageTraitSharedPtr = mvMapRef.readPage(pageRef.mvMap.clone(), pageReferenceRef.getPosition()).unwrap();
+            assert_eq!(pageReferenceRef.getPosition(), get_ref!(pageTraitSharedPtr).getPosition());
             assert_eq!(pageReferenceRef.count, get_ref!(pageTraitSharedPtr).getTotalCount());
         }
 
@@ -575,6 +1108,20 @@ impl PageTrait for NonLeaf {
         self.totalCount
     }
 
+    fn getCounts(&self, index: Integer) -> Long {
+        get_ref!(self.children[index as usize]).count
+    }
+
+    fn getChildPagePos(&self, index: Integer) -> Long {
+        let pageReferenceRef = get_ref!(self.children[index as usize]);
+        let page = pageReferenceRef.getPage();
+        if page.is_some() {
+            get_ref!(page).getPosition()
+        } else {
+            pageReferenceRef.getPosition()
+        }
+    }
+
     fn getPosition(&self) -> Long {
         get_ref!(self.page).getPosition()
     }
@@ -596,21 +1143,17 @@ impl PageTrait for NonLeaf {
         todo!()
     }
 
-    fn readPayLoad(&mut self, byteBuffer: &mut ByteBuffer) {
+    fn readPayLoad(&mut self, byteBuffer: &mut ByteBuffer) -> Result<()> {
         let keyCount = get_ref!(self.page).getKeyCount();
         self.children = createRefStorage((keyCount + 1) as usize);
 
-        let mut positions = Vec::with_capacity(keyCount as usize + 1);
-        for a in 0..keyCount as usize {
-            positions[a] = byteBuffer.getI64();
-        }
+        let positions: Vec<Long> = (0..=keyCount).map(|_| byteBuffer.getI64()).collect();
 
         let mut total: i64 = 0;
 
-        for a in 0..keyCount as usize {
+        for (a, position) in positions.into_iter().enumerate() {
             let count = data_utils::readVarLong(byteBuffer);
 
-            let position = positions[a];
             if position == 0 {
                 assert_eq!(count, 0);
             } else {
@@ -627,6 +1170,7 @@ impl PageTrait for NonLeaf {
         }
 
         self.totalCount = total;
+        Ok(())
     }
 
     fn is_persistent(&self) -> bool {
@@ -642,13 +1186,219 @@ impl PageTrait for NonLeaf {
     }
 
     fn copy(&self, mvMap: SharedPtr<MVMap>, eraseChildrenRefs: bool, actual: SharedPtr<dyn PageTrait>) -> SharedPtr<dyn PageTrait> {
-        todo!()
+        let children = if eraseChildrenRefs {
+            // 只保留position和count,不再引用内存中的子page
+            self.children.iter().map(|pageReference| {
+                let pageReferenceRef = get_ref!(pageReference);
+                if pageReferenceRef.getPosition() == 0 {
+                    PageReference::empty()
+                } else {
+                    PageReference::new2(pageReferenceRef.getPosition(), pageReferenceRef.count)
+                }
+            }).collect()
+        } else {
+            self.children.clone()
+        };
+
+        let nonLeaf = NonLeaf::new4(mvMap, get_ref!(self.page).keys.clone(), children, self.totalCount);
+        get_ref_mut!(nonLeaf.page).memory = self.getMemory();
+
+        build_option_arc_h2RustCell!(nonLeaf)
     }
 
 
     fn isSaved(&self) -> bool {
         get_ref!(self.page).isSaved()
     }
+
+    fn write(&mut self,
+             actual: SharedPtr<dyn PageTrait>,
+             chunk: &mut Chunk,
+             writeBuffer: &mut WriteBuffer,
+             toc: &mut Vec<Long>) -> Result<usize> {
+        get_ref_mut!(self.page).write(actual, chunk, writeBuffer, toc)
+    }
+
+    fn writeChildren(&self, writeBuffer: &mut WriteBuffer, withCounts: bool) {
+        let keyCount = self.getKeyCount() as usize;
+
+        for a in 0..=keyCount {
+            writeBuffer.putI64(get_ref!(self.children[a]).getPosition());
+        }
+
+        if withCounts {
+            for a in 0..=keyCount {
+                writeBuffer.putVarLong(get_ref!(self.children[a]).count);
+            }
+        }
+    }
+
+    fn writeValues(&self, writeBuffer: &mut WriteBuffer) -> Result<()> {
+        // nonLeaf没有value
+        Ok(())
+    }
+
+    fn writeUnsavedRecursive(&mut self,
+                             actual: SharedPtr<dyn PageTrait>,
+                             chunk: &mut Chunk,
+                             writeBuffer: &mut WriteBuffer,
+                             toc: &mut Vec<Long>) -> Result<()> {
+        if !self.isSaved() {
+            let patch = self.write(actual, chunk, writeBuffer, toc)?;
+            self.writeChildrenRecursive(chunk, writeBuffer, toc)?;
+
+            // 子page的position到这时才知道,回头覆盖之前写的
+            let old = writeBuffer.getPosition();
+            writeBuffer.setPosition(patch);
+            self.writeChildren(writeBuffer, false);
+            writeBuffer.setPosition(old);
+        }
+
+        Ok(())
+    }
+
+    fn releaseSavedPages(&mut self) {
+        for pageReference in &self.children {
+            get_ref!(pageReference).clearPageReference();
+        }
+    }
+
+    fn getKey(&self, index: Integer) -> H2RustType {
+        get_ref!(self.page).getKey(index)
+    }
+
+    fn copy0(&self) -> SharedPtr<dyn PageTrait> {
+        let nonLeaf = NonLeaf {
+            page: get_ref!(self.page).copyPage(),
+            children: self.children.clone(),
+            totalCount: self.totalCount,
+        };
+
+        build_option_arc_h2RustCell!(nonLeaf)
+    }
+
+    fn split(&mut self, at: Integer) -> SharedPtr<dyn PageTrait> {
+        assert!(!self.isSaved());
+
+        let b = self.getKeyCount() - at;
+        let bKeys = get_ref_mut!(self.page).splitKeys(at, b - 1);
+        let bChildren = self.children.split_off((at + 1) as usize);
+
+        self.totalCount = self.children.iter().map(|pageReference| get_ref!(pageReference).count).sum();
+        let t = bChildren.iter().map(|pageReference| get_ref!(pageReference).count).sum();
+
+        let newPage = Page::createNode(self.getMvMap(), bKeys, bChildren, t, 0);
+        if self.is_persistent() {
+            self.recalculateMemory();
+        }
+
+        newPage
+    }
+
+    fn insertLeaf(&mut self, index: Integer, key: H2RustType, value: H2RustType) {
+        unimplemented!("non leaf not support")
+    }
+
+    fn insertNode(&mut self, index: Integer, key: H2RustType, childPage: SharedPtr<dyn PageTrait>) {
+        get_ref_mut!(self.page).insertKey(index, key);
+
+        self.totalCount += get_ref!(childPage).getTotalCount();
+        self.children.insert(index as usize, PageReference::new1(childPage));
+
+        if self.is_persistent() {
+            get_ref_mut!(self.page).add_memory(constant::MEMORY_POINTER + PAGE_MEMORY_CHILD);
+        }
+    }
+
+    fn setValue(&mut self, index: Integer, value: H2RustType) -> H2RustType {
+        unimplemented!("non leaf not support")
+    }
+
+    fn setChild(&mut self, index: Integer, childPage: SharedPtr<dyn PageTrait>) {
+        assert!(childPage.is_some());
+
+        let child = get_ref!(self.children[index as usize]);
+        let page = child.getPage();
+        if !h2_rust_cell_equals!(childPage, page) || get_ref!(childPage).getPosition() != child.getPosition() {
+            self.totalCount += get_ref!(childPage).getTotalCount() - child.count;
+            self.children[index as usize] = PageReference::new1(childPage);
+        }
+    }
+
+    fn remove(&mut self, index: Integer) {
+        let childCount = self.children.len() as Integer;
+        get_ref_mut!(self.page).remove(index);
+
+        if self.is_persistent() {
+            let mvMap = self.getMvMap();
+            let memory = if get_ref!(mvMap).isMemoryEstimationAllowed() {
+                -self.getMemory() / childCount
+            } else {
+                -constant::MEMORY_POINTER - PAGE_MEMORY_CHILD
+            };
+            get_ref_mut!(self.page).add_memory(memory);
+        }
+
+        self.totalCount -= get_ref!(self.children[index as usize]).count;
+        self.children.remove(index as usize);
+    }
+
+    fn calculateMemory(&self) -> Integer {
+        get_ref!(self.page).calculateMemory() + PAGE_NODE_MEMORY +
+            self.children.len() as Integer * (constant::MEMORY_POINTER + PAGE_MEMORY_CHILD)
+    }
+
+    fn recalculateMemory(&mut self) {
+        assert!(self.is_persistent());
+        let memory = self.calculateMemory();
+        get_ref_mut!(self.page).memory = memory;
+    }
+
+    fn getPrependCursorPos(&self, actual: SharedPtr<dyn PageTrait>, cursorPos: SharedPtr<CursorPos>) -> SharedPtr<CursorPos> {
+        let childPage = self.getChildPage(0);
+        let cursorPos = CursorPos::new(actual, 0, cursorPos);
+        get_ref!(childPage).getPrependCursorPos(childPage.clone(), cursorPos)
+    }
+
+    fn getAppendCursorPos(&self, actual: SharedPtr<dyn PageTrait>, cursorPos: SharedPtr<CursorPos>) -> SharedPtr<CursorPos> {
+        let keyCount = self.getKeyCount();
+        let childPage = self.getChildPage(keyCount);
+        let cursorPos = CursorPos::new(actual, keyCount, cursorPos);
+        get_ref!(childPage).getAppendCursorPos(childPage.clone(), cursorPos)
+    }
+
+    fn removePage(&self, version: Long) -> Integer {
+        get_ref!(self.page).removePage(version)
+    }
+
+    fn removeAllRecursive(&self, version: Long) -> Integer {
+        let mut unsavedMemory = self.removePage(version);
+
+        if self.is_persistent() {
+            let mvMap = self.getMvMap();
+
+            for (index, pageReference) in self.children.iter().enumerate() {
+                let pageReferenceRef = get_ref!(pageReference);
+                let page = pageReferenceRef.getPage();
+                if page.is_some() {
+                    unsavedMemory += get_ref!(page).removeAllRecursive(version);
+                } else {
+                    let position = pageReferenceRef.getPosition();
+                    assert!(data_utils::isPageSaved(position));
+
+                    // 不在内存中的leaf不用读出来,只有non leaf需要读出来看它的子page
+                    if data_utils::getPageType(position) == data_utils::PAGE_TYPE_LEAF {
+                        weak_get_ref_mut!(get_ref!(mvMap).getMvStore()).accountForRemovedPage(position, version, -1);
+                    } else {
+                        let childPage = self.getChildPage(index as Integer);
+                        unsavedMemory += get_ref!(childPage).removeAllRecursive(version);
+                    }
+                }
+            }
+        }
+
+        unsavedMemory
+    }
 }
 
 pub type PageReferenceSharedPtr = Option<Arc<H2RustCell<PageReference>>>;
@@ -661,10 +1411,11 @@ lazy_static! {
 #[derive(Default)]
 pub struct PageReference {
     /// The position, if known, or 0.
-    pub position: Long,
+    position: AtomicI64,
 
     /// The page, if in memory, or null.
-    pub page: SharedPtr<dyn PageTrait>,
+    /// 后台线程保存后会clearPageReference,和读写线程并发,所以加锁
+    page: RwLock<SharedPtr<dyn PageTrait>>,
 
     /// The descendant count for this child page.
     count: Long,
@@ -673,22 +1424,58 @@ pub struct PageReference {
 impl PageReference {
     pub fn new3(page: SharedPtr<dyn PageTrait>, position: Long, count: Long) -> PageReferenceSharedPtr {
         build_option_arc_h2RustCell!(PageReference{
-            page,
-            position,
+            position: AtomicI64::new(position),
+            page: RwLock::new(page),
             count
         })
     }
 
+    pub fn new1(page: SharedPtr<dyn PageTrait>) -> PageReferenceSharedPtr {
+        let position = get_ref!(page).getPosition();
+        let count = get_ref!(page).getTotalCount();
+        Self::new3(page, position, count)
+    }
+
     pub fn new2(position: Long, count: Long) -> PageReferenceSharedPtr {
-        build_option_arc_h2RustCell!(PageReference{
-            page:None,
-            position,
-            count
-        })
+        Self::new3(None, position, count)
     }
+
     pub fn empty() -> PageReferenceSharedPtr {
         EMPTY.clone()
     }
+
+    pub fn getPosition(&self) -> Long {
+        self.position.load(Ordering::Acquire)
+    }
+
+    pub fn getPage(&self) -> SharedPtr<dyn PageTrait> {
+        self.page.read().clone()
+    }
+
+    /// Clear if necessary, reference to the actual child Page object,
+    /// so it can be garbage collected if not actively used elsewhere.
+    /// Reference is cleared only if corresponding page was already saved on a disk.
+    fn clearPageReference(&self) {
+        let page = self.getPage();
+        if page.is_some() {
+            get_ref_mut!(page).releaseSavedPages();
+
+            if get_ref!(page).isSaved() {
+                assert_eq!(self.getPosition(), get_ref!(page).getPosition());
+                assert_eq!(self.count, get_ref!(page).getTotalCount());
+                *self.page.write() = None;
+            }
+        }
+    }
+
+    /// 写入后page的position已确定,同步过来
+    fn resetPos(&self) {
+        let page = self.getPage();
+        if page.is_some() && get_ref!(page).isSaved() {
+            self.position.store(get_ref!(page).getPosition(), Ordering::Release);
+            assert_eq!(self.count, get_ref!(page).getTotalCount());
+        }
+    }
 }
 
 pub fn readFromByteBuffer(byteBuffer: &mut ByteBuffer, position: Long, mvMap: SharedPtr<MVMap>) -> Result<SharedPtr<dyn PageTrait>> {
@@ -709,6 +1496,36 @@ pub fn readFromByteBuffer(byteBuffer: &mut ByteBuffer, position: Long, mvMap: Sh
     Ok(pageTrait)
 }
 
+/// 只读取non leaf page中子page的position,不需要反序列化key,compact时用来找出还在使用的page
+pub fn readChildrenPositions(byteBuffer: &mut ByteBuffer, position: Long) -> Result<Vec<Long>> {
+    let chunkId = data_utils::getPageChunkId(position);
+
+    let pageLength = byteBuffer.getI32();
+    let remaining = byteBuffer.getRemaining() + 4;
+    if pageLength as usize > remaining || pageLength < 4 {
+        throw!(DbError::get(error_code::FILE_CORRUPTED_1,
+                     vec![&format!("file corrupted in chunk {}, expected page length 4..{}, got {}", chunkId, remaining, pageLength)]));
+    }
+
+    // check, pageNo, mapId
+    byteBuffer.getI16();
+    data_utils::readVarInt(byteBuffer);
+    data_utils::readVarInt(byteBuffer);
+
+    let keyCount = data_utils::readVarInt(byteBuffer);
+    let type7 = byteBuffer.getI8() as Integer;
+    if (type7 & 1) != data_utils::PAGE_TYPE_NODE {
+        throw!(db_error_template!(error_code::FILE_CORRUPTED_1, "file corrupted in chunk {}, expected node type {}, got {}", chunkId, data_utils::PAGE_TYPE_NODE, type7));
+    }
+
+    let mut positions = Vec::with_capacity(keyCount as usize + 1);
+    for _ in 0..=keyCount {
+        positions.push(byteBuffer.getI64());
+    }
+
+    Ok(positions)
+}
+
 pub fn createRefStorage(size: usize) -> Vec<PageReferenceSharedPtr> {
-    Vec::with_capacity(size)
+    vec![PageReference::empty(); size]
 }
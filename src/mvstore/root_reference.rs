@@ -1,6 +1,6 @@
 use std::sync::Arc;
 use std::thread;
-use crate::{build_option_arc_h2RustCell, get_ref, h2_rust_cell_equals};
+use crate::{build_option_arc_h2RustCell, get_ref, get_ref_mut, h2_rust_cell_equals};
 use crate::h2_rust_common::{Byte, Integer, Long};
 use crate::h2_rust_common::h2_rust_cell::{H2RustCell, SharedPtr};
 use crate::mvstore::page::{PageTrait};
@@ -51,9 +51,155 @@ impl RootReference {
         build_option_arc_h2RustCell!(root_reference)
     }
 
+    /// This one is used for version change
+    fn newVersion(rSharedPtr: &SharedPtr<RootReference>, version: Long, attempt: Integer) -> SharedPtr<RootReference> {
+        let r = get_ref!(rSharedPtr);
+
+        let mut previous = rSharedPtr.clone();
+        loop {
+            let tmp = get_ref!(previous).previous.clone();
+            if tmp.is_none() || !h2_rust_cell_equals!(get_ref!(tmp).root, r.root) {
+                break;
+            }
+            previous = tmp;
+        }
+
+        let holdCount = if r.holdCount == 0 { 0 } else { r.holdCount - 1 };
+
+        assert_eq!(r.appendCounter, 0);
+
+        let root_reference = RootReference {
+            root: r.root.clone(),
+            version,
+            holdCount,
+            ownerId: if holdCount == 0 { 0 } else { r.ownerId },
+            previous,
+            updateCounter: r.updateCounter + 1,
+            updateAttemptCounter: r.updateAttemptCounter + attempt as Long,
+            appendCounter: 0,
+        };
+
+        build_option_arc_h2RustCell!(root_reference)
+    }
+
+    /// This one is used for locking
+    fn newLocked(r: &RootReference, attempt: Integer) -> SharedPtr<RootReference> {
+        let currentThreadId = thread::current().id().as_u64().get() as Long;
+        assert!(r.holdCount == 0 || r.ownerId == currentThreadId);
+
+        let root_reference = RootReference {
+            root: r.root.clone(),
+            version: r.version,
+            holdCount: r.holdCount + 1,
+            ownerId: currentThreadId,
+            previous: r.previous.clone(),
+            updateCounter: r.updateCounter + 1,
+            updateAttemptCounter: r.updateAttemptCounter + attempt as Long,
+            appendCounter: r.appendCounter,
+        };
+
+        build_option_arc_h2RustCell!(root_reference)
+    }
+
+    /// This one is used for unlocking
+    fn newUnlocked(r: &RootReference, root: SharedPtr<dyn PageTrait>, keepLocked: bool, appendCounter: Integer) -> SharedPtr<RootReference> {
+        assert!(r.holdCount > 0 && r.ownerId == thread::current().id().as_u64().get() as Long);
+
+        let holdCount = r.holdCount - if keepLocked { 0 } else { 1 };
+
+        let root_reference = RootReference {
+            root,
+            version: r.version,
+            holdCount,
+            ownerId: if holdCount == 0 { 0 } else { r.ownerId },
+            previous: r.previous.clone(),
+            updateCounter: r.updateCounter,
+            updateAttemptCounter: r.updateAttemptCounter,
+            appendCounter: appendCounter as Byte,
+        };
+
+        build_option_arc_h2RustCell!(root_reference)
+    }
+
+    /// This one is used for concurrent modifications
+    fn newRootPage(r: &RootReference, root: SharedPtr<dyn PageTrait>, updateAttemptCounter: Long) -> SharedPtr<RootReference> {
+        let root_reference = RootReference {
+            root,
+            version: r.version,
+            holdCount: 0,
+            ownerId: 0,
+            previous: r.previous.clone(),
+            updateCounter: r.updateCounter + 1,
+            updateAttemptCounter: r.updateAttemptCounter + updateAttemptCounter,
+            appendCounter: r.appendCounter,
+        };
+
+        build_option_arc_h2RustCell!(root_reference)
+    }
+
+    /// Try to unconditionally change the root page.
+    ///
+    /// return new RootReference or None if update failed
+    pub fn updateRootPage(this: &SharedPtr<RootReference>, newRootPage: SharedPtr<dyn PageTrait>, attemptCounter: Long) -> SharedPtr<RootReference> {
+        if get_ref!(this).isFree() {
+            Self::tryUpdate(this, Self::newRootPage(get_ref!(this), newRootPage, attemptCounter))
+        } else {
+            None
+        }
+    }
+
+    /// Try to lock.
+    ///
+    /// return new RootReference or None if lock failed
+    pub fn tryLock(this: &SharedPtr<RootReference>, attemptCounter: Integer) -> SharedPtr<RootReference> {
+        if get_ref!(this).canUpdate() {
+            Self::tryUpdate(this, Self::newLocked(get_ref!(this), attemptCounter))
+        } else {
+            None
+        }
+    }
+
+    /// Try to unlock and set the root page.
+    ///
+    /// return new RootReference or None if update failed
+    pub fn updatePageAndLockedStatus(this: &SharedPtr<RootReference>,
+                                     page: SharedPtr<dyn PageTrait>,
+                                     keepLocked: bool,
+                                     appendCounter: Integer) -> SharedPtr<RootReference> {
+        if get_ref!(this).canUpdate() {
+            Self::tryUpdate(this, Self::newUnlocked(get_ref!(this), page, keepLocked, appendCounter))
+        } else {
+            None
+        }
+    }
+
+    fn isFree(&self) -> bool {
+        self.holdCount == 0
+    }
+
+    pub fn isLocked(&self) -> bool {
+        self.holdCount != 0
+    }
+
+    pub fn getHoldCount(&self) -> Byte {
+        self.holdCount
+    }
+
+    pub fn getPrevious(&self) -> SharedPtr<RootReference> {
+        self.previous.clone()
+    }
+
+    pub fn getUpdateCounter(&self) -> Long {
+        self.updateCounter
+    }
+
+    pub fn getUpdateAttemptCounter(&self) -> Long {
+        self.updateAttemptCounter
+    }
+
     pub fn getVersion(&self) -> Long {
         let prev = self.previous.clone();
-        if prev.is_none() || h2_rust_cell_equals!(get_ref!(prev).root,self.root) || get_ref!(prev).appendCounter != self.appendCounter {
+        if prev.is_none() || !h2_rust_cell_equals!(get_ref!(prev).root,self.root) || get_ref!(prev).appendCounter != self.appendCounter {
             self.version
         } else {
             get_ref!(prev).getVersion()
@@ -64,6 +210,54 @@ impl RootReference {
         self.holdCount != 0 && self.ownerId == thread::current().id().as_u64().get() as Long
     }
 
+    fn canUpdate(&self) -> bool {
+        self.holdCount == 0 || self.ownerId == thread::current().id().as_u64().get() as Long
+    }
+
+    pub fn tryUnlockAndUpdateVersion(this: &SharedPtr<RootReference>, version: Long, attempt: Integer) -> SharedPtr<RootReference> {
+        let thisRef = get_ref!(this);
+        if !thisRef.canUpdate() {
+            return None;
+        }
+
+        let updatedRootReference = RootReference::newVersion(this, version, attempt);
+        Self::tryUpdate(this, updatedRootReference)
+    }
+
+    fn tryUpdate(this: &SharedPtr<RootReference>, updatedRootReference: SharedPtr<RootReference>) -> SharedPtr<RootReference> {
+        assert!(get_ref!(this).canUpdate());
+
+        let mvMap = get_ref!(get_ref!(this).root).getMvMap();
+        if get_ref_mut!(mvMap).compareAndSetRoot(this, updatedRootReference.clone()) {
+            updatedRootReference
+        } else {
+            None
+        }
+    }
+
+    /// 把版本早于oldestVersionToKeep的previous链断开
+    pub fn removeUnusedOldVersions(&mut self, oldestVersionToKeep: Long) {
+        // We need to keep at least one previous version (if any) here,
+        // because in order to retain whole history of some version
+        // we really need last root of the previous version.
+        // Root labeled with version "X" is the LAST known root for that version
+        // and therefore the FIRST known root for the version "X+1"
+        if self.version < oldestVersionToKeep {
+            self.previous = None;
+            return;
+        }
+
+        let mut rootReference = self.previous.clone();
+        while rootReference.is_some() {
+            let rootReferenceMutRef = get_ref_mut!(rootReference);
+            let previous = rootReferenceMutRef.previous.clone();
+            if rootReferenceMutRef.version < oldestVersionToKeep {
+                rootReferenceMutRef.previous = None;
+            }
+            rootReference = previous;
+        }
+    }
+
     pub fn hasChangesSince(&self, version: Long, persistent: bool) -> bool {
             persistent &&
                 if get_ref!(self.root).isSaved() {
@@ -75,10 +269,10 @@ impl RootReference {
     }
 
     pub fn getAppendCounter(&self) -> Integer {
-        return (self.appendCounter & 0xff) as Integer;
+        (self.appendCounter as u8) as Integer
     }
 
     pub fn getTotalCount(&self) -> Long {
-        get_ref!(self.root).getTotalCount() + self.getAppendCounter()
+        get_ref!(self.root).getTotalCount() + self.getAppendCounter() as Long
     }
 }
\ No newline at end of file
@@ -0,0 +1,67 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Arc;
+use anyhow::Result;
+use crate::build_arc_h2RustCell;
+use crate::h2_rust_common::h2_rust_cell::{H2RustCell, SharedPtr};
+use crate::h2_rust_common::h2_rust_type::H2RustType;
+use crate::h2_rust_common::Integer;
+use crate::mvstore::mv_store::{MVStore, MVStoreBuilder};
+
+/// A store file in the temp dir, removed when created and again when dropped.
+pub struct TempStore {
+    fileName: String,
+}
+
+impl TempStore {
+    pub fn new(name: &str) -> TempStore {
+        let fileName = std::env::temp_dir().join(format!("{}.mv.db", name)).to_str().unwrap().to_string();
+        let _ = std::fs::remove_file(&fileName);
+        TempStore { fileName }
+    }
+
+    pub fn getFileName(&self) -> &str {
+        &self.fileName
+    }
+
+    pub fn getFileSize(&self) -> u64 {
+        std::fs::metadata(&self.fileName).unwrap().len()
+    }
+
+    /// 默认配置打开
+    pub fn open(&self) -> SharedPtr<MVStore> {
+        self.openWith(&[])
+    }
+
+    /// 额外的配置项,比如("autoCommitDelay", 0)
+    pub fn openWith(&self, options: &[(&str, Integer)]) -> SharedPtr<MVStore> {
+        openStore(&self.fileName, options).unwrap()
+    }
+
+    /// fileName已经设置好的builder
+    pub fn builder(&self) -> MVStoreBuilder {
+        let mut mvStoreBuilder = MVStoreBuilder::new();
+        mvStoreBuilder.fileName(&self.fileName);
+        mvStoreBuilder
+    }
+}
+
+impl Drop for TempStore {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.fileName);
+    }
+}
+
+/// 打开任意的fileName,比如带有file_channel前缀的
+pub fn openStore(fileName: &str, options: &[(&str, Integer)]) -> Result<SharedPtr<MVStore>> {
+    let mut config: HashMap<String, Box<dyn Any>> = HashMap::new();
+    config.insert("fileName".to_string(), Box::new(fileName.to_string()));
+    for (key, value) in options {
+        config.insert(key.to_string(), Box::new(*value));
+    }
+    MVStore::new(&mut config)
+}
+
+pub fn string(s: &str) -> H2RustType {
+    H2RustType::String(build_arc_h2RustCell!(s.to_string()))
+}
@@ -0,0 +1,38 @@
+use std::sync::atomic::{AtomicI32, Ordering};
+use crate::h2_rust_common::{Integer, Long};
+
+/// Class TxCounter is a simple data structure to hold version of the store
+/// along with the counter of open transactions, which are still operating on this version.
+///
+/// 对应java的MVStore.TxCounter,counter变成负数表示这个版本已经不再是当前版本,并且没有人在使用
+#[derive(Default)]
+pub struct TxCounter {
+    /// Version of a store, this TxCounter is related to
+    pub version: Long,
+
+    /// Counter of outstanding operation on this version of a store
+    counter: AtomicI32,
+}
+
+impl TxCounter {
+    pub fn new(version: Long) -> TxCounter {
+        TxCounter {
+            version,
+            counter: AtomicI32::new(0),
+        }
+    }
+
+    pub fn get(&self) -> Integer {
+        self.counter.load(Ordering::Acquire)
+    }
+
+    /// Increment and get the counter value.
+    pub fn incrementAndGet(&self) -> Integer {
+        self.counter.fetch_add(1, Ordering::AcqRel) + 1
+    }
+
+    /// Decrement and get the counter values.
+    pub fn decrementAndGet(&self) -> Integer {
+        self.counter.fetch_sub(1, Ordering::AcqRel) - 1
+    }
+}
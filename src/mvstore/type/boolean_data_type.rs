@@ -0,0 +1,53 @@
+use std::cmp::Ordering;
+use anyhow::Result;
+use std::sync::Arc;
+use lazy_static::lazy_static;
+use crate::h2_rust_common::Integer;
+use crate::h2_rust_common::byte_buffer::ByteBuffer;
+use crate::h2_rust_common::h2_rust_type::H2RustType;
+use crate::h2_rust_common::h2_rust_type::H2RustType::Boolean;
+use crate::mvstore::r#type::data_type::DataType;
+use crate::mvstore::write_buffer::WriteBuffer;
+
+lazy_static! {
+    pub static ref INSTANCE:Arc<BooleanDataType> = Arc::new(BooleanDataType);
+}
+
+/// The name of this type in the map metadata, see data_type_registry.
+pub const TYPE_NAME: &str = "boolean";
+
+/// Class BooleanDataType. Stored as a single byte, false is 0 and true is 1.
+pub struct BooleanDataType;
+
+impl DataType for BooleanDataType {
+    fn get_type_name(&self) -> String {
+        TYPE_NAME.to_string()
+    }
+
+    fn compare(&self, a: &H2RustType, b: &H2RustType) -> Ordering {
+        match (a, b) {
+            (Boolean(boolean_a), Boolean(boolean_b)) => boolean_a.cmp(boolean_b),
+            _ => panic!("not Boolean")
+        }
+    }
+
+    fn get_memory(&self, obj: &H2RustType) -> Integer {
+        1
+    }
+
+    fn write_2(&self, writeBuffer: &mut WriteBuffer, obj: &H2RustType) -> Result<()> {
+        match obj {
+            Boolean(boolean) => { writeBuffer.putU8(*boolean as u8); }
+            _ => panic!("not Boolean")
+        }
+        Ok(())
+    }
+
+    fn read_1(&self, byteBuffer: &mut ByteBuffer) -> Result<H2RustType> {
+        Ok(Boolean(byteBuffer.getU8() != 0))
+    }
+
+    fn create_storage(&self, size: Integer) -> Vec<H2RustType> {
+        vec![H2RustType::Null; size as usize]
+    }
+}
@@ -0,0 +1,66 @@
+use std::cmp::Ordering;
+use anyhow::Result;
+use std::sync::Arc;
+use lazy_static::lazy_static;
+use crate::build_arc_h2RustCell;
+use crate::h2_rust_common::Integer;
+use crate::h2_rust_common::byte_buffer::ByteBuffer;
+use crate::h2_rust_common::h2_rust_cell::H2RustCell;
+use crate::h2_rust_common::h2_rust_type::H2RustType;
+use crate::h2_rust_common::h2_rust_type::H2RustType::Bytes;
+use crate::mvstore::data_utils;
+use crate::mvstore::r#type::data_type::DataType;
+use crate::mvstore::write_buffer::WriteBuffer;
+
+lazy_static! {
+    pub static ref INSTANCE:Arc<ByteArrayDataType> = Arc::new(ByteArrayDataType);
+}
+
+/// The name of this type in the map metadata, see data_type_registry.
+pub const TYPE_NAME: &str = "bytes";
+
+/// Class ByteArrayDataType. Stored as the length (variable size int) followed by the bytes.
+pub struct ByteArrayDataType;
+
+impl DataType for ByteArrayDataType {
+    fn get_type_name(&self) -> String {
+        TYPE_NAME.to_string()
+    }
+
+    /// 按照无符号byte逐个比较,相同前缀时短的在前
+    fn compare(&self, a: &H2RustType, b: &H2RustType) -> Ordering {
+        match (a, b) {
+            (Bytes(bytes_a), Bytes(bytes_b)) => bytes_a.get_ref().cmp(bytes_b.get_ref()),
+            _ => panic!("not Bytes")
+        }
+    }
+
+    fn get_memory(&self, obj: &H2RustType) -> Integer {
+        match obj {
+            Bytes(bytes) => bytes.get_ref().len() as Integer,
+            _ => panic!("not Bytes")
+        }
+    }
+
+    fn write_2(&self, writeBuffer: &mut WriteBuffer, obj: &H2RustType) -> Result<()> {
+        match obj {
+            Bytes(bytes) => {
+                let bytes = bytes.get_ref();
+                writeBuffer.putVarInt(bytes.len() as Integer).putSlice(bytes);
+            }
+            _ => panic!("not Bytes")
+        }
+        Ok(())
+    }
+
+    fn read_1(&self, byteBuffer: &mut ByteBuffer) -> Result<H2RustType> {
+        let len = data_utils::readVarInt(byteBuffer) as usize;
+        let mut bytes = vec![0; len];
+        byteBuffer.getSlice(&mut bytes);
+        Ok(Bytes(build_arc_h2RustCell!(bytes)))
+    }
+
+    fn create_storage(&self, size: Integer) -> Vec<H2RustType> {
+        vec![H2RustType::Null; size as usize]
+    }
+}
@@ -1,11 +1,15 @@
 use std::cmp::Ordering;
+use anyhow::Result;
 use crate::h2_rust_common::Integer;
 use crate::mvstore::write_buffer::WriteBuffer;
 use Ordering::{Equal, Greater, Less};
 use crate::h2_rust_common::byte_buffer::ByteBuffer;
 use crate::h2_rust_common::h2_rust_type::H2RustType;
 
-pub trait DataType {
+pub trait DataType: Send + Sync {
+    /// The name under which this type is recorded in the map metadata and found in data_type_registry.
+    fn get_type_name(&self) -> String;
+
     fn compare(&self, a: &H2RustType, b: &H2RustType) -> Ordering;
 
     /// Perform binary search for the key within the storage
@@ -44,6 +48,11 @@ pub trait DataType {
     /// Calculates the amount of used memory in bytes.
     fn get_memory(&self, obj: &H2RustType) -> Integer;
 
+    /// Check an object before it is put into a map, so that it can be written later.
+    fn validate(&self, obj: &H2RustType) -> Result<()> {
+        Ok(())
+    }
+
     /// Whether memory estimation based on previously seen values is allowed/desirable
     fn is_memory_estimation_allowed(&self) -> bool {
         true
@@ -53,34 +62,36 @@ pub trait DataType {
     ///
     /// @param buff the target buffer
     /// @param obj  the value
-    fn write_2(&self, writerBuffer: &WriteBuffer, obj: &H2RustType);
+    fn write_2(&self, writeBuffer: &mut WriteBuffer, obj: &H2RustType) -> Result<()>;
 
     /// Write a list of objects.
     ///
     /// @param buff    the target buffer
     /// @param storage the objects
     /// @param len     the number of objects to write
-    fn write_3(&self, writeBuffer: &WriteBuffer, storage: &Vec<H2RustType>, len: Integer) {
-        for a in 0..len as usize {
-            self.write_2(&writeBuffer, &storage[a]);
+    fn write_3(&self, writeBuffer: &mut WriteBuffer, storage: &[H2RustType], len: Integer) -> Result<()> {
+        for obj in &storage[..len as usize] {
+            self.write_2(writeBuffer, obj)?;
         }
+        Ok(())
     }
 
     /// Read an object.
     ///
     /// @param buff the source buffer
-    /// @return the object
-    fn read_1(&self, byteBuffer: &mut ByteBuffer) -> H2RustType;
+    /// @return the object, an error if the data is corrupted
+    fn read_1(&self, byteBuffer: &mut ByteBuffer) -> Result<H2RustType>;
 
     /// Read a list of object
     ///
     /// @param buff    the target buffer
     /// @param storage the objects
     /// @param len     the number of objects to read
-    fn read_3(&self, byteBuffer: &mut ByteBuffer, storage: &mut Vec<H2RustType>, len: Integer) {
+    fn read_3(&self, byteBuffer: &mut ByteBuffer, storage: &mut Vec<H2RustType>, len: Integer) -> Result<()> {
         for a in 0..len as usize {
-            storage[a] = self.read_1(byteBuffer);
+            storage[a] = self.read_1(byteBuffer)?;
         }
+        Ok(())
     }
 
     /// Create storage object of array type to hold values
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use anyhow::Result;
+use lazy_static::lazy_static;
+use crate::db::store;
+use crate::message::db_error::DbError;
+use crate::mvstore::data_utils;
+use crate::mvstore::r#type::data_type::DataType;
+use crate::mvstore::r#type::serde_data_type::{self, SerdeDataType, SerdeType};
+use crate::mvstore::r#type::{boolean_data_type, byte_array_data_type, double_data_type, integer_data_type, long_data_type, row_data_type, string_data_type, uuid_data_type};
+use crate::throw;
+
+/// Creates the DataType registered under a name.
+pub type DataTypeFactory = Box<dyn Fn() -> Arc<dyn DataType> + Send + Sync>;
+
+lazy_static! {
+    static ref FACTORIES: RwLock<HashMap<String, DataTypeFactory>> = RwLock::new(builtIns());
+}
+
+fn builtIns() -> HashMap<String, DataTypeFactory> {
+    let mut factories: HashMap<String, DataTypeFactory> = HashMap::new();
+    factories.insert(string_data_type::TYPE_NAME.to_string(), Box::new(|| string_data_type::INSTANCE.clone()));
+    factories.insert(long_data_type::TYPE_NAME.to_string(), Box::new(|| long_data_type::INSTANCE.clone()));
+    factories.insert(integer_data_type::TYPE_NAME.to_string(), Box::new(|| integer_data_type::INSTANCE.clone()));
+    factories.insert(double_data_type::TYPE_NAME.to_string(), Box::new(|| double_data_type::INSTANCE.clone()));
+    factories.insert(byte_array_data_type::TYPE_NAME.to_string(), Box::new(|| byte_array_data_type::INSTANCE.clone()));
+    factories.insert(uuid_data_type::TYPE_NAME.to_string(), Box::new(|| uuid_data_type::INSTANCE.clone()));
+    factories.insert(boolean_data_type::TYPE_NAME.to_string(), Box::new(|| boolean_data_type::INSTANCE.clone()));
+    factories
+}
+
+/// Register a factory for the given type name, replacing an existing one.
+pub fn register(typeName: &str, factory: DataTypeFactory) {
+    FACTORIES.write().unwrap().insert(typeName.to_string(), factory);
+}
+
+/// Register SerdeDataType::<T> under the given stable name,
+/// needed before a store holding such a map is reopened by MVStore::openMap.
+pub fn registerSerde<T: SerdeType>(name: &str) {
+    let owned = name.to_string();
+    register(&serde_data_type::getTypeName(name), Box::new(move || SerdeDataType::<T>::new(&owned)));
+}
+
+/// Create the data type registered under the given name.
+pub fn get(typeName: &str) -> Result<Arc<dyn DataType>> {
+    // row的类型名包含各列的类型,不需要注册
+    if typeName.starts_with(row_data_type::TYPE_NAME_PREFIX) {
+        return row_data_type::parse(typeName);
+    }
+
+    match FACTORIES.read().unwrap().get(typeName) {
+        Some(factory) => Ok(factory()),
+        None => {
+            let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_UNKNOWN_DATA_TYPE);
+            throw!(DbError::get(error_code, vec![&format!("Unknown data type {}", typeName)]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use crate::{get_ref, get_ref_mut};
+    use crate::api::error_code;
+    use crate::h2_rust_common::h2_rust_type::H2RustType;
+    use crate::message::db_error::DbError;
+    use crate::mvstore::mv_map;
+    use crate::mvstore::mv_map::MVMapBuilder;
+    use crate::mvstore::r#type::{double_data_type, long_data_type};
+    use crate::mvstore::test_utils::{string, TempStore};
+
+    #[test]
+    fn test_data_type_registry() {
+        let tempStore = TempStore::new("test_data_type_registry");
+
+        {
+            let mvStore = tempStore.open();
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+            let mut longDouble = MVMapBuilder::new();
+            longDouble.keyType(long_data_type::INSTANCE.clone());
+            longDouble.valueType(double_data_type::INSTANCE.clone());
+            let longDouble = mvStoreMutRef.openMap("longDouble", &longDouble).unwrap();
+            get_ref_mut!(longDouble).put(H2RustType::Long(7), H2RustType::Double(0.5)).unwrap();
+            let strings = mvStoreMutRef.openMap("strings", &MVMapBuilder::new()).unwrap();
+            get_ref_mut!(strings).put(string("a"), string("b")).unwrap();
+            let mut typed = mvStoreMutRef.openTypedMap::<u64, String>("typed", "u64", "string").unwrap();
+            typed.put(1, "one".to_string()).unwrap();
+            mvStoreMutRef.close().unwrap();
+        }
+
+        // 不指定类型,按meta中记录的类型名重建
+        {
+            let mvStore = tempStore.open();
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+            let longDouble = mvStoreMutRef.openMap("longDouble", &MVMapBuilder::new()).unwrap();
+            assert_eq!(get_ref!(longDouble).getKeyType().get_type_name(), long_data_type::TYPE_NAME);
+            assert_eq!(get_ref!(longDouble).get(&H2RustType::Long(7)).castAsDouble(), 0.5);
+            let strings = mvStoreMutRef.openMap("strings", &MVMapBuilder::new()).unwrap();
+            assert_eq!(get_ref!(strings).get(&string("a")).castAsStringRef(), "b");
+            let typed = mvStoreMutRef.openMap("typed", &MVMapBuilder::new()).unwrap();
+            assert_eq!(get_ref!(typed).get(&H2RustType::Object(Arc::new(1u64))).castAsObjectRef::<String>(), "one");
+
+            // 把记录的类型名改成没有注册的
+            let meta = mvStoreMutRef.getMetaMap().unwrap();
+            let mapKey = string(&mv_map::getMapKey(get_ref!(longDouble).getId()));
+            let config = get_ref!(meta).get(&mapKey).castAsStringRef().replace("val:double", "val:unknown");
+            get_ref_mut!(meta).put(mapKey, string(&config)).unwrap();
+            // 单独修改meta不算未保存的变更
+            get_ref_mut!(strings).put(string("c"), string("d")).unwrap();
+            mvStoreMutRef.commit().unwrap();
+            mvStoreMutRef.close().unwrap();
+        }
+
+        let mvStore = tempStore.open();
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+        let error = mvStoreMutRef.openMap("longDouble", &MVMapBuilder::new()).err().unwrap();
+        assert_eq!(error.downcast_ref::<DbError>().unwrap().error_code, error_code::UNKNOWN_DATA_TYPE_1);
+        mvStoreMutRef.close().unwrap();
+    }
+}
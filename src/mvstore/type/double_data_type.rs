@@ -0,0 +1,61 @@
+use std::cmp::Ordering;
+use anyhow::Result;
+use std::sync::Arc;
+use lazy_static::lazy_static;
+use crate::h2_rust_common::Integer;
+use crate::h2_rust_common::byte_buffer::ByteBuffer;
+use crate::h2_rust_common::h2_rust_type::H2RustType;
+use crate::h2_rust_common::h2_rust_type::H2RustType::Double;
+use crate::mvstore::r#type::data_type::DataType;
+use crate::mvstore::write_buffer::WriteBuffer;
+
+lazy_static! {
+    pub static ref INSTANCE:Arc<DoubleDataType> = Arc::new(DoubleDataType);
+}
+
+/// The name of this type in the map metadata, see data_type_registry.
+pub const TYPE_NAME: &str = "double";
+
+/// Class DoubleDataType. Stored as a fixed size 8 bytes double.
+pub struct DoubleDataType;
+
+impl DataType for DoubleDataType {
+    fn get_type_name(&self) -> String {
+        TYPE_NAME.to_string()
+    }
+
+    /// 和java的Double.compare()一样,-0.0小于0.0,NaN比其它值都大
+    fn compare(&self, a: &H2RustType, b: &H2RustType) -> Ordering {
+        match (a, b) {
+            (Double(double_a), Double(double_b)) => {
+                match (double_a.is_nan(), double_b.is_nan()) {
+                    (true, true) => Ordering::Equal,
+                    (true, false) => Ordering::Greater,
+                    (false, true) => Ordering::Less,
+                    (false, false) => double_a.total_cmp(double_b)
+                }
+            }
+            _ => panic!("not Double")
+        }
+    }
+
+    fn get_memory(&self, obj: &H2RustType) -> Integer {
+        8
+    }
+
+    fn write_2(&self, writeBuffer: &mut WriteBuffer, obj: &H2RustType) -> Result<()> {
+        match obj {
+            Double(double) => { writeBuffer.putF64(*double); }
+            _ => panic!("not Double")
+        }
+        Ok(())
+    }
+
+    fn read_1(&self, byteBuffer: &mut ByteBuffer) -> Result<H2RustType> {
+        Ok(Double(f64::from_bits(byteBuffer.getI64() as u64)))
+    }
+
+    fn create_storage(&self, size: Integer) -> Vec<H2RustType> {
+        vec![H2RustType::Null; size as usize]
+    }
+}
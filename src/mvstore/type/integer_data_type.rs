@@ -0,0 +1,53 @@
+use std::cmp::Ordering;
+use anyhow::Result;
+use std::sync::Arc;
+use lazy_static::lazy_static;
+use crate::h2_rust_common::Integer;
+use crate::h2_rust_common::byte_buffer::ByteBuffer;
+use crate::h2_rust_common::h2_rust_type::H2RustType;
+use crate::mvstore::data_utils;
+use crate::mvstore::r#type::data_type::DataType;
+use crate::mvstore::write_buffer::WriteBuffer;
+
+lazy_static! {
+    pub static ref INSTANCE:Arc<IntegerDataType> = Arc::new(IntegerDataType);
+}
+
+/// The name of this type in the map metadata, see data_type_registry.
+pub const TYPE_NAME: &str = "int";
+
+/// Class IntegerDataType. Stored as a variable size int.
+pub struct IntegerDataType;
+
+impl DataType for IntegerDataType {
+    fn get_type_name(&self) -> String {
+        TYPE_NAME.to_string()
+    }
+
+    fn compare(&self, a: &H2RustType, b: &H2RustType) -> Ordering {
+        match (a, b) {
+            (H2RustType::Integer(integer_a), H2RustType::Integer(integer_b)) => integer_a.cmp(integer_b),
+            _ => panic!("not Integer")
+        }
+    }
+
+    fn get_memory(&self, obj: &H2RustType) -> Integer {
+        4
+    }
+
+    fn write_2(&self, writeBuffer: &mut WriteBuffer, obj: &H2RustType) -> Result<()> {
+        match obj {
+            H2RustType::Integer(integer) => { writeBuffer.putVarInt(*integer); }
+            _ => panic!("not Integer")
+        }
+        Ok(())
+    }
+
+    fn read_1(&self, byteBuffer: &mut ByteBuffer) -> Result<H2RustType> {
+        Ok(H2RustType::Integer(data_utils::readVarInt(byteBuffer)))
+    }
+
+    fn create_storage(&self, size: Integer) -> Vec<H2RustType> {
+        vec![H2RustType::Null; size as usize]
+    }
+}
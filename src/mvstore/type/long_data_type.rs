@@ -0,0 +1,54 @@
+use std::cmp::Ordering;
+use anyhow::Result;
+use std::sync::Arc;
+use lazy_static::lazy_static;
+use crate::h2_rust_common::Integer;
+use crate::h2_rust_common::byte_buffer::ByteBuffer;
+use crate::h2_rust_common::h2_rust_type::H2RustType;
+use crate::h2_rust_common::h2_rust_type::H2RustType::Long;
+use crate::mvstore::data_utils;
+use crate::mvstore::r#type::data_type::DataType;
+use crate::mvstore::write_buffer::WriteBuffer;
+
+lazy_static! {
+    pub static ref INSTANCE:Arc<LongDataType> = Arc::new(LongDataType);
+}
+
+/// The name of this type in the map metadata, see data_type_registry.
+pub const TYPE_NAME: &str = "long";
+
+/// Class LongDataType. Stored as a variable size long.
+pub struct LongDataType;
+
+impl DataType for LongDataType {
+    fn get_type_name(&self) -> String {
+        TYPE_NAME.to_string()
+    }
+
+    fn compare(&self, a: &H2RustType, b: &H2RustType) -> Ordering {
+        match (a, b) {
+            (Long(long_a), Long(long_b)) => long_a.cmp(long_b),
+            _ => panic!("not Long")
+        }
+    }
+
+    fn get_memory(&self, obj: &H2RustType) -> Integer {
+        8
+    }
+
+    fn write_2(&self, writeBuffer: &mut WriteBuffer, obj: &H2RustType) -> Result<()> {
+        match obj {
+            Long(long) => { writeBuffer.putVarLong(*long); }
+            _ => panic!("not Long")
+        }
+        Ok(())
+    }
+
+    fn read_1(&self, byteBuffer: &mut ByteBuffer) -> Result<H2RustType> {
+        Ok(Long(data_utils::readVarLong(byteBuffer)))
+    }
+
+    fn create_storage(&self, size: Integer) -> Vec<H2RustType> {
+        vec![H2RustType::Null; size as usize]
+    }
+}
@@ -1,3 +1,89 @@
 pub mod data_type;
 pub mod basic_data_type;
-pub mod string_data_type;
\ No newline at end of file
+pub mod string_data_type;
+pub mod long_data_type;
+pub mod integer_data_type;
+pub mod double_data_type;
+pub mod byte_array_data_type;
+pub mod uuid_data_type;
+pub mod boolean_data_type;
+pub mod serde_data_type;
+pub mod data_type_registry;
+pub mod row_data_type;
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use crate::{build_arc_h2RustCell, get_ref, get_ref_mut};
+    use crate::h2_rust_common::h2_rust_cell::H2RustCell;
+    use crate::h2_rust_common::h2_rust_type::H2RustType;
+    use crate::h2_rust_common::{Integer, Long};
+    use crate::mvstore::mv_map::MVMapBuilder;
+    use crate::mvstore::r#type::{boolean_data_type, byte_array_data_type, double_data_type, integer_data_type, long_data_type, uuid_data_type};
+    use crate::mvstore::r#type::data_type::DataType;
+    use crate::mvstore::test_utils::TempStore;
+
+    #[test]
+    fn test_data_types() {
+        let tempStore = TempStore::new("test_data_types");
+
+        let bytes = |v: Vec<u8>| H2RustType::Bytes(build_arc_h2RustCell!(v));
+        let builders = || {
+            let mut longDouble = MVMapBuilder::new();
+            longDouble.keyType(long_data_type::INSTANCE.clone());
+            longDouble.valueType(double_data_type::INSTANCE.clone());
+            let mut integerBytes = MVMapBuilder::new();
+            integerBytes.keyType(integer_data_type::INSTANCE.clone());
+            integerBytes.valueType(byte_array_data_type::INSTANCE.clone());
+            let mut uuidBoolean = MVMapBuilder::new();
+            uuidBoolean.keyType(uuid_data_type::INSTANCE.clone());
+            uuidBoolean.valueType(boolean_data_type::INSTANCE.clone());
+            (longDouble, integerBytes, uuidBoolean)
+        };
+
+        {
+            let mvStore = tempStore.open();
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+            let (longDouble, integerBytes, uuidBoolean) = builders();
+            let longDouble = mvStoreMutRef.openMap("longDouble", &longDouble).unwrap();
+            let integerBytes = mvStoreMutRef.openMap("integerBytes", &integerBytes).unwrap();
+            let uuidBoolean = mvStoreMutRef.openMap("uuidBoolean", &uuidBoolean).unwrap();
+            for a in -500..500 {
+                get_ref_mut!(longDouble).put(H2RustType::Long(a * 1_000_000_007), H2RustType::Double(a as f64 / 3.0)).unwrap();
+                get_ref_mut!(integerBytes).put(H2RustType::Integer(a as Integer), bytes(vec![a as u8; (a + 500) as usize % 7])).unwrap();
+                get_ref_mut!(uuidBoolean).put(H2RustType::Uuid(a, -a), H2RustType::Boolean(a % 2 == 0)).unwrap();
+            }
+            mvStoreMutRef.close().unwrap();
+        }
+
+        let mvStore = tempStore.open();
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+        let (longDouble, integerBytes, uuidBoolean) = builders();
+        let longDouble = mvStoreMutRef.openMap("longDouble", &longDouble).unwrap();
+        let integerBytes = mvStoreMutRef.openMap("integerBytes", &integerBytes).unwrap();
+        let uuidBoolean = mvStoreMutRef.openMap("uuidBoolean", &uuidBoolean).unwrap();
+        assert_eq!(get_ref!(longDouble).size_as_long(), 1000);
+        assert_eq!(get_ref!(longDouble).get(&H2RustType::Long(-7 * 1_000_000_007)).castAsDouble(), -7.0 / 3.0);
+        assert_eq!(get_ref!(integerBytes).get(&H2RustType::Integer(-1)).castAsBytesRef(), &vec![255u8; 499 % 7]);
+        assert!(get_ref!(integerBytes).get(&H2RustType::Integer(1000)).isNull());
+        assert!(get_ref!(uuidBoolean).get(&H2RustType::Uuid(42, -42)).castAsBoolean());
+        assert!(!get_ref!(uuidBoolean).get(&H2RustType::Uuid(43, -43)).castAsBoolean());
+
+        // key按照数值而不是字节顺序排列
+        let cursor = get_ref!(longDouble).cursor(&H2RustType::Null);
+        let mut last = Long::MIN;
+        for key in cursor {
+            assert!(key.castAsLong() > last);
+            last = key.castAsLong();
+        }
+        assert_eq!(last, 499 * 1_000_000_007);
+        mvStoreMutRef.close().unwrap();
+
+        let doubleType = double_data_type::INSTANCE.clone();
+        assert_eq!(doubleType.compare(&H2RustType::Double(-0.0), &H2RustType::Double(0.0)), std::cmp::Ordering::Less);
+        assert_eq!(doubleType.compare(&H2RustType::Double(f64::NAN), &H2RustType::Double(f64::INFINITY)), std::cmp::Ordering::Greater);
+        let bytesType = byte_array_data_type::INSTANCE.clone();
+        assert_eq!(bytesType.compare(&bytes(vec![0x80]), &bytes(vec![0x7f, 0x7f])), std::cmp::Ordering::Greater);
+        assert_eq!(bytesType.compare(&bytes(vec![1]), &bytes(vec![1, 0])), std::cmp::Ordering::Less);
+    }
+}
@@ -0,0 +1,325 @@
+use std::cmp::Ordering;
+use std::sync::Arc;
+use anyhow::Result;
+use crate::api::error_code;
+use crate::build_arc_h2RustCell;
+use crate::db::store;
+use crate::engine::constant;
+use crate::h2_rust_common::Integer;
+use crate::h2_rust_common::byte_buffer::ByteBuffer;
+use crate::h2_rust_common::h2_rust_cell::H2RustCell;
+use crate::h2_rust_common::h2_rust_type::H2RustType;
+use crate::h2_rust_common::h2_rust_type::H2RustType::Array;
+use crate::message::db_error::DbError;
+use crate::mode::default_null_ordering::DefaultNullOrdering;
+use crate::mvstore::data_utils;
+use crate::mvstore::r#type::data_type::DataType;
+use crate::mvstore::r#type::data_type_registry;
+use crate::mvstore::write_buffer::WriteBuffer;
+use crate::result::sort_orders;
+use crate::result::sort_orders::SortOrder;
+use crate::throw;
+
+/// The prefix of the type name, see parse().
+pub const TYPE_NAME_PREFIX: &str = "row(";
+
+/// A data type for rows held by H2RustType::Array, compared column by column.
+/// Each column has its own data type and sort type, a NULL column is ordered by the DefaultNullOrdering.
+///
+/// 列数少的行(比如只有前几列的查找条件)在前缀相同时排在前面
+pub struct RowDataType {
+    columnTypes: Vec<Arc<dyn DataType>>,
+    sortOrder: SortOrder,
+    defaultNullOrdering: &'static DefaultNullOrdering,
+}
+
+impl RowDataType {
+    pub fn new(columnTypes: Vec<Arc<dyn DataType>>,
+               sortOrder: SortOrder,
+               defaultNullOrdering: &'static DefaultNullOrdering) -> Result<Arc<RowDataType>> {
+        data_utils::check_argument(columnTypes.len() == sortOrder.get_column_count(),
+                                   &format!("{} columns but {} sort types", columnTypes.len(), sortOrder.get_column_count()))?;
+        Ok(Arc::new(RowDataType {
+            columnTypes,
+            sortOrder,
+            defaultNullOrdering,
+        }))
+    }
+
+    pub fn getColumnTypes(&self) -> &Vec<Arc<dyn DataType>> {
+        &self.columnTypes
+    }
+
+    pub fn getSortOrder(&self) -> &SortOrder {
+        &self.sortOrder
+    }
+
+    pub fn getDefaultNullOrdering(&self) -> &'static DefaultNullOrdering {
+        self.defaultNullOrdering
+    }
+
+    fn checkColumnCount(&self, len: usize) -> Result<()> {
+        data_utils::check_argument(len <= self.columnTypes.len(),
+                                   &format!("row with {} columns, the type has {}", len, self.columnTypes.len()))
+    }
+
+    fn compareValues(&self, a: &H2RustType, b: &H2RustType, index: usize) -> Ordering {
+        let sortType = self.sortOrder.get_sort_type(index);
+        match (a.isNull(), b.isNull()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => self.defaultNullOrdering.compare_null(true, sortType).cmp(&0),
+            (false, true) => self.defaultNullOrdering.compare_null(false, sortType).cmp(&0),
+            (false, false) => {
+                let comp = self.columnTypes[index].compare(a, b);
+                if sortType & sort_orders::DESCENDING != 0 {
+                    comp.reverse()
+                } else {
+                    comp
+                }
+            }
+        }
+    }
+}
+
+/// Rebuild a row data type from its name, for example "row(LOW|1:long|0:string)":
+/// the DefaultNullOrdering followed by the sort type and the data type name of each column.
+pub fn parse(typeName: &str) -> Result<Arc<dyn DataType>> {
+    let body = typeName.strip_prefix(TYPE_NAME_PREFIX).and_then(|s| s.strip_suffix(')'));
+    let parts = match body {
+        Some(body) => splitTopLevel(body),
+        None => unknownDataType(typeName)?,
+    };
+
+    let defaultNullOrdering = match DefaultNullOrdering::value_of(parts[0]) {
+        Some(defaultNullOrdering) => defaultNullOrdering,
+        None => unknownDataType(typeName)?,
+    };
+
+    let mut columnTypes = Vec::with_capacity(parts.len() - 1);
+    let mut sortTypes = Vec::with_capacity(parts.len() - 1);
+    for part in &parts[1..] {
+        let (sortType, columnTypeName) = match part.split_once(':') {
+            Some(pair) => pair,
+            None => unknownDataType(typeName)?,
+        };
+        match sortType.parse::<Integer>() {
+            Ok(sortType) => sortTypes.push(sortType),
+            Err(_) => unknownDataType(typeName)?,
+        }
+        columnTypes.push(data_type_registry::get(columnTypeName)?);
+    }
+
+    Ok(RowDataType::new(columnTypes, SortOrder::new(sortTypes), defaultNullOrdering)?)
+}
+
+/// 按不在括号内的'|'切分,列本身也可以是row
+fn splitTopLevel(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '|' if depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+fn unknownDataType<T>(typeName: &str) -> Result<T> {
+    let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_UNKNOWN_DATA_TYPE);
+    throw!(DbError::get(error_code, vec![&format!("Unknown data type {}", typeName)]))
+}
+
+impl DataType for RowDataType {
+    fn get_type_name(&self) -> String {
+        let mut typeName = format!("{}{}", TYPE_NAME_PREFIX, self.defaultNullOrdering.name());
+        for (index, columnType) in self.columnTypes.iter().enumerate() {
+            typeName.push_str(&format!("|{}:{}", self.sortOrder.get_sort_type(index), columnType.get_type_name()));
+        }
+        typeName.push(')');
+        typeName
+    }
+
+    fn compare(&self, a: &H2RustType, b: &H2RustType) -> Ordering {
+        let (a, b) = (a.castAsArrayRef(), b.castAsArrayRef());
+        for index in 0..usize::min(a.len(), b.len()) {
+            let comp = self.compareValues(&a[index], &b[index], index);
+            if comp != Ordering::Equal {
+                return comp;
+            }
+        }
+        a.len().cmp(&b.len())
+    }
+
+    /// 列数不能超过类型声明的列数,少于时是只有前几列的查找条件
+    fn validate(&self, obj: &H2RustType) -> Result<()> {
+        let row = obj.castAsArrayRef();
+        self.checkColumnCount(row.len())?;
+        for (value, columnType) in row.iter().zip(&self.columnTypes) {
+            if !value.isNull() {
+                columnType.validate(value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn get_memory(&self, obj: &H2RustType) -> Integer {
+        let row = obj.castAsArrayRef();
+        let mut memory = 24 + row.len() as Integer * constant::MEMORY_POINTER;
+        for (value, columnType) in row.iter().zip(&self.columnTypes) {
+            if !value.isNull() {
+                memory += columnType.get_memory(value);
+            }
+        }
+        memory
+    }
+
+    /// 列数,然后每列一个byte标记是否为NULL,不是NULL时接着是该列类型的数据
+    fn write_2(&self, writeBuffer: &mut WriteBuffer, obj: &H2RustType) -> Result<()> {
+        let row = obj.castAsArrayRef();
+        self.checkColumnCount(row.len())?;
+        writeBuffer.putVarInt(row.len() as Integer);
+        for (value, columnType) in row.iter().zip(&self.columnTypes) {
+            if value.isNull() {
+                writeBuffer.putU8(0);
+            } else {
+                writeBuffer.putU8(1);
+                columnType.write_2(writeBuffer, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_1(&self, byteBuffer: &mut ByteBuffer) -> Result<H2RustType> {
+        let len = data_utils::readVarInt(byteBuffer);
+        if len < 0 || len as usize > self.columnTypes.len() {
+            throw!(DbError::get(error_code::FILE_CORRUPTED_1,
+                                vec![&format!("row with {} columns, expected at most {}", len, self.columnTypes.len())]));
+        }
+        let len = len as usize;
+        let mut row = Vec::with_capacity(len);
+        for index in 0..len {
+            if byteBuffer.getU8() == 0 {
+                row.push(H2RustType::Null);
+            } else {
+                row.push(self.columnTypes[index].read_1(byteBuffer)?);
+            }
+        }
+        Ok(Array(build_arc_h2RustCell!(row)))
+    }
+
+    fn create_storage(&self, size: Integer) -> Vec<H2RustType> {
+        vec![H2RustType::Null; size as usize]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use crate::{build_arc_h2RustCell, get_ref, get_ref_mut};
+    use crate::h2_rust_common::h2_rust_cell::{H2RustCell, SharedPtr};
+    use crate::api::error_code;
+    use crate::h2_rust_common::h2_rust_type::H2RustType;
+    use crate::message::db_error::DbError;
+    use crate::mode::default_null_ordering;
+    use crate::mvstore::mv_map::{MVMap, MVMapBuilder};
+    use crate::mvstore::r#type::data_type::DataType;
+    use crate::mvstore::r#type::{long_data_type, string_data_type};
+    use crate::mvstore::r#type::row_data_type::RowDataType;
+    use crate::mvstore::test_utils::{string, TempStore};
+    use crate::mvstore::write_buffer::WriteBuffer;
+    use crate::result::sort_orders;
+    use crate::result::sort_orders::SortOrder;
+
+    #[test]
+    fn test_row_data_type() {
+        let tempStore = TempStore::new("test_row_data_type");
+
+        let row = |values: Vec<H2RustType>| H2RustType::Array(build_arc_h2RustCell!(values));
+        let keys = |mvMap: &SharedPtr<MVMap>, from: &H2RustType| {
+            let cursor = get_ref!(mvMap).cursor(from);
+            cursor.map(|key| key.castAsArrayRef().iter().map(|v| match v {
+                H2RustType::Long(l) => l.to_string(),
+                H2RustType::String(s) => s.get_ref().clone(),
+                _ => "null".to_string(),
+            }).collect::<Vec<String>>().join(",")).collect::<Vec<String>>()
+        };
+
+        // LOW: 升序时NULL在前,降序时NULL在后
+        let rowType = RowDataType::new(vec![long_data_type::INSTANCE.clone(), string_data_type::INSTANCE.clone()],
+                                       SortOrder::new(vec![sort_orders::DESCENDING, sort_orders::ASCENDING]),
+                                       &default_null_ordering::LOW).unwrap();
+        let expected = vec!["2,null", "2,x", "2,y", "1,null", "1,x", "1,y", "0,null", "0,x", "0,y", "null,z"];
+        {
+            let mvStore = tempStore.open();
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+            let mut mvMapBuilder = MVMapBuilder::new();
+            mvMapBuilder.keyType(rowType.clone());
+            let mvMap = mvStoreMutRef.openMap("rows", &mvMapBuilder).unwrap();
+            for a in 0..3 {
+                for name in [H2RustType::Null, string("y"), string("x")] {
+                    get_ref_mut!(mvMap).put(row(vec![H2RustType::Long(a), name]), string("v")).unwrap();
+                }
+            }
+            get_ref_mut!(mvMap).put(row(vec![H2RustType::Null, string("z")]), string("v")).unwrap();
+            assert_eq!(keys(&mvMap, &H2RustType::Null), expected);
+            mvStoreMutRef.close().unwrap();
+        }
+
+        // 按meta中记录的类型名重建,只给出第一列时从该前缀的第一行开始
+        let mvStore = tempStore.open();
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+        let mvMap = mvStoreMutRef.openMap("rows", &MVMapBuilder::new()).unwrap();
+        assert_eq!(get_ref!(mvMap).getKeyType().get_type_name(), "row(LOW|1:long|0:string)");
+        assert_eq!(keys(&mvMap, &H2RustType::Null), expected);
+        assert_eq!(keys(&mvMap, &row(vec![H2RustType::Long(1)]))[0], "1,null");
+        assert_eq!(get_ref!(mvMap).get(&row(vec![H2RustType::Null, string("z")])).castAsStringRef(), "v");
+        mvStoreMutRef.close().unwrap();
+
+        // 显式的NULLS_FIRST/NULLS_LAST优先于DefaultNullOrdering
+        let nullsFirst = RowDataType::new(vec![long_data_type::INSTANCE.clone()],
+                                          SortOrder::new(vec![sort_orders::DESCENDING | sort_orders::NULLS_FIRST]),
+                                          &default_null_ordering::LOW).unwrap();
+        assert_eq!(nullsFirst.compare(&row(vec![H2RustType::Null]), &row(vec![H2RustType::Long(5)])), std::cmp::Ordering::Less);
+        let high = RowDataType::new(vec![long_data_type::INSTANCE.clone()], SortOrder::ascending(1), &default_null_ordering::HIGH).unwrap();
+        assert_eq!(high.compare(&row(vec![H2RustType::Null]), &row(vec![H2RustType::Long(5)])), std::cmp::Ordering::Greater);
+        assert_eq!(default_null_ordering::HIGH.add_explicit_null_ordering(sort_orders::DESCENDING),
+                   sort_orders::DESCENDING | sort_orders::NULLS_FIRST);
+    }
+
+    #[test]
+    fn test_row_column_count() {
+        let tempStore = TempStore::new("test_row_column_count");
+        let row = |values: Vec<H2RustType>| H2RustType::Array(build_arc_h2RustCell!(values));
+
+        assert!(RowDataType::new(vec![long_data_type::INSTANCE.clone()], SortOrder::ascending(2), &default_null_ordering::LOW).is_err());
+
+        let rowType = RowDataType::new(vec![long_data_type::INSTANCE.clone()], SortOrder::ascending(1), &default_null_ordering::LOW).unwrap();
+        let mvStore = tempStore.open();
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+        let mut mvMapBuilder = MVMapBuilder::new();
+        mvMapBuilder.keyType(rowType.clone());
+        let mvMap = mvStoreMutRef.openMap("rows", &mvMapBuilder).unwrap();
+
+        // 比类型声明的列数多的行在put时就被拒绝
+        let error = get_ref_mut!(mvMap).put(row(vec![H2RustType::Long(1), H2RustType::Long(2)]), string("v")).err().unwrap();
+        assert_eq!(error.downcast_ref::<DbError>().unwrap().error_code, error_code::GENERAL_ERROR_1);
+        get_ref_mut!(mvMap).put(row(vec![H2RustType::Long(1)]), string("v")).unwrap();
+        assert_eq!(get_ref!(mvMap).size(), 1);
+        mvStoreMutRef.close().unwrap();
+
+        // 损坏的列数
+        let mut writeBuffer = WriteBuffer::new(16);
+        writeBuffer.putVarInt(5).putU8(0);
+        let byteBuffer = writeBuffer.flip().getBuffer();
+        let error = rowType.read_1(byteBuffer).err().unwrap();
+        assert_eq!(error.downcast_ref::<DbError>().unwrap().error_code, error_code::FILE_CORRUPTED_1);
+    }
+}
@@ -0,0 +1,102 @@
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use anyhow::Result;
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::api::error_code;
+use crate::h2_rust_common::Integer;
+use crate::h2_rust_common::byte_buffer::ByteBuffer;
+use crate::h2_rust_common::h2_rust_type::H2RustType;
+use crate::message::db_error::DbError;
+use crate::mvstore::r#type::data_type::DataType;
+use crate::mvstore::write_buffer::WriteBuffer;
+use crate::throw;
+
+/// The prefix of the type name, followed by the name given by the caller.
+pub const TYPE_NAME_PREFIX: &str = "serde:";
+
+/// The rust types which can be stored in a map through SerdeDataType.
+pub trait SerdeType: Serialize + DeserializeOwned + Ord + Send + Sync + 'static {}
+
+impl<T: Serialize + DeserializeOwned + Ord + Send + Sync + 'static> SerdeType for T {}
+
+/// A data type for any serde serializable rust type, the value is held by H2RustType::Object.
+///
+/// 使用bincode的varint编码,不需要长度前缀,比较使用T自身的Ord
+pub struct SerdeDataType<T> {
+    typeName: String,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T: SerdeType> SerdeDataType<T> {
+    /// name: 调用者给出的稳定的名字,记录在map的meta中,重新打开store时用它找回这个类型
+    pub fn new(name: &str) -> Arc<SerdeDataType<T>> {
+        Arc::new(SerdeDataType { typeName: getTypeName(name), phantom: PhantomData })
+    }
+
+    fn cast(obj: &H2RustType) -> &T {
+        obj.castAsObjectRef::<T>()
+    }
+}
+
+/// 不使用std::any::type_name,它在模块改名或者升级编译器之后可能变化。
+/// 重新打开store之前需要先以同样的名字通过data_type_registry::registerSerde()注册
+pub fn getTypeName(name: &str) -> String {
+    format!("{}{}", TYPE_NAME_PREFIX, name)
+}
+
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+impl<T: SerdeType> DataType for SerdeDataType<T> {
+    fn get_type_name(&self) -> String {
+        self.typeName.clone()
+    }
+
+    fn compare(&self, a: &H2RustType, b: &H2RustType) -> Ordering {
+        Self::cast(a).cmp(Self::cast(b))
+    }
+
+    /// 有的Serialize实现(比如长度未知的序列)bincode不支持,在put时就报错,而不是等到写chunk时
+    fn validate(&self, obj: &H2RustType) -> Result<()> {
+        if let Err(e) = options().serialized_size(Self::cast(obj)) {
+            throw!(DbError::get(error_code::SERIALIZATION_FAILED_1, vec![&e.to_string()]));
+        }
+        Ok(())
+    }
+
+    fn get_memory(&self, obj: &H2RustType) -> Integer {
+        // 放入map之前validate过,这里不会失败
+        24 + options().serialized_size(Self::cast(obj)).unwrap_or(0) as Integer
+    }
+
+    fn write_2(&self, writeBuffer: &mut WriteBuffer, obj: &H2RustType) -> Result<()> {
+        match options().serialize(Self::cast(obj)) {
+            Ok(bytes) => {
+                writeBuffer.putSlice(&bytes);
+                Ok(())
+            }
+            Err(e) => throw!(DbError::get(error_code::SERIALIZATION_FAILED_1, vec![&e.to_string()])),
+        }
+    }
+
+    /// 数据损坏或者以别的类型写入时返回FILE_CORRUPTED
+    fn read_1(&self, byteBuffer: &mut ByteBuffer) -> Result<H2RustType> {
+        let data = &byteBuffer.getInternalSlice()[byteBuffer.getPosition()..byteBuffer.getLimit()];
+        let mut reader = data;
+        let value: T = match options().deserialize_from(&mut reader) {
+            Ok(value) => value,
+            Err(e) => throw!(DbError::get(error_code::FILE_CORRUPTED_1, vec![&format!("can not read {}: {}", self.typeName, e)])),
+        };
+        let consumed = data.len() - reader.len();
+        byteBuffer.advance(consumed);
+        Ok(H2RustType::Object(Arc::new(value)))
+    }
+
+    fn create_storage(&self, size: Integer) -> Vec<H2RustType> {
+        vec![H2RustType::Null; size as usize]
+    }
+}
@@ -1,4 +1,5 @@
 use std::cmp::Ordering;
+use anyhow::Result;
 use std::sync::Arc;
 use lazy_static::lazy_static;
 use crate::build_arc_h2RustCell;
@@ -6,6 +7,7 @@ use crate::h2_rust_common::{h2_rust_constant, Integer};
 use crate::h2_rust_common::byte_buffer::ByteBuffer;
 use crate::h2_rust_common::h2_rust_type::H2RustType;
 use crate::h2_rust_common::h2_rust_type::H2RustType::String;
+use crate::mvstore::data_utils;
 use crate::mvstore::r#type::basic_data_type::BasicDataType;
 use crate::mvstore::r#type::data_type::DataType;
 use crate::mvstore::write_buffer::WriteBuffer;
@@ -15,9 +17,16 @@ lazy_static! {
     pub static ref INSTANCE:Arc<StringDataType> = Arc::new(StringDataType);
 }
 
+/// The name of this type in the map metadata, see data_type_registry.
+pub const TYPE_NAME: &str = "string";
+
 pub struct StringDataType;
 
 impl DataType for StringDataType {
+    fn get_type_name(&self) -> std::string::String {
+        TYPE_NAME.to_string()
+    }
+
     fn compare(&self, a: &H2RustType, b: &H2RustType) -> Ordering {
         match a {
             String(string_a) => {
@@ -39,19 +48,24 @@ impl DataType for StringDataType {
         }
     }
 
-    fn write_2(&self, buff: &WriteBuffer, obj: &H2RustType) {
+    fn write_2(&self, writeBuffer: &mut WriteBuffer, obj: &H2RustType) -> Result<()> {
         match obj {
-            String(string) => todo!(),
+            String(string) => {
+                let string = string.get_ref();
+                let len = string.encode_utf16().count();
+                writeBuffer.putVarInt(len as Integer).putStringData(string, len);
+            }
             _ => panic!("not String")
         }
+        Ok(())
     }
 
-    fn read_1(&self, byteBuffer: &mut ByteBuffer) -> H2RustType {
-        String(build_arc_h2RustCell!(h2_rust_constant::EMPTY_STR.to_string()))
+    fn read_1(&self, byteBuffer: &mut ByteBuffer) -> Result<H2RustType> {
+        Ok(String(build_arc_h2RustCell!(data_utils::readString1(byteBuffer))))
     }
 
     fn create_storage(&self, size: Integer) -> Vec<H2RustType> {
-        Vec::with_capacity(size as usize)
+        vec![H2RustType::Null; size as usize]
     }
 }
 /*
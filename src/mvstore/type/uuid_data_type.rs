@@ -0,0 +1,56 @@
+use std::cmp::Ordering;
+use anyhow::Result;
+use std::sync::Arc;
+use lazy_static::lazy_static;
+use crate::h2_rust_common::Integer;
+use crate::h2_rust_common::byte_buffer::ByteBuffer;
+use crate::h2_rust_common::h2_rust_type::H2RustType;
+use crate::h2_rust_common::h2_rust_type::H2RustType::Uuid;
+use crate::mvstore::r#type::data_type::DataType;
+use crate::mvstore::write_buffer::WriteBuffer;
+
+lazy_static! {
+    pub static ref INSTANCE:Arc<UuidDataType> = Arc::new(UuidDataType);
+}
+
+/// The name of this type in the map metadata, see data_type_registry.
+pub const TYPE_NAME: &str = "uuid";
+
+/// Class UuidDataType. Stored as 16 bytes, the most significant bits first.
+pub struct UuidDataType;
+
+impl DataType for UuidDataType {
+    fn get_type_name(&self) -> String {
+        TYPE_NAME.to_string()
+    }
+
+    /// 和java的UUID.compareTo()一样,先比较高64位再比较低64位,都是有符号比较
+    fn compare(&self, a: &H2RustType, b: &H2RustType) -> Ordering {
+        match (a, b) {
+            (Uuid(high_a, low_a), Uuid(high_b, low_b)) => high_a.cmp(high_b).then(low_a.cmp(low_b)),
+            _ => panic!("not Uuid")
+        }
+    }
+
+    fn get_memory(&self, obj: &H2RustType) -> Integer {
+        16
+    }
+
+    fn write_2(&self, writeBuffer: &mut WriteBuffer, obj: &H2RustType) -> Result<()> {
+        match obj {
+            Uuid(high, low) => { writeBuffer.putI64(*high).putI64(*low); }
+            _ => panic!("not Uuid")
+        }
+        Ok(())
+    }
+
+    fn read_1(&self, byteBuffer: &mut ByteBuffer) -> Result<H2RustType> {
+        let high = byteBuffer.getI64();
+        let low = byteBuffer.getI64();
+        Ok(Uuid(high, low))
+    }
+
+    fn create_storage(&self, size: Integer) -> Vec<H2RustType> {
+        vec![H2RustType::Null; size as usize]
+    }
+}
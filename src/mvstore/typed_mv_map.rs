@@ -0,0 +1,166 @@
+use anyhow::Result;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use crate::{get_ref, get_ref_mut};
+use crate::h2_rust_common::Long;
+use crate::h2_rust_common::h2_rust_cell::SharedPtr;
+use crate::h2_rust_common::h2_rust_type::H2RustType;
+use crate::mvstore::mv_map::MVMap;
+use crate::mvstore::r#type::serde_data_type::SerdeType;
+
+/// A typed view over a MVMap whose key and value types are SerdeDataType,
+/// created by MVStore::openTypedMap().
+pub struct TypedMVMap<K, V> {
+    map: SharedPtr<MVMap>,
+    phantom: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K: SerdeType + Clone, V: SerdeType + Clone> TypedMVMap<K, V> {
+    pub fn new(map: SharedPtr<MVMap>) -> TypedMVMap<K, V> {
+        TypedMVMap { map, phantom: PhantomData }
+    }
+
+    /// Get the underlying untyped map.
+    pub fn getMap(&self) -> SharedPtr<MVMap> {
+        self.map.clone()
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        toValue(get_ref!(self.map).get(&toObject(key.clone())))
+    }
+
+    pub fn containsKey(&self, key: &K) -> bool {
+        get_ref!(self.map).containsKey(&toObject(key.clone()))
+    }
+
+    /// Add or replace a key-value pair, return the old value if the key existed.
+    pub fn put(&mut self, key: K, value: V) -> Result<Option<V>> {
+        Ok(toValue(get_ref_mut!(self.map).put(toObject(key), toObject(value))?))
+    }
+
+    /// Remove a key-value pair, return the old value if the key existed.
+    pub fn remove(&mut self, key: &K) -> Result<Option<V>> {
+        Ok(toValue(get_ref_mut!(self.map).remove(toObject(key.clone()))?))
+    }
+
+    pub fn size(&self) -> Long {
+        get_ref!(self.map).size_as_long()
+    }
+
+    /// All keys in ascending order, starting at the given key (inclusive), or at the first key if None.
+    pub fn keys(&self, from: Option<&K>) -> Vec<K> {
+        let from = from.map_or(H2RustType::Null, |key| toObject(key.clone()));
+        let cursor = get_ref!(self.map).cursor(&from);
+        cursor.map(|key| key.castAsObjectRef::<K>().clone()).collect()
+    }
+}
+
+fn toObject<T: SerdeType>(t: T) -> H2RustType {
+    H2RustType::Object(Arc::new(t))
+}
+
+fn toValue<T: SerdeType + Clone>(h2RustType: H2RustType) -> Option<T> {
+    if h2RustType.isNull() {
+        None
+    } else {
+        Some(h2RustType.castAsObjectRef::<T>().clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{get_ref, get_ref_mut};
+    use crate::api::error_code;
+    use crate::message::db_error::DbError;
+    use crate::mvstore::mv_map::MVMapBuilder;
+    use crate::mvstore::r#type::serde_data_type::SerdeDataType;
+    use crate::mvstore::test_utils::TempStore;
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+    struct Record {
+        name: String,
+        tags: Vec<String>,
+        score: Option<i64>,
+    }
+
+    #[test]
+    fn test_typed_map() {
+        let tempStore = TempStore::new("test_typed_map");
+
+        let record = |a: u64| Record {
+            name: format!("name{}", a),
+            tags: vec!["t".to_string(); (a % 3) as usize],
+            score: if a.is_multiple_of(2) { Some(a as i64 * -10) } else { None },
+        };
+
+        {
+            let mvStore = tempStore.open();
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+            let mut records = mvStoreMutRef.openTypedMap::<u64, Record>("records", "u64", "record").unwrap();
+            for a in (0..1000u64).rev() {
+                assert!(records.put(a * 300, record(a)).unwrap().is_none());
+            }
+            assert_eq!(records.put(0, record(1)).unwrap(), Some(record(0)));
+            assert_eq!(records.put(0, record(0)).unwrap(), Some(record(1)));
+            assert_eq!(records.remove(&(999 * 300)).unwrap(), Some(record(999)));
+            assert_eq!(records.remove(&1).unwrap(), None);
+            mvStoreMutRef.close().unwrap();
+        }
+
+        let mvStore = tempStore.open();
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+        // 以别的类型打开已有的map
+        let error = mvStoreMutRef.openTypedMap::<u64, String>("records", "u64", "string").err().unwrap();
+        assert_eq!(error.downcast_ref::<DbError>().unwrap().error_code, error_code::UNKNOWN_DATA_TYPE_1);
+        let error = mvStoreMutRef.openTypedMap::<u64, Record>("records", "long", "record").err().unwrap();
+        assert_eq!(error.downcast_ref::<DbError>().unwrap().error_code, error_code::UNKNOWN_DATA_TYPE_1);
+
+        let records = mvStoreMutRef.openTypedMap::<u64, Record>("records", "u64", "record").unwrap();
+        assert_eq!(get_ref!(records.getMap()).getValueType().get_type_name(), "serde:record");
+        assert_eq!(records.size(), 999);
+        assert_eq!(records.get(&(42 * 300)), Some(record(42)));
+        assert_eq!(records.get(&(42 * 300 + 1)), None);
+        assert!(records.containsKey(&0));
+        assert!(!records.containsKey(&(999 * 300)));
+
+        // 按照u64的大小而不是序列化之后的字节排序
+        let keys = records.keys(Some(&(997 * 300)));
+        assert_eq!(keys, vec![997 * 300, 998 * 300]);
+        let keys = records.keys(None);
+        assert_eq!(keys.len(), 999);
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+
+        // 结构体作为key
+        let mut byRecord = mvStoreMutRef.openTypedMap::<Record, bool>("byRecord", "record", "bool").unwrap();
+        byRecord.put(record(2), true).unwrap();
+        byRecord.put(record(1), false).unwrap();
+        assert_eq!(byRecord.keys(None), vec![record(1), record(2)]);
+        assert_eq!(byRecord.get(&record(1)), Some(false));
+
+        // 不能序列化的值在put时就报错
+        let mut unserializable = mvStoreMutRef.openTypedMap::<u64, Unserializable>("unserializable", "u64", "unserializable").unwrap();
+        let error = unserializable.put(1, Unserializable(1)).unwrap_err();
+        assert_eq!(error.downcast_ref::<DbError>().unwrap().error_code, error_code::SERIALIZATION_FAILED_1);
+        assert_eq!(unserializable.size(), 0);
+        mvStoreMutRef.close().unwrap();
+
+        // 绕过类型名的检查以别的类型读取,得到FILE_CORRUPTED而不是panic
+        let mvStore = tempStore.open();
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+        let mut mvMapBuilder = MVMapBuilder::new();
+        mvMapBuilder.keyType(SerdeDataType::<bool>::new("record"));
+        mvMapBuilder.valueType(SerdeDataType::<bool>::new("bool"));
+        let error = mvStoreMutRef.openMap("byRecord", &mvMapBuilder).err().unwrap();
+        assert_eq!(error.downcast_ref::<DbError>().unwrap().error_code, error_code::FILE_CORRUPTED_1);
+        mvStoreMutRef.close().unwrap();
+    }
+
+    #[derive(serde::Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+    struct Unserializable(u8);
+
+    impl serde::Serialize for Unserializable {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("not serializable"))
+        }
+    }
+}
@@ -1 +1,251 @@
-pub struct WriteBuffer {}
\ No newline at end of file
+use crate::h2_rust_common::byte_buffer;
+use crate::h2_rust_common::byte_buffer::ByteBuffer;
+use crate::h2_rust_common::{Integer, Long};
+
+/// The maximum size of the buffer in order to be re-used after a clear operation.
+const MAX_REUSE_CAPACITY: usize = 4 * 1024 * 1024;
+
+/// The minimum number of bytes to grow a buffer at a time.
+const MIN_GROW: usize = 1024 * 1024;
+
+/// An auto-resize buffer to write data into a ByteBuffer.
+pub struct WriteBuffer {
+    /// The buffer that is used after a clear operation. <br>
+    /// None表示buff本身就是reuse,rust里不能像java那样两个引用指向同1个对象
+    reuse: Option<ByteBuffer>,
+
+    /// The current buffer (may be replaced if it is too small).
+    buff: ByteBuffer,
+}
+
+impl WriteBuffer {
+    pub fn new(initialSize: usize) -> WriteBuffer {
+        WriteBuffer {
+            reuse: None,
+            buff: byte_buffer::allocate(initialSize),
+        }
+    }
+
+    /// Write a variable size integer.
+    pub fn putVarInt(&mut self, mut x: Integer) -> &mut Self {
+        self.ensureCapacity(5);
+        while (x & !0x7f) != 0 {
+            self.buff.putU8((x | 0x80) as u8);
+            x = ((x as u32) >> 7) as Integer;
+        }
+        self.buff.putU8(x as u8);
+        self
+    }
+
+    /// Write a variable size long.
+    pub fn putVarLong(&mut self, mut x: Long) -> &mut Self {
+        self.ensureCapacity(10);
+        while (x & !0x7f) != 0 {
+            self.buff.putU8((x | 0x80) as u8);
+            x = ((x as u64) >> 7) as Long;
+        }
+        self.buff.putU8(x as u8);
+        self
+    }
+
+    /// Write the characters of a string in a format similar to UTF-8.
+    ///
+    /// len: the number of characters(java char,也就是utf16) to write
+    pub fn putStringData(&mut self, s: &str, len: usize) -> &mut Self {
+        self.ensureCapacity(3 * len);
+        for c in s.encode_utf16().take(len) {
+            let c = c as Integer;
+            if c < 0x80 {
+                self.buff.putU8(c as u8);
+            } else if c >= 0x800 {
+                self.buff.putU8((0xe0 | (c >> 12)) as u8);
+                self.buff.putU8(((c >> 6) & 0x3f) as u8);
+                self.buff.putU8((c & 0x3f) as u8);
+            } else {
+                self.buff.putU8((0xc0 | (c >> 6)) as u8);
+                self.buff.putU8((c & 0x3f) as u8);
+            }
+        }
+        self
+    }
+
+    pub fn putI8(&mut self, x: i8) -> &mut Self {
+        self.putU8(x as u8)
+    }
+
+    pub fn putU8(&mut self, x: u8) -> &mut Self {
+        self.ensureCapacity(1);
+        self.buff.putU8(x);
+        self
+    }
+
+    pub fn putI16(&mut self, x: i16) -> &mut Self {
+        self.ensureCapacity(2);
+        self.buff.putU16(x as u16);
+        self
+    }
+
+    pub fn putI32(&mut self, x: Integer) -> &mut Self {
+        self.ensureCapacity(4);
+        self.buff.putI32(x);
+        self
+    }
+
+    pub fn putI64(&mut self, x: Long) -> &mut Self {
+        self.ensureCapacity(8);
+        self.buff.putI64(x);
+        self
+    }
+
+    pub fn putF32(&mut self, x: f32) -> &mut Self {
+        self.putI32(x.to_bits() as Integer)
+    }
+
+    pub fn putF64(&mut self, x: f64) -> &mut Self {
+        self.putI64(x.to_bits() as Long)
+    }
+
+    pub fn putSlice(&mut self, slice: &[u8]) -> &mut Self {
+        self.putSlice_(slice, 0, slice.len())
+    }
+
+    pub fn putSlice_(&mut self, slice: &[u8], offset: usize, length: usize) -> &mut Self {
+        self.ensureCapacity(length);
+        self.buff.putSlice_(slice, offset, length);
+        self
+    }
+
+    /// Put the remaining part of the byteBuffer.
+    pub fn putByteBuffer(&mut self, src: &mut ByteBuffer) -> &mut Self {
+        self.ensureCapacity(src.getRemaining());
+        self.buff.putByteBuffer(src);
+        self
+    }
+
+    /// Update an integer at the given index.
+    pub fn putI32At(&mut self, index: usize, x: Integer) -> &mut Self {
+        self.buff.putI32At(index, x);
+        self
+    }
+
+    /// Update a short at the given index.
+    pub fn putI16At(&mut self, index: usize, x: i16) -> &mut Self {
+        self.buff.putU16At(index, x as u16);
+        self
+    }
+
+    /// Set the limit, possibly growing the buffer.
+    pub fn setLimit(&mut self, newLimit: usize) -> &mut Self {
+        if newLimit > self.buff.getPosition() {
+            self.ensureCapacity(newLimit - self.buff.getPosition());
+        }
+        self.buff.setLimit(newLimit);
+        self
+    }
+
+    pub fn getLimit(&self) -> usize {
+        self.buff.getLimit()
+    }
+
+    pub fn getCapacity(&self) -> usize {
+        self.buff.getCapacity()
+    }
+
+    /// Set the position, possibly growing the buffer.
+    pub fn setPosition(&mut self, newPosition: usize) -> &mut Self {
+        if newPosition > self.buff.getLimit() {
+            self.ensureCapacity(newPosition - self.buff.getPosition());
+        }
+        self.buff.setPosition(newPosition);
+        self
+    }
+
+    pub fn getPosition(&self) -> usize {
+        self.buff.getPosition()
+    }
+
+    /// Copy the data into the destination array.
+    pub fn getSlice(&mut self, dest: &mut [u8]) -> &mut Self {
+        self.buff.getSlice(dest);
+        self
+    }
+
+    /// Clear the buffer after use.
+    pub fn clear(&mut self) -> &mut Self {
+        if self.buff.getLimit() > MAX_REUSE_CAPACITY {
+            if let Some(reuse) = self.reuse.take() {
+                self.buff = reuse;
+            }
+        } else {
+            self.reuse = None;
+        }
+        self.buff.clear();
+        self
+    }
+
+    /// Flip the buffer, 之后从0到原来的position便是已写入的内容
+    pub fn flip(&mut self) -> &mut Self {
+        self.buff.flip();
+        self
+    }
+
+    /// Get the byte buffer, 可以直接交给FileStore::writeFully
+    pub fn getBuffer(&mut self) -> &mut ByteBuffer {
+        &mut self.buff
+    }
+
+    fn ensureCapacity(&mut self, len: usize) {
+        if self.buff.getRemaining() < len {
+            self.grow(len);
+        }
+    }
+
+    fn grow(&mut self, additional: usize) {
+        let needed = additional - self.buff.getRemaining();
+
+        // grow at least MIN_GROW
+        let mut grow = usize::max(needed, MIN_GROW);
+
+        // grow at least 50% of the current size
+        grow = usize::max(self.buff.getCapacity() / 2, grow);
+
+        // the new capacity is at most Integer.MAX_VALUE
+        let newCapacity = usize::min(Integer::MAX as usize, self.buff.getCapacity() + grow);
+        if newCapacity < needed {
+            panic!("Capacity: {} needed: {}", newCapacity, needed);
+        }
+
+        let mut temp = std::mem::replace(&mut self.buff, byte_buffer::allocate(newCapacity));
+        temp.flip();
+        self.buff.putByteBuffer(&mut temp);
+
+        if newCapacity <= MAX_REUSE_CAPACITY {
+            self.reuse = None;
+        } else if self.reuse.is_none() {
+            self.reuse = Some(temp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mvstore::data_utils;
+    use crate::mvstore::write_buffer::WriteBuffer;
+
+    #[test]
+    fn test_write_read() {
+        let mut writeBuffer = WriteBuffer::new(4);
+        writeBuffer.putVarInt(300).putVarLong(-1).putI32(7);
+
+        let s = "abc中文";
+        let len = s.encode_utf16().count();
+        writeBuffer.putVarInt(len as i32).putStringData(s, len);
+        writeBuffer.flip();
+
+        let byteBuffer = writeBuffer.getBuffer();
+        assert_eq!(data_utils::readVarInt(byteBuffer), 300);
+        assert_eq!(data_utils::readVarLong(byteBuffer), -1);
+        assert_eq!(byteBuffer.getI32(), 7);
+        assert_eq!(data_utils::readString1(byteBuffer), s);
+    }
+}
@@ -14,4 +14,31 @@ pub const NULLS_FIRST: Integer = 2;
 /// if ascending or descending order is used.
 pub const NULLS_LAST: Integer = 4;
 
-pub struct SortOrder {}
\ No newline at end of file
+/// The sort types of the columns of a row, one combination of the bit masks above per column.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SortOrder {
+    sort_types: Vec<Integer>,
+}
+
+impl SortOrder {
+    pub fn new(sort_types: Vec<Integer>) -> SortOrder {
+        SortOrder { sort_types }
+    }
+
+    /// All columns sorted ascending, NULLs ordered by the DefaultNullOrdering.
+    pub fn ascending(column_count: usize) -> SortOrder {
+        SortOrder::new(vec![ASCENDING; column_count])
+    }
+
+    pub fn get_sort_types(&self) -> &Vec<Integer> {
+        &self.sort_types
+    }
+
+    pub fn get_sort_type(&self, index: usize) -> Integer {
+        self.sort_types[index]
+    }
+
+    pub fn get_column_count(&self) -> usize {
+        self.sort_types.len()
+    }
+}
\ No newline at end of file
@@ -1,4 +1,283 @@
-use crate::h2_rust_common::Integer;
+use anyhow::Result;
+use sha2::Sha256;
+use crate::api::error_code;
+use crate::db::store;
+use crate::h2_rust_common::{Integer, Long};
+use crate::message::db_error::DbError;
+use crate::mvstore::data_utils;
+use crate::store::fs::encrypt::xts::Xts;
+use crate::store::fs::file_channel::FileChannel;
+use crate::throw;
+use crate::util::utils;
 
+/// The block size.
+pub const BLOCK_SIZE: Integer = 4096;
 
-pub const BLOCK_SIZE: Integer = 4096;
\ No newline at end of file
+/// The block size bit mask.
+const BLOCK_SIZE_MASK: Long = BLOCK_SIZE as Long - 1;
+
+/// The length of the file header. Using a smaller header is possible,
+/// but would mean reads and writes are not aligned to the block size.
+const HEADER_LENGTH: Long = BLOCK_SIZE as Long;
+
+const HEADER: &[u8] = b"H2encrypt\n";
+const SALT_POS: usize = HEADER.len();
+
+/// The length of the salt, in bytes.
+const SALT_LENGTH: usize = 8;
+
+/// The number of iterations. It is relatively low; a higher value would
+/// slow down opening files on Android too much.
+const HASH_ITERATIONS: u32 = 10;
+
+/// 用来推导AES key的长度,AES-128
+const KEY_LENGTH: usize = 16;
+
+/// Convert a char array to a byte array, in UTF-16 format. The char array is not cleared after use.
+pub fn get_password_bytes(password: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(password.len() * 2);
+    for c in password.encode_utf16() {
+        bytes.extend_from_slice(&c.to_be_bytes());
+    }
+    bytes
+}
+
+/// An encrypted file, 对应h2的FilePathEncrypt.FileEncrypt,文件格式和h2一致
+///
+/// 第1个block是文件头,含有"H2encrypt\n"和salt,之后的数据按照4KB的block使用AES XTS加密,
+/// block的序号作为tweak,文件头不计入size
+pub struct FileEncrypt {
+    name: String,
+
+    /// The underlying file.
+    base: Box<dyn FileChannel>,
+
+    /// The current file size, from a user perspective.
+    size: Long,
+
+    xts: Xts,
+}
+
+impl FileEncrypt {
+    pub fn new(name: &str, encryption_key: &[u8], mut base: Box<dyn FileChannel>) -> Result<FileEncrypt> {
+        let base_size = base.size();
+
+        let mut size = base_size - HEADER_LENGTH;
+        let new_file = size < 0;
+
+        let salt = if new_file {
+            let salt = utils::secureRandomBytes(SALT_LENGTH)?;
+
+            let mut header = vec![0u8; BLOCK_SIZE as usize];
+            header[..SALT_POS].copy_from_slice(HEADER);
+            header[SALT_POS..SALT_POS + SALT_LENGTH].copy_from_slice(&salt);
+            base.write_fully(0, &header)?;
+
+            size = 0;
+            salt
+        } else {
+            let mut salt = vec![0u8; SALT_LENGTH];
+            base.read_fully(SALT_POS as Long, &mut salt)?;
+
+            if (size & BLOCK_SIZE_MASK) != 0 {
+                size -= BLOCK_SIZE as Long;
+            }
+            salt
+        };
+
+        let mut key = [0u8; KEY_LENGTH];
+        pbkdf2::pbkdf2_hmac::<Sha256>(encryption_key, &salt, HASH_ITERATIONS, &mut key);
+
+        Ok(FileEncrypt {
+            name: name.to_string(),
+            base,
+            size,
+            xts: Xts::new(&key),
+        })
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn read_internal(&self, position: Long, dst: &mut [u8]) -> Result<()> {
+        self.base.read_fully(position + HEADER_LENGTH, dst)?;
+
+        let block = position / BLOCK_SIZE as Long;
+        for (i, data) in dst.chunks_exact_mut(BLOCK_SIZE as usize).enumerate() {
+            self.xts.decrypt(block + i as Long, data);
+        }
+
+        Ok(())
+    }
+
+    fn write_internal(&mut self, position: Long, crypt: &mut [u8]) -> Result<()> {
+        let block = position / BLOCK_SIZE as Long;
+        for (i, data) in crypt.chunks_exact_mut(BLOCK_SIZE as usize).enumerate() {
+            self.xts.encrypt(block + i as Long, data);
+        }
+
+        self.base.write_fully(position + HEADER_LENGTH, crypt)
+    }
+}
+
+impl FileChannel for FileEncrypt {
+    fn read_fully(&self, position: Long, dst: &mut [u8]) -> Result<()> {
+        let len = dst.len() as Long;
+        if len == 0 {
+            return Ok(());
+        }
+
+        if position < 0 || position + len > self.size {
+            let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_READING_FAILED);
+            throw!(DbError::get(error_code, vec![&format!("Reading past end of file, position {}", position)]));
+        }
+
+        if (position & BLOCK_SIZE_MASK) != 0 || (len & BLOCK_SIZE_MASK) != 0 {
+            // either the position or the len is unaligned: read aligned, and then truncate
+            let p = position / BLOCK_SIZE as Long * BLOCK_SIZE as Long;
+            let offset = (position - p) as usize;
+            let l = (len as usize + offset).div_ceil(BLOCK_SIZE as usize) * BLOCK_SIZE as usize;
+
+            let mut temp = vec![0u8; l];
+            self.read_internal(p, &mut temp)?;
+            dst.copy_from_slice(&temp[offset..offset + len as usize]);
+
+            return Ok(());
+        }
+
+        self.read_internal(position, dst)
+    }
+
+    fn write_fully(&mut self, position: Long, src: &[u8]) -> Result<()> {
+        let len = src.len() as Long;
+
+        if (position & BLOCK_SIZE_MASK) != 0 || (len & BLOCK_SIZE_MASK) != 0 {
+            // either the position or the len is unaligned: read, decrypt, modify, encrypt, write
+            let p = position / BLOCK_SIZE as Long * BLOCK_SIZE as Long;
+            let offset = (position - p) as usize;
+            let l = (len + offset as Long + BLOCK_SIZE as Long - 1) / BLOCK_SIZE as Long * BLOCK_SIZE as Long;
+
+            let mut temp = vec![0u8; l as usize];
+            let available = (self.size - p + BLOCK_SIZE as Long - 1) / BLOCK_SIZE as Long * BLOCK_SIZE as Long;
+            let read_len = Long::min(l, available);
+            if read_len > 0 {
+                self.read_internal(p, &mut temp[..read_len as usize])?;
+            }
+
+            temp[offset..offset + len as usize].copy_from_slice(src);
+            self.write_internal(p, &mut temp)?;
+
+            let p2 = position + len;
+            if p2 >= self.size {
+                self.size = p2;
+
+                // 末尾不满一个block的时候在底层文件后边补上相应的长度,重新打开的时候据此得到size
+                let plus = (self.size & BLOCK_SIZE_MASK) as usize;
+                if plus > 0 {
+                    self.base.write_fully(p + HEADER_LENGTH + l, &vec![0u8; plus])?;
+                }
+            }
+
+            return Ok(());
+        }
+
+        let mut crypt = src.to_vec();
+        self.write_internal(position, &mut crypt)?;
+        self.size = Long::max(self.size, position + len);
+
+        Ok(())
+    }
+
+    fn truncate(&mut self, new_size: Long) -> Result<()> {
+        if new_size > self.size {
+            return Ok(());
+        }
+
+        if new_size < 0 {
+            throw!(DbError::get_internal_error(&format!("newSize: {}", new_size)));
+        }
+
+        let offset = new_size & BLOCK_SIZE_MASK;
+        let base_size = if offset > 0 {
+            new_size + HEADER_LENGTH + BLOCK_SIZE as Long
+        } else {
+            new_size + HEADER_LENGTH
+        };
+
+        self.base.truncate(base_size)?;
+
+        self.size = new_size;
+
+        Ok(())
+    }
+
+    fn size(&self) -> Long {
+        self.size
+    }
+
+    fn sync(&self) -> Result<()> {
+        self.base.sync()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.base.is_read_only()
+    }
+
+    fn try_lock(&mut self, shared: bool) -> Result<()> {
+        self.base.try_lock(shared)
+    }
+
+    fn release_lock(&mut self) {
+        self.base.release_lock()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{get_ref, get_ref_mut};
+    use crate::api::error_code;
+    use crate::message::db_error::DbError;
+    use crate::mvstore::mv_map::MVMapBuilder;
+    use crate::mvstore::test_utils::{string, TempStore};
+    use crate::store::fs::encrypt::file_encrypt;
+
+    #[test]
+    fn test_encryption() {
+        let tempStore = TempStore::new("test_encryption");
+
+        let openEncrypted = |password: &str| {
+            let mut mvStoreBuilder = tempStore.builder();
+            mvStoreBuilder.encryptionKey(&file_encrypt::get_password_bytes(password));
+            mvStoreBuilder.open()
+        };
+
+        {
+            let mvStore = openEncrypted("007").unwrap();
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+            let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+            for a in 0..100 {
+                get_ref_mut!(data).put(string(&format!("k{}", a)), string(&format!("secret value {}", a))).unwrap();
+            }
+            mvStoreMutRef.commit().unwrap();
+            mvStoreMutRef.close().unwrap();
+        }
+
+        // 文件中看不到明文,文件头和h2的FilePathEncrypt一致
+        let bytes = std::fs::read(tempStore.getFileName()).unwrap();
+        assert!(bytes.starts_with(b"H2encrypt\n"));
+        assert!(!bytes.windows(6).any(|window| window == b"secret"));
+        assert!(!bytes.windows(9).any(|window| window == b"fletcher:"));
+
+        let error = openEncrypted("008").err().unwrap();
+        assert_eq!(error.downcast_ref::<DbError>().unwrap().error_code, error_code::FILE_CORRUPTED_1);
+
+        let mvStore = openEncrypted("007").unwrap();
+        let mvStoreMutRef = get_ref_mut!(mvStore);
+        let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+        assert_eq!(get_ref!(data).size_as_long(), 100);
+        assert_eq!(get_ref!(data).get(&string("k42")).castAsStringRef(), "secret value 42");
+
+        mvStoreMutRef.close().unwrap();
+    }
+}
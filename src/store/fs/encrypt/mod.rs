@@ -1 +1,2 @@
-pub mod file_encrypt;
\ No newline at end of file
+pub mod file_encrypt;
+pub mod xts;
\ No newline at end of file
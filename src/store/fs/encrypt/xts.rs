@@ -0,0 +1,99 @@
+use aes::Aes128;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::cipher::generic_array::GenericArray;
+use crate::h2_rust_common::Long;
+
+/// Galois field feedback.
+const GF_128_FEEDBACK: u8 = 0x87;
+
+/// The AES encryption block size.
+const CIPHER_BLOCK_SIZE: usize = 16;
+
+/// An XTS implementation as described in
+/// IEEE P1619 (Standard Architecture for Encrypted Shared Storage Media).
+/// See also
+/// http://axelkenzo.ru/downloads/1619-2007-NIST-Submission.pdf
+///
+/// 和h2一样tweak和数据使用同一个key
+pub struct Xts {
+    cipher: Aes128,
+}
+
+impl Xts {
+    pub fn new(key: &[u8]) -> Xts {
+        Xts {
+            cipher: Aes128::new(GenericArray::from_slice(key)),
+        }
+    }
+
+    /// Encrypt the data.
+    ///
+    /// id: the (sector) id<br>
+    /// data: the data, length必须是CIPHER_BLOCK_SIZE的整数倍
+    pub fn encrypt(&self, id: Long, data: &mut [u8]) {
+        assert_eq!(data.len() % CIPHER_BLOCK_SIZE, 0);
+
+        let mut tweak = self.init_tweak(id);
+
+        for (i, block) in data.chunks_exact_mut(CIPHER_BLOCK_SIZE).enumerate() {
+            if i > 0 {
+                update_tweak(&mut tweak);
+            }
+
+            xor_tweak(block, &tweak);
+            self.cipher.encrypt_block(GenericArray::from_mut_slice(block));
+            xor_tweak(block, &tweak);
+        }
+    }
+
+    /// Decrypt the data.
+    pub fn decrypt(&self, id: Long, data: &mut [u8]) {
+        assert_eq!(data.len() % CIPHER_BLOCK_SIZE, 0);
+
+        let mut tweak = self.init_tweak(id);
+
+        for (i, block) in data.chunks_exact_mut(CIPHER_BLOCK_SIZE).enumerate() {
+            if i > 0 {
+                update_tweak(&mut tweak);
+            }
+
+            xor_tweak(block, &tweak);
+            self.cipher.decrypt_block(GenericArray::from_mut_slice(block));
+            xor_tweak(block, &tweak);
+        }
+    }
+
+    fn init_tweak(&self, mut id: Long) -> [u8; CIPHER_BLOCK_SIZE] {
+        let mut tweak = [0u8; CIPHER_BLOCK_SIZE];
+
+        for b in tweak.iter_mut() {
+            *b = (id & 0xff) as u8;
+            id = ((id as u64) >> 8) as Long;
+        }
+
+        self.cipher.encrypt_block(GenericArray::from_mut_slice(&mut tweak));
+
+        tweak
+    }
+}
+
+fn xor_tweak(data: &mut [u8], tweak: &[u8; CIPHER_BLOCK_SIZE]) {
+    for (d, t) in data.iter_mut().zip(tweak.iter()) {
+        *d ^= *t;
+    }
+}
+
+fn update_tweak(tweak: &mut [u8; CIPHER_BLOCK_SIZE]) {
+    let mut ci = 0u8;
+    let mut co = 0u8;
+
+    for b in tweak.iter_mut() {
+        co = (*b >> 7) & 1;
+        *b = (*b << 1).wrapping_add(ci);
+        ci = co;
+    }
+
+    if co != 0 {
+        tweak[0] ^= GF_128_FEEDBACK;
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::Result;
+use crate::h2_rust_common::Long;
+use crate::store::fs::file_disk::FileDisk;
+use crate::store::fs::file_mem::FileMem;
+use crate::store::fs::file_nio_mapped::FileNioMapped;
+
+/// 使用内存映射读写文件
+pub const PREFIX_NIO_MAPPED: &str = "nioMapped:";
+
+/// 进程内的虚拟文件系统,进程结束后内容消失
+pub const PREFIX_MEM_FS: &str = "memFS:";
+
+/// FileStore底层的文件,对应java的FileChannel,
+/// 不同的实现通过文件名的前缀区分,参见open()
+pub trait FileChannel: Send + Sync {
+    /// 读取dst.len()个字节,超出文件末尾的话报错
+    fn read_fully(&self, position: Long, dst: &mut [u8]) -> Result<()>;
+
+    /// 写入src全部的字节,必要时扩大文件
+    fn write_fully(&mut self, position: Long, src: &[u8]) -> Result<()>;
+
+    fn size(&self) -> Long;
+
+    /// Truncate the file, 比当前size大的话什么也不做
+    fn truncate(&mut self, size: Long) -> Result<()>;
+
+    /// Force changes to the physical location.
+    fn sync(&self) -> Result<()>;
+
+    fn is_read_only(&self) -> bool;
+
+    /// Try to lock the whole file, 失败的话报错
+    fn try_lock(&mut self, shared: bool) -> Result<()>;
+
+    fn release_lock(&mut self);
+}
+
+/// 根据文件名的前缀打开相应的实现,没有前缀的是普通的磁盘文件
+///
+/// 文件已存在但不可写的话以只读方式打开
+pub fn open(file_name: &str, read_only: bool) -> Result<Box<dyn FileChannel>> {
+    if let Some(name) = file_name.strip_prefix(PREFIX_NIO_MAPPED) {
+        return Ok(Box::new(FileNioMapped::open(name, read_only)?));
+    }
+
+    if file_name.starts_with(PREFIX_MEM_FS) {
+        return Ok(Box::new(FileMem::open(file_name, read_only)?));
+    }
+
+    Ok(Box::new(FileDisk::open(file_name, read_only)?))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{get_ref, get_ref_mut};
+    use crate::mvstore::mv_map::MVMapBuilder;
+    use crate::mvstore::test_utils;
+    use crate::mvstore::test_utils::{string, TempStore};
+    use crate::store::fs::file_channel;
+    use crate::store::fs::file_mem::FileMem;
+
+    #[test]
+    fn test_file_channels() {
+        let diskStore = TempStore::new("test_file_channels");
+        let mappedStore = TempStore::new("test_file_channels_mapped");
+
+        let fileNames = vec![
+            diskStore.getFileName().to_string(),
+            format!("{}{}", file_channel::PREFIX_NIO_MAPPED, mappedStore.getFileName()),
+            format!("{}test_file_channels", file_channel::PREFIX_MEM_FS),
+        ];
+
+        for fileName in &fileNames {
+            {
+                let mvStore = test_utils::openStore(fileName, &[]).unwrap();
+                let mvStoreMutRef = get_ref_mut!(mvStore);
+                let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+                for a in 0..1000 {
+                    get_ref_mut!(data).put(string(&format!("k{}", a)), string(&format!("v{}", a))).unwrap();
+                }
+                mvStoreMutRef.commit().unwrap();
+                for a in 0..500 {
+                    get_ref_mut!(data).remove(string(&format!("k{}", a))).unwrap();
+                }
+                mvStoreMutRef.close().unwrap();
+            }
+
+            let mvStore = test_utils::openStore(fileName, &[]).unwrap();
+            let mvStoreMutRef = get_ref_mut!(mvStore);
+            let data = mvStoreMutRef.openMap("data", &MVMapBuilder::new()).unwrap();
+            assert_eq!(get_ref!(data).size_as_long(), 500);
+            assert_eq!(get_ref!(data).get(&string("k999")).castAsStringRef(), "v999");
+            assert!(get_ref!(data).get(&string("k1")).isNull());
+            mvStoreMutRef.close().unwrap();
+        }
+
+        assert!(mappedStore.getFileSize() > 0);
+
+        // memFS的文件在进程内只能被独占打开1次
+        let memFileName = &fileNames[2];
+        let mvStore = test_utils::openStore(memFileName, &[]).unwrap();
+        assert!(test_utils::openStore(memFileName, &[]).is_err());
+        get_ref_mut!(mvStore).close().unwrap();
+        FileMem::delete(memFileName);
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::Result;
+use std::fs::{File, OpenOptions};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use crate::api::error_code;
+use crate::db::store;
+use crate::h2_rust_common::file_lock::FileLock;
+use crate::h2_rust_common::Long;
+use crate::message::db_error::DbError;
+use crate::mvstore::data_utils;
+use crate::store::fs::file_channel::FileChannel;
+use crate::store::fs::file_utils;
+use crate::throw;
+
+/// 普通的磁盘文件,使用pread/pwrite
+pub struct FileDisk {
+    file: File,
+    read_only: bool,
+    file_lock: Option<FileLock>,
+}
+
+impl FileDisk {
+    pub fn open(file_name: &str, read_only: bool) -> Result<FileDisk> {
+        let (file, read_only) = open_file(file_name, read_only)?;
+
+        Ok(FileDisk {
+            file,
+            read_only,
+            file_lock: None,
+        })
+    }
+}
+
+impl FileChannel for FileDisk {
+    fn read_fully(&self, position: Long, dst: &mut [u8]) -> Result<()> {
+        read_fully(&self.file, position, dst)
+    }
+
+    fn write_fully(&mut self, position: Long, src: &[u8]) -> Result<()> {
+        write_fully(&self.file, position, src)
+    }
+
+    fn size(&self) -> Long {
+        file_size(&self.file)
+    }
+
+    fn truncate(&mut self, size: Long) -> Result<()> {
+        if size < self.size() {
+            set_length(&self.file, size)?;
+        }
+
+        Ok(())
+    }
+
+    fn sync(&self) -> Result<()> {
+        sync(&self.file)
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn try_lock(&mut self, shared: bool) -> Result<()> {
+        self.file_lock = Some(try_lock(&self.file, shared)?);
+        Ok(())
+    }
+
+    fn release_lock(&mut self) {
+        if let Some(file_lock) = self.file_lock.take() {
+            let _ = file_lock.release();
+        }
+    }
+}
+
+/// 打开磁盘上的文件,返回的bool表示是否只读,文件已存在但不可写的话以只读方式打开
+pub fn open_file(file_name: &str, mut read_only: bool) -> Result<(File, bool)> {
+    let file_path = Path::new(file_name);
+    let parent_dir_path = file_path.parent();
+    if parent_dir_path.is_some() && !parent_dir_path.unwrap().as_os_str().is_empty() && !parent_dir_path.unwrap().exists() {
+        throw!(DbError::get_internal_error("parent dir not exist"));
+    }
+
+    if file_path.exists() && !file_utils::can_write(file_path) {
+        read_only = true;
+    }
+
+    let mut open_options = OpenOptions::new();
+    open_options.read(true);
+
+    if !read_only {
+        open_options.create(true);
+        open_options.write(true);
+    }
+
+    match open_options.open(file_path) {
+        Ok(file) => Ok((file, read_only)),
+        Err(e) => throw!(DbError::get_internal_error(&format!("ERROR_READING_FAILED,Could not open file {}, cause: {}", file_name, e)))
+    }
+}
+
+pub fn try_lock(file: &File, shared: bool) -> Result<FileLock> {
+    match FileLock::try_lock(file.as_raw_fd(), 0, Long::MAX, shared) {
+        Ok(file_lock) => Ok(file_lock),
+        Err(e) => throw!(DbError::get_internal_error(&format!("try lock file failed,cause: {}", e)))
+    }
+}
+
+pub fn file_size(file: &File) -> Long {
+    file.metadata().map(|metadata| metadata.len() as Long).unwrap_or(0)
+}
+
+pub fn set_length(file: &File, size: Long) -> Result<()> {
+    if let Err(e) = file.set_len(size as u64) {
+        throw!(DbError::get(error_code::IO_EXCEPTION_1, vec![&e.to_string()]));
+    }
+
+    Ok(())
+}
+
+pub fn sync(file: &File) -> Result<()> {
+    if let Err(e) = file.sync_all() {
+        throw!(DbError::get(error_code::IO_EXCEPTION_1, vec![&e.to_string()]));
+    }
+
+    Ok(())
+}
+
+fn read_fully(file: &File, mut position: Long, mut dst: &mut [u8]) -> Result<()> {
+    while !dst.is_empty() {
+        let len = match file.read_at(dst, position as u64) {
+            Ok(len) => len,
+            Err(e) => throw!(DbError::get(error_code::IO_EXCEPTION_1, vec![&e.to_string()]))
+        };
+
+        if len == 0 {
+            let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_READING_FAILED);
+            throw!(DbError::get(error_code, vec![&format!("Reading past end of file, position {}", position)]));
+        }
+
+        position += len as Long;
+        dst = &mut dst[len..];
+    }
+
+    Ok(())
+}
+
+fn write_fully(file: &File, position: Long, src: &[u8]) -> Result<()> {
+    if let Err(e) = file.write_all_at(src, position as u64) {
+        let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_WRITING_FAILED);
+        throw!(DbError::get(error_code, vec![&e.to_string()]));
+    }
+
+    Ok(())
+}
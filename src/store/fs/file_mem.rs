@@ -0,0 +1,154 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use lazy_static::lazy_static;
+use crate::db::store;
+use crate::h2_rust_common::{Integer, Long};
+use crate::message::db_error::DbError;
+use crate::mvstore::data_utils;
+use crate::store::fs::file_channel::FileChannel;
+use crate::throw;
+
+lazy_static! {
+    /// 进程内全部的memFS文件,文件名包含"memFS:"前缀
+    static ref NAME_MEM_FILE_DATA: Mutex<HashMap<String, Arc<MemFileData>>> = Mutex::new(HashMap::new());
+}
+
+/// This class contains the data of an in-memory random access file, 对应java的FileMemData
+struct MemFileData {
+    data: RwLock<Vec<u8>>,
+
+    /// 0表示没有锁,正数表示共享锁的数量,-1表示独占锁
+    lock_count: Mutex<Integer>,
+}
+
+/// 进程内的虚拟文件,对应java的FilePathMem/FileMem,同名的文件在关闭之后仍然保留直到被删除
+pub struct FileMem {
+    name: String,
+    data: Arc<MemFileData>,
+    read_only: bool,
+
+    /// 当前持有的锁,None表示没有
+    lock_shared: Option<bool>,
+}
+
+impl FileMem {
+    pub fn open(name: &str, read_only: bool) -> Result<FileMem> {
+        let mut name_mem_file_data = NAME_MEM_FILE_DATA.lock().unwrap();
+
+        let data = match name_mem_file_data.get(name) {
+            Some(data) => data.clone(),
+            None => {
+                if read_only {
+                    let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_READING_FAILED);
+                    throw!(DbError::get(error_code, vec![&format!("File not found: {}", name)]));
+                }
+
+                let data = Arc::new(MemFileData {
+                    data: RwLock::new(Vec::new()),
+                    lock_count: Mutex::new(0),
+                });
+                name_mem_file_data.insert(name.to_string(), data.clone());
+                data
+            }
+        };
+
+        Ok(FileMem {
+            name: name.to_string(),
+            data,
+            read_only,
+            lock_shared: None,
+        })
+    }
+
+    pub fn exists(name: &str) -> bool {
+        NAME_MEM_FILE_DATA.lock().unwrap().contains_key(name)
+    }
+
+    pub fn delete(name: &str) {
+        NAME_MEM_FILE_DATA.lock().unwrap().remove(name);
+    }
+}
+
+impl FileChannel for FileMem {
+    fn read_fully(&self, position: Long, dst: &mut [u8]) -> Result<()> {
+        let data = self.data.data.read().unwrap();
+
+        let end = position + dst.len() as Long;
+        if position < 0 || end > data.len() as Long {
+            let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_READING_FAILED);
+            throw!(DbError::get(error_code, vec![&format!("Reading past end of file {}, position {}", self.name, position)]));
+        }
+
+        dst.copy_from_slice(&data[position as usize..end as usize]);
+
+        Ok(())
+    }
+
+    fn write_fully(&mut self, position: Long, src: &[u8]) -> Result<()> {
+        if self.read_only {
+            let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_WRITING_FAILED);
+            throw!(DbError::get(error_code, vec![&format!("File is read only: {}", self.name)]));
+        }
+
+        let mut data = self.data.data.write().unwrap();
+
+        let end = position as usize + src.len();
+        if end > data.len() {
+            data.resize(end, 0);
+        }
+        data[position as usize..end].copy_from_slice(src);
+
+        Ok(())
+    }
+
+    fn size(&self) -> Long {
+        self.data.data.read().unwrap().len() as Long
+    }
+
+    fn truncate(&mut self, size: Long) -> Result<()> {
+        let mut data = self.data.data.write().unwrap();
+        if size < data.len() as Long {
+            data.truncate(size as usize);
+            data.shrink_to_fit();
+        }
+
+        Ok(())
+    }
+
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn try_lock(&mut self, shared: bool) -> Result<()> {
+        let mut lock_count = self.data.lock_count.lock().unwrap();
+
+        let locked = if shared { *lock_count >= 0 } else { *lock_count == 0 };
+        if !locked {
+            let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_FILE_LOCKED);
+            throw!(DbError::get(error_code, vec![&format!("try lock file failed: {}", self.name)]));
+        }
+
+        *lock_count = if shared { *lock_count + 1 } else { -1 };
+        self.lock_shared = Some(shared);
+
+        Ok(())
+    }
+
+    fn release_lock(&mut self) {
+        if let Some(shared) = self.lock_shared.take() {
+            let mut lock_count = self.data.lock_count.lock().unwrap();
+            *lock_count = if shared { *lock_count - 1 } else { 0 };
+        }
+    }
+}
+
+impl Drop for FileMem {
+    fn drop(&mut self) {
+        self.release_lock();
+    }
+}
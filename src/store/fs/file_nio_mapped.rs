@@ -0,0 +1,161 @@
+use anyhow::Result;
+use std::fs::File;
+use memmap2::{Mmap, MmapMut};
+use crate::api::error_code;
+use crate::db::store;
+use crate::h2_rust_common::file_lock::FileLock;
+use crate::h2_rust_common::Long;
+use crate::message::db_error::DbError;
+use crate::mvstore::data_utils;
+use crate::store::fs::file_channel::FileChannel;
+use crate::store::fs::file_disk;
+use crate::throw;
+
+enum Mapped {
+    ReadOnly(Mmap),
+    ReadWrite(MmapMut),
+}
+
+impl Mapped {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Mapped::ReadOnly(mmap) => mmap,
+            Mapped::ReadWrite(mmap) => mmap,
+        }
+    }
+}
+
+/// Uses memory mapped files, 对应java的FilePathNioMapped,
+/// 整个文件映射到内存,文件变大或截短的时候重新映射
+pub struct FileNioMapped {
+    file: File,
+    read_only: bool,
+    file_lock: Option<FileLock>,
+
+    /// 空文件不能映射,此时是None
+    mapped: Option<Mapped>,
+    file_length: Long,
+}
+
+impl FileNioMapped {
+    pub fn open(file_name: &str, read_only: bool) -> Result<FileNioMapped> {
+        let (file, read_only) = file_disk::open_file(file_name, read_only)?;
+
+        let mut file_nio_mapped = FileNioMapped {
+            file,
+            read_only,
+            file_lock: None,
+            mapped: None,
+            file_length: 0,
+        };
+        file_nio_mapped.re_map()?;
+
+        Ok(file_nio_mapped)
+    }
+
+    fn re_map(&mut self) -> Result<()> {
+        // 先解除旧的映射
+        self.mapped = None;
+
+        self.file_length = file_disk::file_size(&self.file);
+        if self.file_length == 0 {
+            return Ok(());
+        }
+
+        let mapped = if self.read_only {
+            unsafe { Mmap::map(&self.file) }.map(Mapped::ReadOnly)
+        } else {
+            unsafe { MmapMut::map_mut(&self.file) }.map(Mapped::ReadWrite)
+        };
+
+        match mapped {
+            Ok(mapped) => self.mapped = Some(mapped),
+            Err(e) => throw!(DbError::get(error_code::IO_EXCEPTION_1, vec![&format!("mmap failed, cause: {}", e)]))
+        }
+
+        Ok(())
+    }
+
+    fn set_file_length(&mut self, new_length: Long) -> Result<()> {
+        if let Some(Mapped::ReadWrite(mmap)) = self.mapped.as_ref() {
+            if let Err(e) = mmap.flush() {
+                throw!(DbError::get(error_code::IO_EXCEPTION_1, vec![&e.to_string()]));
+            }
+        }
+        self.mapped = None;
+
+        file_disk::set_length(&self.file, new_length)?;
+        self.re_map()
+    }
+}
+
+impl FileChannel for FileNioMapped {
+    fn read_fully(&self, position: Long, dst: &mut [u8]) -> Result<()> {
+        let end = position + dst.len() as Long;
+        if position < 0 || end > self.file_length {
+            let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_READING_FAILED);
+            throw!(DbError::get(error_code, vec![&format!("Reading past end of file, position {}", position)]));
+        }
+
+        if let Some(mapped) = self.mapped.as_ref() {
+            dst.copy_from_slice(&mapped.as_slice()[position as usize..end as usize]);
+        }
+
+        Ok(())
+    }
+
+    fn write_fully(&mut self, position: Long, src: &[u8]) -> Result<()> {
+        if self.read_only {
+            let error_code = store::dataUtilsErrorCode2ErrorCode(data_utils::ERROR_WRITING_FAILED);
+            throw!(DbError::get(error_code, vec!["This file is read-only"]));
+        }
+
+        let end = position + src.len() as Long;
+        if end > self.file_length {
+            self.set_file_length(end)?;
+        }
+
+        if let Some(Mapped::ReadWrite(mmap)) = self.mapped.as_mut() {
+            mmap[position as usize..end as usize].copy_from_slice(src);
+        }
+
+        Ok(())
+    }
+
+    fn size(&self) -> Long {
+        self.file_length
+    }
+
+    fn truncate(&mut self, size: Long) -> Result<()> {
+        if size < self.file_length {
+            self.set_file_length(size)?;
+        }
+
+        Ok(())
+    }
+
+    fn sync(&self) -> Result<()> {
+        if let Some(Mapped::ReadWrite(mmap)) = self.mapped.as_ref() {
+            if let Err(e) = mmap.flush() {
+                throw!(DbError::get(error_code::IO_EXCEPTION_1, vec![&e.to_string()]));
+            }
+        }
+
+        file_disk::sync(&self.file)
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn try_lock(&mut self, shared: bool) -> Result<()> {
+        self.file_lock = Some(file_disk::try_lock(&self.file, shared)?);
+        Ok(())
+    }
+
+    fn release_lock(&mut self) {
+        if let Some(file_lock) = self.file_lock.take() {
+            let _ = file_lock.release();
+        }
+    }
+}
@@ -1,2 +1,6 @@
 pub mod file_utils;
-pub mod encrypt;
\ No newline at end of file
+pub mod encrypt;
+pub mod file_channel;
+pub mod file_disk;
+pub mod file_nio_mapped;
+pub mod file_mem;
\ No newline at end of file
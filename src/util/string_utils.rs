@@ -93,6 +93,19 @@ pub fn convertHexString2ByteArr(s: &str) -> Result<Vec<u8>> {
     Ok(byteVec)
 }
 
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+pub fn convertBytes2HexString(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+
+    for b in bytes {
+        s.push(HEX[(b >> 4) as usize] as char);
+        s.push(HEX[(b & 0xf) as usize] as char);
+    }
+
+    s
+}
+
 mod test {
     use crate::h2_rust_common::Integer;
 
@@ -1,3 +1,5 @@
+use std::fs::File;
+use std::io::Read;
 use anyhow::Result;
 use crate::api::error_code;
 use crate::h2_rust_common::{h2_rust_constant, h2_rust_utils, Integer};
@@ -62,4 +64,19 @@ pub fn scaleForAvailableMemory(value: Integer) -> Integer {
         }
         Err(e) => value
     }
-}
\ No newline at end of file
+}
+
+/// Round the value up to the next block size. The block size must be a power of two.
+pub fn roundUpInt(x: Integer, blockSizePowerOf2: Integer) -> Integer {
+    (x + blockSizePowerOf2 - 1) & -blockSizePowerOf2
+}
+
+/// Get a number of cryptographically secure pseudo random bytes.
+pub fn secureRandomBytes(len: usize) -> Result<Vec<u8>> {
+    let mut buffer = vec![0u8; len];
+
+    match File::open("/dev/urandom").and_then(|mut file| file.read_exact(&mut buffer)) {
+        Ok(_) => Ok(buffer),
+        Err(e) => throw!(DbError::get(error_code::IO_EXCEPTION_1, vec![&e.to_string()]))
+    }
+}